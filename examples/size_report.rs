@@ -0,0 +1,30 @@
+//! Reports how much memory a song and a player take up, and fails to compile if the song is over budget.
+//!
+//! Run it with `cargo run --example size_report --features strict-lints,rp2040`.
+//! Running `cargo clippy` with the `strict-lints` feature also checks the crate doesn't use unwraps,
+//! explicit panics, or float math. Those are only lints: check the firmware's own binary to see
+//! what it links.
+
+use buzzer_music::{Player, Song, declare_song};
+
+const SONG: Song = declare_song!(
+	"0 D5 1 11;2 D5 1 11;4 D6 1 11;8 A5 1 11;14 G#5 1 11;18 G5 1 11;22 F5 1 11;26 D5 1 11;28 F5 1 11;30 G5 1 11;0 D4 1 15;2 D4 1 15;4 D5 1 15;8 A4 1 15;14 G#4 1 15;18 G4 1 15;22 F4 1 15;26 D4 1 15;28 F4 1 15;30 G4 1 15"
+);
+
+/// The most flash the song may use.
+const SONG_BUDGET: usize = 1024;
+
+const SONG_SIZE: usize = SONG.storage_size();
+const _: () = assert!(
+	SONG_SIZE <= SONG_BUDGET,
+	"The song is over its flash budget"
+);
+
+type BuzzerPlayer = Player<'static, embassy_rp::pwm::Pwm<'static>, 2, 8>;
+
+const PLAYER_SIZE: usize = core::mem::size_of::<BuzzerPlayer>();
+
+fn main() {
+	println!("song: {SONG_SIZE} of {SONG_BUDGET} bytes of flash");
+	println!("player: {PLAYER_SIZE} bytes of RAM");
+}
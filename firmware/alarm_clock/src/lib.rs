@@ -0,0 +1,208 @@
+#![no_std]
+
+//! An alarm clock that fades its alarm in, plays a chime over it when snoozed, fades it out, and
+//! rings again once the snooze is over.
+//!
+//! This is the board-independent part of the firmware, so its tests can run on the host with
+//! `ShadowOutput`s. `main.rs` wires it to an RP2040's PWMs and buttons.
+
+use buzzer_music::{Player, Song, StingerPolicy, ToneOutput, declare_song};
+
+/// How often the player is ticked, in milliseconds.
+pub const TICK_MS: u16 = 40;
+
+/// Returns how many ticks last `minutes` minutes.
+pub const fn minutes_to_ticks(minutes: u32) -> u32 {
+	minutes * 60_000 / TICK_MS as u32
+}
+
+const ALARM: Song = declare_song!("0 A5 1 0;1 E5 1 0;2 A5 1 0;3 E5 1 0");
+const SNOOZE_CHIME: Song = declare_song!("0 C6 1 0;1 G6 1 0");
+
+/// How many ticks the alarm takes to reach full volume.
+pub const FADE_IN_TICKS: u16 = 8;
+/// How many ticks the alarm takes to go quiet once snoozed or turned off.
+pub const FADE_OUT_TICKS: u16 = 6;
+
+/// What the alarm clock is doing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlarmState {
+	/// Waiting to ring, with this many ticks left.
+	Waiting(u32),
+	/// Ringing until snoozed or turned off.
+	Ringing,
+	/// Snoozed, ringing again after this many ticks.
+	Snoozed(u32),
+	/// Turned off until it is set again.
+	Off,
+}
+
+/// The alarm, its player, and the buttons' actions.
+pub struct AlarmClock<O: ToneOutput> {
+	player: Player<'static, O, 2, 4>,
+	state: AlarmState,
+	snooze_ticks: u32,
+}
+
+impl<O: ToneOutput> AlarmClock<O> {
+	/// Sets the alarm to ring on `buzzers` after `alarm_ticks` ticks, with a raw duty of `duty`.
+	/// Snoozing it makes it ring again after `snooze_ticks` ticks.
+	pub fn new(buzzers: [O; 2], duty: u16, alarm_ticks: u32, snooze_ticks: u32) -> Self {
+		let mut player = Player::new(&ALARM, true, 2, duty, buzzers);
+		player.set_tick_interval_hint(TICK_MS);
+		player.pause();
+		Self {
+			player,
+			state: AlarmState::Waiting(alarm_ticks),
+			snooze_ticks,
+		}
+	}
+
+	/// Counts down to the alarm, and plays the next tick.
+	pub fn tick(&mut self) {
+		match self.state {
+			AlarmState::Waiting(0) | AlarmState::Snoozed(0) => self.ring(),
+			AlarmState::Waiting(ticks) => self.state = AlarmState::Waiting(ticks - 1),
+			AlarmState::Snoozed(ticks) => self.state = AlarmState::Snoozed(ticks - 1),
+			AlarmState::Ringing | AlarmState::Off => {}
+		}
+		self.player.tick();
+	}
+
+	/// Starts ringing, starting quietly.
+	pub fn ring(&mut self) {
+		self.state = AlarmState::Ringing;
+		self.player.restart();
+		self.player.pause();
+		self.player.fade_in(FADE_IN_TICKS);
+	}
+
+	/// Plays the chime over the alarm and fades it out, to ring again after the snooze.
+	/// Does nothing unless it is ringing.
+	pub fn snooze(&mut self) {
+		if self.state != AlarmState::Ringing {
+			return;
+		}
+		self.state = AlarmState::Snoozed(self.snooze_ticks);
+		self.player
+			.play_stinger(&SNOOZE_CHIME, StingerPolicy::SpareVoices, true);
+		self.player.fade_out(FADE_OUT_TICKS);
+	}
+
+	/// Fades the alarm out and turns it off. Does nothing unless it is ringing or snoozed.
+	pub fn turn_off(&mut self) {
+		if !matches!(self.state, AlarmState::Ringing | AlarmState::Snoozed(_)) {
+			return;
+		}
+		self.state = AlarmState::Off;
+		self.player.fade_out(FADE_OUT_TICKS);
+	}
+
+	/// Returns what the alarm clock is doing.
+	pub fn state(&self) -> AlarmState {
+		self.state
+	}
+
+	/// Returns the player, to see what is playing.
+	pub fn player(&self) -> &Player<'static, O, 2, 4> {
+		&self.player
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use buzzer_music::{FrequencyRange, ShadowOutput};
+
+	use super::*;
+
+	fn alarm_clock(alarm_ticks: u32) -> AlarmClock<ShadowOutput> {
+		let output = ShadowOutput::new(
+			FrequencyRange {
+				min: 20,
+				max: 20_000,
+			},
+			1000,
+		);
+		AlarmClock::new([output; 2], 500, alarm_ticks, 10)
+	}
+
+	/// Returns the raw duty the alarm is playing at, or `0` if it is silent.
+	fn alarm_duty(clock: &AlarmClock<ShadowOutput>) -> u16 {
+		clock.player().pwms()[0].tone().map_or(0, |(_, duty)| duty)
+	}
+
+	#[test]
+	fn counts_minutes_in_ticks() {
+		assert_eq!(minutes_to_ticks(1), 1500);
+	}
+
+	#[test]
+	fn waits_then_fades_in() {
+		let mut clock = alarm_clock(3);
+		for _ in 0..3 {
+			clock.tick();
+			assert_eq!(alarm_duty(&clock), 0);
+		}
+
+		clock.tick();
+		assert_eq!(clock.state(), AlarmState::Ringing);
+		let mut last_duty = 0;
+		for _ in 0..FADE_IN_TICKS {
+			clock.tick();
+			let duty = alarm_duty(&clock);
+			assert!(
+				duty >= last_duty,
+				"the alarm only gets louder while fading in"
+			);
+			last_duty = duty;
+		}
+		assert_eq!(last_duty, 500);
+	}
+
+	#[test]
+	fn snoozes_with_a_chime_and_rings_again() {
+		let mut clock = alarm_clock(0);
+		for _ in 0..=FADE_IN_TICKS {
+			clock.tick();
+		}
+
+		clock.snooze();
+		let mut chimed = false;
+		for _ in 0..FADE_OUT_TICKS {
+			clock.tick();
+			chimed |= clock.player().pwms()[1].frequency().is_some();
+		}
+		assert!(chimed, "the snooze chime plays over the alarm");
+		assert!(
+			clock.player().is_paused(),
+			"the alarm is silent once snoozed"
+		);
+		assert!(
+			clock
+				.player()
+				.pwms()
+				.iter()
+				.all(|output| output.tone().is_none())
+		);
+
+		while clock.state() != AlarmState::Ringing {
+			clock.tick();
+		}
+		clock.tick();
+		assert!(!clock.player().is_paused());
+	}
+
+	#[test]
+	fn stays_off_once_turned_off() {
+		let mut clock = alarm_clock(0);
+		clock.turn_off();
+		assert_eq!(clock.state(), AlarmState::Waiting(0));
+		clock.tick();
+		clock.turn_off();
+		for _ in 0..100 {
+			clock.tick();
+		}
+		assert_eq!(clock.state(), AlarmState::Off);
+		assert!(clock.player().is_paused());
+	}
+}
@@ -0,0 +1,65 @@
+//! The alarm clock firmware for a Raspberry Pi Pico, with buzzers on GPIO 14 and 16, a "snooze"
+//! button on GPIO 2, and an "off" button on GPIO 3. The buttons connect their pin to ground.
+//! The alarm rings `WAKE_AFTER_MINUTES` after the board is powered on.
+//!
+//! Build it with `cargo build --release -p alarm_clock --target thumbv6m-none-eabi`.
+
+#![cfg_attr(target_os = "none", no_std)]
+#![cfg_attr(target_os = "none", no_main)]
+
+#[cfg(target_os = "none")]
+use {
+	alarm_clock::{AlarmClock, TICK_MS, minutes_to_ticks},
+	embassy_executor::Spawner,
+	embassy_rp::gpio::{Input, Pull},
+	embassy_rp::pwm::{Config, Pwm},
+	embassy_time::{Duration, Ticker},
+	panic_halt as _,
+};
+
+/// How long after power on the alarm rings.
+#[cfg(target_os = "none")]
+const WAKE_AFTER_MINUTES: u32 = 8 * 60;
+/// How long the alarm stays quiet when snoozed.
+#[cfg(target_os = "none")]
+const SNOOZE_MINUTES: u32 = 9;
+/// The raw duty the buzzers play at.
+#[cfg(target_os = "none")]
+const DUTY: u16 = 0x8000;
+
+#[cfg(target_os = "none")]
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+	let p = embassy_rp::init(Default::default());
+	let buzzers = [
+		Pwm::new_output_a(p.PWM_SLICE7, p.PIN_14, Config::default()),
+		Pwm::new_output_a(p.PWM_SLICE0, p.PIN_16, Config::default()),
+	];
+	let snooze = Input::new(p.PIN_2, Pull::Up);
+	let off = Input::new(p.PIN_3, Pull::Up);
+
+	let mut clock = AlarmClock::new(
+		buzzers,
+		DUTY,
+		minutes_to_ticks(WAKE_AFTER_MINUTES),
+		minutes_to_ticks(SNOOZE_MINUTES),
+	);
+	let mut ticker = Ticker::every(Duration::from_millis(TICK_MS as u64));
+	loop {
+		// Both buttons do nothing until the alarm goes off.
+		if snooze.is_low() {
+			clock.snooze();
+		}
+		if off.is_low() {
+			clock.turn_off();
+		}
+
+		clock.tick();
+		ticker.next().await;
+	}
+}
+
+#[cfg(not(target_os = "none"))]
+fn main() {
+	println!("This is RP2040 firmware. Build it with `--target thumbv6m-none-eabi`.");
+}
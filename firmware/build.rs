@@ -0,0 +1,23 @@
+//! The build script shared by every firmware in this directory.
+//!
+//! When building for the board, this puts `memory.x` where the linker looks for it and links with
+//! the `cortex-m-rt` and `embassy-rp` linker scripts. Host builds, which only check the firmware's
+//! logic, are left alone.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() {
+	println!("cargo:rerun-if-changed=../memory.x");
+	if env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("none") {
+		return;
+	}
+
+	let out = PathBuf::from(env::var_os("OUT_DIR").expect("cargo sets OUT_DIR"));
+	fs::copy("../memory.x", out.join("memory.x")).expect("memory.x should be readable");
+	println!("cargo:rustc-link-search={}", out.display());
+	println!("cargo:rustc-link-arg-bins=--nmagic");
+	println!("cargo:rustc-link-arg-bins=-Tlink.x");
+	println!("cargo:rustc-link-arg-bins=-Tlink-rp.x");
+}
@@ -0,0 +1,163 @@
+#![no_std]
+
+//! A jukebox that plays a playlist of songs round and round on two buzzers, with a button that
+//! skips to the next section of the current song and one that skips to the next song.
+//!
+//! This is the board-independent part of the firmware, so its tests can run on the host with
+//! `ShadowOutput`s. `main.rs` wires it to an RP2040's PWMs and buttons.
+
+use buzzer_music::{
+	Player, Playlist, Song, SongSections, StingerPolicy, TickEvents, ToneOutput, declare_song,
+};
+
+/// How often the player is ticked, in milliseconds.
+pub const TICK_MS: u16 = 40;
+
+/// A short intro, a verse played twice, and a chorus, each after a rest.
+pub const VERSE_AND_CHORUS: Song = declare_song!(
+	"0 C4 1 0;4 E5 1 0;5 G5 1 0;6 E5 1 0;7 C5 1 0;8 E5 1 0;9 G5 1 0;10 E5 1 0;11 C5 1 0;16 A5 2 0;18 G5 2 0;16 F3 4 1",
+	title = "Verse and Chorus",
+	bpm = 150
+);
+/// A short tune over a bass note.
+pub const TUNE: Song = declare_song!("0 G5 1 0;1 E5 1 0;2 C5 2 0;0 C3 4 1", title = "Tune");
+
+/// Played over the song when a button is pressed.
+const CLICK: Song = declare_song!("0 C7 1 0");
+
+const SONG_COUNT: usize = 2;
+
+/// Where the sections of each song start, found from their repeats.
+const SECTIONS: [SongSections<4>; SONG_COUNT] = [
+	VERSE_AND_CHORUS.find_sections(3, 4),
+	TUNE.find_sections(3, 4),
+];
+
+/// The playlist, its player, and the buttons' actions.
+pub struct Jukebox<O: ToneOutput> {
+	playlist: Playlist<'static, SONG_COUNT>,
+	player: Player<'static, O, 2, 4>,
+}
+
+impl<O: ToneOutput> Jukebox<O> {
+	/// Starts playing the first song on `buzzers`, with a raw duty of `duty`.
+	pub fn new(buzzers: [O; 2], duty: u16) -> Self {
+		let mut playlist = Playlist::new([&VERSE_AND_CHORUS, &TUNE]);
+		playlist.set_looping(true);
+		let mut player = Player::new(playlist.current(), false, 2, duty, buzzers);
+		player.set_tick_interval_hint(TICK_MS);
+		player.set_sections(SECTIONS[0].as_slice());
+		Self { playlist, player }
+	}
+
+	/// Plays the next tick, moving on to the next song when one ends.
+	pub fn tick(&mut self) {
+		let events = self.playlist.tick(&mut self.player);
+		if events.contains(TickEvents::FINISHED) {
+			self.player
+				.set_sections(SECTIONS[self.playlist.index()].as_slice());
+		}
+	}
+
+	/// Skips to the next section of the current song. Returns `false` if it is in its last section.
+	pub fn skip_section(&mut self) -> bool {
+		self.click();
+		self.player.skip_to_next_section()
+	}
+
+	/// Skips to the start of the next song, or back to the first after the last.
+	pub fn next_song(&mut self) {
+		self.click();
+		self.playlist.next(&mut self.player);
+		self.player
+			.set_sections(SECTIONS[self.playlist.index()].as_slice());
+	}
+
+	/// Returns the index of the song playing.
+	pub fn song_index(&self) -> usize {
+		self.playlist.index()
+	}
+
+	/// Returns the player, to see what is playing.
+	pub fn player(&self) -> &Player<'static, O, 2, 4> {
+		&self.player
+	}
+
+	/// Plays a click on a spare buzzer, so pressing a button can be heard.
+	fn click(&mut self) {
+		self.player
+			.play_stinger(&CLICK, StingerPolicy::SpareVoices, false);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use buzzer_music::{FrequencyRange, ShadowOutput};
+
+	use super::*;
+
+	fn jukebox() -> Jukebox<ShadowOutput> {
+		let output = ShadowOutput::new(
+			FrequencyRange {
+				min: 20,
+				max: 20_000,
+			},
+			1000,
+		);
+		Jukebox::new([output; 2], 500)
+	}
+
+	#[test]
+	fn finds_the_sections() {
+		assert_eq!(SECTIONS[0].as_slice(), &[0, 4, 16]);
+		// At 40ms per tick, the first song's 150 beats per minute is 10 ticks per beat.
+		assert_eq!(VERSE_AND_CHORUS.ticks_per_beat(TICK_MS), Some(10));
+	}
+
+	#[test]
+	fn plays_the_playlist_round_and_round() {
+		let mut jukebox = jukebox();
+		for song in [1, 0] {
+			let mut ticks = 0;
+			while jukebox.song_index() != song {
+				jukebox.tick();
+				ticks += 1;
+				assert!(ticks < 1000, "the song should end");
+			}
+		}
+	}
+
+	#[test]
+	fn skips_from_the_verse_to_the_chorus() {
+		let mut jukebox = jukebox();
+		while jukebox.player().current_beat() != Some(5) {
+			jukebox.tick();
+		}
+		assert!(jukebox.skip_section());
+		while jukebox.player().current_beat() != Some(16) {
+			jukebox.tick();
+		}
+
+		let sounding = jukebox
+			.player()
+			.pwms()
+			.iter()
+			.filter_map(ShadowOutput::frequency)
+			.count();
+		assert_eq!(
+			sounding, 2,
+			"the chorus starts with a melody and a bass note"
+		);
+	}
+
+	#[test]
+	fn skips_to_the_next_song() {
+		let mut jukebox = jukebox();
+		jukebox.tick();
+		jukebox.next_song();
+		assert_eq!(jukebox.song_index(), 1);
+		assert_eq!(jukebox.player().current_beat(), None);
+		jukebox.tick();
+		assert_eq!(jukebox.player().pwms()[0].frequency(), Some(784));
+	}
+}
@@ -0,0 +1,56 @@
+//! The jukebox firmware for a Raspberry Pi Pico, with buzzers on GPIO 14 and 16, a "skip section"
+//! button on GPIO 2, and a "next song" button on GPIO 3. The buttons connect their pin to ground.
+//!
+//! Build it with `cargo build --release -p jukebox --target thumbv6m-none-eabi`.
+
+#![cfg_attr(target_os = "none", no_std)]
+#![cfg_attr(target_os = "none", no_main)]
+
+#[cfg(target_os = "none")]
+use {
+	embassy_executor::Spawner,
+	embassy_rp::gpio::{Input, Pull},
+	embassy_rp::pwm::{Config, Pwm},
+	embassy_time::{Duration, Ticker},
+	jukebox::{Jukebox, TICK_MS},
+	panic_halt as _,
+};
+
+/// The raw duty the buzzers play at.
+#[cfg(target_os = "none")]
+const DUTY: u16 = 0x8000;
+
+#[cfg(target_os = "none")]
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+	let p = embassy_rp::init(Default::default());
+	let buzzers = [
+		Pwm::new_output_a(p.PWM_SLICE7, p.PIN_14, Config::default()),
+		Pwm::new_output_a(p.PWM_SLICE0, p.PIN_16, Config::default()),
+	];
+	let skip_section = Input::new(p.PIN_2, Pull::Up);
+	let next_song = Input::new(p.PIN_3, Pull::Up);
+
+	let mut jukebox = Jukebox::new(buzzers, DUTY);
+	let mut ticker = Ticker::every(Duration::from_millis(TICK_MS as u64));
+	let mut held = (false, false);
+	loop {
+		// Buttons act once when pressed, not for as long as they are held.
+		let pressed = (skip_section.is_low(), next_song.is_low());
+		if pressed.0 && !held.0 {
+			jukebox.skip_section();
+		}
+		if pressed.1 && !held.1 {
+			jukebox.next_song();
+		}
+		held = pressed;
+
+		jukebox.tick();
+		ticker.next().await;
+	}
+}
+
+#[cfg(not(target_os = "none"))]
+fn main() {
+	println!("This is RP2040 firmware. Build it with `--target thumbv6m-none-eabi`.");
+}
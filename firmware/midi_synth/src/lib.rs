@@ -0,0 +1,319 @@
+#![no_std]
+
+//! A synth that plays the notes it is sent over MIDI, one note per buzzer. When more keys are held
+//! than there are buzzers, the newest note takes over the buzzer of the oldest.
+//!
+//! This is the board-independent part of the firmware, so its tests can run on the host with
+//! `ShadowOutput`s. `main.rs` wires it to an RP2040's PWMs and a UART.
+
+use buzzer_music::{DutyFraction, ToneOutput};
+
+/// The MIDI baud rate.
+pub const MIDI_BAUD_RATE: u32 = 31_250;
+
+/// The frequencies of the keys from C9 (`120`) to B9, the highest octave a key can be in.
+const TOP_OCTAVE: [u16; 12] = [
+	8372, 8870, 9397, 9956, 10548, 11175, 11840, 12544, 13290, 14080, 14917, 15804,
+];
+
+/// Returns the frequency of the MIDI `key` in hertz, where `69` is A4 at 440 Hz.
+pub const fn key_frequency(key: u8) -> u16 {
+	let key = if key > 127 { 127 } else { key };
+	// Each octave down halves the frequency, rounding to the nearest hertz.
+	let shift = 10 - key / 12;
+	let frequency = TOP_OCTAVE[(key % 12) as usize] as u32;
+	((frequency + (1 << shift >> 1)) >> shift) as u16
+}
+
+/// A MIDI message the synth responds to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MidiMessage {
+	/// A key was pressed. A note on with a velocity of `0` is read as a [`MidiMessage::NoteOff`].
+	NoteOn { channel: u8, key: u8, velocity: u8 },
+	/// A key was released.
+	NoteOff { channel: u8, key: u8 },
+	/// The "all sound off" or "all notes off" controller was sent.
+	AllNotesOff { channel: u8 },
+}
+
+/// Reads [`MidiMessage`]s from a MIDI byte stream, including messages sent with running status.
+///
+/// Bytes of messages the synth doesn't respond to are skipped.
+#[derive(Clone, Debug, Default)]
+pub struct MidiParser {
+	/// The status byte of the message being read, or `0` before the first one.
+	status: u8,
+	/// The data bytes read so far.
+	data: [u8; 2],
+	length: usize,
+}
+
+impl MidiParser {
+	/// The constructor.
+	pub const fn new() -> Self {
+		Self {
+			status: 0,
+			data: [0; 2],
+			length: 0,
+		}
+	}
+
+	/// Reads the next byte, and returns the message it completes, if any.
+	pub fn feed(&mut self, byte: u8) -> Option<MidiMessage> {
+		match byte {
+			// Real time messages can come between any two bytes, and don't change the status.
+			0xF8.. => return None,
+			// System messages cancel the running status, and their data is skipped.
+			0xF0.. => {
+				self.status = 0;
+				return None;
+			}
+			0x80.. => {
+				self.status = byte;
+				self.length = 0;
+				return None;
+			}
+			_ => {}
+		}
+		if self.status == 0 {
+			return None;
+		}
+
+		self.data[self.length] = byte;
+		self.length += 1;
+		let data_length = match self.status & 0xF0 {
+			0xC0 | 0xD0 => 1,
+			_ => 2,
+		};
+		if self.length < data_length {
+			return None;
+		}
+		self.length = 0;
+
+		let channel = self.status & 0x0F;
+		let [key, value] = self.data;
+		match self.status & 0xF0 {
+			0x90 if value > 0 => Some(MidiMessage::NoteOn {
+				channel,
+				key,
+				velocity: value,
+			}),
+			0x80 | 0x90 => Some(MidiMessage::NoteOff { channel, key }),
+			0xB0 if key == 120 || key == 123 => Some(MidiMessage::AllNotesOff { channel }),
+			_ => None,
+		}
+	}
+}
+
+/// Plays MIDI notes on `VOICES` buzzers, one note each.
+pub struct Synth<O: ToneOutput, const VOICES: usize> {
+	outputs: [O; VOICES],
+	/// The key each voice is playing, and the channel it came from.
+	notes: [Option<(u8, u8)>; VOICES],
+	/// When each voice started its note, counted in notes, to find the oldest.
+	started: [u32; VOICES],
+	notes_started: u32,
+}
+
+impl<O: ToneOutput, const VOICES: usize> Synth<O, VOICES> {
+	/// Plays on `outputs`, which start silent.
+	pub fn new(mut outputs: [O; VOICES]) -> Self {
+		for output in &mut outputs {
+			output.set_off();
+		}
+		Self {
+			outputs,
+			notes: [None; VOICES],
+			started: [0; VOICES],
+			notes_started: 0,
+		}
+	}
+
+	/// Responds to `message`.
+	pub fn handle(&mut self, message: MidiMessage) {
+		match message {
+			MidiMessage::NoteOn {
+				channel,
+				key,
+				velocity,
+			} => self.note_on(channel, key, velocity),
+			MidiMessage::NoteOff { channel, key } => {
+				if let Some(voice) = self.voice_playing(channel, key) {
+					self.silence(voice);
+				}
+			}
+			MidiMessage::AllNotesOff { channel } => {
+				for voice in 0..VOICES {
+					if matches!(self.notes[voice], Some((c, _)) if c == channel) {
+						self.silence(voice);
+					}
+				}
+			}
+		}
+	}
+
+	/// Returns the key each voice is playing.
+	pub fn keys(&self) -> [Option<u8>; VOICES] {
+		self.notes.map(|note| note.map(|(_, key)| key))
+	}
+
+	/// Returns the buzzers.
+	pub fn outputs(&self) -> &[O; VOICES] {
+		&self.outputs
+	}
+
+	/// Plays `key` on a free voice, or the one that has been playing the longest.
+	/// The louder the note, the higher its duty, up to half.
+	fn note_on(&mut self, channel: u8, key: u8, velocity: u8) {
+		let voice = self
+			.voice_playing(channel, key)
+			.or_else(|| self.notes.iter().position(Option::is_none))
+			.or_else(|| (0..VOICES).min_by_key(|&voice| self.started[voice]));
+		let Some(voice) = voice else {
+			return;
+		};
+
+		let duty = DutyFraction::from_ratio(velocity as u32, 2 * 127);
+		if self.outputs[voice]
+			.set_tone_fraction(key_frequency(key), duty)
+			.is_err()
+		{
+			// The buzzer can't play this key.
+			self.silence(voice);
+			return;
+		}
+		self.notes[voice] = Some((channel, key));
+		self.started[voice] = self.notes_started;
+		self.notes_started = self.notes_started.wrapping_add(1);
+	}
+
+	/// Returns the voice playing `key` from `channel`.
+	fn voice_playing(&self, channel: u8, key: u8) -> Option<usize> {
+		self.notes
+			.iter()
+			.position(|note| *note == Some((channel, key)))
+	}
+
+	fn silence(&mut self, voice: usize) {
+		self.outputs[voice].set_off();
+		self.notes[voice] = None;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use buzzer_music::{FrequencyRange, ShadowOutput};
+
+	use super::*;
+
+	fn synth() -> Synth<ShadowOutput, 2> {
+		let output = ShadowOutput::new(
+			FrequencyRange {
+				min: 20,
+				max: 20_000,
+			},
+			1000,
+		);
+		Synth::new([output; 2])
+	}
+
+	/// Feeds `bytes` to a new parser and returns the messages they make up.
+	fn parse(bytes: &[u8]) -> ([Option<MidiMessage>; 4], usize) {
+		let mut parser = MidiParser::new();
+		let mut messages = [None; 4];
+		let mut count = 0;
+		for &byte in bytes {
+			if let Some(message) = parser.feed(byte) {
+				messages[count] = Some(message);
+				count += 1;
+			}
+		}
+		(messages, count)
+	}
+
+	#[test]
+	fn converts_keys_to_frequencies() {
+		assert_eq!(key_frequency(69), 440);
+		assert_eq!(key_frequency(60), 262);
+		assert_eq!(key_frequency(57), 220);
+		assert_eq!(key_frequency(127), 12544);
+		assert_eq!(key_frequency(0), 8);
+	}
+
+	#[test]
+	fn parses_notes_with_running_status() {
+		// A note on, a second note on with running status around a clock tick, and a note off as a
+		// note on with a velocity of 0.
+		let (messages, count) = parse(&[0x91, 60, 100, 64, 0xF8, 90, 60, 0]);
+		assert_eq!(count, 3);
+		assert_eq!(
+			messages[..3],
+			[
+				Some(MidiMessage::NoteOn {
+					channel: 1,
+					key: 60,
+					velocity: 100
+				}),
+				Some(MidiMessage::NoteOn {
+					channel: 1,
+					key: 64,
+					velocity: 90
+				}),
+				Some(MidiMessage::NoteOff {
+					channel: 1,
+					key: 60
+				}),
+			]
+		);
+	}
+
+	#[test]
+	fn skips_other_messages() {
+		// A program change, a SysEx message, a stray data byte, and then "all notes off".
+		let (messages, count) = parse(&[0xC0, 5, 0xF0, 1, 2, 3, 0xF7, 60, 0xB2, 123, 0]);
+		assert_eq!(count, 1);
+		assert_eq!(messages[0], Some(MidiMessage::AllNotesOff { channel: 2 }));
+	}
+
+	#[test]
+	fn plays_a_note_per_buzzer() {
+		let mut synth = synth();
+		synth.handle(MidiMessage::NoteOn {
+			channel: 0,
+			key: 69,
+			velocity: 127,
+		});
+		synth.handle(MidiMessage::NoteOn {
+			channel: 0,
+			key: 57,
+			velocity: 127,
+		});
+		assert_eq!(synth.keys(), [Some(69), Some(57)]);
+		assert_eq!(synth.outputs()[0].tone(), Some((440, 500)));
+		assert_eq!(synth.outputs()[1].tone(), Some((220, 500)));
+
+		synth.handle(MidiMessage::NoteOff {
+			channel: 0,
+			key: 69,
+		});
+		assert_eq!(synth.keys(), [None, Some(57)]);
+		assert_eq!(synth.outputs()[0].tone(), None);
+	}
+
+	#[test]
+	fn steals_the_oldest_voice() {
+		let mut synth = synth();
+		for key in [60, 64, 67] {
+			synth.handle(MidiMessage::NoteOn {
+				channel: 0,
+				key,
+				velocity: 64,
+			});
+		}
+		assert_eq!(synth.keys(), [Some(67), Some(64)]);
+
+		synth.handle(MidiMessage::AllNotesOff { channel: 0 });
+		assert_eq!(synth.keys(), [None, None]);
+		assert!(synth.outputs().iter().all(|output| output.tone().is_none()));
+	}
+}
@@ -0,0 +1,54 @@
+//! The MIDI synth firmware for a Raspberry Pi Pico, with buzzers on GPIO 14 and 16, and MIDI in on
+//! GPIO 1 (UART0 RX) through the usual optocoupler circuit.
+//!
+//! Build it with `cargo build --release -p midi_synth --target thumbv6m-none-eabi`.
+
+#![cfg_attr(target_os = "none", no_std)]
+#![cfg_attr(target_os = "none", no_main)]
+
+#[cfg(target_os = "none")]
+use {
+	embassy_executor::Spawner,
+	embassy_rp::bind_interrupts,
+	embassy_rp::peripherals::UART0,
+	embassy_rp::pwm::{Config, Pwm},
+	embassy_rp::uart::{self, UartRx},
+	midi_synth::{MIDI_BAUD_RATE, MidiParser, Synth},
+	panic_halt as _,
+};
+
+#[cfg(target_os = "none")]
+bind_interrupts!(struct Irqs {
+	UART0_IRQ => uart::InterruptHandler<UART0>;
+});
+
+#[cfg(target_os = "none")]
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+	let p = embassy_rp::init(Default::default());
+	let buzzers = [
+		Pwm::new_output_a(p.PWM_SLICE7, p.PIN_14, Config::default()),
+		Pwm::new_output_a(p.PWM_SLICE0, p.PIN_16, Config::default()),
+	];
+	let mut config = uart::Config::default();
+	config.baudrate = MIDI_BAUD_RATE;
+	let mut midi_in = UartRx::new(p.UART0, p.PIN_1, Irqs, p.DMA_CH0, config);
+
+	let mut synth = Synth::new(buzzers);
+	let mut parser = MidiParser::new();
+	let mut byte = [0];
+	loop {
+		// A garbled byte is dropped. The parser picks up again at the next status byte.
+		if midi_in.read(&mut byte).await.is_err() {
+			continue;
+		}
+		if let Some(message) = parser.feed(byte[0]) {
+			synth.handle(message);
+		}
+	}
+}
+
+#[cfg(not(target_os = "none"))]
+fn main() {
+	println!("This is RP2040 firmware. Build it with `--target thumbv6m-none-eabi`.");
+}
@@ -0,0 +1,63 @@
+/*
+ * C interface to buzzer_music, enabled by its `ffi` feature.
+ * See the documentation of the `buzzer_music::ffi` module.
+ */
+
+#ifndef BUZZER_MUSIC_H
+#define BUZZER_MUSIC_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* How many channels an engine plays on. */
+#define BUZZER_CHANNELS 4
+
+/* A song and the player playing it. Allocate `buzzer_engine_size()` bytes aligned to
+ * `buzzer_engine_align()`, and don't move it once it's initialized. */
+typedef struct BuzzerEngine BuzzerEngine;
+
+/* The PWM driver an engine plays its tones on. */
+typedef struct BuzzerOutput {
+	void *context;
+	/* Plays `frequency` with the raw `duty` on `channel`. Returns false if it can't. */
+	bool (*set_tone)(void *context, uint8_t channel, uint16_t frequency, uint16_t duty);
+	/* Stops playing on `channel`. */
+	void (*set_off)(void *context, uint8_t channel);
+	uint16_t min_frequency;
+	uint16_t max_frequency;
+	/* The raw duty that is fully on. */
+	uint16_t max_duty;
+} BuzzerOutput;
+
+/* Something that happened while an engine played its song. */
+typedef struct BuzzerEvent {
+	/* 0: beat started, 1: note on, 2: note off, 3: song ended, 4: tempo changed. */
+	uint8_t kind;
+	uint8_t track;
+	/* The beat, the frequency of the note, or the tempo in beats per minute. */
+	uint16_t value;
+} BuzzerEvent;
+
+size_t buzzer_engine_size(void);
+size_t buzzer_engine_align(void);
+
+/* Loads a song written in the `buzzer_music::raw` format. Returns 0, or an error code. */
+uint8_t buzzer_engine_init(BuzzerEngine *engine, const uint8_t *song, size_t song_len,
+	BuzzerOutput output, uint16_t ticks_per_beat, uint16_t duty, bool looping);
+
+/* Returns false if the engine is paused, for example because its song ended. */
+bool buzzer_engine_tick(BuzzerEngine *engine);
+
+/* Returns false if there are no more events. */
+bool buzzer_engine_next_event(const BuzzerEngine *engine, BuzzerEvent *event);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
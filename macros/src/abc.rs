@@ -0,0 +1,560 @@
+use crate::{TONES, TimedNote, TimedTempo, gcd};
+
+/// A fraction of a whole note.
+#[derive(Clone, Copy, PartialEq)]
+struct Ratio {
+	num: u64,
+	den: u64,
+}
+
+impl Ratio {
+	fn new(num: u64, den: u64) -> Self {
+		let divisor = gcd(num, den).max(1);
+		Self {
+			num: num / divisor,
+			den: den / divisor,
+		}
+	}
+
+	fn add(self, other: Self) -> Self {
+		Self::new(
+			self.num * other.den + other.num * self.den,
+			self.den * other.den,
+		)
+	}
+
+	fn mul(self, other: Self) -> Self {
+		Self::new(self.num * other.num, self.den * other.den)
+	}
+
+	fn to_f64(self) -> f64 {
+		self.num as f64 / self.den as f64
+	}
+}
+
+/// Something in the body of an ABC tune, after accidentals and lengths are worked out.
+enum Item {
+	/// One or more notes (a chord) starting together.
+	Notes {
+		tones: Vec<usize>,
+		length: Ratio,
+		tie: bool,
+	},
+	Rest(Ratio),
+	RepeatStart,
+	RepeatEnd,
+	/// The start of the `n`th ending of a repeat.
+	Ending(u8),
+	/// A new tempo, in quarter notes per minute.
+	Tempo(f64),
+}
+
+/// A tune parsed by [`parse_abc`].
+pub(crate) struct AbcTune {
+	pub(crate) notes: Vec<TimedNote>,
+	pub(crate) tempos: Vec<TimedTempo>,
+	/// The units of the times and durations in `notes` and `tempos`, in parts of a whole note.
+	pub(crate) whole: u64,
+	/// When the tune ends, including any rests at its end.
+	pub(crate) end: u64,
+}
+
+/// The state of the parser while reading the header and body of a tune.
+struct Parser {
+	items: Vec<Item>,
+	meter: Ratio,
+	unit: Option<Ratio>,
+	/// The accidental of each letter (C to B) in the key signature, in semitones.
+	key: [i64; 7],
+	/// The accidentals written so far in this bar, by letter and octave.
+	bar_accidentals: Vec<(usize, i64, i64)>,
+	/// A tuplet being played: its length multiplier and how many notes are left in it.
+	tuplet: Option<(Ratio, u64)>,
+	/// The length multiplier of the next note, from a broken rhythm like `A>B`.
+	broken: Option<Ratio>,
+}
+
+/// Parses a tune in ABC notation.
+///
+/// This supports the `M:`, `L:`, `Q:`, and `K:` fields, notes with accidentals, octaves, and lengths,
+/// rests, chords, ties, broken rhythms, tuplets, repeats, and first and second endings.
+/// Decorations, chord symbols, annotations, grace notes, and slurs are ignored.
+pub(crate) fn parse_abc(text: &str) -> Result<AbcTune, String> {
+	let mut parser = Parser {
+		items: vec![],
+		meter: Ratio::new(4, 4),
+		unit: None,
+		key: [0; 7],
+		bar_accidentals: vec![],
+		tuplet: None,
+		broken: None,
+	};
+
+	for line in text.lines() {
+		let line = line.split('%').next().unwrap_or_default().trim();
+		let bytes = line.as_bytes();
+		if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+			parser.field(bytes[0], line[2..].trim())?;
+		} else {
+			parser.body(line)?;
+		}
+	}
+
+	Ok(parser.unroll())
+}
+
+impl Parser {
+	/// Returns the length of a note without a length, which defaults to an eighth note
+	/// (or a sixteenth note in meters shorter than 3/4).
+	fn unit(&self) -> Ratio {
+		self.unit.unwrap_or(if self.meter.to_f64() < 0.75 {
+			Ratio::new(1, 16)
+		} else {
+			Ratio::new(1, 8)
+		})
+	}
+
+	/// Applies an information field like `L:1/8`. Fields that don't affect the music are ignored.
+	fn field(&mut self, name: u8, value: &str) -> Result<(), String> {
+		match name {
+			b'M' => {
+				self.meter = match value {
+					"C" => Ratio::new(4, 4),
+					"C|" => Ratio::new(2, 2),
+					"none" | "" => Ratio::new(4, 4),
+					_ => parse_fraction(value).ok_or(format!("invalid meter `{value}`"))?,
+				}
+			}
+			b'L' => {
+				self.unit = Some(parse_fraction(value).ok_or(format!("invalid length `{value}`"))?)
+			}
+			b'Q' => {
+				let tempo = self
+					.parse_tempo(value)
+					.ok_or(format!("invalid tempo `{value}`"))?;
+				self.items.push(Item::Tempo(tempo));
+			}
+			b'K' => self.key = parse_key(value).ok_or(format!("invalid key `{value}`"))?,
+			_ => {}
+		}
+		Ok(())
+	}
+
+	/// Parses a tempo like `1/4=120` or `120`, returning quarter notes per minute.
+	fn parse_tempo(&self, value: &str) -> Option<f64> {
+		// Skip any text like `"Allegro"`.
+		let value = value.rsplit('"').next()?.trim();
+		let (beat, bpm) = match value.split_once('=') {
+			Some((beats, bpm)) => {
+				let beat = beats
+					.split_whitespace()
+					.map(parse_fraction)
+					.try_fold(Ratio::new(0, 1), |sum, beat| Some(sum.add(beat?)))?;
+				(beat, bpm.trim())
+			}
+			// A bare number counts unit lengths.
+			None => (self.unit(), value),
+		};
+		let bpm = bpm.parse::<f64>().ok().filter(|bpm| *bpm > 0.)?;
+		Some(bpm * beat.to_f64() * 4.)
+	}
+
+	/// Parses a line of music.
+	fn body(&mut self, line: &str) -> Result<(), String> {
+		let chars = line.chars().collect::<Vec<char>>();
+		let mut i = 0;
+		while i < chars.len() {
+			let c = chars[i];
+			match c {
+				' ' | '\t' | '`' | '\\' | ')' | '~' | '.' | 'H' | 'L' | 'M' | 'O' | 'P' | 'S'
+				| 'T' | 'u' | 'v' => i += 1,
+				'"' | '!' | '+' | '{' => {
+					let close = if c == '{' { '}' } else { c };
+					i += 1;
+					while i < chars.len() && chars[i] != close {
+						i += 1;
+					}
+					i += 1;
+				}
+				'(' => {
+					i += 1;
+					let p = parse_digits(&chars, &mut i);
+					if let Some(p) = p.filter(|p| *p >= 2) {
+						// `(3` plays 3 notes in the time of 2, and `(2` plays 2 in the time of 3.
+						let q = match p {
+							3 | 6 => 2,
+							2 | 4 | 8 => 3,
+							_ => 2,
+						};
+						self.tuplet = Some((Ratio::new(q, p), p));
+					}
+				}
+				'-' => {
+					if let Some(Item::Notes { tie, .. }) = self.items.last_mut() {
+						*tie = true;
+					}
+					i += 1;
+				}
+				'>' | '<' => {
+					let mut count = 0;
+					while i < chars.len() && chars[i] == c {
+						count += 1;
+						i += 1;
+					}
+					// `A>B` is `A3/2 B/2`, `A>>B` is `A7/4 B/4`, and so on.
+					let short = Ratio::new(1, 1 << count);
+					let long = Ratio::new((2 << count) - 1, 1 << count);
+					let (previous, next) = if c == '>' {
+						(long, short)
+					} else {
+						(short, long)
+					};
+					match self.items.last_mut() {
+						Some(Item::Notes { length, .. } | Item::Rest(length)) => {
+							*length = length.mul(previous);
+						}
+						_ => return Err(format!("`{c}` must follow a note")),
+					}
+					self.broken = Some(next);
+				}
+				'[' if chars.get(i + 2) == Some(&':') => {
+					// An inline field like `[K:D]`.
+					let start = i + 1;
+					while i < chars.len() && chars[i] != ']' {
+						i += 1;
+					}
+					let field = chars[start..i].iter().collect::<String>();
+					self.field(chars[start] as u8, field[2..].trim())?;
+					i += 1;
+				}
+				'[' if chars.get(i + 1).is_some_and(char::is_ascii_digit) => {
+					i += 1;
+					let ending = parse_digits(&chars, &mut i).unwrap_or(1);
+					self.items.push(Item::Ending(ending as u8));
+				}
+				'[' if chars.get(i + 1) != Some(&'|') => {
+					i += 1;
+					let mut tones = vec![];
+					let mut length = None;
+					while i < chars.len() && chars[i] != ']' {
+						let (tone, note_length) = self.note(&chars, &mut i)?;
+						tones.push(tone);
+						length.get_or_insert(note_length);
+					}
+					i += 1;
+					let length = length
+						.ok_or("empty chord")?
+						.mul(parse_length(&chars, &mut i));
+					self.push_notes(tones, length);
+				}
+				'|' | ':' | '[' | ']' => {
+					let start = i;
+					while i < chars.len() && matches!(chars[i], '|' | ':' | '[' | ']') {
+						i += 1;
+					}
+					let bar = chars[start..i].iter().collect::<String>();
+					if bar.starts_with(':') {
+						self.items.push(Item::RepeatEnd);
+					}
+					if bar.ends_with(':') && bar.len() > 1 {
+						self.items.push(Item::RepeatStart);
+					}
+					if let Some(ending) = parse_digits(&chars, &mut i) {
+						self.items.push(Item::Ending(ending as u8));
+					}
+					self.bar_accidentals.clear();
+				}
+				'z' | 'x' => {
+					i += 1;
+					let length = self.unit().mul(parse_length(&chars, &mut i));
+					self.push_rest(length);
+				}
+				'Z' | 'X' => {
+					i += 1;
+					let bars = parse_digits(&chars, &mut i).unwrap_or(1);
+					self.items
+						.push(Item::Rest(self.meter.mul(Ratio::new(bars, 1))));
+				}
+				_ => {
+					let (tone, length) = self.note(&chars, &mut i)?;
+					self.push_notes(vec![tone], length);
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Parses a note like `^c'3/2`, returning its index in `TONES` and its length.
+	fn note(&mut self, chars: &[char], i: &mut usize) -> Result<(usize, Ratio), String> {
+		let mut accidental = None;
+		while let Some(c) = chars.get(*i) {
+			let change = match c {
+				'^' => 1,
+				'_' => -1,
+				'=' => 0,
+				_ => break,
+			};
+			accidental = Some(accidental.unwrap_or(0) + change);
+			*i += 1;
+		}
+
+		let Some(&letter) = chars.get(*i) else {
+			return Err("expected a note".to_string());
+		};
+		let Some(index) = "CDEFGAB".find(letter.to_ascii_uppercase()) else {
+			return Err(format!("unexpected `{letter}`"));
+		};
+		*i += 1;
+		let mut octave: i64 = if letter.is_ascii_lowercase() { 5 } else { 4 };
+		while let Some(c) = chars.get(*i) {
+			match c {
+				'\'' => octave += 1,
+				',' => octave -= 1,
+				_ => break,
+			}
+			*i += 1;
+		}
+
+		// Accidentals last until the end of the bar.
+		let accidental = match accidental {
+			Some(accidental) => {
+				self.bar_accidentals
+					.retain(|(letter, in_octave, _)| (*letter, *in_octave) != (index, octave));
+				self.bar_accidentals.push((index, octave, accidental));
+				accidental
+			}
+			None => self
+				.bar_accidentals
+				.iter()
+				.find(|(letter, in_octave, _)| (*letter, *in_octave) == (index, octave))
+				.map_or(self.key[index], |(_, _, accidental)| *accidental),
+		};
+
+		const SEMITONES: [i64; 7] = [0, 2, 4, 5, 7, 9, 11];
+		let tone = octave * 12 + SEMITONES[index] + accidental;
+		if !(0..TONES.len() as i64).contains(&tone) {
+			return Err(format!("note out of range `{letter}`"));
+		}
+		let length = self.unit().mul(parse_length(chars, i));
+		Ok((tone as usize, length))
+	}
+
+	/// Applies any tuplet or broken rhythm to `length`.
+	fn adjust_length(&mut self, mut length: Ratio) -> Ratio {
+		if let Some(broken) = self.broken.take() {
+			length = length.mul(broken);
+		}
+		if let Some((multiplier, left)) = &mut self.tuplet {
+			length = length.mul(*multiplier);
+			*left -= 1;
+			if *left == 0 {
+				self.tuplet = None;
+			}
+		}
+		length
+	}
+
+	fn push_notes(&mut self, tones: Vec<usize>, length: Ratio) {
+		let length = self.adjust_length(length);
+		self.items.push(Item::Notes {
+			tones,
+			length,
+			tie: false,
+		});
+	}
+
+	fn push_rest(&mut self, length: Ratio) {
+		let length = self.adjust_length(length);
+		self.items.push(Item::Rest(length));
+	}
+
+	/// Plays through the items, following repeats, and returns the timed notes.
+	fn unroll(self) -> AbcTune {
+		let mut notes: Vec<(usize, Ratio, Ratio)> = vec![];
+		let mut tempos: Vec<(Ratio, f64)> = vec![];
+		let mut time = Ratio::new(0, 1);
+		let mut tied: Vec<usize> = vec![];
+
+		let mut repeat_start = 0;
+		let mut pass = 1;
+		let mut i = 0;
+		while i < self.items.len() {
+			match &self.items[i] {
+				Item::Notes { tones, length, tie } => {
+					for tone in tones {
+						// A tied note carries on instead of starting again.
+						match notes.iter_mut().rev().find(|(other, start, duration)| {
+							other == tone && tied.contains(tone) && start.add(*duration) == time
+						}) {
+							Some((_, _, duration)) => *duration = duration.add(*length),
+							None => notes.push((*tone, time, *length)),
+						}
+					}
+					tied = if *tie { tones.clone() } else { vec![] };
+					time = time.add(*length);
+				}
+				Item::Rest(length) => {
+					tied.clear();
+					time = time.add(*length);
+				}
+				Item::RepeatStart => {
+					repeat_start = i + 1;
+					pass = 1;
+				}
+				Item::RepeatEnd => {
+					if pass == 1 {
+						pass = 2;
+						i = repeat_start;
+						continue;
+					}
+					pass = 1;
+					repeat_start = i + 1;
+				}
+				Item::Ending(ending) => {
+					if *ending as u64 == pass {
+						// The last ending finishes the repeat.
+						if pass > 1 {
+							pass = 1;
+							repeat_start = i + 1;
+						}
+					} else {
+						// Skip endings for other passes, up to the ending for this one or the end of the repeat.
+						i += 1;
+						while i < self.items.len() {
+							match self.items[i] {
+								Item::Ending(ending) if ending as u64 == pass => break,
+								Item::RepeatStart => break,
+								Item::RepeatEnd => {
+									i += 1;
+									// Without an ending for this pass, the repeat is over.
+									if !matches!(self.items.get(i), Some(Item::Ending(_))) {
+										pass = 1;
+										repeat_start = i;
+									}
+									break;
+								}
+								_ => i += 1,
+							}
+						}
+						continue;
+					}
+				}
+				Item::Tempo(bpm) => tempos.push((time, *bpm)),
+			}
+			i += 1;
+		}
+
+		// Measure everything in the largest unit all the times and durations are whole numbers of.
+		let whole = notes
+			.iter()
+			.flat_map(|(_, time, duration)| [time.den, duration.den])
+			.chain(tempos.iter().map(|(time, _)| time.den))
+			.chain([time.den])
+			.fold(1, |whole, den| whole / gcd(whole, den) * den);
+		let units = |ratio: Ratio| ratio.num * (whole / ratio.den);
+
+		AbcTune {
+			notes: notes
+				.into_iter()
+				.map(|(tone, time, duration)| TimedNote {
+					tone,
+					time: units(time),
+					duration: units(duration),
+					track: 0,
+				})
+				.collect(),
+			tempos: tempos
+				.into_iter()
+				.map(|(time, bpm)| TimedTempo {
+					time: units(time),
+					bpm,
+				})
+				.collect(),
+			whole,
+			end: units(time),
+		}
+	}
+}
+
+/// Parses digits at `chars[*i]`, moving past them.
+fn parse_digits(chars: &[char], i: &mut usize) -> Option<u64> {
+	let start = *i;
+	while chars.get(*i).is_some_and(char::is_ascii_digit) {
+		*i += 1;
+	}
+	chars[start..*i].iter().collect::<String>().parse().ok()
+}
+
+/// Parses a length multiplier like `3`, `/`, `//`, `/4`, or `3/2` at `chars[*i]`, moving past it.
+fn parse_length(chars: &[char], i: &mut usize) -> Ratio {
+	let num = parse_digits(chars, i).unwrap_or(1);
+	let mut den = 1;
+	while chars.get(*i) == Some(&'/') {
+		*i += 1;
+		den *= parse_digits(chars, i).unwrap_or(2);
+	}
+	Ratio::new(num, den)
+}
+
+/// Parses a fraction like `3/4`.
+fn parse_fraction(text: &str) -> Option<Ratio> {
+	let (num, den) = text.trim().split_once('/')?;
+	let num = num.trim().parse().ok()?;
+	let den = den.trim().parse().ok().filter(|den| *den > 0)?;
+	Some(Ratio::new(num, den))
+}
+
+/// Parses a key like `G`, `Em`, `Bb`, or `D dor`, returning the accidental of each letter from C to B.
+fn parse_key(value: &str) -> Option<[i64; 7]> {
+	let mut key = [0; 7];
+	let value = value
+		.split_whitespace()
+		.collect::<String>()
+		.to_ascii_lowercase();
+	let mut chars = value.chars().peekable();
+
+	// Count the sharps (or negative flats) of the major key first.
+	let mut sharps: i64 = match chars.next() {
+		None => return Some(key),
+		Some('c') => 0,
+		Some('g') => 1,
+		Some('d') => 2,
+		Some('a') => 3,
+		Some('e') => 4,
+		Some('b') => 5,
+		Some('f') => -1,
+		// Highland bagpipe music, and keys without a signature.
+		Some('h') | Some('n') => return Some(key),
+		_ => return None,
+	};
+	if chars.next_if_eq(&'#').is_some() {
+		sharps += 7;
+	} else if chars.next_if_eq(&'b').is_some() {
+		sharps -= 7;
+	}
+
+	let mode = chars.take(3).collect::<String>();
+	sharps += match mode.as_str() {
+		"" | "maj" | "ion" => 0,
+		"mix" => -1,
+		"dor" => -2,
+		"m" | "min" | "aeo" => -3,
+		"phr" => -4,
+		"loc" => -5,
+		"lyd" => 1,
+		_ if mode.starts_with('m') => -3,
+		_ => return None,
+	};
+
+	// Sharps are added in the order F C G D A E B, and flats in the reverse order.
+	const ORDER: [usize; 7] = [3, 0, 4, 1, 5, 2, 6];
+	for (count, letter) in ORDER.iter().enumerate() {
+		if (count as i64) < sharps {
+			key[*letter] = 1;
+		}
+		if (count as i64) < -sharps {
+			key[ORDER[6 - count]] = -1;
+		}
+	}
+	Some(key)
+}
@@ -1,25 +1,455 @@
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
-use syn::{LitStr, parse_macro_input};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Expr, ExprLit, ExprUnary, Lit, LitStr, Meta, Token, UnOp, parse_macro_input};
+
+mod abc;
+mod midi;
+
+/// The arguments passed to [`declare_song`].
+///
+/// The song string comes first and can be followed by `key = value` options.
+/// Without a song string, the song is read from the file given by the `file` option.
+struct SongArgs {
+	source: Option<LitStr>,
+	options: Punctuated<Meta, Token![,]>,
+}
+
+impl Parse for SongArgs {
+	fn parse(input: ParseStream) -> syn::Result<Self> {
+		let source = if input.peek(LitStr) {
+			let source = input.parse()?;
+			if input.parse::<Option<Token![,]>>()?.is_none() {
+				return Ok(Self {
+					source: Some(source),
+					options: Punctuated::new(),
+				});
+			}
+			Some(source)
+		} else {
+			None
+		};
+		let options = Punctuated::parse_terminated(input)?;
+		Ok(Self { source, options })
+	}
+}
+
+/// How note start times are snapped to the quantization grid.
+#[derive(Clone, Copy, PartialEq)]
+enum Rounding {
+	/// Snap to the nearest grid step.
+	Nearest,
+	/// Snap to the grid step at or before the note.
+	Floor,
+	/// Treat every second grid step as a swung offbeat, sitting two thirds of the way
+	/// through each pair of steps, and snap to the nearest of those.
+	Swing,
+}
+
+impl Rounding {
+	fn from_lit(lit: &LitStr) -> syn::Result<Self> {
+		match lit.value().as_str() {
+			"nearest" => Ok(Self::Nearest),
+			"floor" => Ok(Self::Floor),
+			"swing" => Ok(Self::Swing),
+			_ => Err(syn::Error::new_spanned(
+				lit,
+				"expected \"nearest\", \"floor\", or \"swing\"",
+			)),
+		}
+	}
+
+	/// Snaps `time` to a multiple of `grid`.
+	fn quantize(self, time: f64, grid: f64) -> usize {
+		(self.steps(time, grid) * grid) as usize
+	}
+
+	/// Returns how many steps of `grid` `time` snaps to.
+	fn steps(self, time: f64, grid: f64) -> f64 {
+		match self {
+			Self::Nearest => (time / grid).round(),
+			Self::Floor => (time / grid).floor(),
+			Self::Swing => {
+				let pair = (time / (grid * 2.)).floor();
+				let within = time / grid - pair * 2.;
+				// The candidates are the downbeat (0), the swung offbeat (4/3), and the next downbeat (2).
+				let step = if within < 2. / 3. {
+					0.
+				} else if within < 5. / 3. {
+					1.
+				} else {
+					2.
+				};
+				pair * 2. + step
+			}
+		}
+	}
+}
+
+/// The options that can follow the song string in [`declare_song`].
+struct SongOptions {
+	/// If set, warn about notes more than this many cents out of tune.
+	accuracy_report: Option<f64>,
+	/// If set, report the size of the generated tables.
+	report_size: bool,
+	/// The system clock frequency the song will be played with.
+	clock_hz: f64,
+	/// The PWM clock divider the song will be played with.
+	divider: f64,
+	/// The number of steps notes are snapped to.
+	grid: f64,
+	/// How note start times are snapped to `grid`.
+	rounding: Rounding,
+	/// Instruments whose notes are left out.
+	drop_tracks: Vec<i64>,
+	/// Pairs of `(instrument, track)`, moving an instrument's notes onto another track.
+	merge_tracks: Vec<(i64, i64)>,
+	/// Pairs of `(instrument, octaves)`, shifting an instrument's notes up or down.
+	octave_shifts: Vec<(i64, i64)>,
+	/// The file to read the song from, relative to the crate root.
+	file: Option<LitStr>,
+	/// The song's title, author, and tempo in beats per minute, for its `SongMetadata`.
+	title: Option<LitStr>,
+	author: Option<LitStr>,
+	bpm: Option<u16>,
+}
+
+impl SongOptions {
+	fn from_args(args: &SongArgs) -> syn::Result<Self> {
+		let mut options = Self {
+			accuracy_report: None,
+			report_size: false,
+			clock_hz: 150_000_000.,
+			divider: 64.,
+			grid: 1.,
+			rounding: Rounding::Nearest,
+			drop_tracks: vec![],
+			merge_tracks: vec![],
+			octave_shifts: vec![],
+			file: None,
+			title: None,
+			author: None,
+			bpm: None,
+		};
+
+		for option in &args.options {
+			let Meta::NameValue(name_value) = option else {
+				return Err(syn::Error::new_spanned(option, "expected `name = value`"));
+			};
+
+			if name_value.path.is_ident("rounding") {
+				options.rounding = Rounding::from_lit(parse_string(&name_value.value)?)?;
+				continue;
+			} else if name_value.path.is_ident("file") {
+				if args.source.is_some() {
+					return Err(syn::Error::new_spanned(
+						option,
+						"`file` can't be used with a song string",
+					));
+				}
+				options.file = Some(parse_string(&name_value.value)?.clone());
+				continue;
+			} else if name_value.path.is_ident("report_size") {
+				options.report_size = parse_bool(&name_value.value)?;
+				continue;
+			} else if name_value.path.is_ident("title") {
+				options.title = Some(parse_string(&name_value.value)?.clone());
+				continue;
+			} else if name_value.path.is_ident("author") {
+				options.author = Some(parse_string(&name_value.value)?.clone());
+				continue;
+			}
+
+			if name_value.path.is_ident("drop_tracks") {
+				options.drop_tracks = parse_list(&name_value.value, parse_integer)?;
+				continue;
+			} else if name_value.path.is_ident("merge_tracks") {
+				options.merge_tracks = parse_list(&name_value.value, parse_pair)?;
+				continue;
+			} else if name_value.path.is_ident("octave_shifts") {
+				options.octave_shifts = parse_list(&name_value.value, parse_pair)?;
+				continue;
+			}
+
+			let value = parse_number(&name_value.value)?;
+			if name_value.path.is_ident("bpm") {
+				if !(1. ..=u16::MAX as f64).contains(&value) || value.fract() != 0. {
+					return Err(syn::Error::new_spanned(
+						&name_value.value,
+						"expected a whole number of beats per minute",
+					));
+				}
+				options.bpm = Some(value as u16);
+			} else if name_value.path.is_ident("accuracy_report") {
+				options.accuracy_report = Some(value);
+			} else if name_value.path.is_ident("clock_hz") {
+				options.clock_hz = value;
+			} else if name_value.path.is_ident("divider") {
+				options.divider = value;
+			} else if name_value.path.is_ident("grid") {
+				if value < 1. || value.fract() != 0. {
+					return Err(syn::Error::new_spanned(
+						&name_value.value,
+						"expected a whole number of steps",
+					));
+				}
+				options.grid = value;
+			} else {
+				return Err(syn::Error::new_spanned(&name_value.path, "unknown option"));
+			}
+		}
+
+		Ok(options)
+	}
+}
+
+/// Parses an integer or float literal expression.
+fn parse_number(expr: &Expr) -> syn::Result<f64> {
+	match expr {
+		Expr::Lit(ExprLit {
+			lit: Lit::Int(int), ..
+		}) => int.base10_parse::<u64>().map(|n| n as f64),
+		Expr::Lit(ExprLit {
+			lit: Lit::Float(float),
+			..
+		}) => float.base10_parse::<f64>(),
+		_ => Err(syn::Error::new_spanned(expr, "expected a number")),
+	}
+}
+
+/// Parses a `true` or `false` literal expression.
+fn parse_bool(expr: &Expr) -> syn::Result<bool> {
+	match expr {
+		Expr::Lit(ExprLit {
+			lit: Lit::Bool(bool),
+			..
+		}) => Ok(bool.value),
+		_ => Err(syn::Error::new_spanned(expr, "expected `true` or `false`")),
+	}
+}
+
+/// Parses a string literal expression.
+fn parse_string(expr: &Expr) -> syn::Result<&LitStr> {
+	match expr {
+		Expr::Lit(ExprLit {
+			lit: Lit::Str(lit), ..
+		}) => Ok(lit),
+		_ => Err(syn::Error::new_spanned(expr, "expected a string")),
+	}
+}
+
+/// Reads the song file at `path`, relative to the root of the crate being compiled.
+///
+/// Returns the song and a statement that makes Cargo rebuild when the file changes.
+fn read_song_file(path: &LitStr) -> syn::Result<(String, TokenStream2)> {
+	let full_path = crate_path(path);
+	let song = std::fs::read_to_string(&full_path).map_err(|error| {
+		syn::Error::new_spanned(
+			path,
+			format!("couldn't read {}: {error}", full_path.display()),
+		)
+	})?;
+
+	let full_path = full_path.to_string_lossy().into_owned();
+	let tracking = quote! {
+		const _: &str = include_str!(#full_path);
+	};
+	Ok((song.trim().to_string(), tracking))
+}
+
+/// Reads the MIDI file at `path`, relative to the root of the crate being compiled.
+///
+/// Returns the file and a statement that makes Cargo rebuild when it changes.
+fn read_midi_file(path: &LitStr) -> syn::Result<(Vec<u8>, TokenStream2)> {
+	let full_path = crate_path(path);
+	let bytes = std::fs::read(&full_path).map_err(|error| {
+		syn::Error::new_spanned(
+			path,
+			format!("couldn't read {}: {error}", full_path.display()),
+		)
+	})?;
+
+	let full_path = full_path.to_string_lossy().into_owned();
+	let tracking = quote! {
+		const _: &[u8] = include_bytes!(#full_path);
+	};
+	Ok((bytes, tracking))
+}
+
+/// Returns `path` joined to the root of the crate being compiled.
+fn crate_path(path: &LitStr) -> std::path::PathBuf {
+	let root = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+	std::path::Path::new(&root).join(path.value())
+}
+
+/// Parses an integer literal expression, which may be negative.
+fn parse_integer(expr: &Expr) -> syn::Result<i64> {
+	match expr {
+		Expr::Lit(ExprLit {
+			lit: Lit::Int(int), ..
+		}) => int.base10_parse::<i64>(),
+		Expr::Unary(ExprUnary {
+			op: UnOp::Neg(_),
+			expr,
+			..
+		}) => parse_integer(expr).map(|n| -n),
+		_ => Err(syn::Error::new_spanned(expr, "expected an integer")),
+	}
+}
+
+/// Parses a tuple expression of two integers, like `(1, -2)`.
+fn parse_pair(expr: &Expr) -> syn::Result<(i64, i64)> {
+	match expr {
+		Expr::Tuple(tuple) if tuple.elems.len() == 2 => Ok((
+			parse_integer(&tuple.elems[0])?,
+			parse_integer(&tuple.elems[1])?,
+		)),
+		_ => Err(syn::Error::new_spanned(expr, "expected `(a, b)`")),
+	}
+}
+
+/// Parses an array expression, using `parse` on each element.
+fn parse_list<T>(expr: &Expr, parse: fn(&Expr) -> syn::Result<T>) -> syn::Result<Vec<T>> {
+	match expr {
+		Expr::Array(array) => array.elems.iter().map(parse).collect(),
+		_ => Err(syn::Error::new_spanned(expr, "expected an array")),
+	}
+}
+
+/// The frequency of every note from C0 to B9.
+#[rustfmt::skip]
+const TONES: &[(&str, u16)] = &[
+	("C0", 16),("C#0", 17),("D0", 18),("D#0", 19),("E0", 21),("F0", 22),("F#0", 23),("G0", 24),("G#0", 26),("A0", 28),("A#0", 29),("B0", 31),
+	("C1", 33),("C#1", 35),("D1", 37),("D#1", 39),("E1", 41),("F1", 44),("F#1", 46),("G1", 49),("G#1", 52),("A1", 55),("A#1", 58),("B1", 62),
+	("C2", 65),("C#2", 69),("D2", 73),("D#2", 78),("E2", 82),("F2", 87),("F#2", 92),("G2", 98),("G#2", 104),("A2", 110),("A#2", 117),("B2", 123),
+	("C3", 131),("C#3", 139),("D3", 147),("D#3", 156),("E3", 165),("F3", 175),("F#3", 185),("G3", 196),("G#3", 208),("A3", 220),("A#3", 233),("B3", 247),
+	("C4", 262),("C#4", 277),("D4", 294),("D#4", 311),("E4", 330),("F4", 349),("F#4", 370),("G4", 392),("G#4", 415),("A4", 440),("A#4", 466),("B4", 494),
+	("C5", 523),("C#5", 554),("D5", 587),("D#5", 622),("E5", 659),("F5", 698),("F#5", 740),("G5", 784),("G#5", 831),("A5", 880),("A#5", 932),("B5", 988),
+	("C6", 1047),("C#6", 1109),("D6", 1175),("D#6", 1245),("E6", 1319),("F6", 1397),("F#6", 1480),("G6", 1568),("G#6", 1661),("A6", 1760),("A#6", 1865),("B6", 1976),
+	("C7", 2093),("C#7", 2217),("D7", 2349),("D#7", 2489),("E7", 2637),("F7", 2794),("F#7", 2960),("G7", 3136),("G#7", 3322),("A7", 3520),("A#7", 3729),("B7", 3951),
+	("C8", 4186),("C#8", 4435),("D8", 4699),("D#8", 4978),("E8", 5274),("F8", 5588),("F#8", 5920),("G8", 6272),("G#8", 6645),("A8", 7040),("A#8", 7459),("B8", 7902),
+	("C9", 8372),("C#9", 8870),("D9", 9397),("D#9", 9956),("E9", 10548),("F9", 11175),("F#9", 11840),("G9", 12544),("G#9", 13290),("A9", 14080),("A#9", 14917),("B9", 15804),
+];
+
+/// Generates a statement that produces a compile-time warning with `message`.
+///
+/// There is no stable way for a proc macro to emit warnings, so this uses a deprecated constant instead.
+fn warning(message: &str) -> TokenStream2 {
+	// Each warning gets its own block, so a song can have several.
+	quote! {
+		{
+			#[deprecated(note = #message)]
+			#[allow(non_upper_case_globals)]
+			const declare_song_warning: () = ();
+			let _ = declare_song_warning;
+		}
+	}
+}
+
+/// How many bytes each part of a song takes on a 32-bit target, for [`size_report`].
+const BEAT_BYTES: usize = 8;
+const NOTE_BYTES: usize = 8;
+const EVENT_BYTES: usize = 16;
+/// The `Song` itself, including the 20 bytes of its `SongMetadata`.
+const SONG_BYTES: usize = 40;
+
+/// Generates a compile-time warning with the size of a song's tables, for the `report_size` option.
+///
+/// `beats` is the notes starting on each beat. Identical slices of notes are counted once,
+/// since the compiler usually merges them. `strings` is how many bytes the song's title and author take.
+fn size_report(beats: &[Option<&[TokenStream2]>], events: usize, strings: usize) -> TokenStream2 {
+	let mut slices = beats
+		.iter()
+		.flatten()
+		.map(|notes| {
+			notes
+				.iter()
+				.map(ToString::to_string)
+				.collect::<Vec<String>>()
+		})
+		.collect::<Vec<Vec<String>>>();
+	let notes = slices.iter().map(Vec::len).sum::<usize>();
+	slices.sort();
+	slices.dedup();
+	let unique_notes = slices.iter().map(Vec::len).sum::<usize>();
+
+	let bytes = SONG_BYTES
+		+ beats.len() * BEAT_BYTES
+		+ unique_notes * NOTE_BYTES
+		+ events * EVENT_BYTES
+		+ strings;
+	warning(&format!(
+		"song size: {} beats, {notes} notes in {} unique slices, {events} events, about {bytes} bytes",
+		beats.len(),
+		slices.len(),
+	))
+}
+
+/// Splits the attributes off a note like `C5*3?50`, returning the note name, its chance after `?`,
+/// and its ratchet after `*`, in either order. Returns `None` if an attribute isn't valid.
+/// This mirrors the note attributes `buzzer_music` parses at runtime.
+fn split_note_attributes(note: &str) -> Option<(&str, u8, u8)> {
+	let (note, mut attributes) = note.split_at(note.find(['?', '*']).unwrap_or(note.len()));
+	let mut chance = 100;
+	let mut ratchet = 1;
+	while let Some(marker) = attributes.chars().next() {
+		let rest = &attributes[1..];
+		let (value, next) = rest.split_at(rest.find(['?', '*']).unwrap_or(rest.len()));
+		match (marker, value.parse().ok()?) {
+			('?', value) if value <= 100 => chance = value,
+			('*', value) if value > 0 => ratchet = value,
+			_ => return None,
+		}
+		attributes = next;
+	}
+	Some((note, chance, ratchet))
+}
+
+/// Returns the frequency a PWM actually produces when asked for `frequency`.
+/// This mirrors the `top` calculation used by `buzzer_music`.
+fn achievable_frequency(frequency: f64, clock_hz: f64, divider: f64) -> Option<f64> {
+	let result = clock_hz / (frequency * divider);
+	if !(1.0..=65535.0).contains(&result) {
+		return None;
+	}
+	Some(clock_hz / (divider * result.trunc()))
+}
 
 /// Given a string literal expression, parses it and generates a [`buzzer_music::Song`] expression.
+///
+/// A note name followed by `?` and a percentage, like `C5?50`, gives the note that chance of playing
+/// each time its beat is reached (see `buzzer_music::NoteAndDuration::chance`). A note name followed
+/// by `*` and a count, like `C5*3`, plays the note as that many evenly spaced hits
+/// (see `buzzer_music::NoteAndDuration::ratchet`). Both can be used, like `C5*3?50`.
+///
+/// Instead of a string, `file = "<path>"` reads the song from a file, relative to the crate's
+/// `Cargo.toml`. The song is rebuilt whenever the file changes.
+///
+/// Options can follow the string:
+/// - `accuracy_report = <cents>` warns about notes whose achievable PWM frequency is more than
+///   `<cents>` away from the intended pitch.
+/// - `clock_hz = <hz>` and `divider = <div>` set the PWM configuration used by `accuracy_report`.
+/// - `report_size = true` warns with the number of beats, notes, and unique slices of notes in the song,
+///   and roughly how many bytes of flash its tables take, to help keep several songs within budget.
+/// - `grid = <beats>` snaps note start times to multiples of `<beats>` of the song's own beats, and rounds
+///   durations up to whole multiples of it. Defaults to `1`.
+/// - `rounding = "nearest" | "floor" | "swing"` chooses how start times are snapped. `"swing"` expects
+///   every second grid step to be played late, like swung eighths. Defaults to `"nearest"`.
+///
+/// These options help fit songs with many instruments onto a few buzzers:
+/// - `drop_tracks = [<instrument>, ...]` leaves out every note of the listed instruments.
+/// - `merge_tracks = [(<instrument>, <track>), ...]` puts an instrument's notes on another track.
+/// - `octave_shifts = [(<instrument>, <octaves>), ...]` moves an instrument's notes up or down.
+///   Notes shifted out of the supported range are left out.
+///
+/// The song's `SongMetadata` can be given too, for a jukebox menu to list it by name:
+/// - `title = "<title>"` and `author = "<author>"`.
+/// - `bpm = <bpm>` is the tempo the song was written at, which `Song::ticks_per_beat` turns
+///   into the `ticks_per_beat` for a player.
 #[proc_macro]
 pub fn declare_song(input: TokenStream) -> TokenStream {
-	#[rustfmt::skip]
-	const TONES: &[(&'static str, u16)] = &[
-		("C0", 16),("C#0", 17),("D0", 18),("D#0", 19),("E0", 21),("F0", 22),("F#0", 23),("G0", 24),("G#0", 26),("A0", 28),("A#0", 29),("B0", 31),
-		("C1", 33),("C#1", 35),("D1", 37),("D#1", 39),("E1", 41),("F1", 44),("F#1", 46),("G1", 49),("G#1", 52),("A1", 55),("A#1", 58),("B1", 62),
-		("C2", 65),("C#2", 69),("D2", 73),("D#2", 78),("E2", 82),("F2", 87),("F#2", 92),("G2", 98),("G#2", 104),("A2", 110),("A#2", 117),("B2", 123),
-		("C3", 131),("C#3", 139),("D3", 147),("D#3", 156),("E3", 165),("F3", 175),("F#3", 185),("G3", 196),("G#3", 208),("A3", 220),("A#3", 233),("B3", 247),
-		("C4", 262),("C#4", 277),("D4", 294),("D#4", 311),("E4", 330),("F4", 349),("F#4", 370),("G4", 392),("G#4", 415),("A4", 440),("A#4", 466),("B4", 494),
-		("C5", 523),("C#5", 554),("D5", 587),("D#5", 622),("E5", 659),("F5", 698),("F#5", 740),("G5", 784),("G#5", 831),("A5", 880),("A#5", 932),("B5", 988),
-		("C6", 1047),("C#6", 1109),("D6", 1175),("D#6", 1245),("E6", 1319),("F6", 1397),("F#6", 1480),("G6", 1568),("G#6", 1661),("A6", 1760),("A#6", 1865),("B6", 1976),
-		("C7", 2093),("C#7", 2217),("D7", 2349),("D#7", 2489),("E7", 2637),("F7", 2794),("F#7", 2960),("G7", 3136),("G#7", 3322),("A7", 3520),("A#7", 3729),("B7", 3951),
-		("C8", 4186),("C#8", 4435),("D8", 4699),("D#8", 4978),("E8", 5274),("F8", 5588),("F#8", 5920),("G8", 6272),("G#8", 6645),("A8", 7040),("A#8", 7459),("B8", 7902),
-		("C9", 8372),("C#9", 8870),("D9", 9397),("D#9", 9956),("E9", 10548),("F9", 11175),("F#9", 11840),("G9", 12544),("G#9", 13290),("A9", 14080),("A#9", 14917),("B9", 15804),
-	];
-
 	// We cannot store a [`std::collections::HashMap`] in `const`, so just make it here?
 	let mut notes_map = std::collections::HashMap::new();
 	for tone in TONES {
@@ -27,17 +457,94 @@ pub fn declare_song(input: TokenStream) -> TokenStream {
 	}
 
 	// Get input as a [`String`].
-	let string = parse_macro_input!(input as LitStr).value();
+	let args = parse_macro_input!(input as SongArgs);
+	let options = match SongOptions::from_args(&args) {
+		Ok(options) => options,
+		Err(error) => return error.to_compile_error().into(),
+	};
+	let (string, tracking, literal) = match (&args.source, &options.file) {
+		(Some(source), _) => (source.value(), quote! {}, source),
+		(None, Some(file)) => match read_song_file(file) {
+			Ok((song, tracking)) => (song, tracking, file),
+			Err(error) => return error.to_compile_error().into(),
+		},
+		(None, None) => {
+			return syn::Error::new(
+				proc_macro2::Span::call_site(),
+				"expected a song string or `file = \"<path>\"`",
+			)
+			.to_compile_error()
+			.into();
+		}
+	};
 
 	// Parse the note data.
-	// Each entry has three values: (time, note name, duration)
-	let mut note_data: Vec<(usize, &str, usize)> = vec![];
+	// Each entry has six values: (time, note name, duration, instrument, chance, ratchet)
+	let mut note_data: Vec<(usize, &str, usize, u8, u8, u8)> = vec![];
 	let mut end: usize = 0;
-	for note in string.split(";") {
-		let snote = note.split(" ").collect::<Vec<&str>>();
-		let time: usize = snote[0].trim().parse::<f64>().unwrap().round() as usize;
-		let duration: usize = snote[2].trim().parse::<f64>().unwrap().ceil() as usize;
-		note_data.push((time, snote[1], duration));
+	for note in string
+		.split(';')
+		.map(str::trim)
+		.filter(|note| !note.is_empty())
+	{
+		let snote = note.split_whitespace().collect::<Vec<&str>>();
+		let (Some(Ok(time)), Some(Ok(duration)), Ok(instrument)) = (
+			snote.first().map(|time| time.parse::<f64>()),
+			snote.get(2).map(|duration| duration.parse::<f64>()),
+			snote
+				.get(3)
+				.map_or(Ok(0), |instrument| instrument.parse::<i64>()),
+		) else {
+			return syn::Error::new_spanned(
+				literal,
+				format!(
+					"invalid entry `{note}`, expected `<time> <note> <duration>` and an optional track"
+				),
+			)
+			.to_compile_error()
+			.into();
+		};
+		let time = options.rounding.quantize(time, options.grid);
+		let duration = ((duration / options.grid).ceil() * options.grid) as usize;
+
+		if options.drop_tracks.contains(&instrument) {
+			continue;
+		}
+		let Some((mut name, chance, ratchet)) = split_note_attributes(snote[1]) else {
+			return syn::Error::new(
+				proc_macro2::Span::call_site(),
+				format!(
+					"invalid note `{}`, expected `?` and a percentage or `*` and a count after its name",
+					snote[1]
+				),
+			)
+			.to_compile_error()
+			.into();
+		};
+		if let Some((_, octaves)) = options
+			.octave_shifts
+			.iter()
+			.find(|(shifted, _)| *shifted == instrument)
+			&& let Some(index) = TONES.iter().position(|tone| tone.0 == name)
+		{
+			match TONES.get((index as i64 + octaves * 12) as usize) {
+				Some(tone) if index as i64 + octaves * 12 >= 0 => name = tone.0,
+				_ => continue,
+			}
+		}
+		let track = options
+			.merge_tracks
+			.iter()
+			.find(|(merged, _)| *merged == instrument)
+			.map_or(instrument, |(_, track)| *track);
+		note_data.push((
+			time,
+			name,
+			duration,
+			track.clamp(0, u8::MAX as i64) as u8,
+			chance,
+			ratchet,
+		));
 
 		let test_end: usize = time + duration;
 		if end < test_end {
@@ -47,7 +554,7 @@ pub fn declare_song(input: TokenStream) -> TokenStream {
 
 	// Convert the parsed data into [`buzzer_music::NoteAndDuration`] expressions.
 	let mut notes: Vec<Option<Vec<TokenStream2>>> = vec![None; end];
-	for note in note_data {
+	for note in &note_data {
 		let beat = note.0;
 		if notes[beat].is_none() {
 			notes[beat] = Some(vec![]);
@@ -58,10 +565,16 @@ pub fn declare_song(input: TokenStream) -> TokenStream {
 		{
 			let frequency = *frequency;
 			let duration = note.2 as u16;
+			let track = note.3;
+			let chance = note.4;
+			let ratchet = note.5;
 			notes_this_beat.push(quote! {
 				buzzer_music::NoteAndDuration {
 					frequency: #frequency,
-					duration: #duration
+					duration: #duration,
+					track: #track,
+					chance: #chance,
+					ratchet: #ratchet
 				}
 			});
 		}
@@ -69,6 +582,49 @@ pub fn declare_song(input: TokenStream) -> TokenStream {
 
 	let end = (end as f32 / 8.).ceil() as u16 * 8;
 
+	// Report notes that will sound out of tune with the configured PWM.
+	let mut warnings = vec![];
+	if let Some(max_cents) = options.accuracy_report {
+		let mut names = note_data.iter().map(|note| note.1).collect::<Vec<&str>>();
+		names.sort_by_key(|name| TONES.iter().position(|tone| tone.0 == *name));
+		names.dedup();
+
+		let mut report = vec![];
+		for name in names {
+			let Some(index) = TONES.iter().position(|tone| tone.0 == name) else {
+				continue;
+			};
+
+			// `TONES` starts at C0, which is 57 semitones below A4.
+			let pitch = 440. * 2f64.powf((index as f64 - 57.) / 12.);
+			match achievable_frequency(TONES[index].1 as f64, options.clock_hz, options.divider) {
+				Some(frequency) => {
+					let cents = 1200. * (frequency / pitch).log2();
+					if cents.abs() > max_cents {
+						report.push(format!("{name}: {frequency:.2} Hz ({cents:+.1} cents)"));
+					}
+				}
+				None => report.push(format!("{name}: cannot be played")),
+			}
+		}
+
+		if !report.is_empty() {
+			warnings.push(warning(&format!(
+				"notes more than {max_cents} cents out of tune:\n{}",
+				report.join("\n")
+			)));
+		}
+	}
+	if options.report_size {
+		let beats = notes.iter().map(Option::as_deref).collect::<Vec<_>>();
+		let strings = [&options.title, &options.author]
+			.into_iter()
+			.flatten()
+			.map(|string| string.value().len())
+			.sum();
+		warnings.push(size_report(&beats, 0, strings));
+	}
+
 	// Collect all the notes as array declarations if they exist.
 	// Generate a `None` value if not.
 	let code = notes
@@ -84,12 +640,713 @@ pub fn declare_song(input: TokenStream) -> TokenStream {
 		})
 		.collect::<Vec<TokenStream2>>();
 
+	let optional = |value: Option<TokenStream2>| match value {
+		Some(value) => quote! { Some(#value) },
+		None => quote! { None },
+	};
+	let title = optional(options.title.as_ref().map(|title| quote! { #title }));
+	let author = optional(options.author.as_ref().map(|author| quote! { #author }));
+	let bpm = optional(options.bpm.map(|bpm| quote! { #bpm }));
+
 	// Put everything together.
 	quote! {
-		buzzer_music::Song {
-			notes: &[#(#code),*],
-			end: #end,
+		{
+			#tracking
+			#(#warnings)*
+			buzzer_music::Song {
+				notes: &[#(#code),*],
+				end: #end,
+				events: &[],
+				metadata: buzzer_music::SongMetadata {
+					title: #title,
+					author: #author,
+					bpm: #bpm,
+				},
+			}
 		}
 	}
 	.into()
 }
+
+/// A note or pause in an RTTTL ringtone.
+struct RtttlNote {
+	/// The index of the note in `TONES`, or `None` for a pause.
+	tone: Option<usize>,
+	/// How long the note lasts, in 64th notes.
+	duration: u64,
+}
+
+/// The length of a whole note in the units used by [`parse_rtttl`].
+const RTTTL_WHOLE: u64 = 64;
+
+/// Parses an RTTTL ringtone like `name:d=4,o=5,b=120:8c,8e,g`.
+///
+/// Returns the notes and the tempo, in quarter notes per minute.
+fn parse_rtttl(text: &str) -> Result<(Vec<RtttlNote>, u64), String> {
+	let mut sections = text.splitn(3, ':');
+	let (Some(_name), Some(header), Some(body)) =
+		(sections.next(), sections.next(), sections.next())
+	else {
+		return Err("expected `name:settings:notes`".to_string());
+	};
+
+	let mut default_duration = 4;
+	let mut default_octave = 6;
+	let mut bpm = 63;
+	for setting in header.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+		let Some((key, value)) = setting.split_once('=') else {
+			return Err(format!("invalid setting `{setting}`"));
+		};
+		let value = value
+			.trim()
+			.parse::<u64>()
+			.map_err(|_| format!("invalid setting `{setting}`"))?;
+		match key.trim().to_ascii_lowercase().as_str() {
+			"d" => default_duration = value,
+			"o" => default_octave = value,
+			"b" => bpm = value,
+			_ => {}
+		}
+	}
+	if bpm == 0 {
+		return Err("the tempo can't be 0".to_string());
+	}
+
+	let mut notes = vec![];
+	for token in body.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+		let token = token.to_ascii_lowercase();
+		let digits = token.bytes().take_while(u8::is_ascii_digit).count();
+		let duration = match digits {
+			0 => default_duration,
+			_ => token[..digits].parse().unwrap_or(0),
+		};
+		if !matches!(duration, 1 | 2 | 4 | 8 | 16 | 32) {
+			return Err(format!("invalid duration in `{token}`"));
+		}
+
+		let mut rest = token[digits..].chars().peekable();
+		let class = match rest.next() {
+			Some('c') => Some(0),
+			Some('d') => Some(2),
+			Some('e') => Some(4),
+			Some('f') => Some(5),
+			Some('g') => Some(7),
+			Some('a') => Some(9),
+			Some('b' | 'h') => Some(11),
+			Some('p') => None,
+			_ => return Err(format!("invalid note `{token}`")),
+		};
+		let sharp = rest.next_if_eq(&'#').is_some();
+		let mut dotted = rest.next_if_eq(&'.').is_some();
+		let octave = match rest.next_if(char::is_ascii_digit) {
+			Some(octave) => octave as u64 - '0' as u64,
+			None => default_octave,
+		};
+		dotted |= rest.next_if_eq(&'.').is_some();
+		if rest.next().is_some() {
+			return Err(format!("invalid note `{token}`"));
+		}
+
+		let tone = match class {
+			Some(class) => {
+				let index = octave as usize * 12 + class + sharp as usize;
+				if index >= TONES.len() {
+					return Err(format!("note out of range `{token}`"));
+				}
+				Some(index)
+			}
+			None => None,
+		};
+		let duration = if dotted {
+			RTTTL_WHOLE / duration * 3 / 2
+		} else {
+			RTTTL_WHOLE / duration
+		};
+		notes.push(RtttlNote { tone, duration });
+	}
+
+	Ok((notes, bpm))
+}
+
+/// Given a string literal expression containing an RTTTL (Nokia ringtone) song,
+/// parses it and generates a [`buzzer_music::Song`] expression.
+///
+/// Each beat of the song lasts as long as its shortest note, and the song starts with a
+/// `SetTempo` event matching the ringtone's tempo when the player ticks every `tick_ms` milliseconds.
+///
+/// Options can follow the string:
+/// - `tick_ms = <ms>` sets how often the player ticks. Defaults to `40`.
+/// - `report_size = true` warns with the size of the song's tables, like in [`declare_song`].
+/// - `beats_per_quarter = <beats>` snaps notes to a grid of `<beats>` beats per quarter note, like in
+///   [`declare_song_from_midi`]. Notes aren't snapped by default.
+/// - `rounding = "nearest" | "floor" | "swing"` chooses how start times are snapped, like in [`declare_song`].
+#[proc_macro]
+pub fn declare_rtttl(input: TokenStream) -> TokenStream {
+	let args = parse_macro_input!(input as SongArgs);
+	let Some(source) = &args.source else {
+		return syn::Error::new(proc_macro2::Span::call_site(), "expected a song string")
+			.to_compile_error()
+			.into();
+	};
+
+	let options = match TimedOptions::from_args(&args) {
+		Ok(options) => options,
+		Err(error) => return error.to_compile_error().into(),
+	};
+
+	let (rtttl, bpm) = match parse_rtttl(&source.value()) {
+		Ok(rtttl) => rtttl,
+		Err(error) => {
+			return syn::Error::new_spanned(source, error)
+				.to_compile_error()
+				.into();
+		}
+	};
+
+	// Rests only move the time on, but are kept at the end so the ringtone's length stays the same.
+	let mut time = 0;
+	let mut notes = vec![];
+	for note in &rtttl {
+		if let Some(tone) = note.tone {
+			notes.push(TimedNote {
+				tone,
+				time,
+				duration: note.duration,
+				track: 0,
+			});
+		}
+		time += note.duration;
+	}
+	let tempos = vec![TimedTempo {
+		time: 0,
+		bpm: bpm as f64,
+	}];
+
+	timed_song(notes, tempos, RTTTL_WHOLE, time, &options).into()
+}
+
+/// Returns the greatest common divisor of `a` and `b`.
+fn gcd(a: u64, b: u64) -> u64 {
+	if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// A note of a song whose timing is measured in fractions of a whole note, like MML and ABC songs.
+struct TimedNote {
+	/// The index of the note in `TONES`.
+	tone: usize,
+	/// When the note starts and how long it lasts, in fractions of a whole note (see [`timed_song`]).
+	time: u64,
+	duration: u64,
+	/// The voice the note belongs to, used as its track.
+	track: u8,
+}
+
+/// A tempo change in a song made of [`TimedNote`]s.
+struct TimedTempo {
+	/// When the tempo changes, in the same units as [`TimedNote::time`].
+	time: u64,
+	/// The new tempo, in quarter notes per minute.
+	bpm: f64,
+}
+
+/// The length of a whole note in the units used by [`parse_mml`]. Dividing it by any valid
+/// MML length (`1`, `2`, `3`, `4`, `6`, `8`, ... `64`, `96`, `192`) gives an even number, so dots work.
+const MML_WHOLE: u64 = 384;
+
+/// Parses an MML string like `t120 o4 l8 cdefgab>c`. Voices are separated by `,`.
+///
+/// Returns the notes, the tempo changes, and when the longest voice ends, including its rests.
+fn parse_mml(text: &str) -> Result<(Vec<TimedNote>, Vec<TimedTempo>, u64), String> {
+	let mut notes: Vec<TimedNote> = vec![];
+	let mut tempos = vec![];
+	let mut end = 0;
+
+	for (track, voice) in text.split(',').enumerate() {
+		let track = track.min(u8::MAX as usize) as u8;
+		let mut octave: i64 = 4;
+		let mut default_length = MML_WHOLE / 4;
+		let mut time = 0;
+		let mut tied = false;
+
+		let mut chars = voice.chars().filter(|c| !c.is_whitespace()).peekable();
+		while let Some(command) = chars.next() {
+			let command = command.to_ascii_lowercase();
+
+			match command {
+				'a'..='g' | 'r' => {
+					let class = match command {
+						'c' => 0,
+						'd' => 2,
+						'e' => 4,
+						'f' => 5,
+						'g' => 7,
+						'a' => 9,
+						'b' => 11,
+						_ => -1,
+					};
+					let mut semitone = class;
+					if chars.next_if(|c| *c == '+' || *c == '#').is_some() {
+						semitone += 1;
+					} else if chars.next_if_eq(&'-').is_some() {
+						semitone -= 1;
+					}
+
+					let mut length = match mml_number(&mut chars) {
+						Some(length) => mml_length(length)?,
+						None => default_length,
+					};
+					let mut dot = length / 2;
+					while chars.next_if_eq(&'.').is_some() {
+						length += dot;
+						dot /= 2;
+					}
+
+					if class >= 0 {
+						let index = octave * 12 + semitone;
+						if !(0..TONES.len() as i64).contains(&index) {
+							return Err(format!("note out of range at octave {octave}"));
+						}
+						let tone = index as usize;
+						// A tie extends the previous note instead of starting a new one.
+						match notes.last_mut() {
+							Some(last) if tied && last.tone == tone && last.track == track => {
+								last.duration += length;
+							}
+							_ => notes.push(TimedNote {
+								tone,
+								time,
+								duration: length,
+								track,
+							}),
+						}
+					}
+					time += length;
+					tied = chars.next_if_eq(&'&').is_some();
+				}
+				'o' => {
+					octave = mml_number(&mut chars).ok_or("expected an octave after `o`")? as i64
+				}
+				'>' => octave += 1,
+				'<' => octave -= 1,
+				'l' => {
+					default_length =
+						mml_length(mml_number(&mut chars).ok_or("expected a length after `l`")?)?
+				}
+				't' => {
+					let bpm = mml_number(&mut chars).ok_or("expected a tempo after `t`")?;
+					if bpm == 0 {
+						return Err("the tempo can't be 0".to_string());
+					}
+					tempos.push(TimedTempo {
+						time,
+						bpm: bpm as f64,
+					});
+				}
+				// Volume isn't supported by buzzers, so it's ignored.
+				'v' => {
+					mml_number(&mut chars);
+				}
+				_ => return Err(format!("unknown command `{command}`")),
+			}
+		}
+		end = end.max(time);
+	}
+
+	Ok((notes, tempos, end))
+}
+
+/// Parses the number following an MML command, if there is one.
+fn mml_number(chars: &mut std::iter::Peekable<impl Iterator<Item = char>>) -> Option<u64> {
+	let mut digits = String::new();
+	while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+		digits.push(digit);
+	}
+	digits.parse().ok()
+}
+
+/// Returns how long an MML note of `length` (`4` for a quarter note) lasts.
+fn mml_length(length: u64) -> Result<u64, String> {
+	if length == 0 || !MML_WHOLE.is_multiple_of(length) || !(MML_WHOLE / length).is_multiple_of(2) {
+		return Err(format!("unsupported length `{length}`"));
+	}
+	Ok(MML_WHOLE / length)
+}
+
+/// Given a string literal expression containing an MML (Music Macro Language) song,
+/// parses it and generates a [`buzzer_music::Song`] expression.
+///
+/// The supported commands are notes `a` to `g` (with `+`, `#`, or `-`, a length, and dots), rests `r`,
+/// ties `&`, `o` (octave), `>` and `<` (octave up and down), `l` (default length), and `t` (tempo).
+/// Volume commands (`v`) are ignored. Voices separated by `,` play at the same time, each on its own track.
+///
+/// Each beat lasts as long as the song's shortest note, and tempo commands become `SetTempo` events
+/// for a player ticking every `tick_ms` milliseconds.
+///
+/// Options can follow the string:
+/// - `tick_ms = <ms>` sets how often the player ticks. Defaults to `40`.
+/// - `report_size = true` warns with the size of the song's tables, like in [`declare_song`].
+/// - `beats_per_quarter = <beats>` snaps notes to a grid of `<beats>` beats per quarter note, like in
+///   [`declare_song_from_midi`]. Notes aren't snapped by default.
+/// - `rounding = "nearest" | "floor" | "swing"` chooses how start times are snapped, like in [`declare_song`].
+#[proc_macro]
+pub fn declare_mml(input: TokenStream) -> TokenStream {
+	let args = parse_macro_input!(input as SongArgs);
+	let Some(source) = &args.source else {
+		return syn::Error::new(proc_macro2::Span::call_site(), "expected a song string")
+			.to_compile_error()
+			.into();
+	};
+	let options = match TimedOptions::from_args(&args) {
+		Ok(options) => options,
+		Err(error) => return error.to_compile_error().into(),
+	};
+
+	let (mml, tempos, end) = match parse_mml(&source.value()) {
+		Ok(mml) => mml,
+		Err(error) => {
+			return syn::Error::new_spanned(source, error)
+				.to_compile_error()
+				.into();
+		}
+	};
+
+	timed_song(mml, tempos, MML_WHOLE, end, &options).into()
+}
+
+/// Given a string literal expression containing a tune in ABC notation,
+/// parses it and generates a [`buzzer_music::Song`] expression.
+///
+/// The header fields `M:` (meter), `L:` (unit note length), `Q:` (tempo), and `K:` (key) are supported,
+/// along with notes (with accidentals, octaves, and lengths), rests, chords, ties, broken rhythms (`>` and `<`),
+/// tuplets, repeats (`|:`, `:|`, and `::`), and numbered endings. Repeats are played out in full.
+/// Other fields, decorations, chord symbols, annotations, grace notes, and slurs are ignored.
+///
+/// Each beat lasts as long as the song's shortest note, and `Q:` fields become `SetTempo` events
+/// for a player ticking every `tick_ms` milliseconds.
+///
+/// Options can follow the string:
+/// - `tick_ms = <ms>` sets how often the player ticks. Defaults to `40`.
+/// - `report_size = true` warns with the size of the song's tables, like in [`declare_song`].
+/// - `beats_per_quarter = <beats>` snaps notes to a grid of `<beats>` beats per quarter note, like in
+///   [`declare_song_from_midi`]. Notes aren't snapped by default.
+/// - `rounding = "nearest" | "floor" | "swing"` chooses how start times are snapped, like in [`declare_song`].
+#[proc_macro]
+pub fn declare_abc(input: TokenStream) -> TokenStream {
+	let args = parse_macro_input!(input as SongArgs);
+	let Some(source) = &args.source else {
+		return syn::Error::new(proc_macro2::Span::call_site(), "expected a song string")
+			.to_compile_error()
+			.into();
+	};
+	let options = match TimedOptions::from_args(&args) {
+		Ok(options) => options,
+		Err(error) => return error.to_compile_error().into(),
+	};
+
+	let tune = match abc::parse_abc(&source.value()) {
+		Ok(tune) => tune,
+		Err(error) => {
+			return syn::Error::new_spanned(source, error)
+				.to_compile_error()
+				.into();
+		}
+	};
+
+	timed_song(tune.notes, tune.tempos, tune.whole, tune.end, &options).into()
+}
+
+/// The MIDI channel used for drums, which a buzzer can't play.
+const MIDI_DRUM_CHANNEL: u8 = 9;
+
+/// Given a string literal expression containing the path of a standard MIDI file, relative to the
+/// crate's `Cargo.toml`, reads and parses it and generates a [`buzzer_music::Song`] expression.
+/// The song is rebuilt whenever the file changes.
+///
+/// Each MIDI channel becomes a track. Drums (channel 10) are left out, as are notes outside C0 to B9.
+/// Tempo changes become `SetTempo` events for a player ticking every `tick_ms` milliseconds.
+///
+/// Options can follow the path:
+/// - `track = <index>` only reads the MIDI file's `<index>`th track, counting from `0`.
+///   Defaults to every track.
+/// - `beats_per_quarter = <beats>` sets how many beats a quarter note is split into. Note start times are snapped to
+///   these beats, and durations rounded to the nearest beat. Defaults to `4`, so each beat is a sixteenth note.
+/// - `rounding = "nearest" | "floor" | "swing"` chooses how start times are snapped, like in [`declare_song`].
+/// - `tick_ms = <ms>` sets how often the player ticks. Defaults to `40`.
+/// - `report_size = true` warns with the size of the song's tables, like in [`declare_song`].
+///
+/// Like in [`declare_song`], `drop_tracks`, `merge_tracks`, and `octave_shifts` help fit the song onto a few
+/// buzzers. Their instruments are MIDI channels, counting from `0` like the tracks they become.
+#[proc_macro]
+pub fn declare_song_from_midi(input: TokenStream) -> TokenStream {
+	let args = parse_macro_input!(input as SongArgs);
+	let Some(path) = &args.source else {
+		return syn::Error::new(proc_macro2::Span::call_site(), "expected a MIDI file path")
+			.to_compile_error()
+			.into();
+	};
+
+	let mut track = None;
+	let mut options = TimedOptions::new(Some(4.));
+	let mut drop_tracks = vec![];
+	let mut merge_tracks = vec![];
+	let mut octave_shifts = vec![];
+	for option in &args.options {
+		let result = match option {
+			Meta::NameValue(name_value) if name_value.path.is_ident("track") => {
+				parse_number(&name_value.value).map(|value| track = Some(value as usize))
+			}
+			Meta::NameValue(name_value) if name_value.path.is_ident("drop_tracks") => {
+				parse_list(&name_value.value, parse_integer).map(|value| drop_tracks = value)
+			}
+			Meta::NameValue(name_value) if name_value.path.is_ident("merge_tracks") => {
+				parse_list(&name_value.value, parse_pair).map(|value| merge_tracks = value)
+			}
+			Meta::NameValue(name_value) if name_value.path.is_ident("octave_shifts") => {
+				parse_list(&name_value.value, parse_pair).map(|value| octave_shifts = value)
+			}
+			_ => options.apply(option),
+		};
+		if let Err(error) = result {
+			return error.to_compile_error().into();
+		}
+	}
+
+	let (bytes, tracking) = match read_midi_file(path) {
+		Ok(file) => file,
+		Err(error) => return error.to_compile_error().into(),
+	};
+	let midi = match midi::parse_midi(&bytes) {
+		Ok(midi) => midi,
+		Err(error) => {
+			return syn::Error::new_spanned(path, error)
+				.to_compile_error()
+				.into();
+		}
+	};
+
+	let notes = midi
+		.notes
+		.iter()
+		.filter(|note| track.is_none_or(|track| track == note.track))
+		.filter(|note| note.channel != MIDI_DRUM_CHANNEL)
+		.filter(|note| !drop_tracks.contains(&(note.channel as i64)))
+		.filter_map(|note| {
+			let channel = note.channel as i64;
+			let octaves = octave_shifts
+				.iter()
+				.find(|(shifted, _)| *shifted == channel)
+				.map_or(0, |(_, octaves)| *octaves);
+			// MIDI note 12 is C0, the first of `TONES`.
+			let tone = note.key as i64 - 12 + octaves * 12;
+			if !(0..TONES.len() as i64).contains(&tone) {
+				return None;
+			}
+			let track = merge_tracks
+				.iter()
+				.find(|(merged, _)| *merged == channel)
+				.map_or(channel, |(_, track)| *track);
+			Some(TimedNote {
+				tone: tone as usize,
+				time: note.start,
+				duration: note.end - note.start,
+				track: track.clamp(0, u8::MAX as i64) as u8,
+			})
+		})
+		.collect::<Vec<TimedNote>>();
+	let tempos = midi
+		.tempos
+		.iter()
+		.map(|(time, microseconds)| TimedTempo {
+			time: *time,
+			bpm: 60_000_000. / (*microseconds).max(1) as f64,
+		})
+		.collect();
+
+	let whole = midi.ticks_per_quarter * 4;
+	let song = timed_song(notes, tempos, whole, 0, &options);
+	quote! {
+		{
+			#tracking
+			#song
+		}
+	}
+	.into()
+}
+
+/// Generates a [`buzzer_music::Song`] expression from `timed` and `tempos`, timed in `whole`ths of a whole note.
+/// The song lasts at least `length`, so it can end with a rest.
+///
+/// If `options` sets `beats_per_quarter`, everything is first snapped to those beats. Each beat then lasts as long as the longest duration
+/// every note and tempo change lines up with.
+fn timed_song(
+	mut timed: Vec<TimedNote>,
+	mut tempos: Vec<TimedTempo>,
+	mut whole: u64,
+	mut length: u64,
+	options: &TimedOptions,
+) -> TokenStream2 {
+	// Measure everything in the given beats, each `step` long.
+	if let Some(beats_per_quarter) = options.beats_per_quarter {
+		let step = whole as f64 / (beats_per_quarter * 4.);
+		let snap = |time: u64| (time as f64 / step).round() as u64;
+		for note in &mut timed {
+			note.time = options.rounding.steps(note.time as f64, step) as u64;
+			note.duration = snap(note.duration).max(1);
+		}
+		for tempo in &mut tempos {
+			tempo.time = snap(tempo.time);
+		}
+		length = snap(length);
+		whole = beats_per_quarter as u64 * 4;
+	}
+
+	// Use the longest beat every note and tempo change lines up with.
+	let step = timed
+		.iter()
+		.flat_map(|note| [note.time, note.duration])
+		.chain(tempos.iter().map(|tempo| tempo.time))
+		.chain([length])
+		.fold(0, gcd)
+		.max(1);
+
+	let end = timed
+		.iter()
+		.map(|note| note.time + note.duration)
+		.chain([length])
+		.max()
+		.unwrap_or(0)
+		/ step;
+	let mut notes: Vec<Vec<TokenStream2>> = vec![vec![]; end as usize];
+	for note in &timed {
+		let frequency = TONES[note.tone].1;
+		let duration = (note.duration / step) as u16;
+		let track = note.track;
+		notes[(note.time / step) as usize].push(quote! {
+			buzzer_music::NoteAndDuration {
+				frequency: #frequency,
+				duration: #duration,
+				track: #track,
+				chance: 100,
+				ratchet: 1
+			}
+		});
+	}
+	let code = notes.iter().map(|notes| {
+		if notes.is_empty() {
+			quote! { None }
+		} else {
+			quote! { Some(&[#(#notes),*]) }
+		}
+	});
+
+	// Songs start at 120 beats per minute. When several voices change the tempo on the same beat, the last one wins.
+	if tempos.first().is_none_or(|tempo| tempo.time != 0) {
+		tempos.insert(0, TimedTempo { time: 0, bpm: 120. });
+	}
+	tempos.sort_by_key(|tempo| tempo.time);
+	let mut events: Vec<(u64, u16)> = vec![];
+	for tempo in &tempos {
+		let beat = tempo.time / step;
+		// A quarter note lasts `60000 / bpm` milliseconds.
+		let ticks_per_beat =
+			tempo_ticks(tempo.bpm, step as f64 * 4. / whole as f64, options.tick_ms);
+		match events.last_mut() {
+			Some(last) if last.0 == beat => last.1 = ticks_per_beat,
+			_ => events.push((beat, ticks_per_beat)),
+		}
+	}
+	let report = options.report_size.then(|| {
+		let beats = notes
+			.iter()
+			.map(|notes| (!notes.is_empty()).then_some(notes.as_slice()))
+			.collect::<Vec<_>>();
+		size_report(&beats, events.len(), 0)
+	});
+	let events = events.iter().map(|(beat, ticks_per_beat)| {
+		let beat = *beat as u16;
+		quote! {
+			buzzer_music::SongEvent {
+				beat: #beat,
+				kind: buzzer_music::SongEventKind::SetTempo { ticks_per_beat: #ticks_per_beat },
+			}
+		}
+	});
+	let end = end as u16;
+
+	quote! {
+		{
+			#report
+			buzzer_music::Song {
+				notes: &[#(#code),*],
+				end: #end,
+				events: &[#(#events),*],
+				metadata: buzzer_music::SongMetadata::NONE,
+			}
+		}
+	}
+}
+
+/// The options of [`declare_rtttl`], [`declare_mml`], [`declare_abc`], and [`declare_song_from_midi`].
+struct TimedOptions {
+	/// How often the player ticks, in milliseconds.
+	tick_ms: f64,
+	/// If set, report the size of the generated tables.
+	report_size: bool,
+	/// If set, the number of beats a quarter note is split into, which notes are snapped to.
+	beats_per_quarter: Option<f64>,
+	/// How note start times are snapped to `beats_per_quarter`.
+	rounding: Rounding,
+}
+
+impl TimedOptions {
+	fn new(beats_per_quarter: Option<f64>) -> Self {
+		Self {
+			tick_ms: 40.,
+			report_size: false,
+			beats_per_quarter,
+			rounding: Rounding::Nearest,
+		}
+	}
+
+	fn from_args(args: &SongArgs) -> syn::Result<Self> {
+		let mut options = Self::new(None);
+		for option in &args.options {
+			options.apply(option)?;
+		}
+		Ok(options)
+	}
+
+	/// Sets the value of `option`, or returns an error if it isn't one of these options.
+	fn apply(&mut self, option: &Meta) -> syn::Result<()> {
+		match option {
+			Meta::NameValue(name_value) if name_value.path.is_ident("tick_ms") => {
+				self.tick_ms = parse_number(&name_value.value)?;
+			}
+			Meta::NameValue(name_value) if name_value.path.is_ident("report_size") => {
+				self.report_size = parse_bool(&name_value.value)?;
+			}
+			Meta::NameValue(name_value) if name_value.path.is_ident("beats_per_quarter") => {
+				let value = parse_number(&name_value.value)?;
+				if value < 1. || value.fract() != 0. {
+					return Err(syn::Error::new_spanned(
+						&name_value.value,
+						"expected a whole number of beats",
+					));
+				}
+				self.beats_per_quarter = Some(value);
+			}
+			Meta::NameValue(name_value) if name_value.path.is_ident("rounding") => {
+				self.rounding = Rounding::from_lit(parse_string(&name_value.value)?)?;
+			}
+			_ => return Err(syn::Error::new_spanned(option, "unknown option")),
+		}
+		Ok(())
+	}
+}
+
+/// Returns how many ticks of `tick_ms` milliseconds a beat lasts, when it is `quarters` quarter notes
+/// long at `bpm` quarter notes per minute.
+fn tempo_ticks(bpm: f64, quarters: f64, tick_ms: f64) -> u16 {
+	let beat_ms = 60000. / bpm * quarters;
+	(beat_ms / tick_ms).round().clamp(1., u16::MAX as f64) as u16
+}
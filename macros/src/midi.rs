@@ -0,0 +1,183 @@
+/// A note read from a MIDI file.
+pub(crate) struct MidiNote {
+	/// When the note starts and stops, in MIDI ticks.
+	pub(crate) start: u64,
+	pub(crate) end: u64,
+	/// The MIDI note number, where `60` is C4.
+	pub(crate) key: u8,
+	pub(crate) channel: u8,
+	/// The index of the track chunk the note was in.
+	pub(crate) track: usize,
+}
+
+/// The contents of a standard MIDI file that matter to a [`buzzer_music::Song`].
+pub(crate) struct MidiFile {
+	pub(crate) notes: Vec<MidiNote>,
+	/// Pairs of `(tick, microseconds per quarter note)`, in the order they were read.
+	pub(crate) tempos: Vec<(u64, u32)>,
+	/// How many ticks a quarter note lasts.
+	pub(crate) ticks_per_quarter: u64,
+}
+
+/// Parses a standard MIDI file (format 0, 1, or 2).
+///
+/// Only notes and tempo changes are kept. Notes still held at the end of their track stop there.
+pub(crate) fn parse_midi(bytes: &[u8]) -> Result<MidiFile, String> {
+	let mut reader = Reader { bytes, position: 0 };
+	if reader.take(4)? != b"MThd" {
+		return Err("not a MIDI file".to_string());
+	}
+	let header_length = reader.u32()? as usize;
+	let header = reader.take(header_length)?;
+	if header.len() < 6 {
+		return Err("invalid MIDI header".to_string());
+	}
+	let division = u16::from_be_bytes([header[4], header[5]]);
+	if division & 0x8000 != 0 || division == 0 {
+		return Err("MIDI files timed in SMPTE frames aren't supported".to_string());
+	}
+
+	let mut file = MidiFile {
+		notes: vec![],
+		tempos: vec![],
+		ticks_per_quarter: division as u64,
+	};
+	let mut track = 0;
+	while reader.position < bytes.len() {
+		let kind = reader.take(4)?;
+		let length = reader.u32()? as usize;
+		let chunk = reader.take(length)?;
+		// Unknown chunks are skipped, as the MIDI specification asks.
+		if kind == b"MTrk" {
+			parse_track(chunk, track, &mut file)?;
+			track += 1;
+		}
+	}
+	Ok(file)
+}
+
+/// Parses the events of the track chunk `chunk`, the `track`th in the file.
+fn parse_track(chunk: &[u8], track: usize, file: &mut MidiFile) -> Result<(), String> {
+	let mut reader = Reader {
+		bytes: chunk,
+		position: 0,
+	};
+	let mut time = 0;
+	let mut running_status = 0;
+	// Notes that have started but not stopped, as `(channel, key, start)`.
+	let mut sounding: Vec<(u8, u8, u64)> = vec![];
+
+	while reader.position < chunk.len() {
+		time += reader.variable()?;
+		// Channel events can leave out their status when it's the same as the last one's.
+		// Meta and system exclusive events always have one, and don't change it.
+		let byte = reader.byte()?;
+		let (status, byte) = if byte >= 0xF0 {
+			(byte, 0)
+		} else if byte & 0x80 != 0 {
+			running_status = byte;
+			(byte, reader.byte()?)
+		} else if running_status != 0 {
+			(running_status, byte)
+		} else {
+			return Err("MIDI event without a status".to_string());
+		};
+
+		let channel = status & 0x0F;
+		match status & 0xF0 {
+			0x80 | 0x90 => {
+				let key = byte;
+				let velocity = reader.byte()?;
+				if status & 0xF0 == 0x90 && velocity > 0 {
+					sounding.push((channel, key, time));
+				} else if let Some(index) =
+					sounding.iter().position(|(other_channel, other_key, _)| {
+						(*other_channel, *other_key) == (channel, key)
+					}) {
+					let (_, _, start) = sounding.remove(index);
+					file.notes.push(MidiNote {
+						start,
+						end: time,
+						key,
+						channel,
+						track,
+					});
+				}
+			}
+			0xA0 | 0xB0 | 0xE0 => {
+				reader.byte()?;
+			}
+			0xC0 | 0xD0 => {}
+			_ => match status {
+				0xFF => {
+					let kind = reader.byte()?;
+					let length = reader.variable()? as usize;
+					let data = reader.take(length)?;
+					match kind {
+						0x51 if data.len() == 3 => {
+							let tempo = u32::from_be_bytes([0, data[0], data[1], data[2]]);
+							file.tempos.push((time, tempo));
+						}
+						0x2F => break,
+						_ => {}
+					}
+				}
+				0xF0 | 0xF7 => {
+					let length = reader.variable()? as usize;
+					reader.take(length)?;
+				}
+				_ => return Err(format!("unsupported MIDI event {status:#04X}")),
+			},
+		}
+	}
+
+	for (channel, key, start) in sounding {
+		file.notes.push(MidiNote {
+			start,
+			end: time,
+			key,
+			channel,
+			track,
+		});
+	}
+	Ok(())
+}
+
+/// Reads big-endian values from a MIDI file.
+struct Reader<'b> {
+	bytes: &'b [u8],
+	position: usize,
+}
+
+impl<'b> Reader<'b> {
+	fn take(&mut self, length: usize) -> Result<&'b [u8], String> {
+		let bytes = self
+			.bytes
+			.get(self.position..self.position + length)
+			.ok_or("unexpected end of MIDI file")?;
+		self.position += length;
+		Ok(bytes)
+	}
+
+	fn byte(&mut self) -> Result<u8, String> {
+		Ok(self.take(1)?[0])
+	}
+
+	fn u32(&mut self) -> Result<u32, String> {
+		let bytes = self.take(4)?;
+		Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+	}
+
+	/// Reads a variable-length quantity, seven bits per byte.
+	fn variable(&mut self) -> Result<u64, String> {
+		let mut value = 0;
+		for _ in 0..4 {
+			let byte = self.byte()?;
+			value = (value << 7) | (byte & 0x7F) as u64;
+			if byte & 0x80 == 0 {
+				return Ok(value);
+			}
+		}
+		Err("invalid MIDI variable-length number".to_string())
+	}
+}
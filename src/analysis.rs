@@ -0,0 +1,458 @@
+use crate::{FrequencyRange, NoteAndDuration, Song, SongEventKind};
+
+/// The number of buckets in [`SongAnalysis::polyphony_histogram`].
+pub const POLYPHONY_HISTOGRAM_SIZE: usize = 9;
+
+/// Statistics about a [`Song`], produced by [`Song::analyze`].
+///
+/// Useful for choosing a transposition, how many buzzers to use, and the `Player`'s const generics.
+///
+/// ```rust
+/// use buzzer_music::declare_song;
+///
+/// const SONG: buzzer_music::Song = declare_song!("0 C5 4 0;0 E5 4 0;2 G5 1 0");
+/// const ANALYSIS: buzzer_music::SongAnalysis = SONG.analyze();
+/// assert_eq!(ANALYSIS.peak_polyphony, 3);
+/// assert_eq!(ANALYSIS.note_count, 3);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SongAnalysis {
+	/// The lowest and highest frequencies used, or `None` if the song has no notes.
+	pub frequency_range: Option<FrequencyRange>,
+	/// How many notes the song has.
+	pub note_count: u32,
+	/// How many beats the song lasts, including the silence rounded up at the end.
+	pub beat_count: u16,
+	/// The most notes sounding on the same beat.
+	pub peak_polyphony: u16,
+	/// How many beats have each number of notes sounding, from `0` upwards.
+	/// The last bucket also counts beats with more notes.
+	pub polyphony_histogram: [u16; POLYPHONY_HISTOGRAM_SIZE],
+}
+
+impl SongAnalysis {
+	/// Analyzes `song`.
+	pub(crate) const fn of(song: &Song) -> Self {
+		let mut analysis = Self {
+			frequency_range: song.frequency_range(),
+			note_count: 0,
+			beat_count: song.end,
+			peak_polyphony: 0,
+			polyphony_histogram: [0; POLYPHONY_HISTOGRAM_SIZE],
+		};
+
+		// Notes can't sound for longer than the longest note, so only look that far back.
+		let mut longest = 0;
+		let mut beat = 0;
+		while beat < song.notes.len() {
+			if let Some(notes) = song.notes[beat] {
+				analysis.note_count += notes.len() as u32;
+				let mut i = 0;
+				while i < notes.len() {
+					if notes[i].duration as usize > longest {
+						longest = notes[i].duration as usize;
+					}
+					i += 1;
+				}
+			}
+			beat += 1;
+		}
+
+		let mut beat = 0;
+		while beat < song.end as usize {
+			let mut sounding: u16 = 0;
+			let mut start = beat.saturating_sub(longest);
+			while start <= beat && start < song.notes.len() {
+				if let Some(notes) = song.notes[start] {
+					let mut i = 0;
+					while i < notes.len() {
+						if start + notes[i].duration as usize > beat {
+							sounding += 1;
+						}
+						i += 1;
+					}
+				}
+				start += 1;
+			}
+
+			if sounding > analysis.peak_polyphony {
+				analysis.peak_polyphony = sounding;
+			}
+			let bucket = if (sounding as usize) < POLYPHONY_HISTOGRAM_SIZE {
+				sounding as usize
+			} else {
+				POLYPHONY_HISTOGRAM_SIZE - 1
+			};
+			analysis.polyphony_histogram[bucket] += 1;
+			beat += 1;
+		}
+
+		analysis
+	}
+
+	/// Returns the average number of notes starting per beat, multiplied by 100.
+	pub const fn notes_per_hundred_beats(&self) -> u32 {
+		if self.beat_count == 0 {
+			return 0;
+		}
+		self.note_count * 100 / self.beat_count as u32
+	}
+}
+
+/// The beats where the sections of a [`Song`] start, found by [`Song::find_sections`].
+///
+/// Beat `0` always starts the first section. Passing [`SongSections::as_slice`] to
+/// `Player::set_sections` lets `Player::skip_to_next_section` use them as implicit markers.
+///
+/// ```rust
+/// use buzzer_music::declare_song;
+///
+/// const SONG: buzzer_music::Song = declare_song!("0 C5 1 0;1 E5 1 0;2 C5 1 0;3 E5 1 0;4 G5 1 0;8 C5 1 0");
+/// const SECTIONS: buzzer_music::SongSections<4> = SONG.find_sections(2, 2);
+/// assert_eq!(SECTIONS.as_slice(), &[0, 4, 8]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SongSections<const N: usize> {
+	starts: [u16; N],
+	len: usize,
+}
+
+impl<const N: usize> SongSections<N> {
+	/// Finds the sections of `song`.
+	pub(crate) const fn of(song: &Song, min_rest_beats: u16, phrase_beats: u16) -> Self {
+		let mut sections = Self {
+			starts: [0; N],
+			len: 0,
+		};
+		sections.insert(0);
+
+		// A note starting after a long enough silence starts a section.
+		if min_rest_beats > 0 {
+			let mut silent_from = 0;
+			let mut beat = 0;
+			while beat < song.notes.len() {
+				if let Some(notes) = song.notes[beat]
+					&& !notes.is_empty()
+				{
+					if beat >= silent_from + min_rest_beats as usize {
+						sections.insert(beat as u16);
+					}
+					let mut i = 0;
+					while i < notes.len() {
+						if beat + notes[i].duration as usize > silent_from {
+							silent_from = beat + notes[i].duration as usize;
+						}
+						i += 1;
+					}
+				}
+				beat += 1;
+			}
+		}
+
+		// A phrase with notes that differs from the one before it starts a section if it returns to an
+		// earlier phrase, or if the phrase before it was itself a repeat.
+		let phrase = phrase_beats as usize;
+		if phrase > 0 {
+			let mut start = phrase;
+			while start < song.end as usize {
+				let previous = start - phrase;
+				if !is_silent(song, start, phrase) && !same_phrase(song, start, previous, phrase) {
+					let mut new_section = previous >= phrase
+						&& same_phrase(song, previous, previous - phrase, phrase);
+					let mut earlier = 0;
+					while !new_section && earlier + phrase <= previous {
+						new_section = same_phrase(song, start, earlier, phrase);
+						earlier += phrase;
+					}
+					if new_section {
+						sections.insert(start as u16);
+					}
+				}
+				start += phrase;
+			}
+		}
+
+		sections
+	}
+
+	/// Returns the beats where sections start, in order.
+	pub const fn as_slice(&self) -> &[u16] {
+		self.starts.split_at(self.len).0
+	}
+
+	/// Adds `beat`, keeping the starts in order. Does nothing if it is already there or `N` are found.
+	const fn insert(&mut self, beat: u16) {
+		if self.len == N {
+			return;
+		}
+		let mut i = 0;
+		while i < self.len && self.starts[i] < beat {
+			i += 1;
+		}
+		if i < self.len && self.starts[i] == beat {
+			return;
+		}
+		let mut j = self.len;
+		while j > i {
+			self.starts[j] = self.starts[j - 1];
+			j -= 1;
+		}
+		self.starts[i] = beat;
+		self.len += 1;
+	}
+}
+
+/// Returns `true` if the `length` beats from `a` start the same notes as those from `b`.
+const fn same_phrase(song: &Song, a: usize, b: usize, length: usize) -> bool {
+	let mut i = 0;
+	while i < length {
+		let first = notes_on(song, a + i);
+		let second = notes_on(song, b + i);
+		if first.len() != second.len() {
+			return false;
+		}
+		let mut n = 0;
+		while n < first.len() {
+			let (x, y) = (&first[n], &second[n]);
+			if x.frequency != y.frequency
+				|| x.duration != y.duration
+				|| x.track != y.track
+				|| x.chance != y.chance
+				|| x.ratchet != y.ratchet
+			{
+				return false;
+			}
+			n += 1;
+		}
+		i += 1;
+	}
+	true
+}
+
+/// Returns `true` if no notes start in the `length` beats from `start`.
+const fn is_silent(song: &Song, start: usize, length: usize) -> bool {
+	let mut i = 0;
+	while i < length {
+		if !notes_on(song, start + i).is_empty() {
+			return false;
+		}
+		i += 1;
+	}
+	true
+}
+
+/// Returns the notes starting on `beat`, which is empty past the last note.
+const fn notes_on<'a>(song: &Song<'a>, beat: usize) -> &'a [NoteAndDuration] {
+	if beat < song.notes.len()
+		&& let Some(notes) = song.notes[beat]
+	{
+		notes
+	} else {
+		&[]
+	}
+}
+
+/// Problems found in a [`Song`] by [`Song::validate_for`].
+///
+/// ```rust
+/// use buzzer_music::declare_song;
+///
+/// const SONG: buzzer_music::Song = declare_song!("0 C5 2 0;1 C5 1 0");
+/// const REPORT: buzzer_music::SongReport = SONG.validate_for(40, 3);
+/// assert_eq!(REPORT.retriggers, 1);
+/// assert!(!REPORT.is_ok());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SongReport {
+	/// Notes with a duration of `0`, which would never be heard.
+	pub zero_length_notes: u16,
+	/// Notes that start while a note of the same frequency is still sounding.
+	/// A buzzer can't play the same frequency twice, so they merge into one.
+	pub retriggers: u16,
+	/// Beats whose tempo events make them shorter than one tick.
+	pub too_fast_beats: u16,
+	/// The first beat with any of the problems above.
+	pub first_problem_beat: Option<u16>,
+	/// How long the shortest beat lasts, in milliseconds.
+	pub shortest_beat_ms: u32,
+}
+
+impl SongReport {
+	/// Returns `true` if no problems were found.
+	pub const fn is_ok(&self) -> bool {
+		self.first_problem_beat.is_none()
+	}
+
+	/// Records a problem on `beat`.
+	const fn problem_on(&mut self, beat: u16) {
+		if self.first_problem_beat.is_none() {
+			self.first_problem_beat = Some(beat);
+		}
+	}
+}
+
+/// Implements [`Song::validate_for`].
+pub(crate) const fn validate(song: &Song, tick_ms: u16, ticks_per_beat: u16) -> SongReport {
+	let mut report = SongReport {
+		zero_length_notes: 0,
+		retriggers: 0,
+		too_fast_beats: 0,
+		first_problem_beat: None,
+		shortest_beat_ms: u32::MAX,
+	};
+
+	let mut longest = 0;
+	let mut timeline = BeatTimeline::new(ticks_per_beat);
+	let mut beat = 0;
+	while beat < song.end as usize {
+		// Find how long this beat lasts.
+		let start = timeline.start_tick();
+		timeline.advance(song);
+		let length = timeline.start_tick() - start;
+		if length == 0 {
+			report.too_fast_beats += 1;
+			report.problem_on(beat as u16);
+		}
+		let length_ms = length * tick_ms as u64;
+		if length_ms < report.shortest_beat_ms as u64 {
+			report.shortest_beat_ms = length_ms as u32;
+		}
+
+		if beat < song.notes.len()
+			&& let Some(notes) = song.notes[beat]
+		{
+			let mut i = 0;
+			while i < notes.len() {
+				let note = &notes[i];
+				if note.duration == 0 {
+					report.zero_length_notes += 1;
+					report.problem_on(beat as u16);
+				}
+				if note.duration as usize > longest {
+					longest = note.duration as usize;
+				}
+				if is_sounding(song, note.frequency, beat, longest) {
+					report.retriggers += 1;
+					report.problem_on(beat as u16);
+				}
+				i += 1;
+			}
+		}
+		beat += 1;
+	}
+
+	if report.shortest_beat_ms == u32::MAX {
+		report.shortest_beat_ms = 0;
+	}
+	report
+}
+
+/// Returns `true` if a note of `frequency` that started before `beat` is still sounding on it.
+/// `longest` is the longest duration of any of those notes.
+const fn is_sounding(song: &Song, frequency: u16, beat: usize, longest: usize) -> bool {
+	let mut start = beat.saturating_sub(longest);
+	while start < beat {
+		if let Some(notes) = song.notes[start] {
+			let mut i = 0;
+			while i < notes.len() {
+				if notes[i].frequency == frequency && start + notes[i].duration as usize > beat {
+					return true;
+				}
+				i += 1;
+			}
+		}
+		start += 1;
+	}
+	false
+}
+
+/// Walks through the beats of a [`Song`], keeping track of when each one starts.
+///
+/// Tempo changes, tempo ramps, and fermatas are followed the same way a [`crate::Player`] does.
+/// Holds and branches are not, since they depend on what happens during playback.
+#[derive(Clone, Copy)]
+pub(crate) struct BeatTimeline {
+	beat: u16,
+	start_tick: u64,
+	tempo: u64,
+	ramp: Option<(u16, u16, u16, u16)>,
+}
+
+impl BeatTimeline {
+	/// Starts at beat `0`, at tick `0`.
+	pub(crate) const fn new(ticks_per_beat: u16) -> Self {
+		Self {
+			beat: 0,
+			start_tick: 0,
+			tempo: ticks_per_beat as u64,
+			ramp: None,
+		}
+	}
+
+	/// Returns the current beat.
+	pub(crate) const fn beat(&self) -> u16 {
+		self.beat
+	}
+
+	/// Returns the tick the current beat starts on.
+	pub(crate) const fn start_tick(&self) -> u64 {
+		self.start_tick
+	}
+
+	/// Moves to the next beat of `song`. Returns `false` if the song has ended.
+	pub(crate) const fn advance(&mut self, song: &Song) -> bool {
+		if self.beat >= song.end {
+			return false;
+		}
+
+		let beat = self.beat;
+		let mut stretch = 100;
+		let mut i = 0;
+		while i < song.events.len() {
+			let event = &song.events[i];
+			if event.beat == beat {
+				match event.kind {
+					SongEventKind::SetTempo { ticks_per_beat } => {
+						self.ramp = None;
+						self.tempo = ticks_per_beat as u64;
+					}
+					SongEventKind::TempoRamp { from, to, beats } => {
+						self.ramp = Some((beat, from, to, beats));
+					}
+					SongEventKind::Fermata { percent } => stretch = percent as u64,
+					_ => {}
+				}
+			}
+			i += 1;
+		}
+
+		if let Some((start, from, to, beats)) = self.ramp {
+			let progress = beat - start;
+			if progress >= beats {
+				self.tempo = to as u64;
+				self.ramp = None;
+			} else {
+				let from = from as i64;
+				let to = to as i64;
+				self.tempo = (from + (to - from) * progress as i64 / beats as i64) as u64;
+			}
+		}
+
+		self.start_tick += self.tempo * stretch / 100;
+		self.beat += 1;
+		true
+	}
+}
+
+/// Implements [`Song::estimated_duration_ticks`].
+pub(crate) const fn estimated_duration_ticks(song: &Song, ticks_per_beat: u16) -> u32 {
+	let mut timeline = BeatTimeline::new(ticks_per_beat);
+	while timeline.advance(song) {}
+
+	let total = timeline.start_tick();
+	if total > u32::MAX as u64 {
+		u32::MAX
+	} else {
+		total as u32
+	}
+}
@@ -0,0 +1,69 @@
+use crate::{NoteAndDuration, Song};
+
+/// A beat of a [`Song`], produced by [`Song::beats`].
+#[derive(Clone, Copy)]
+pub struct Beat<'s> {
+	/// The index of this beat in the song.
+	pub index: u16,
+	/// The notes that start on this beat.
+	pub notes: &'s [NoteAndDuration],
+	rest: bool,
+}
+
+impl Beat<'_> {
+	/// Returns `true` if nothing is sounding on this beat: no note starts on it,
+	/// and no earlier note is still being held.
+	///
+	/// Beats where no note starts but an earlier note is held aren't rests.
+	pub fn is_rest(&self) -> bool {
+		self.rest
+	}
+}
+
+/// An iterator over the beats of a [`Song`], from the first beat up to its `end`.
+pub struct Beats<'s> {
+	song: &'s Song<'s>,
+	index: u16,
+	sounding_until: u32,
+}
+
+impl<'s> Beats<'s> {
+	pub(crate) fn new(song: &'s Song<'s>) -> Self {
+		Self {
+			song,
+			index: 0,
+			sounding_until: 0,
+		}
+	}
+}
+
+impl<'s> Iterator for Beats<'s> {
+	type Item = Beat<'s>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.index >= self.song.end {
+			return None;
+		}
+
+		let index = self.index;
+		let notes: &'s [NoteAndDuration] = match self.song.notes.get(index as usize) {
+			Some(Some(notes)) => notes,
+			_ => &[],
+		};
+		for note in notes {
+			self.sounding_until = self.sounding_until.max(index as u32 + note.duration as u32);
+		}
+
+		self.index += 1;
+		Some(Beat {
+			index,
+			notes,
+			rest: index as u32 >= self.sounding_until,
+		})
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let remaining = (self.song.end - self.index) as usize;
+		(remaining, Some(remaining))
+	}
+}
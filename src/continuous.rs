@@ -0,0 +1,186 @@
+use crate::duty_limit::DutyLimiter;
+use crate::{DutyLimit, ToneError, ToneOutput};
+
+/// Plays a single fixed frequency for long periods, such as an ultrasonic pest deterrent or a test tone.
+///
+/// Any frequency the output can produce is allowed, up to its ceiling. For safety, the duty is
+/// capped at `max_duty`, the tone can be switched on and off on a schedule, and a [`DutyLimit`]
+/// can silence it if it's on for too long.
+///
+/// ```rust,ignore
+/// let mut tone = buzzer_music::ContinuousTone::new(buzzer, 25_000, 50)?;
+/// tone.set_cycle(Some((1500, 500))); // On for 60 seconds, off for 20 when ticking every 40ms.
+/// tone.set_duty_limit(Some(buzzer_music::DutyLimit { window_ticks: 3000, max_on_percent: 80 }));
+/// tone.start();
+/// loop {
+///     tone.tick();
+///     embassy_time::Timer::after_millis(40).await;
+/// }
+/// ```
+pub struct ContinuousTone<O: ToneOutput> {
+	output: O,
+	frequency: u16,
+	duty: u16,
+	max_duty: u16,
+	cycle: Option<(u32, u32)>,
+	duty_limit: Option<DutyLimit>,
+	duty_limiter: DutyLimiter,
+
+	running: bool,
+	on: bool,
+	cycle_ticks: u32,
+}
+
+impl<O: ToneOutput> ContinuousTone<O> {
+	/// The constructor. The tone doesn't play until [`start`] is called.
+	///
+	/// Returns an error if `output` can't produce `frequency`.
+	pub fn new(output: O, frequency: u16, duty: u16) -> Result<Self, ToneError> {
+		check_frequency(&output, frequency)?;
+		Ok(Self {
+			output,
+			frequency,
+			duty,
+			max_duty: u16::MAX,
+			cycle: None,
+			duty_limit: None,
+			duty_limiter: DutyLimiter::new(),
+
+			running: false,
+			on: false,
+			cycle_ticks: 0,
+		})
+	}
+
+	/// Changes the frequency. Returns an error, and keeps the old frequency, if the output can't produce it.
+	pub fn set_frequency(&mut self, frequency: u16) -> Result<(), ToneError> {
+		check_frequency(&self.output, frequency)?;
+		self.frequency = frequency;
+		self.refresh();
+		Ok(())
+	}
+
+	/// Returns the frequency being played.
+	pub fn frequency(&self) -> u16 {
+		self.frequency
+	}
+
+	/// Changes the duty. It is never raised above the `max_duty` set by [`set_max_duty`].
+	pub fn set_duty(&mut self, duty: u16) {
+		self.duty = duty;
+		self.refresh();
+	}
+
+	/// Caps the duty, so it can't be raised too high by mistake. Defaults to no cap.
+	pub fn set_max_duty(&mut self, max_duty: u16) {
+		self.max_duty = max_duty;
+		self.refresh();
+	}
+
+	/// Switches the tone on for `on_ticks` ticks, then off for `off_ticks` ticks, repeatedly.
+	/// `None` keeps it on the whole time. The cycle restarts with the tone on.
+	pub fn set_cycle(&mut self, cycle: Option<(u32, u32)>) {
+		self.cycle = cycle;
+		self.cycle_ticks = 0;
+	}
+
+	/// Sets a budget limiting how long the tone can be on, or `None` to remove it. See [`DutyLimit`].
+	pub fn set_duty_limit(&mut self, limit: Option<DutyLimit>) {
+		self.duty_limit = limit;
+		if limit.is_none() {
+			self.duty_limiter = DutyLimiter::new();
+		}
+	}
+
+	/// Returns `true` if the tone is being silenced by its [`DutyLimit`].
+	pub fn is_duty_limited(&self) -> bool {
+		self.duty_limiter.is_limited()
+	}
+
+	/// Starts playing. The tone is switched on by the next [`tick`].
+	pub fn start(&mut self) {
+		self.running = true;
+		self.cycle_ticks = 0;
+	}
+
+	/// Stops playing.
+	pub fn stop(&mut self) {
+		self.running = false;
+		self.turn_off();
+	}
+
+	/// Returns `true` if the output is producing the tone right now.
+	pub fn is_on(&self) -> bool {
+		self.on
+	}
+
+	/// Updates the schedule and limits. This should be called every loop, like [`crate::Player::tick`].
+	pub fn tick(&mut self) {
+		if let Some(limit) = self.duty_limit {
+			self.duty_limiter.update(&limit, self.on);
+		}
+		if !self.running {
+			return;
+		}
+
+		let scheduled = match self.cycle {
+			Some((on_ticks, off_ticks)) => {
+				let period = on_ticks.saturating_add(off_ticks).max(1);
+				let on = self.cycle_ticks < on_ticks;
+				self.cycle_ticks = (self.cycle_ticks + 1) % period;
+				on
+			}
+			None => true,
+		};
+
+		if scheduled && !self.duty_limiter.is_limited() {
+			if !self.on {
+				self.turn_on();
+			}
+		} else {
+			self.turn_off();
+		}
+	}
+
+	/// Consumes this and returns its output.
+	pub fn into_output(mut self) -> O {
+		self.turn_off();
+		self.output
+	}
+
+	/// Plays the tone with the current settings if it is on.
+	fn refresh(&mut self) {
+		if self.on {
+			self.turn_on();
+		}
+	}
+
+	/// Starts the output with the current frequency and duty.
+	fn turn_on(&mut self) {
+		let duty = self.duty.min(self.max_duty);
+		self.on = duty > 0 && self.output.set_tone(self.frequency, duty).is_ok();
+		if !self.on {
+			self.output.set_off();
+		}
+	}
+
+	/// Stops the output.
+	fn turn_off(&mut self) {
+		if self.on {
+			self.output.set_off();
+			self.on = false;
+		}
+	}
+}
+
+/// Returns an error if `output` can't produce `frequency`.
+fn check_frequency(output: &impl ToneOutput, frequency: u16) -> Result<(), ToneError> {
+	let range = output.playable_range();
+	if frequency < range.min {
+		Err(ToneError::FrequencyTooLow)
+	} else if frequency > range.max {
+		Err(ToneError::FrequencyTooHigh)
+	} else {
+		Ok(())
+	}
+}
@@ -0,0 +1,45 @@
+/// A duty cycle as a fraction of fully on, independent of any output's duty resolution.
+///
+/// The fraction is stored in 65535ths, so `0` is off and [`DutyFraction::FULL`] is fully on.
+/// Outputs convert it to their own duty range in [`crate::ToneOutput::set_tone_fraction`], so volumes,
+/// fades, and envelopes sound the same on a 16-bit PWM as on an 8-bit one.
+///
+/// ```rust
+/// use buzzer_music::DutyFraction;
+///
+/// let half = DutyFraction::from_percent(50);
+/// assert_eq!(half.to_duty(255), 128);
+/// assert_eq!(half.to_duty(u16::MAX), 32768);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DutyFraction(pub u16);
+
+impl DutyFraction {
+	/// Fully off.
+	pub const OFF: Self = Self(0);
+	/// Fully on.
+	pub const FULL: Self = Self(u16::MAX);
+
+	/// Returns `percent` percent, from `0` to `100`. Larger values are fully on.
+	pub const fn from_percent(percent: u8) -> Self {
+		Self::from_ratio(percent as u32, 100)
+	}
+
+	/// Returns `numerator / denominator`. Ratios above `1` are fully on, and a `denominator` of `0` is off.
+	pub const fn from_ratio(numerator: u32, denominator: u32) -> Self {
+		if denominator == 0 {
+			return Self::OFF;
+		}
+		if numerator >= denominator {
+			return Self::FULL;
+		}
+		let fraction =
+			(numerator as u64 * u16::MAX as u64 + denominator as u64 / 2) / denominator as u64;
+		Self(fraction as u16)
+	}
+
+	/// Converts this into a raw duty for an output whose fully on duty is `max_duty`, rounding to the nearest.
+	pub const fn to_duty(self, max_duty: u16) -> u16 {
+		((self.0 as u32 * max_duty as u32 + u16::MAX as u32 / 2) / u16::MAX as u32) as u16
+	}
+}
@@ -0,0 +1,61 @@
+/// A budget limiting how long an output can be on, to protect small buzzers from overheating.
+///
+/// Each output may be on for at most `max_on_percent` percent of any `window_ticks` ticks.
+/// This is tracked with a leaky bucket, so short bursts of continuous output are allowed as long
+/// as the output is quiet often enough. Once the budget runs out the output is silenced until
+/// it has cooled down to half the budget.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DutyLimit {
+	pub window_ticks: u16,
+	pub max_on_percent: u8,
+}
+
+impl DutyLimit {
+	/// Returns how much heat an output can build up before it is limited.
+	const fn capacity(&self) -> u32 {
+		self.window_ticks as u32 * self.max_on_percent as u32
+	}
+}
+
+/// Tracks the duty budget of a single output.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct DutyLimiter {
+	heat: u32,
+	limited: bool,
+}
+
+impl DutyLimiter {
+	pub(crate) const fn new() -> Self {
+		Self {
+			heat: 0,
+			limited: false,
+		}
+	}
+
+	/// Returns `true` if the output should be kept silent.
+	pub(crate) fn is_limited(&self) -> bool {
+		self.limited
+	}
+
+	/// Updates the budget for a tick where the output was `on`.
+	/// Returns `Some(true)` if limiting engaged, or `Some(false)` if it was released.
+	pub(crate) fn update(&mut self, limit: &DutyLimit, on: bool) -> Option<bool> {
+		// Being on heats the output up, and it constantly cools down by the allowed ratio.
+		// This balances out exactly when the output is on for `max_on_percent` of the time.
+		if on {
+			self.heat += 100;
+		}
+		self.heat = self.heat.saturating_sub(limit.max_on_percent as u32);
+
+		let capacity = limit.capacity();
+		if !self.limited && self.heat > capacity {
+			self.limited = true;
+			Some(true)
+		} else if self.limited && self.heat <= capacity / 2 {
+			self.limited = false;
+			Some(false)
+		} else {
+			None
+		}
+	}
+}
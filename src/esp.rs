@@ -0,0 +1,235 @@
+use core::cell::RefCell;
+use core::convert::Infallible;
+
+use embedded_hal::pwm::{ErrorType, SetDutyCycle};
+use esp_hal::gpio::DriveMode;
+use esp_hal::ledc::LowSpeed;
+use esp_hal::ledc::channel::{self, Channel, ChannelHW, ChannelIFace};
+use esp_hal::ledc::timer::{self, LSClockSource, Timer, TimerIFace};
+use esp_hal::time::Rate;
+
+use crate::hal::{set_pwm_tone, set_pwm_tone_fraction};
+use crate::{DutyFraction, FrequencyRange, SetFrequency, ToneError, ToneOutput, saturate_u16};
+
+/// The most bits of duty resolution a buzzer uses, which every LEDC timer supports.
+const MAX_DUTY_BITS: u32 = 14;
+
+/// The largest clock divisor an LEDC timer takes, which has 8 fractional bits.
+const MAX_DIVISOR: u64 = 0x3FFFF;
+
+/// The frequency a buzzer's timer is set to before it plays anything.
+const START_FREQUENCY: u16 = 440;
+
+/// An `esp-hal` LEDC timer that an [`EspBuzzer`] can change the frequency and duty resolution of
+/// while its channel stays pointed at it.
+///
+/// A channel holds on to the timer it was configured with, so the timer can't also be owned by the
+/// buzzer. This shares it with the channel instead.
+pub struct EspTimer<'d> {
+	timer: RefCell<Timer<'d, LowSpeed>>,
+}
+
+impl<'d> EspTimer<'d> {
+	/// Wraps `timer`, which should be used by one buzzer only.
+	pub const fn new(timer: Timer<'d, LowSpeed>) -> Self {
+		Self {
+			timer: RefCell::new(timer),
+		}
+	}
+}
+
+impl TimerIFace<LowSpeed> for EspTimer<'_> {
+	fn freq(&self) -> Option<Rate> {
+		self.timer.borrow().freq()
+	}
+
+	fn configure(
+		&mut self,
+		config: timer::config::Config<LSClockSource>,
+	) -> Result<(), timer::Error> {
+		self.timer.get_mut().configure(config)
+	}
+
+	fn is_configured(&self) -> bool {
+		self.timer.borrow().is_configured()
+	}
+
+	fn duty(&self) -> Option<timer::config::Duty> {
+		self.timer.borrow().duty()
+	}
+
+	fn number(&self) -> timer::Number {
+		self.timer.borrow().number()
+	}
+
+	fn frequency(&self) -> u32 {
+		self.timer.borrow().frequency()
+	}
+}
+
+/// A buzzer on an `esp-hal` LEDC channel, which a [`crate::Player`] can play on directly.
+///
+/// On ESP32 chips, the frequency belongs to an LEDC timer, which is given a duty resolution in bits
+/// rather than working out its own period. The higher the frequency, the fewer bits the APB clock
+/// leaves room for, so each frequency change picks the most bits it can, up to 14, and the
+/// maximum duty is `1 << bits`. The low speed clock has to be set to the APB clock first.
+///
+/// ```rust,ignore
+/// use esp_hal::ledc::{LSGlobalClkSource, Ledc, LowSpeed, channel, timer};
+///
+/// let mut ledc = Ledc::new(peripherals.LEDC);
+/// ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+/// let timer = buzzer_music::EspTimer::new(ledc.timer::<LowSpeed>(timer::Number::Timer0));
+/// let channel = ledc.channel(channel::Number::Channel0, peripherals.GPIO4);
+/// let buzzer = buzzer_music::EspBuzzer::new(&timer, channel)?;
+/// let mut player = buzzer_music::Player::new(&SONG, true, 3, 100, [buzzer]);
+/// ```
+pub struct EspBuzzer<'a, 'd> {
+	timer: &'a EspTimer<'d>,
+	channel: Channel<'a, LowSpeed>,
+	/// The duty resolution the timer was last configured with.
+	bits: u32,
+}
+
+impl<'a, 'd> EspBuzzer<'a, 'd> {
+	/// Plays on `channel`, pointing it at `timer`. It starts silent.
+	///
+	/// Returns [`ToneError::Output`] if the timer or channel can't be configured.
+	pub fn new(
+		timer: &'a EspTimer<'d>,
+		mut channel: Channel<'a, LowSpeed>,
+	) -> Result<Self, ToneError> {
+		let bits = configure_timer(timer, START_FREQUENCY)?;
+		channel
+			.configure(channel::config::Config {
+				timer,
+				duty_pct: 0,
+				drive_mode: DriveMode::PushPull,
+			})
+			.map_err(|_| ToneError::Output)?;
+		Ok(Self {
+			timer,
+			channel,
+			bits,
+		})
+	}
+
+	/// Consumes this and returns the channel.
+	pub fn into_inner(self) -> Channel<'a, LowSpeed> {
+		self.channel
+	}
+}
+
+impl ErrorType for EspBuzzer<'_, '_> {
+	type Error = Infallible;
+}
+
+impl SetDutyCycle for EspBuzzer<'_, '_> {
+	fn max_duty_cycle(&self) -> u16 {
+		1 << self.bits
+	}
+
+	fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+		self.channel
+			.set_duty_hw(duty.min(self.max_duty_cycle()) as u32);
+		Ok(())
+	}
+}
+
+impl SetFrequency for EspBuzzer<'_, '_> {
+	fn frequency_range(&self) -> FrequencyRange {
+		frequency_range(source_hz(self.timer))
+	}
+
+	fn set_frequency(&mut self, frequency: u16) -> Result<(), ToneError> {
+		self.bits = configure_timer(self.timer, frequency)?;
+		Ok(())
+	}
+}
+
+impl ToneOutput for EspBuzzer<'_, '_> {
+	fn playable_range(&self) -> FrequencyRange {
+		self.frequency_range()
+	}
+
+	fn set_tone(&mut self, frequency: u16, duty: u16) -> Result<(), ToneError> {
+		set_pwm_tone(self, frequency, duty)
+	}
+
+	fn max_duty(&self) -> u16 {
+		self.max_duty_cycle()
+	}
+
+	fn set_tone_fraction(&mut self, frequency: u16, duty: DutyFraction) -> Result<(), ToneError> {
+		set_pwm_tone_fraction(self, frequency, duty)
+	}
+
+	fn set_off(&mut self) {
+		self.channel.set_duty_hw(0);
+	}
+}
+
+/// Returns the frequency of the clock driving `timer`, which is the APB clock once it is configured.
+fn source_hz(timer: &EspTimer) -> u32 {
+	timer.freq().map_or(0, |rate| rate.as_hz())
+}
+
+/// Returns the most bits of duty resolution a timer clocked at `source_hz` has room for at `frequency`,
+/// or `0` if there isn't room for any.
+const fn duty_bits(source_hz: u32, frequency: u16) -> u32 {
+	if frequency == 0 || source_hz < frequency as u32 {
+		return 0;
+	}
+	let bits = (source_hz / frequency as u32).ilog2();
+	if bits < MAX_DUTY_BITS {
+		bits
+	} else {
+		MAX_DUTY_BITS
+	}
+}
+
+/// Returns the frequencies a timer clocked at `source_hz` can play.
+///
+/// The timer divides the clock by `source_hz / (frequency << bits)`, with 8 fractional bits.
+/// At the lowest frequencies that runs into [`MAX_DIVISOR`] even with [`MAX_DUTY_BITS`] bits,
+/// and at the highest there's no room left for even 1 bit.
+const fn frequency_range(source_hz: u32) -> FrequencyRange {
+	let min = ((source_hz as u64) << 8).div_ceil(MAX_DIVISOR << MAX_DUTY_BITS);
+	FrequencyRange {
+		min: if min > 1 { min as u16 } else { 1 },
+		max: saturate_u16(source_hz / 2),
+	}
+}
+
+/// Sets `timer` to play `frequency` with as many bits of duty resolution as it has room for,
+/// and returns the number of bits.
+fn configure_timer(timer: &EspTimer, frequency: u16) -> Result<u32, ToneError> {
+	// Before the first configuration, the timer doesn't know its clock yet.
+	let source_hz = source_hz(timer);
+	if source_hz != 0 {
+		let range = frequency_range(source_hz);
+		if frequency < range.min {
+			return Err(ToneError::FrequencyTooLow);
+		}
+		if frequency > range.max {
+			return Err(ToneError::FrequencyTooHigh);
+		}
+	}
+
+	let bits = if source_hz == 0 {
+		MAX_DUTY_BITS
+	} else {
+		duty_bits(source_hz, frequency)
+	};
+	let duty = timer::config::Duty::try_from(bits).map_err(|_| ToneError::FrequencyTooHigh)?;
+	timer
+		.timer
+		.borrow_mut()
+		.configure(timer::config::Config {
+			duty,
+			clock_source: LSClockSource::APBClk,
+			frequency: Rate::from_hz(frequency as u32),
+		})
+		.map_err(|_| ToneError::Output)?;
+	Ok(bits)
+}
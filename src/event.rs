@@ -0,0 +1,181 @@
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::channel::{Channel, DynamicSender};
+use embassy_sync::pubsub::PubSubChannel;
+
+use crate::NoteAndDuration;
+
+/// Something that happened while a [`crate::Player`] played its song.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+	/// A new beat started.
+	BeatStarted { beat: u16 },
+	/// A note started playing.
+	NoteOn { frequency: u16, track: u8 },
+	/// A note finished playing.
+	NoteOff { frequency: u16, track: u8 },
+	/// The song reached its end without looping.
+	SongEnded,
+	/// A [`crate::SpeedTrainer`] changed the tempo to `bpm` beats per minute.
+	TempoChanged { bpm: u16 },
+}
+
+impl Event {
+	/// Creates an [`Event::NoteOn`] for `note`.
+	pub const fn note_on(note: &NoteAndDuration) -> Self {
+		Self::NoteOn {
+			frequency: note.frequency,
+			track: note.track,
+		}
+	}
+
+	/// Creates an [`Event::NoteOff`] for `note`.
+	pub const fn note_off(note: &NoteAndDuration) -> Self {
+		Self::NoteOff {
+			frequency: note.frequency,
+			track: note.track,
+		}
+	}
+}
+
+/// What happened during a [`crate::Player::tick_events`] call. Several things can happen in one tick.
+///
+/// ```rust,ignore
+/// let events = player.tick_events();
+/// if events.contains(buzzer_music::TickEvents::LOOPED) {
+///     display.show_next_lap();
+/// }
+/// if events.contains(buzzer_music::TickEvents::FINISHED) {
+///     player.swap_song(&NEXT_SONG, false);
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TickEvents(pub u8);
+
+impl TickEvents {
+	/// Nothing happened.
+	pub const NONE: Self = Self(0);
+	/// A new beat started, like an [`Event::BeatStarted`].
+	pub const BEAT_ADVANCED: Self = Self(1);
+	/// At least one note started, like an [`Event::NoteOn`].
+	pub const NOTES_STARTED: Self = Self(1 << 1);
+	/// A looping song went back to its start.
+	pub const LOOPED: Self = Self(1 << 2);
+	/// The song reached its end without looping, like an [`Event::SongEnded`].
+	pub const FINISHED: Self = Self(1 << 3);
+
+	/// Returns `true` if everything in `other` happened.
+	pub const fn contains(self, other: Self) -> bool {
+		self.0 & other.0 == other.0
+	}
+
+	/// Returns `true` if nothing happened.
+	pub const fn is_empty(self) -> bool {
+		self.0 == 0
+	}
+
+	/// Returns everything in either `self` or `other`.
+	pub const fn union(self, other: Self) -> Self {
+		Self(self.0 | other.0)
+	}
+}
+
+/// A queue of [`Event`]s sent by a [`crate::Player`], which another task can wait on.
+///
+/// This lets an application react to the music (lights, haptics, logging, ...) without touching the
+/// player or its outputs. Holds up to `N` events; events sent while it is full are dropped.
+///
+/// ```rust,ignore
+/// static EVENTS: buzzer_music::EventQueue<CriticalSectionRawMutex, 16> = buzzer_music::EventQueue::new();
+///
+/// player.set_event_queue(Some(&EVENTS));
+///
+/// // In another task:
+/// loop {
+///     match EVENTS.next_event().await {
+///         buzzer_music::Event::NoteOn { .. } => led.set_high(),
+///         buzzer_music::Event::NoteOff { .. } => led.set_low(),
+///         _ => {}
+///     }
+/// }
+/// ```
+pub struct EventQueue<M: RawMutex, const N: usize> {
+	channel: Channel<M, Event, N>,
+}
+
+impl<M: RawMutex, const N: usize> EventQueue<M, N> {
+	/// The constructor.
+	pub const fn new() -> Self {
+		Self {
+			channel: Channel::new(),
+		}
+	}
+
+	/// Waits for the next event.
+	pub async fn next_event(&self) -> Event {
+		self.channel.receive().await
+	}
+
+	/// Waits for the next [`Event::BeatStarted`] and returns its beat, discarding any other events.
+	///
+	/// This is meant for queues given to [`crate::Player::set_beat_queue`], which only receive beats.
+	pub async fn next_beat(&self) -> u16 {
+		loop {
+			if let Event::BeatStarted { beat } = self.next_event().await {
+				return beat;
+			}
+		}
+	}
+
+	/// Returns the next event if there is one, without waiting.
+	pub fn try_next_event(&self) -> Option<Event> {
+		self.channel.try_receive().ok()
+	}
+
+	/// Removes every event in the queue.
+	pub fn clear(&self) {
+		self.channel.clear();
+	}
+
+	/// Publishes every event sent to this queue on `channel`, forever.
+	///
+	/// This lets any number of tasks subscribe to playback (a display, LEDs, a logger, ...) while the
+	/// [`crate::Player`] only knows about this queue. Run it in its own task, and don't read this queue
+	/// anywhere else. Subscribers that fall behind miss the oldest events instead of blocking playback.
+	///
+	/// ```rust,ignore
+	/// static EVENTS: buzzer_music::EventQueue<CriticalSectionRawMutex, 16> = buzzer_music::EventQueue::new();
+	/// static PLAYBACK: PubSubChannel<CriticalSectionRawMutex, buzzer_music::Event, 16, 3, 0> = PubSubChannel::new();
+	///
+	/// #[embassy_executor::task]
+	/// async fn relay() {
+	///     EVENTS.republish(&PLAYBACK).await
+	/// }
+	///
+	/// // In the display task:
+	/// let mut events = PLAYBACK.subscriber().unwrap();
+	/// loop {
+	///     let event = events.next_message_pure().await;
+	///     // ...
+	/// }
+	/// ```
+	pub async fn republish<P: RawMutex, const CAP: usize, const SUBS: usize, const PUBS: usize>(
+		&self,
+		channel: &PubSubChannel<P, Event, CAP, SUBS, PUBS>,
+	) -> ! {
+		let publisher = channel.immediate_publisher();
+		loop {
+			publisher.publish_immediate(self.next_event().await);
+		}
+	}
+
+	/// Returns a sender that adds events to this queue.
+	pub fn sender(&self) -> DynamicSender<'_, Event> {
+		self.channel.dyn_sender()
+	}
+}
+
+impl<M: RawMutex, const N: usize> Default for EventQueue<M, N> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
@@ -0,0 +1,277 @@
+//! A C interface to the hardware-free parts of a [`Player`], for firmwares written partly in C.
+//!
+//! C code loads a song in the [`crate::raw`] format, ticks the engine, and reads its events, while
+//! the tones are played by its own PWM drivers through the callbacks of a [`BuzzerOutput`]:
+//!
+//! ```c
+//! static bool set_tone(void *context, uint8_t channel, uint16_t frequency, uint16_t duty) { ... }
+//! static void set_off(void *context, uint8_t channel) { ... }
+//!
+//! static _Alignas(8) uint8_t storage[16384];
+//! BuzzerEngine *engine = (BuzzerEngine *)storage;
+//! if (buzzer_engine_size() > sizeof(storage)) { ... }
+//!
+//! BuzzerOutput output = { NULL, set_tone, set_off, 20, 20000, 1000 };
+//! uint8_t error = buzzer_engine_init(engine, SONG, sizeof(SONG), output, 3, 500, true);
+//!
+//! while (buzzer_engine_tick(engine)) {
+//!     BuzzerEvent event;
+//!     while (buzzer_engine_next_event(engine, &event)) { ... }
+//!     sleep_ms(40);
+//! }
+//! ```
+//!
+//! The functions are exported unmangled, so linking this crate into a `staticlib` makes them
+//! available to C, and `include/buzzer_music.h` declares them. Every engine plays on
+//! [`BUZZER_CHANNELS`] channels. Drivers with fewer buzzers can leave the extra channels silent,
+//! losing the notes that land on them when several notes overlap.
+
+use core::ffi::c_void;
+use core::mem::{align_of, size_of};
+use core::ptr::{addr_of, addr_of_mut};
+
+use arrayvec::ArrayVec;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+use crate::raw;
+use crate::{
+	Event, EventQueue, FrequencyRange, NoteAndDuration, Player, Song, SongEvent, SongMetadata,
+	ToneError, ToneOutput,
+};
+
+/// How many channels an engine plays on.
+pub const BUZZER_CHANNELS: usize = 4;
+
+/// The most notes a song loaded into an engine can have.
+const MAX_NOTES: usize = 256;
+/// The most beats a song loaded into an engine can last.
+const MAX_BEATS: usize = 512;
+/// The most events a song loaded into an engine can have.
+const MAX_EVENTS: usize = 32;
+/// The most notes an engine plays at once.
+const MAX_SIMULTANEOUS_NOTES: usize = 8;
+/// The most events an engine holds before [`buzzer_engine_next_event`] reads them.
+const MAX_QUEUED_EVENTS: usize = 16;
+
+/// The PWM driver of a C firmware, which an engine plays its tones on.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct BuzzerOutput {
+	/// Passed back to the callbacks as it is.
+	pub context: *mut c_void,
+	/// Plays `frequency` with the raw `duty` on `channel`. Returns `false` if it can't.
+	pub set_tone:
+		extern "C" fn(context: *mut c_void, channel: u8, frequency: u16, duty: u16) -> bool,
+	/// Stops playing on `channel`.
+	pub set_off: extern "C" fn(context: *mut c_void, channel: u8),
+	/// The range of frequencies the driver can play.
+	pub min_frequency: u16,
+	pub max_frequency: u16,
+	/// The raw duty that is fully on.
+	pub max_duty: u16,
+}
+
+/// Something that happened while an engine played its song. See [`Event`].
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct BuzzerEvent {
+	/// `0` when a beat started, `1` when a note started, `2` when a note finished, `3` when
+	/// the song reached its end without looping, and `4` when the tempo changed.
+	pub kind: u8,
+	/// The track of the note, or `0`.
+	pub track: u8,
+	/// The beat that started, the frequency of the note, the new tempo in beats per minute, or `0`.
+	pub value: u16,
+}
+
+impl From<Event> for BuzzerEvent {
+	fn from(event: Event) -> Self {
+		match event {
+			Event::BeatStarted { beat } => Self {
+				kind: 0,
+				track: 0,
+				value: beat,
+			},
+			Event::NoteOn { frequency, track } => Self {
+				kind: 1,
+				track,
+				value: frequency,
+			},
+			Event::NoteOff { frequency, track } => Self {
+				kind: 2,
+				track,
+				value: frequency,
+			},
+			Event::SongEnded => Self {
+				kind: 3,
+				track: 0,
+				value: 0,
+			},
+			Event::TempoChanged { bpm } => Self {
+				kind: 4,
+				track: 0,
+				value: bpm,
+			},
+		}
+	}
+}
+
+/// One channel of a [`BuzzerOutput`].
+struct CallbackOutput {
+	output: BuzzerOutput,
+	channel: u8,
+}
+
+impl ToneOutput for CallbackOutput {
+	fn playable_range(&self) -> FrequencyRange {
+		FrequencyRange {
+			min: self.output.min_frequency,
+			max: self.output.max_frequency,
+		}
+	}
+
+	fn set_tone(&mut self, frequency: u16, duty: u16) -> Result<(), ToneError> {
+		if (self.output.set_tone)(self.output.context, self.channel, frequency, duty) {
+			Ok(())
+		} else {
+			Err(ToneError::Output)
+		}
+	}
+
+	fn max_duty(&self) -> u16 {
+		self.output.max_duty
+	}
+
+	fn set_off(&mut self) {
+		(self.output.set_off)(self.output.context, self.channel);
+	}
+}
+
+/// A song and the player playing it, kept together in memory owned by C.
+///
+/// The song refers to the buffers and the player to the song and the queue, so an engine must not
+/// move once it is initialized. Fields are only ever reached through raw pointers for the same reason.
+pub struct BuzzerEngine {
+	notes: ArrayVec<NoteAndDuration, MAX_NOTES>,
+	beats: ArrayVec<Option<&'static [NoteAndDuration]>, MAX_BEATS>,
+	events: ArrayVec<SongEvent, MAX_EVENTS>,
+	song: Song<'static>,
+	queue: EventQueue<NoopRawMutex, MAX_QUEUED_EVENTS>,
+	player: Option<Player<'static, CallbackOutput, BUZZER_CHANNELS, MAX_SIMULTANEOUS_NOTES>>,
+}
+
+/// Returns how many bytes a [`BuzzerEngine`] takes.
+#[unsafe(no_mangle)]
+pub extern "C" fn buzzer_engine_size() -> usize {
+	size_of::<BuzzerEngine>()
+}
+
+/// Returns the alignment a [`BuzzerEngine`] needs.
+#[unsafe(no_mangle)]
+pub extern "C" fn buzzer_engine_align() -> usize {
+	align_of::<BuzzerEngine>()
+}
+
+/// Initializes the engine at `engine` to play the song in the `song_len` bytes at `song`, written in
+/// the [`crate::raw`] format, like [`Player::new`] does. The song is copied, so it can be freed afterwards.
+///
+/// Returns `0` on success, or the code of the [`raw::RawError`] the song couldn't be read with.
+///
+/// # Safety
+///
+/// `engine` must point to at least [`buzzer_engine_size`] writable bytes aligned to
+/// [`buzzer_engine_align`], which must not be moved or used by anything else while the engine is in use.
+/// `song` must point to `song_len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn buzzer_engine_init(
+	engine: *mut BuzzerEngine,
+	song: *const u8,
+	song_len: usize,
+	output: BuzzerOutput,
+	ticks_per_beat: u16,
+	duty: u16,
+	looping: bool,
+) -> u8 {
+	// SAFETY: the caller promises `engine` is valid for writes, `song` is valid for reads, and the
+	// engine stays where it is, so references into its fields can be treated as `'static`.
+	unsafe {
+		engine.write(BuzzerEngine {
+			notes: ArrayVec::new_const(),
+			beats: ArrayVec::new_const(),
+			events: ArrayVec::new_const(),
+			song: Song {
+				notes: &[],
+				end: 0,
+				events: &[],
+				metadata: SongMetadata::NONE,
+			},
+			queue: EventQueue::new(),
+			player: None,
+		});
+
+		let bytes = core::slice::from_raw_parts(song, song_len);
+		let decoded = raw::decode(
+			bytes,
+			&mut *addr_of_mut!((*engine).notes),
+			&mut *addr_of_mut!((*engine).beats),
+			&mut *addr_of_mut!((*engine).events),
+		);
+		match decoded {
+			Ok(song) => addr_of_mut!((*engine).song).write(song),
+			Err(error) => return error.as_code(),
+		}
+
+		let pwms = core::array::from_fn(|channel| CallbackOutput {
+			output,
+			channel: channel as u8,
+		});
+		let mut player = Player::new(
+			&*addr_of!((*engine).song),
+			looping,
+			ticks_per_beat,
+			duty,
+			pwms,
+		);
+		player.set_event_queue(Some(&*addr_of!((*engine).queue)));
+		addr_of_mut!((*engine).player).write(Some(player));
+	}
+	0
+}
+
+/// Updates the engine, like [`Player::tick`]. Call it at a steady rate.
+///
+/// Returns `false` if the engine is paused (for example because its song ended) or not initialized.
+///
+/// # Safety
+///
+/// `engine` must have been initialized with [`buzzer_engine_init`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn buzzer_engine_tick(engine: *mut BuzzerEngine) -> bool {
+	// SAFETY: the caller promises `engine` was initialized, and only the player is borrowed mutably.
+	match unsafe { &mut *addr_of_mut!((*engine).player) } {
+		Some(player) => player.tick(),
+		None => false,
+	}
+}
+
+/// Moves the oldest event the engine sent into `event`, returning `false` if there are none.
+///
+/// # Safety
+///
+/// `engine` must have been initialized with [`buzzer_engine_init`], and `event` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn buzzer_engine_next_event(
+	engine: *const BuzzerEngine,
+	event: *mut BuzzerEvent,
+) -> bool {
+	// SAFETY: the caller promises `engine` was initialized and `event` can be written.
+	unsafe {
+		match (*addr_of!((*engine).queue)).try_next_event() {
+			Some(next) => {
+				event.write(next.into());
+				true
+			}
+			None => false,
+		}
+	}
+}
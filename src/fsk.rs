@@ -0,0 +1,198 @@
+use crate::ToneOutput;
+
+/// The most payload bytes an [`FskTransmitter`] can send at once.
+pub const MAX_FSK_PAYLOAD: usize = 4;
+
+/// How many symbols the rising sweep at the start of each transmission lasts.
+const PREAMBLE_SYMBOLS: u16 = 8;
+
+/// The frequencies and timing an [`FskTransmitter`] signals with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FskConfig {
+	/// The frequency of a `0` bit, and the start of the preamble sweep.
+	pub space_frequency: u16,
+	/// The frequency of a `1` bit, and the end of the preamble sweep.
+	pub mark_frequency: u16,
+	/// How many ticks each symbol lasts.
+	pub symbol_ticks: u16,
+	pub duty: u16,
+}
+
+impl FskConfig {
+	/// Audible tones that most phones and cheap microphones pick up well.
+	pub const AUDIBLE: Self = Self {
+		space_frequency: 2000,
+		mark_frequency: 3000,
+		symbol_ticks: 2,
+		duty: 100,
+	};
+
+	/// Tones most adults can barely hear, if at all. Not every microphone picks them up.
+	pub const NEAR_ULTRASONIC: Self = Self {
+		space_frequency: 18000,
+		mark_frequency: 19000,
+		symbol_ticks: 2,
+		duty: 100,
+	};
+}
+
+/// An error produced when a payload cannot be sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FskError {
+	/// The payload is longer than [`MAX_FSK_PAYLOAD`] bytes.
+	TooLong,
+	/// A transmission is already in progress.
+	Busy,
+}
+
+impl FskError {
+	/// Returns the stable numeric code of this error, from `0x30`. See [`crate::ToneError::as_code`].
+	pub const fn as_code(self) -> u8 {
+		match self {
+			Self::TooLong => 0x30,
+			Self::Busy => 0x31,
+		}
+	}
+
+	/// Returns the error with the code `code`, or `None` if it isn't one of these errors.
+	pub const fn from_code(code: u8) -> Option<Self> {
+		match code {
+			0x30 => Some(Self::TooLong),
+			0x31 => Some(Self::Busy),
+			_ => None,
+		}
+	}
+}
+
+/// Sends small payloads as tones (frequency-shift keying), for toy device-to-phone signaling.
+///
+/// Each transmission is made of symbols lasting [`FskConfig::symbol_ticks`] ticks:
+/// 1. A preamble of 8 symbols sweeping from the space frequency up to the mark frequency.
+///    Receivers can look for the sweep to find the start of a transmission.
+/// 2. One symbol of silence.
+/// 3. The frame: a length byte, the payload bytes, and a checksum byte (the XOR of the length
+///    and payload bytes). Each byte is sent most significant bit first, with the mark frequency
+///    for `1` and the space frequency for `0`.
+/// 4. The output turns off.
+///
+/// ```rust,ignore
+/// let mut transmitter = buzzer_music::FskTransmitter::new(buzzer, buzzer_music::FskConfig::AUDIBLE);
+/// transmitter.send(&[0x42]).unwrap();
+/// while transmitter.is_sending() {
+///     transmitter.tick();
+///     embassy_time::Timer::after_millis(10).await;
+/// }
+/// ```
+pub struct FskTransmitter<O: ToneOutput> {
+	output: O,
+	config: FskConfig,
+
+	frame: arrayvec::ArrayVec<u8, { MAX_FSK_PAYLOAD + 2 }>,
+	symbol: u16,
+	symbol_ticks_left: u16,
+	sending: bool,
+}
+
+impl<O: ToneOutput> FskTransmitter<O> {
+	/// The constructor.
+	pub fn new(output: O, config: FskConfig) -> Self {
+		Self {
+			output,
+			config,
+
+			frame: arrayvec::ArrayVec::new(),
+			symbol: 0,
+			symbol_ticks_left: 0,
+			sending: false,
+		}
+	}
+
+	/// Sets the frequencies and timing. Takes effect on the next transmission.
+	pub fn set_config(&mut self, config: FskConfig) {
+		self.config = config;
+	}
+
+	/// Starts sending `payload`.
+	pub fn send(&mut self, payload: &[u8]) -> Result<(), FskError> {
+		if self.sending {
+			return Err(FskError::Busy);
+		}
+		if payload.len() > MAX_FSK_PAYLOAD {
+			return Err(FskError::TooLong);
+		}
+
+		self.frame.clear();
+		self.frame.push(payload.len() as u8);
+		let mut checksum = payload.len() as u8;
+		for byte in payload {
+			self.frame.push(*byte);
+			checksum ^= byte;
+		}
+		self.frame.push(checksum);
+
+		self.symbol = 0;
+		self.symbol_ticks_left = 0;
+		self.sending = true;
+		Ok(())
+	}
+
+	/// Stops the transmission in progress.
+	pub fn cancel(&mut self) {
+		if self.sending {
+			self.sending = false;
+			self.output.set_off();
+		}
+	}
+
+	/// Returns `true` while a transmission is in progress.
+	pub fn is_sending(&self) -> bool {
+		self.sending
+	}
+
+	/// Updates the transmission. This should be called every loop, like [`crate::Player::tick`].
+	pub fn tick(&mut self) {
+		if !self.sending {
+			return;
+		}
+		if self.symbol_ticks_left > 0 {
+			self.symbol_ticks_left -= 1;
+			return;
+		}
+
+		let frame_symbols = self.frame.len() as u16 * 8;
+		let symbol = self.symbol;
+		self.symbol += 1;
+		self.symbol_ticks_left = self.config.symbol_ticks.saturating_sub(1);
+
+		let space = self.config.space_frequency as i32;
+		let mark = self.config.mark_frequency as i32;
+		let frequency = if symbol < PREAMBLE_SYMBOLS {
+			Some((space + (mark - space) * symbol as i32 / (PREAMBLE_SYMBOLS as i32 - 1)) as u16)
+		} else if symbol == PREAMBLE_SYMBOLS {
+			None
+		} else if symbol <= PREAMBLE_SYMBOLS + frame_symbols {
+			let bit = (symbol - PREAMBLE_SYMBOLS - 1) as usize;
+			let byte = self.frame[bit / 8];
+			let set = byte & (0x80 >> (bit % 8)) != 0;
+			Some(if set { mark as u16 } else { space as u16 })
+		} else {
+			self.cancel();
+			return;
+		};
+
+		match frequency {
+			Some(frequency) => {
+				if self.output.set_tone(frequency, self.config.duty).is_err() {
+					self.cancel();
+				}
+			}
+			None => self.output.set_off(),
+		}
+	}
+
+	/// Consumes the transmitter and returns its output.
+	pub fn into_output(mut self) -> O {
+		self.cancel();
+		self.output
+	}
+}
@@ -0,0 +1,113 @@
+use crate::{AudioPolicy, Chirp, RandomSource, SoundCategory, ToneOutput};
+
+/// Plays random clicks whose rate follows an input value, like a Geiger counter.
+///
+/// Every tick a click starts with a probability of `rate / 1000`, so clicks arrive irregularly
+/// (roughly a Poisson process) but average out to `rate` clicks every 1000 ticks.
+/// Randomness comes from a [`RandomSource`], such as a hardware RNG or a seeded [`crate::XorShift32`].
+///
+/// ```rust,ignore
+/// fn random() -> u32 {
+///     embassy_rp::clocks::RoscRng.next_u32()
+/// }
+///
+/// let click = buzzer_music::Chirp { frequency: 3000, duty: 100, length_ticks: 1 };
+/// let mut geiger = buzzer_music::Geiger::new(buzzer, click, random);
+/// loop {
+///     geiger.set_rate(sensor.read() / 4);
+///     geiger.tick();
+///     embassy_time::Timer::after_millis(5).await;
+/// }
+/// ```
+pub struct Geiger<'p, O: ToneOutput, R: RandomSource> {
+	output: O,
+	click: Chirp,
+	random: R,
+	rate: u16,
+	category: SoundCategory,
+	policy: Option<&'p AudioPolicy>,
+
+	click_ticks_left: u16,
+}
+
+impl<'p, O: ToneOutput, R: RandomSource> Geiger<'p, O, R> {
+	/// The constructor.
+	///
+	/// `output` is what the clicks are played on. It can be a [`crate::PoolVoice`] to share a buzzer.
+	/// `click` is the sound of each click.
+	/// `random` decides when clicks happen.
+	pub fn new(output: O, click: Chirp, random: R) -> Self {
+		Self {
+			output,
+			click,
+			random,
+			rate: 0,
+			category: SoundCategory::Telemetry,
+			policy: None,
+
+			click_ticks_left: 0,
+		}
+	}
+
+	/// Sets the average number of clicks every 1000 ticks. Rates of `1000` and up click every tick.
+	pub fn set_rate(&mut self, rate: u16) {
+		self.rate = rate;
+	}
+
+	/// Returns the rate set by [`set_rate`].
+	pub fn rate(&self) -> u16 {
+		self.rate
+	}
+
+	/// Sets the sound of each click.
+	pub fn set_click(&mut self, click: Chirp) {
+		self.click = click;
+	}
+
+	/// Sets the [`SoundCategory`] of the clicks. Defaults to [`SoundCategory::Telemetry`].
+	pub fn set_category(&mut self, category: SoundCategory) {
+		self.category = category;
+	}
+
+	/// Sets the [`AudioPolicy`] the clicks follow, or `None` to ignore policies.
+	pub fn set_policy(&mut self, policy: Option<&'p AudioPolicy>) {
+		self.policy = policy;
+	}
+
+	/// Returns `true` if a click is playing.
+	pub fn is_clicking(&self) -> bool {
+		self.click_ticks_left > 0
+	}
+
+	/// Updates the clicks. This should be called every loop, like [`crate::Player::tick`].
+	pub fn tick(&mut self) {
+		if self.click_ticks_left > 0 {
+			self.click_ticks_left -= 1;
+			if self.click_ticks_left == 0 {
+				self.output.set_off();
+			}
+			return;
+		}
+
+		if self.rate == 0 || self.random.next_below(1000) >= self.rate as u32 {
+			return;
+		}
+
+		let duty = match self.policy {
+			Some(policy) => policy.apply(self.category, self.click.duty),
+			None => self.click.duty,
+		};
+		if duty > 0
+			&& self.click.length_ticks > 0
+			&& self.output.set_tone(self.click.frequency, duty).is_ok()
+		{
+			self.click_ticks_left = self.click.length_ticks;
+		}
+	}
+
+	/// Consumes this and returns its output.
+	pub fn into_output(mut self) -> O {
+		self.output.set_off();
+		self.output
+	}
+}
@@ -0,0 +1,367 @@
+use std::fmt::Write;
+use std::string::String;
+use std::vec;
+use std::vec::Vec;
+
+use arrayvec::ArrayVec;
+
+use crate::parse::{index_beats, note_frequency, parse_note_name, split_attributes};
+use crate::{NoteAndDuration, Song, SongParseError};
+
+/// The cell of a beat grid that holds the note above it for another beat.
+const HOLD: &str = "-";
+
+/// A JSON value, as much of one as a beat grid needs.
+enum Json {
+	Null,
+	Bool,
+	Number(f64),
+	String(String),
+	Array(Vec<Json>),
+	Object(Vec<(String, Json)>),
+}
+
+/// Implements [`Song::parse_grid_json`].
+pub(crate) fn parse_grid_json<'a: 'b, 'b, const MAX_NOTES: usize, const MAX_BEATS: usize>(
+	text: &str,
+	notes: &'a mut ArrayVec<NoteAndDuration, MAX_NOTES>,
+	beats: &'b mut ArrayVec<Option<&'a [NoteAndDuration]>, MAX_BEATS>,
+) -> Result<Song<'b>, SongParseError> {
+	notes.clear();
+	beats.clear();
+
+	let mut parser = JsonParser {
+		text: text.as_bytes(),
+		position: 0,
+	};
+	let json = parser.value()?;
+	parser.whitespace();
+	if parser.position != text.len() {
+		return Err(SongParseError::InvalidGrid);
+	}
+
+	let Json::Object(fields) = json else {
+		return Err(SongParseError::InvalidGrid);
+	};
+	let field = |name: &str| {
+		fields
+			.iter()
+			.find(|(key, _)| key == name)
+			.map(|(_, value)| value)
+	};
+	let Some(Json::Array(rows)) = field("grid") else {
+		return Err(SongParseError::InvalidGrid);
+	};
+	let tracks = match field("tracks") {
+		Some(Json::Array(tracks)) => tracks
+			.iter()
+			.map(|track| match track {
+				Json::Number(track) if track.fract() == 0. && (0. ..=255.).contains(track) => {
+					Ok(*track as u8)
+				}
+				_ => Err(SongParseError::InvalidGrid),
+			})
+			.collect::<Result<Vec<u8>, _>>()?,
+		None => Vec::new(),
+		Some(_) => return Err(SongParseError::InvalidGrid),
+	};
+
+	if rows.len() > MAX_BEATS || rows.len() > u16::MAX as usize {
+		return Err(SongParseError::TooManyBeats);
+	}
+
+	// The note each column is holding, as an index into `notes`.
+	let mut held: Vec<Option<usize>> = Vec::new();
+	let mut times = ArrayVec::<u16, MAX_NOTES>::new();
+	for (beat, row) in rows.iter().enumerate() {
+		let invalid = SongParseError::InvalidEntry { index: beat };
+		let Json::Array(cells) = row else {
+			return Err(invalid);
+		};
+		if held.len() < cells.len() {
+			held.resize(cells.len(), None);
+		}
+
+		for (column, held) in held.iter_mut().enumerate() {
+			let (frequency, chance, ratchet) = match cells.get(column) {
+				None | Some(Json::Null) => (None, 100, 1),
+				Some(Json::String(cell)) if cell.trim().is_empty() => (None, 100, 1),
+				Some(Json::String(cell)) if cell.trim() == HOLD => {
+					let index = held.ok_or(invalid)?;
+					notes[index].duration = notes[index].duration.checked_add(1).ok_or(invalid)?;
+					continue;
+				}
+				Some(Json::String(cell)) => {
+					let (name, chance, ratchet) = split_attributes(cell.trim()).ok_or(invalid)?;
+					// A frequency needs to be a string to have attributes.
+					let frequency = parse_note_name(name)
+						.or_else(|| name.parse().ok().filter(|hertz| *hertz > 0))
+						.ok_or(SongParseError::UnknownNote { index: beat })?;
+					(Some(frequency), chance, ratchet)
+				}
+				Some(Json::Number(hertz))
+					if hertz.fract() == 0. && (1. ..=65535.).contains(hertz) =>
+				{
+					(Some(*hertz as u16), 100, 1)
+				}
+				Some(_) => return Err(invalid),
+			};
+
+			*held = None;
+			if let Some(frequency) = frequency {
+				let note = NoteAndDuration {
+					frequency,
+					duration: 1,
+					track: tracks
+						.get(column)
+						.copied()
+						.unwrap_or(column.min(u8::MAX as usize) as u8),
+					chance,
+					ratchet,
+				};
+				*held = Some(notes.len());
+				notes
+					.try_push(note)
+					.map_err(|_| SongParseError::TooManyNotes)?;
+				times
+					.try_push(beat as u16)
+					.map_err(|_| SongParseError::TooManyNotes)?;
+			}
+		}
+	}
+
+	Ok(index_beats(notes, &times, rows.len(), beats))
+}
+
+/// Implements [`Song::to_grid_json`].
+pub(crate) fn to_grid_json(song: &Song) -> String {
+	// Give every note a column of its track that is free when it starts.
+	// Each column is the track it belongs to and the beat it is free from.
+	let mut columns: Vec<(u8, usize)> = Vec::new();
+	let mut cells: Vec<(usize, usize, &NoteAndDuration)> = Vec::new();
+	for (beat, notes) in song.notes.iter().enumerate() {
+		for note in notes.iter().flat_map(|notes| notes.iter()) {
+			let column = match columns
+				.iter()
+				.position(|(track, free)| *track == note.track && *free <= beat)
+			{
+				Some(column) => column,
+				None => {
+					columns.push((note.track, 0));
+					columns.len() - 1
+				}
+			};
+			columns[column].1 = beat + note.duration.max(1) as usize;
+			cells.push((beat, column, note));
+		}
+	}
+
+	// Keep each track's columns together.
+	let mut order = (0..columns.len()).collect::<Vec<usize>>();
+	order.sort_by_key(|column| columns[*column].0);
+	let mut positions = vec![0; columns.len()];
+	for (position, column) in order.iter().enumerate() {
+		positions[*column] = position;
+	}
+
+	let rows = (song.end as usize).max(columns.iter().map(|(_, free)| *free).max().unwrap_or(0));
+	let mut grid = vec![vec![String::new(); columns.len()]; rows];
+	for (beat, column, note) in cells {
+		let column = positions[column];
+		grid[beat][column] = note_name(note.frequency);
+		if note.ratchet > 1 {
+			let _ = write!(grid[beat][column], "*{}", note.ratchet);
+		}
+		if note.chance < 100 {
+			let _ = write!(grid[beat][column], "?{}", note.chance);
+		}
+		for row in grid
+			.iter_mut()
+			.skip(beat + 1)
+			.take(note.duration as usize - 1)
+		{
+			row[column] = String::from(HOLD);
+		}
+	}
+
+	let mut json = String::from("{\n\t\"tracks\": [");
+	for (index, column) in order.iter().enumerate() {
+		let separator = if index == 0 { "" } else { ", " };
+		let _ = write!(json, "{separator}{}", columns[*column].0);
+	}
+	json.push_str("],\n\t\"grid\": [\n");
+	for (index, row) in grid.iter().enumerate() {
+		json.push_str("\t\t[");
+		for (column, cell) in row.iter().enumerate() {
+			let separator = if column == 0 { "" } else { ", " };
+			if !cell.is_empty() && cell.bytes().all(|c| c.is_ascii_digit()) {
+				let _ = write!(json, "{separator}{cell}");
+			} else {
+				let _ = write!(json, "{separator}\"{cell}\"");
+			}
+		}
+		json.push_str(if index + 1 == grid.len() {
+			"]\n"
+		} else {
+			"],\n"
+		});
+	}
+	json.push_str("\t]\n}\n");
+	json
+}
+
+/// Returns the name of the note at `frequency`, like `C5` or `F#3`, or the frequency itself
+/// if it isn't one of the notes [`parse_note_name`] knows.
+fn note_name(frequency: u16) -> String {
+	const NAMES: [&str; 12] = [
+		"C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+	];
+	for octave in 0..=9 {
+		for (class, name) in NAMES.iter().enumerate() {
+			if note_frequency(class, octave) == frequency {
+				let mut text = String::from(*name);
+				let _ = write!(text, "{octave}");
+				return text;
+			}
+		}
+	}
+	let mut text = String::new();
+	let _ = write!(text, "{frequency}");
+	text
+}
+
+/// Reads [`Json`] values from text.
+struct JsonParser<'t> {
+	text: &'t [u8],
+	position: usize,
+}
+
+impl JsonParser<'_> {
+	fn whitespace(&mut self) {
+		while self
+			.text
+			.get(self.position)
+			.is_some_and(u8::is_ascii_whitespace)
+		{
+			self.position += 1;
+		}
+	}
+
+	/// Moves past `byte` (after any whitespace), returning `true` if it was there.
+	fn eat(&mut self, byte: u8) -> bool {
+		self.whitespace();
+		let found = self.text.get(self.position) == Some(&byte);
+		if found {
+			self.position += 1;
+		}
+		found
+	}
+
+	/// Moves past `word`, or fails if it isn't there.
+	fn word(&mut self, word: &str) -> Result<(), SongParseError> {
+		if !self.text[self.position..].starts_with(word.as_bytes()) {
+			return Err(SongParseError::InvalidGrid);
+		}
+		self.position += word.len();
+		Ok(())
+	}
+
+	fn value(&mut self) -> Result<Json, SongParseError> {
+		self.whitespace();
+		match self.text.get(self.position) {
+			Some(b'n') => self.word("null").map(|_| Json::Null),
+			Some(b't') => self.word("true").map(|_| Json::Bool),
+			Some(b'f') => self.word("false").map(|_| Json::Bool),
+			Some(b'"') => self.string().map(Json::String),
+			Some(b'[') => {
+				self.position += 1;
+				let mut values = Vec::new();
+				if !self.eat(b']') {
+					loop {
+						values.push(self.value()?);
+						if self.eat(b']') {
+							break;
+						}
+						if !self.eat(b',') {
+							return Err(SongParseError::InvalidGrid);
+						}
+					}
+				}
+				Ok(Json::Array(values))
+			}
+			Some(b'{') => {
+				self.position += 1;
+				let mut fields = Vec::new();
+				if !self.eat(b'}') {
+					loop {
+						self.whitespace();
+						let key = self.string()?;
+						if !self.eat(b':') {
+							return Err(SongParseError::InvalidGrid);
+						}
+						fields.push((key, self.value()?));
+						if self.eat(b'}') {
+							break;
+						}
+						if !self.eat(b',') {
+							return Err(SongParseError::InvalidGrid);
+						}
+					}
+				}
+				Ok(Json::Object(fields))
+			}
+			Some(b'-' | b'0'..=b'9') => {
+				let start = self.position;
+				while self
+					.text
+					.get(self.position)
+					.is_some_and(|c| matches!(c, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+				{
+					self.position += 1;
+				}
+				core::str::from_utf8(&self.text[start..self.position])
+					.ok()
+					.and_then(|number| number.parse().ok())
+					.map(Json::Number)
+					.ok_or(SongParseError::InvalidGrid)
+			}
+			_ => Err(SongParseError::InvalidGrid),
+		}
+	}
+
+	/// Reads a string, starting at its opening quote.
+	fn string(&mut self) -> Result<String, SongParseError> {
+		if self.text.get(self.position) != Some(&b'"') {
+			return Err(SongParseError::InvalidGrid);
+		}
+		self.position += 1;
+		let mut bytes = Vec::new();
+		loop {
+			let byte = *self
+				.text
+				.get(self.position)
+				.ok_or(SongParseError::InvalidGrid)?;
+			self.position += 1;
+			match byte {
+				b'"' => break,
+				b'\\' => {
+					let escaped = *self
+						.text
+						.get(self.position)
+						.ok_or(SongParseError::InvalidGrid)?;
+					self.position += 1;
+					match escaped {
+						b'n' => bytes.push(b'\n'),
+						b't' => bytes.push(b'\t'),
+						b'r' => bytes.push(b'\r'),
+						// Note names never need other escapes, so `\u` escapes are kept as they are.
+						b'u' => bytes.extend_from_slice(b"\\u"),
+						_ => bytes.push(escaped),
+					}
+				}
+				_ => bytes.push(byte),
+			}
+		}
+		String::from_utf8(bytes).map_err(|_| SongParseError::InvalidGrid)
+	}
+}
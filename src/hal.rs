@@ -0,0 +1,183 @@
+use embedded_hal::pwm::SetDutyCycle;
+
+use crate::{DutyFraction, FrequencyRange, ToneError, ToneOutput, saturate_u16};
+
+/// A PWM whose frequency can be changed, which [`SetDutyCycle`] alone doesn't cover.
+///
+/// Implementing this for a HAL's PWM lets [`PwmOutput`] play songs on it, so a [`crate::Player`]
+/// works on any microcontroller with an `embedded-hal` PWM driver.
+///
+/// On STM32 chips with `embassy-stm32`, the frequency belongs to the timer while the duty belongs to
+/// one of its channels, so a buzzer is the timer's `SimplePwm` together with the channel it is on.
+/// The `stm32` feature adds `Stm32Buzzer`, which does this and plays without a [`PwmOutput`].
+///
+/// On ESP32 chips with `esp-hal`, the LEDC peripheral's frequency also belongs to a timer, but the
+/// timer is given a duty resolution in bits rather than working out its own period, and the higher
+/// the frequency, the fewer bits the source clock leaves room for. The `esp` feature adds
+/// `EspBuzzer`, which picks the resolution for each frequency.
+///
+/// On nRF52 chips with `embassy-nrf`, `SimplePwm` counts up to `COUNTERTOP` from a 16 MHz clock
+/// divided by its prescaler, so [`PwmClock`] works out the counter top and the maximum duty is the same.
+/// The `nrf` feature adds `NrfBuzzer`, which does this.
+pub trait SetFrequency {
+	/// Returns the range of frequencies the PWM can be set to.
+	fn frequency_range(&self) -> FrequencyRange;
+
+	/// Sets the frequency of the PWM in hertz. This may change its maximum duty cycle.
+	fn set_frequency(&mut self, frequency: u16) -> Result<(), ToneError>;
+
+	/// Tells the PWM the frequency of the clock driving it, in hertz.
+	/// PWMs that don't depend on it can ignore this.
+	fn set_clock_hz(&mut self, clock_hz: u32) {
+		let _ = clock_hz;
+	}
+}
+
+/// How a PWM counter turns its clock into a frequency, for working out the counter's period.
+///
+/// The counter ticks at `clock_hz / divider`, and one cycle of the output lasts `period` ticks,
+/// which can be from `min_period` to `max_period`. PWMs differ in all of these: an RP2040 slice
+/// counts up to a 16-bit `top` from the system clock, while an nRF52 PWM counts up to a 15-bit
+/// `COUNTERTOP` from a 16 MHz clock and a power of two prescaler.
+///
+/// ```rust
+/// use buzzer_music::{FrequencyRange, PwmClock};
+///
+/// let nrf = PwmClock { clock_hz: 16_000_000, divider: 16, min_period: 3, max_period: 32767 };
+/// assert_eq!(nrf.period(440), Ok(2272));
+/// assert_eq!(nrf.playable_range(), FrequencyRange { min: 31, max: u16::MAX });
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PwmClock {
+	/// The frequency of the clock driving the PWM, in hertz.
+	pub clock_hz: u32,
+	/// How many clock cycles each tick of the counter lasts. Must not be `0`.
+	pub divider: u32,
+	/// The shortest period the counter allows, in ticks.
+	pub min_period: u16,
+	/// The longest period the counter allows, in ticks. Must not be `0`.
+	pub max_period: u16,
+}
+
+impl PwmClock {
+	/// Returns how many counter ticks one cycle of `frequency` lasts.
+	///
+	/// Returns an error instead of panicking if the frequency cannot be produced.
+	/// This only uses integer math, so no soft-float routines are linked.
+	pub const fn period(&self, frequency: u16) -> Result<u16, ToneError> {
+		let Some(cycle_clocks) = (frequency as u32).checked_mul(self.divider) else {
+			return Err(ToneError::FrequencyTooLow);
+		};
+		let Some(period) = self.clock_hz.checked_div(cycle_clocks) else {
+			return Err(ToneError::FrequencyTooLow);
+		};
+
+		if period < self.min_period as u32 {
+			return Err(ToneError::FrequencyTooHigh);
+		}
+		if period > self.max_period as u32 {
+			return Err(ToneError::FrequencyTooLow);
+		}
+
+		Ok(period as u16)
+	}
+
+	/// Returns the range of frequencies [`PwmClock::period`] accepts.
+	pub const fn playable_range(&self) -> FrequencyRange {
+		let min_period = if self.min_period == 0 {
+			1
+		} else {
+			self.min_period as u32
+		};
+		let min = self
+			.clock_hz
+			.div_ceil(self.divider.saturating_mul(self.max_period as u32));
+		let max = self.clock_hz / self.divider.saturating_mul(min_period);
+
+		FrequencyRange {
+			min: saturate_u16(min),
+			max: saturate_u16(max),
+		}
+	}
+}
+
+/// A [`ToneOutput`] for any PWM implementing [`SetDutyCycle`] and [`SetFrequency`].
+///
+/// ```rust,ignore
+/// impl buzzer_music::SetFrequency for MyPwm {
+///     fn frequency_range(&self) -> buzzer_music::FrequencyRange {
+///         buzzer_music::FrequencyRange { min: 20, max: 20_000 }
+///     }
+///
+///     fn set_frequency(&mut self, frequency: u16) -> Result<(), buzzer_music::ToneError> {
+///         self.timer.set_period(frequency.hz());
+///         Ok(())
+///     }
+/// }
+///
+/// let buzzer = buzzer_music::PwmOutput::new(my_pwm);
+/// let mut player = buzzer_music::Player::new(&SONG, true, 3, 100, [buzzer]);
+/// ```
+pub struct PwmOutput<P: SetDutyCycle + SetFrequency> {
+	pwm: P,
+}
+
+impl<P: SetDutyCycle + SetFrequency> PwmOutput<P> {
+	/// The constructor.
+	pub fn new(pwm: P) -> Self {
+		Self { pwm }
+	}
+
+	/// Consumes this and returns the PWM.
+	pub fn into_inner(self) -> P {
+		self.pwm
+	}
+}
+
+impl<P: SetDutyCycle + SetFrequency> ToneOutput for PwmOutput<P> {
+	fn playable_range(&self) -> FrequencyRange {
+		self.pwm.frequency_range()
+	}
+
+	fn set_tone(&mut self, frequency: u16, duty: u16) -> Result<(), ToneError> {
+		set_pwm_tone(&mut self.pwm, frequency, duty)
+	}
+
+	fn max_duty(&self) -> u16 {
+		self.pwm.max_duty_cycle()
+	}
+
+	fn set_tone_fraction(&mut self, frequency: u16, duty: DutyFraction) -> Result<(), ToneError> {
+		set_pwm_tone_fraction(&mut self.pwm, frequency, duty)
+	}
+
+	fn set_off(&mut self) {
+		let _ = self.pwm.set_duty_cycle_fully_off();
+	}
+
+	fn set_clock_hz(&mut self, clock_hz: u32) {
+		self.pwm.set_clock_hz(clock_hz);
+	}
+}
+
+/// Sets `pwm` to play `frequency` with the raw `duty`.
+pub(crate) fn set_pwm_tone<P: SetDutyCycle + SetFrequency>(
+	pwm: &mut P,
+	frequency: u16,
+	duty: u16,
+) -> Result<(), ToneError> {
+	pwm.set_frequency(frequency)?;
+	pwm.set_duty_cycle(duty).map_err(|_| ToneError::Output)
+}
+
+/// Sets `pwm` to play `frequency` with `duty` as a fraction of fully on.
+pub(crate) fn set_pwm_tone_fraction<P: SetDutyCycle + SetFrequency>(
+	pwm: &mut P,
+	frequency: u16,
+	duty: DutyFraction,
+) -> Result<(), ToneError> {
+	// The duty range can depend on the frequency, so it has to be converted after setting it.
+	pwm.set_frequency(frequency)?;
+	pwm.set_duty_cycle_fraction(duty.0, u16::MAX)
+		.map_err(|_| ToneError::Output)
+}
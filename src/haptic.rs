@@ -0,0 +1,107 @@
+use embedded_hal::pwm::SetDutyCycle;
+
+use crate::{FrequencyRange, ToneError, ToneOutput};
+
+/// A [`ToneOutput`] that plays notes as vibration on an ERM or LRA motor instead of sound.
+///
+/// The motor's PWM runs at a fixed frequency set up by the user, and each note's frequency is
+/// mapped to an intensity instead: notes at the bottom of `frequency_range` vibrate at
+/// `min_intensity_percent` and notes at the top at `max_intensity_percent`.
+/// The result is scaled by the duty given to the [`crate::Player`] relative to `full_duty`, so
+/// channel gains and [`crate::AudioPolicy`] volumes still apply.
+///
+/// Every frequency is playable, so the same songs can be used for sound and silent-mode feedback.
+///
+/// ```rust,ignore
+/// let motor = buzzer_music::HapticOutput::new(motor_pwm, 100);
+/// let mut player = buzzer_music::Player::new(&SONG, false, 3, 100, [motor]);
+/// ```
+pub struct HapticOutput<P: SetDutyCycle> {
+	motor: P,
+	full_duty: u16,
+	frequency_range: FrequencyRange,
+	min_intensity_percent: u8,
+	max_intensity_percent: u8,
+}
+
+impl<P: SetDutyCycle> HapticOutput<P> {
+	/// The constructor.
+	///
+	/// `motor` is the PWM driving the motor, already set to a frequency the motor likes.
+	/// `full_duty` is the duty given to the [`crate::Player`] that means full intensity.
+	pub fn new(motor: P, full_duty: u16) -> Self {
+		Self {
+			motor,
+			full_duty,
+			frequency_range: FrequencyRange {
+				min: 131,
+				max: 2093,
+			},
+			min_intensity_percent: 40,
+			max_intensity_percent: 100,
+		}
+	}
+
+	/// Sets the frequencies mapped onto the intensity range. Defaults to C3 to C7.
+	/// Notes outside the range use the nearest intensity.
+	pub fn set_frequency_range(&mut self, range: FrequencyRange) {
+		self.frequency_range = range;
+	}
+
+	/// Sets the intensities the lowest and highest notes vibrate at. Defaults to 40% and 100%.
+	///
+	/// Most motors barely move below a certain duty, so `min_percent` should be above that.
+	pub fn set_intensity_range(&mut self, min_percent: u8, max_percent: u8) {
+		self.min_intensity_percent = min_percent.min(100);
+		self.max_intensity_percent = max_percent.min(100);
+	}
+
+	/// Returns the intensity `frequency` is played at, in percent, before scaling by duty.
+	pub fn intensity_percent(&self, frequency: u16) -> u8 {
+		let range = self.frequency_range;
+		let min = self.min_intensity_percent as i32;
+		let max = self.max_intensity_percent as i32;
+		if range.max <= range.min {
+			return max as u8;
+		}
+
+		let position = (range.clamp(frequency) - range.min) as i32;
+		let span = (range.max - range.min) as i32;
+		(min + (max - min) * position / span) as u8
+	}
+
+	/// Returns the motor PWM.
+	pub fn motor(&self) -> &P {
+		&self.motor
+	}
+
+	/// Consumes this output and returns the motor PWM.
+	pub fn into_motor(self) -> P {
+		self.motor
+	}
+}
+
+impl<P: SetDutyCycle> ToneOutput for HapticOutput<P> {
+	fn playable_range(&self) -> FrequencyRange {
+		FrequencyRange {
+			min: 1,
+			max: u16::MAX,
+		}
+	}
+
+	fn set_tone(&mut self, frequency: u16, duty: u16) -> Result<(), ToneError> {
+		let full_duty = self.full_duty.max(1) as u32;
+		let scale = (duty as u32).min(full_duty);
+		let percent = self.intensity_percent(frequency) as u32 * scale / full_duty;
+		let _ = self.motor.set_duty_cycle_percent(percent as u8);
+		Ok(())
+	}
+
+	fn max_duty(&self) -> u16 {
+		self.full_duty
+	}
+
+	fn set_off(&mut self) {
+		let _ = self.motor.set_duty_cycle_fully_off();
+	}
+}
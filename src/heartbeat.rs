@@ -0,0 +1,146 @@
+use crate::{AudioPolicy, PlayerPool, PoolVoice, SoundCategory, ToneOutput};
+
+/// A short beep played by a [`Heartbeat`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Chirp {
+	pub frequency: u16,
+	pub duty: u16,
+	pub length_ticks: u16,
+}
+
+/// Plays a [`Chirp`] every so often while everything else sharing a [`PlayerPool`] is silent.
+///
+/// This is a common requirement for locator beacons and safety devices. The chirp borrows an output
+/// from the pool with `priority` only for as long as it plays, so it coexists with any [`crate::Player`]s.
+///
+/// ```rust,ignore
+/// let pool = buzzer_music::PlayerPool::new([buzzer]);
+/// let chirp = buzzer_music::Chirp { frequency: 4000, duty: 100, length_ticks: 2 };
+///
+/// // Chirp after 30 seconds of silence when ticking every 40ms.
+/// let mut heartbeat = buzzer_music::Heartbeat::new(&pool, 750, chirp, 1);
+/// loop {
+///     heartbeat.tick();
+///     embassy_time::Timer::after_millis(40).await;
+/// }
+/// ```
+pub struct Heartbeat<'p, O: ToneOutput, const OUTPUT_COUNT: usize> {
+	pool: &'p PlayerPool<O, OUTPUT_COUNT>,
+	interval_ticks: u32,
+	chirp: Chirp,
+	priority: u8,
+	category: SoundCategory,
+	policy: Option<&'p AudioPolicy>,
+
+	enabled: bool,
+	silent_ticks: u32,
+	chirp_ticks_left: u16,
+	voice: Option<PoolVoice<'p, O, OUTPUT_COUNT>>,
+}
+
+impl<'p, O: ToneOutput, const OUTPUT_COUNT: usize> Heartbeat<'p, O, OUTPUT_COUNT> {
+	/// The constructor.
+	///
+	/// `pool` is the [`PlayerPool`] an output is borrowed from.
+	/// `interval_ticks` is how many ticks of silence there must be before each chirp.
+	/// `chirp` is the sound to play.
+	/// `priority` is the priority the output is leased with.
+	pub fn new(
+		pool: &'p PlayerPool<O, OUTPUT_COUNT>,
+		interval_ticks: u32,
+		chirp: Chirp,
+		priority: u8,
+	) -> Self {
+		Self {
+			pool,
+			interval_ticks,
+			chirp,
+			priority,
+			category: SoundCategory::Telemetry,
+			policy: None,
+
+			enabled: true,
+			silent_ticks: 0,
+			chirp_ticks_left: 0,
+			voice: None,
+		}
+	}
+
+	/// Sets how many ticks of silence there must be before each chirp.
+	pub fn set_interval_ticks(&mut self, interval_ticks: u32) {
+		self.interval_ticks = interval_ticks;
+	}
+
+	/// Sets the sound to play.
+	pub fn set_chirp(&mut self, chirp: Chirp) {
+		self.chirp = chirp;
+	}
+
+	/// Sets the [`SoundCategory`] of the chirp. Defaults to [`SoundCategory::Telemetry`].
+	pub fn set_category(&mut self, category: SoundCategory) {
+		self.category = category;
+	}
+
+	/// Sets the [`AudioPolicy`] the chirp follows, or `None` to ignore policies.
+	pub fn set_policy(&mut self, policy: Option<&'p AudioPolicy>) {
+		self.policy = policy;
+	}
+
+	/// Starts or stops chirping. Stopping ends a chirp that is playing.
+	pub fn set_enabled(&mut self, enabled: bool) {
+		self.enabled = enabled;
+		if !enabled {
+			self.voice = None;
+			self.silent_ticks = 0;
+		}
+	}
+
+	/// Returns `true` if a chirp is playing.
+	pub fn is_chirping(&self) -> bool {
+		self.voice.is_some()
+	}
+
+	/// Updates the heartbeat.
+	/// This should be called every loop, like [`crate::Player::tick`].
+	pub fn tick(&mut self) {
+		if !self.enabled {
+			return;
+		}
+
+		// Finish the chirp that's playing, giving the output back to the pool.
+		if self.voice.is_some() {
+			self.chirp_ticks_left = self.chirp_ticks_left.saturating_sub(1);
+			if self.chirp_ticks_left == 0 {
+				self.voice = None;
+			}
+			return;
+		}
+
+		// Only count ticks where nothing else is playing.
+		if !self.pool.is_silent() {
+			self.silent_ticks = 0;
+			return;
+		}
+
+		self.silent_ticks += 1;
+		if self.silent_ticks < self.interval_ticks {
+			return;
+		}
+		self.silent_ticks = 0;
+
+		let duty = match self.policy {
+			Some(policy) => policy.apply(self.category, self.chirp.duty),
+			None => self.chirp.duty,
+		};
+		if duty == 0 {
+			return;
+		}
+
+		if let Some(mut voice) = self.pool.lease(self.priority)
+			&& voice.set_tone(self.chirp.frequency, duty).is_ok()
+		{
+			self.chirp_ticks_left = self.chirp.length_ticks.max(1);
+			self.voice = Some(voice);
+		}
+	}
+}
@@ -0,0 +1,133 @@
+use embedded_hal::pwm::SetDutyCycle;
+
+use crate::{FrequencyRange, ToneError, ToneOutput};
+
+/// The frequencies of C4 to B4 in hundredths of a hertz, used to find a note's pitch class.
+pub(crate) const OCTAVE_CENTIHZ: [u32; 12] = [
+	26163, 27718, 29366, 31113, 32963, 34923, 36999, 39200, 41530, 44000, 46616, 49388,
+];
+
+/// A [`ToneOutput`] that "plays" notes as light on one or more PWM LEDs, for devices without a speaker.
+///
+/// Each note is turned into a brightness for every LED by a color function, then scaled by
+/// the duty given to the [`crate::Player`] relative to `full_duty`.
+/// The default color function is [`pitch_color`], which gives every pitch class its own color.
+///
+/// Every frequency is playable, so the same songs can be shown and heard.
+///
+/// ```rust,ignore
+/// let rgb = buzzer_music::LedOutput::new([red_pwm, green_pwm, blue_pwm], 100);
+/// let mut player = buzzer_music::Player::new(&SONG, true, 3, 100, [rgb]);
+/// ```
+pub struct LedOutput<P: SetDutyCycle, const CHANNELS: usize> {
+	leds: [P; CHANNELS],
+	full_duty: u16,
+	color: fn(u16) -> [u8; CHANNELS],
+}
+
+impl<P: SetDutyCycle, const CHANNELS: usize> LedOutput<P, CHANNELS> {
+	/// The constructor.
+	///
+	/// `leds` are the PWMs driving each color channel, for example red, green, and blue.
+	/// `full_duty` is the duty given to the [`crate::Player`] that means full brightness.
+	pub fn new(leds: [P; CHANNELS], full_duty: u16) -> Self {
+		Self {
+			leds,
+			full_duty,
+			color: pitch_color::<CHANNELS>,
+		}
+	}
+
+	/// Sets the function converting a frequency into a brightness percentage for each LED.
+	pub fn set_color(&mut self, color: fn(u16) -> [u8; CHANNELS]) {
+		self.color = color;
+	}
+
+	/// Returns the LED PWMs.
+	pub fn leds(&self) -> &[P; CHANNELS] {
+		&self.leds
+	}
+
+	/// Consumes this output and returns the LED PWMs.
+	pub fn into_leds(self) -> [P; CHANNELS] {
+		self.leds
+	}
+}
+
+impl<P: SetDutyCycle, const CHANNELS: usize> ToneOutput for LedOutput<P, CHANNELS> {
+	fn playable_range(&self) -> FrequencyRange {
+		FrequencyRange {
+			min: 1,
+			max: u16::MAX,
+		}
+	}
+
+	fn set_tone(&mut self, frequency: u16, duty: u16) -> Result<(), ToneError> {
+		let full_duty = self.full_duty.max(1) as u32;
+		let scale = (duty as u32).min(full_duty);
+		let color = (self.color)(frequency);
+		for (led, brightness) in self.leds.iter_mut().zip(color) {
+			let percent = brightness.min(100) as u32 * scale / full_duty;
+			let _ = led.set_duty_cycle_percent(percent as u8);
+		}
+		Ok(())
+	}
+
+	fn max_duty(&self) -> u16 {
+		self.full_duty
+	}
+
+	fn set_off(&mut self) {
+		for led in &mut self.leds {
+			let _ = led.set_duty_cycle_fully_off();
+		}
+	}
+}
+
+/// Returns the pitch class of `frequency`, from `0` for C to `11` for B.
+pub const fn pitch_class(frequency: u16) -> u8 {
+	if frequency == 0 {
+		return 0;
+	}
+
+	// Move the frequency into the octave starting at C4.
+	let mut centihz = frequency as u32 * 100;
+	while centihz < OCTAVE_CENTIHZ[0] {
+		centihz *= 2;
+	}
+	while centihz >= OCTAVE_CENTIHZ[0] * 2 {
+		centihz /= 2;
+	}
+
+	// Raise it by a quarter tone so it rounds to the nearest note.
+	let centihz = centihz * 10293 / 10000;
+	if centihz >= OCTAVE_CENTIHZ[0] * 2 {
+		return 0;
+	}
+
+	let mut class = 11;
+	while OCTAVE_CENTIHZ[class] > centihz {
+		class -= 1;
+	}
+	class as u8
+}
+
+/// The default color function of [`LedOutput`].
+///
+/// The pitch classes are spread evenly around the LEDs like a color wheel, blending between neighbours.
+/// With red, green, and blue LEDs, C is red, E is green, and G# is blue.
+/// With a single LED, every note is at full brightness.
+pub fn pitch_color<const CHANNELS: usize>(frequency: u16) -> [u8; CHANNELS] {
+	let mut color = [0; CHANNELS];
+	if CHANNELS == 0 {
+		return color;
+	}
+
+	// The position around the wheel, in hundredths of a channel.
+	let position = pitch_class(frequency) as usize * CHANNELS * 100 / 12;
+	let channel = position / 100;
+	let blend = (position % 100) as u8;
+	color[channel] = 100 - blend;
+	color[(channel + 1) % CHANNELS] += blend;
+	color
+}
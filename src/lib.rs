@@ -1,59 +1,1037 @@
 #![no_std]
+#![cfg_attr(
+	feature = "strict-lints",
+	deny(
+		clippy::unwrap_used,
+		clippy::expect_used,
+		clippy::panic,
+		clippy::float_arithmetic
+	)
+)]
 
-///! ------------------------------------
-///! Buzzer Music for Rust using Embassy.
-///! ------------------------------------
-///! Create music using one or more piezo buzzers with Rust!
-///!
-///! https://github.com/SomeRanDev/buzzer_music.rs/blob/main/LICENSE
-///!
-///! Heavily based on https://github.com/james1236/buzzer_music
-///! https://github.com/james1236/buzzer_music/blob/main/LICENSE
+//! ------------------------------------
+//! Buzzer Music for Rust using Embassy.
+//! ------------------------------------
+//! Create music using one or more piezo buzzers with Rust!
+//!
+//! https://github.com/SomeRanDev/buzzer_music.rs/blob/main/LICENSE
+//!
+//! Heavily based on https://github.com/james1236/buzzer_music
+//! https://github.com/james1236/buzzer_music/blob/main/LICENSE
+
+#[cfg(feature = "std")]
+extern crate std;
+
+mod analysis;
+mod beats;
+mod continuous;
+mod duty;
+mod duty_limit;
+#[cfg(feature = "esp")]
+mod esp;
+mod event;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod fsk;
+mod geiger;
+#[cfg(feature = "std")]
+mod grid;
+mod hal;
+mod haptic;
+mod heartbeat;
+mod led;
+mod live;
+mod mixer;
+#[cfg(feature = "nrf")]
+mod nrf;
+mod pan;
+mod parse;
+mod playlist;
+mod policy;
+mod pool;
+mod profile;
+mod random;
+pub mod raw;
+#[cfg(feature = "rp")]
+mod rp;
+#[cfg(feature = "time")]
+mod run;
+mod runtime;
+mod shadow;
+mod sonify;
+mod stats;
+#[cfg(feature = "stm32")]
+mod stm32;
+mod strum;
+mod teaching;
+mod tee;
+#[cfg(feature = "std")]
+pub mod testing;
+#[cfg(test)]
+mod tests;
+mod tuner;
+
+pub use analysis::{POLYPHONY_HISTOGRAM_SIZE, SongAnalysis, SongReport, SongSections};
+pub use beats::{Beat, Beats};
+pub use continuous::ContinuousTone;
+pub use duty::DutyFraction;
+pub use duty_limit::DutyLimit;
+#[cfg(feature = "esp")]
+pub use esp::{EspBuzzer, EspTimer};
+pub use event::{Event, EventQueue, TickEvents};
+pub use fsk::{FskConfig, FskError, FskTransmitter, MAX_FSK_PAYLOAD};
+pub use geiger::Geiger;
+pub use hal::{PwmClock, PwmOutput, SetFrequency};
+pub use haptic::HapticOutput;
+pub use heartbeat::{Chirp, Heartbeat};
+pub use led::{LedOutput, pitch_class, pitch_color};
+pub use live::{
+	LiveAction, LivePattern, LiveRule, LiveSource, MAX_LIVE_RULES, RuleBuilder, Schedule,
+};
+pub use mixer::Mixer;
+#[cfg(feature = "nrf")]
+pub use nrf::NrfBuzzer;
+pub use pan::{MAX_PANNED_TRACKS, pan_gain_percent};
+pub use parse::SongParseError;
+pub use playlist::Playlist;
+pub use policy::{AudioPolicy, SoundCategory};
+pub use pool::{PlayerPool, PoolVoice};
+pub use profile::{
+	BoardProfile, MAX_RESONANCE_POINTS, ProfileError, ProfileStorage, ResonanceCurve,
+	ResonancePoint,
+};
+pub use random::{RandomSource, XorShift32};
+#[cfg(feature = "time")]
+pub use run::RunControl;
+pub use runtime::RuntimeSong;
+pub use shadow::ShadowOutput;
+pub use sonify::{Scale, Sonifier};
+pub use stats::PlaybackStats;
+#[cfg(feature = "stm32")]
+pub use stm32::Stm32Buzzer;
+pub use strum::{MAX_STRUMMED_TRACKS, StrumDirection};
+pub use teaching::{Grade, HitWindows, Judgment, PlayAlong, Score};
+pub use tee::TeeOutput;
+pub use tuner::{Tuner, TuningReading};
 
 /// Creates an instance of [`buzzer_music::Song`] using the `onlinesequencer.net` format.
 /// This parses the content at compile-time and produces a packed version of the song.
 ///
 /// ```rust
+/// use buzzer_music::declare_song;
+///
 /// const MYSTERY_SONG: buzzer_music::Song = declare_song!("0 D5 1 11;2 D5 1 11;4 D6 1 11;8 A5 1 11;14 G#5 1 11;18 G5 1 11;22 F5 1 11;26 D5 1 11;28 F5 1 11;30 G5 1 11;0 D4 1 15;2 D4 1 15;4 D5 1 15;8 A4 1 15;14 G#4 1 15;18 G4 1 15;22 F4 1 15;26 D4 1 15;28 F4 1 15;30 G4 1 15;0 D4 1.75 14;2 D4 1.75 14;4 D5 1.75 14;8 A4 1.75 14;14 G#4 1.75 14;18 G4 1.75 14;22 F4 1.75 14;26 D4 1.75 14;28 F4 1.75 14;30 G4 1.75 14");
 /// ```
+///
+/// Entries can be split over several lines, and empty entries are skipped.
+///
+/// ```rust
+/// const SCALE: buzzer_music::Song = buzzer_music::declare_song!("
+///     0 C5 1 0;
+///     1 D5 1 0;
+///     2 E5 1 0;
+/// ");
+/// assert_eq!(SCALE.notes[2].unwrap()[0].frequency, 659);
+/// ```
+///
+/// An entry that can't be read is a compile error, rather than a panic.
+///
+/// ```rust,compile_fail
+/// const SONG: buzzer_music::Song = buzzer_music::declare_song!("0 C5 1 0;1 D5");
+/// ```
 pub use buzzer_music_macros::declare_song;
 
+/// Creates an instance of [`buzzer_music::Song`] from an RTTTL (Nokia ringtone) string.
+/// This parses the content at compile-time, like [`declare_song`].
+///
+/// Each beat lasts as long as the ringtone's shortest note. The song starts with a [`SongEventKind::SetTempo`]
+/// matching the ringtone's tempo when [`Player::tick`] is called every `tick_ms` milliseconds (`40` by default),
+/// so the `ticks_per_beat` given to [`Player::new`] doesn't matter.
+///
+/// ```rust
+/// use buzzer_music::declare_rtttl;
+///
+/// const SONG: buzzer_music::Song = declare_rtttl!("Beep:d=8,o=5,b=120:c,e,g,4c6,p", tick_ms = 10);
+/// assert_eq!(SONG.end, 6);
+/// ```
+pub use buzzer_music_macros::declare_rtttl;
+
+/// Creates an instance of [`buzzer_music::Song`] from an MML (Music Macro Language) string.
+/// This parses the content at compile-time, like [`declare_song`].
+///
+/// Voices separated by `,` play at the same time on their own tracks. Tempo commands become
+/// [`SongEventKind::SetTempo`] events for a [`Player`] ticking every `tick_ms` milliseconds (`40` by default).
+/// Like in [`declare_song_from_midi`], `beats_per_quarter` and `rounding` snap notes to a number of beats per
+/// quarter note, as they can for [`declare_rtttl`] and [`declare_abc`] too.
+///
+/// ```rust
+/// use buzzer_music::declare_mml;
+///
+/// const SONG: buzzer_music::Song = declare_mml!("t120 o4 l8 cdefgab>c4, o3 c2 g2", tick_ms = 10);
+/// assert_eq!(SONG.end, 9);
+/// assert_eq!(SONG.notes[0].unwrap().len(), 2);
+///
+/// // Two beats per quarter note snaps notes to eighth notes, so the sixteenth note moves onto the next beat.
+/// const SNAPPED: buzzer_music::Song = declare_mml!("t120 c8. d16 e4", beats_per_quarter = 2);
+/// assert_eq!(SNAPPED.end, 4);
+/// assert_eq!(SNAPPED.notes[2].unwrap().len(), 2);
+///
+/// // Rests count toward the song's length, even at its end.
+/// const RESTING: buzzer_music::Song = declare_mml!("t120 c4 r2");
+/// assert_eq!(RESTING.end, 3);
+/// ```
+pub use buzzer_music_macros::declare_mml;
+
+/// Creates an instance of [`buzzer_music::Song`] from a tune in ABC notation.
+/// This parses the content at compile-time, like [`declare_song`].
+///
+/// Repeats and endings are played out in full. `Q:` fields become [`SongEventKind::SetTempo`] events
+/// for a [`Player`] ticking every `tick_ms` milliseconds (`40` by default).
+///
+/// ```rust
+/// use buzzer_music::declare_abc;
+///
+/// const SONG: buzzer_music::Song = declare_abc!("X:1\nM:4/4\nL:1/8\nQ:1/4=120\nK:G\n|: GABc d2 d2 :|", tick_ms = 10);
+/// assert_eq!(SONG.end, 16);
+/// assert_eq!(SONG.notes[8].unwrap()[0].frequency, 392);
+///
+/// // Rests count toward the song's length, even at its end.
+/// const RESTING: buzzer_music::Song = declare_abc!("X:1\nL:1/4\nK:C\nC z2|");
+/// assert_eq!(RESTING.end, 3);
+/// ```
+pub use buzzer_music_macros::declare_abc;
+
+/// Creates an instance of [`buzzer_music::Song`] from a standard MIDI file, relative to the crate's `Cargo.toml`.
+/// This parses the file at compile-time, like [`declare_song`], and rebuilds when it changes.
+///
+/// Each MIDI channel becomes a track, leaving out drums. Note times are snapped to `beats_per_quarter` beats per
+/// quarter note (`4` by default), and tempo changes become [`SongEventKind::SetTempo`] events for a [`Player`]
+/// ticking every `tick_ms` milliseconds (`40` by default). `track = <index>` reads only one of the file's tracks.
+/// Channels can be left out, merged, or moved by octaves with `drop_tracks`, `merge_tracks`, and `octave_shifts`.
+///
+/// ```rust,ignore
+/// const SONG: buzzer_music::Song = buzzer_music::declare_song_from_midi!("assets/song.mid", track = 1, tick_ms = 10);
+/// const DUET: buzzer_music::Song =
+///     buzzer_music::declare_song_from_midi!("assets/band.mid", drop_tracks = [3], merge_tracks = [(2, 1)], octave_shifts = [(1, -1)]);
+/// ```
+pub use buzzer_music_macros::declare_song_from_midi;
+
 /// Represents a song.
-pub struct Song {
-	pub notes: &'static [Option<&'static [NoteAndDuration]>],
+pub struct Song<'a> {
+	pub notes: &'a [Option<&'a [NoteAndDuration]>],
 	pub end: u16,
+	/// Events applied during playback, sorted by beat.
+	pub events: &'a [SongEvent],
+	/// Information about the song that isn't needed to play it, like its title.
+	pub metadata: SongMetadata<'a>,
+}
+
+/// Information about a [`Song`], set with the `title`, `author`, and `bpm` options of [`declare_song`]
+/// or with [`Song::with_metadata`], for listing songs in a jukebox menu and the like.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SongMetadata<'a> {
+	pub title: Option<&'a str>,
+	pub author: Option<&'a str>,
+	/// The tempo the song was written at, in beats per minute. See [`Song::ticks_per_beat`].
+	pub bpm: Option<u16>,
+}
+
+impl SongMetadata<'_> {
+	/// No information at all.
+	pub const NONE: Self = Self {
+		title: None,
+		author: None,
+		bpm: None,
+	};
+}
+
+impl<'a> Song<'a> {
+	/// Parses a song in the `onlinesequencer.net` format at runtime, like [`declare_song`] does at compile-time.
+	///
+	/// The song's notes are stored in `notes`, and the list of notes starting on each beat in `beats`.
+	/// Both are cleared first. Entries are `time note duration instrument`, separated by `;`.
+	/// Times are rounded to the nearest beat and durations rounded up, like [`declare_song`].
+	/// A note followed by `?` and a percentage, like `C5?50`, has that [`NoteAndDuration::chance`] of playing,
+	/// and a note followed by `*` and a count, like `C5*3`, is played as that many [`NoteAndDuration::ratchet`] hits.
+	///
+	/// ```rust
+	/// use arrayvec::ArrayVec;
+	///
+	/// let mut notes = ArrayVec::<buzzer_music::NoteAndDuration, 32>::new();
+	/// let mut beats = ArrayVec::<_, 64>::new();
+	/// let song = buzzer_music::Song::parse("0 C5 1 0;2 E5 1.5 0;0 G4?25 1 1", &mut notes, &mut beats).unwrap();
+	/// assert_eq!(song.end, 8);
+	/// assert_eq!(song.notes[0].unwrap().len(), 2);
+	/// assert_eq!(song.notes[0].unwrap()[1].chance, 25);
+	/// assert_eq!(song.notes[2].unwrap()[0].duration, 2);
+	/// ```
+	pub fn parse<'n: 'a, const MAX_NOTES: usize, const MAX_BEATS: usize>(
+		text: &str,
+		notes: &'n mut arrayvec::ArrayVec<NoteAndDuration, MAX_NOTES>,
+		beats: &'a mut arrayvec::ArrayVec<Option<&'n [NoteAndDuration]>, MAX_BEATS>,
+	) -> Result<Self, SongParseError> {
+		parse::parse(text, notes, beats)
+	}
+
+	/// Parses an RTTTL (Nokia ringtone) song at runtime, like [`declare_rtttl`] does at compile-time.
+	///
+	/// The buffers are used like in [`Song::parse`]. Returns the song and the `ticks_per_beat` that plays it
+	/// at the ringtone's tempo when [`Player::tick`] is called every `tick_ms` milliseconds.
+	///
+	/// ```rust
+	/// use arrayvec::ArrayVec;
+	///
+	/// let mut notes = ArrayVec::<buzzer_music::NoteAndDuration, 32>::new();
+	/// let mut beats = ArrayVec::<_, 64>::new();
+	/// let (song, ticks_per_beat) =
+	///     buzzer_music::Song::parse_rtttl("Beep:d=8,o=5,b=120:c,e,g,4c6,p", 10, &mut notes, &mut beats).unwrap();
+	/// assert_eq!(song.end, 6);
+	/// assert_eq!(ticks_per_beat, 25);
+	/// ```
+	pub fn parse_rtttl<'n: 'a, const MAX_NOTES: usize, const MAX_BEATS: usize>(
+		text: &str,
+		tick_ms: u16,
+		notes: &'n mut arrayvec::ArrayVec<NoteAndDuration, MAX_NOTES>,
+		beats: &'a mut arrayvec::ArrayVec<Option<&'n [NoteAndDuration]>, MAX_BEATS>,
+	) -> Result<(Self, u16), SongParseError> {
+		parse::parse_rtttl(text, tick_ms, notes, beats)
+	}
+
+	/// Parses a song from beat-grid JSON, the format written by [`Song::to_grid_json`].
+	///
+	/// A beat grid is a `"grid"` of rows, one per beat, with a cell for each column. A cell is a note name
+	/// like `"C5"` or `"F#3"` (or a frequency in hertz), `"-"` to hold the note above it for another beat,
+	/// or `""` or `null` for silence. The optional `"tracks"` list gives the track of each column,
+	/// which otherwise defaults to the column's index. This is easy to edit in a spreadsheet or a small web tool.
+	///
+	/// Like a step sequencer's trigger probability, a note followed by `?` and a percentage, like `"C5?50"`,
+	/// has that [`NoteAndDuration::chance`] of playing each pass. A note followed by `*` and a count, like
+	/// `"C5*3"`, is played as that many [`NoteAndDuration::ratchet`] hits. Both can be used, like `"C5*3?50"`.
+	///
+	/// The buffers are used like in [`Song::parse`]. Only available with the `std` feature.
+	///
+	/// ```rust
+	/// # #[cfg(feature = "std")] {
+	/// use arrayvec::ArrayVec;
+	///
+	/// let json = r#"{ "tracks": [0, 1], "grid": [["C5", "C3"], ["-", null], ["E5", "G3?50"]] }"#;
+	/// let mut notes = ArrayVec::<buzzer_music::NoteAndDuration, 32>::new();
+	/// let mut beats = ArrayVec::<_, 64>::new();
+	/// let song = buzzer_music::Song::parse_grid_json(json, &mut notes, &mut beats).unwrap();
+	/// assert_eq!(song.end, 3);
+	/// assert_eq!(song.notes[0].unwrap()[0].duration, 2);
+	/// assert_eq!(song.notes[2].unwrap()[1].track, 1);
+	/// assert_eq!(song.notes[2].unwrap()[1].chance, 50);
+	/// # }
+	/// ```
+	#[cfg(feature = "std")]
+	pub fn parse_grid_json<'n: 'a, const MAX_NOTES: usize, const MAX_BEATS: usize>(
+		text: &str,
+		notes: &'n mut arrayvec::ArrayVec<NoteAndDuration, MAX_NOTES>,
+		beats: &'a mut arrayvec::ArrayVec<Option<&'n [NoteAndDuration]>, MAX_BEATS>,
+	) -> Result<Self, SongParseError> {
+		grid::parse_grid_json(text, notes, beats)
+	}
+
+	/// Writes this song as beat-grid JSON, which [`Song::parse_grid_json`] reads back.
+	///
+	/// Notes that overlap on a track get their own columns. Events aren't included.
+	/// Only available with the `std` feature, for converting songs on the host or in a `build.rs`.
+	#[cfg(feature = "std")]
+	pub fn to_grid_json(&self) -> std::string::String {
+		grid::to_grid_json(self)
+	}
+
+	/// Returns this song with `events` applied during playback.
+	///
+	/// ```rust
+	/// use buzzer_music::{SongEvent, SongEventKind, declare_song};
+	///
+	/// const SONG: buzzer_music::Song = declare_song!("0 C5 1 0;4 E5 1 0;8 G5 4 0").with_events(&[SongEvent {
+	///     beat: 4,
+	///     kind: SongEventKind::TempoRamp { from: 3, to: 6, beats: 8 },
+	/// }]);
+	/// ```
+	///
+	/// Fails to compile (when used in a `const`) if the events aren't sorted by beat,
+	/// or a tempo event has a tempo of `0`.
+	pub const fn with_events(self, events: &'a [SongEvent]) -> Self {
+		let mut i = 0;
+		while i < events.len() {
+			if i > 0 {
+				assert!(
+					events[i - 1].beat <= events[i].beat,
+					"Song events must be sorted by beat"
+				);
+			}
+			match events[i].kind {
+				SongEventKind::SetTempo { ticks_per_beat } => {
+					assert!(ticks_per_beat > 0, "`SetTempo` must not have a tempo of 0");
+				}
+				SongEventKind::TempoRamp { from, to, .. } => {
+					assert!(from > 0 && to > 0, "`TempoRamp` must not have a tempo of 0");
+				}
+				_ => {}
+			}
+			i += 1;
+		}
+
+		Self { events, ..self }
+	}
+
+	/// Returns this song with `metadata`, replacing any it had.
+	///
+	/// ```rust
+	/// use buzzer_music::{SongMetadata, declare_song};
+	///
+	/// const SONG: buzzer_music::Song = declare_song!("0 C5 1 0").with_metadata(SongMetadata {
+	///     title: Some("Beep"),
+	///     author: None,
+	///     bpm: Some(150),
+	/// });
+	/// assert_eq!(SONG.metadata.title, Some("Beep"));
+	/// ```
+	pub const fn with_metadata(self, metadata: SongMetadata<'a>) -> Self {
+		Self { metadata, ..self }
+	}
+
+	/// Returns the `ticks_per_beat` that plays this song at the tempo it was written at, when
+	/// [`Player::tick`] is called every `tick_period_ms` milliseconds, or `None` if the song's
+	/// [`SongMetadata`] doesn't have a `bpm`. See [`bpm_to_ticks_per_beat`].
+	///
+	/// ```rust
+	/// use buzzer_music::declare_song;
+	///
+	/// const SONG: buzzer_music::Song = declare_song!("0 C5 1 0;1 E5 1 0", title = "Two notes", bpm = 120);
+	/// assert_eq!(SONG.metadata.title, Some("Two notes"));
+	/// assert_eq!(SONG.ticks_per_beat(10), Some(50));
+	/// ```
+	pub const fn ticks_per_beat(&self, tick_period_ms: u16) -> Option<u16> {
+		match self.metadata.bpm {
+			Some(bpm) => Some(bpm_to_ticks_per_beat(bpm, tick_period_ms)),
+			None => None,
+		}
+	}
+
+	/// Returns an iterator over every beat of this song, for visualizers and analysis.
+	///
+	/// ```rust
+	/// use buzzer_music::declare_song;
+	///
+	/// const SONG: buzzer_music::Song = declare_song!("0 C5 2 0;4 E5 1 0");
+	/// let rests = SONG.beats().filter(|beat| beat.is_rest()).count();
+	/// assert_eq!(rests, 5);
+	/// ```
+	pub fn beats(&self) -> Beats<'_> {
+		Beats::new(self)
+	}
+
+	/// Returns the lowest and highest frequencies used by this song.
+	/// Returns `None` if the song has no notes.
+	pub const fn frequency_range(&self) -> Option<FrequencyRange> {
+		let mut range: Option<FrequencyRange> = None;
+
+		let mut beat = 0;
+		while beat < self.notes.len() {
+			if let Some(notes) = self.notes[beat] {
+				let mut i = 0;
+				while i < notes.len() {
+					let frequency = notes[i].frequency;
+					range = match range {
+						Some(r) => Some(FrequencyRange {
+							min: if frequency < r.min { frequency } else { r.min },
+							max: if frequency > r.max { frequency } else { r.max },
+						}),
+						None => Some(FrequencyRange {
+							min: frequency,
+							max: frequency,
+						}),
+					};
+					i += 1;
+				}
+			}
+			beat += 1;
+		}
+
+		range
+	}
+
+	/// Returns how many bytes of flash this song's notes and events take up, including the `Song` itself.
+	///
+	/// This can be checked at compile time to keep large songs within a flash budget.
+	pub const fn storage_size(&self) -> usize {
+		let mut size = core::mem::size_of::<Song>()
+			+ core::mem::size_of_val(self.notes)
+			+ core::mem::size_of_val(self.events);
+
+		let mut beat = 0;
+		while beat < self.notes.len() {
+			if let Some(notes) = self.notes[beat] {
+				size += core::mem::size_of_val(notes);
+			}
+			beat += 1;
+		}
+
+		size
+	}
+
+	/// Returns statistics about this song's pitch range, note density, and polyphony.
+	/// See [`SongAnalysis`].
+	pub const fn analyze(&self) -> SongAnalysis {
+		SongAnalysis::of(self)
+	}
+
+	/// Finds where this song's sections start, for songs without [`SongEventKind::Marker`]s to jump
+	/// between. See [`SongSections`]; at most `N` are found.
+	///
+	/// A section starts on the first note after at least `min_rest_beats` silent beats, and on a
+	/// phrase of `phrase_beats` beats that changes the material: it returns to an earlier phrase,
+	/// or follows a phrase that was repeated. Passing `0` for either skips that check.
+	pub const fn find_sections<const N: usize>(
+		&self,
+		min_rest_beats: u16,
+		phrase_beats: u16,
+	) -> SongSections<N> {
+		SongSections::of(self, min_rest_beats, phrase_beats)
+	}
+
+	/// Estimates how many ticks this song takes to play once, starting at `ticks_per_beat`.
+	///
+	/// Tempo changes, tempo ramps, and fermatas are included. Holds and branches are not,
+	/// since they depend on what happens during playback.
+	pub const fn estimated_duration_ticks(&self, ticks_per_beat: u16) -> u32 {
+		analysis::estimated_duration_ticks(self, ticks_per_beat)
+	}
+
+	/// Checks this song for arrangement bugs when played with `ticks_per_beat`, calling
+	/// [`Player::tick`] every `tick_ms` milliseconds. See [`SongReport`].
+	///
+	/// Being `const`, this can be checked at compile time before deploying a song.
+	pub const fn validate_for(&self, tick_ms: u16, ticks_per_beat: u16) -> SongReport {
+		analysis::validate(self, tick_ms, ticks_per_beat)
+	}
+
+	/// Returns `true` if every note in this song can be produced by `output`.
+	///
+	/// This can be used to warn (or transpose) before playing a song whose notes
+	/// the current clock/divider configuration cannot produce.
+	pub fn is_playable_on(&self, output: &impl ToneOutput) -> bool {
+		match self.frequency_range() {
+			Some(range) => output.playable_range().contains_range(&range),
+			None => true,
+		}
+	}
+}
+
+/// A change to how a [`Song`] is played, applied when `beat` is reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SongEvent {
+	pub beat: u16,
+	pub kind: SongEventKind,
+}
+
+/// The kinds of [`SongEvent`].
+///
+/// Tempos are measured in ticks per beat, the same unit as the `ticks_per_beat` given to [`Player::new`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SongEventKind {
+	/// Immediately changes the tempo.
+	SetTempo { ticks_per_beat: u16 },
+	/// Gradually changes the tempo from `from` to `to` over `beats` beats.
+	/// Each beat's length is interpolated fractionally, so a ramp can be smoother than whole ticks allow.
+	TempoRamp { from: u16, to: u16, beats: u16 },
+	/// Stretches this beat to `percent` percent of its normal length, without changing the tempo.
+	Fermata { percent: u16 },
+	/// Holds this beat after its normal length until [`Player::release_hold`] is called.
+	/// Useful for "press to continue" moments.
+	Hold,
+	/// Jumps to one of several beats when this beat is reached, instead of playing it.
+	///
+	/// The target is chosen with [`Player::select_branch`] or [`Player::set_branch_selector`].
+	/// If the chosen index is out of bounds, playback continues normally from this beat.
+	Branch { targets: &'static [u16] },
+	/// Names this beat so [`Player::jump_to_marker`] can jump to it.
+	/// `transition` decides how the jump sounds.
+	Marker { id: u8, transition: Transition },
+	/// Plays one of several takes of the `beats` beats starting here, chosen again on each pass of a
+	/// looping song so it doesn't sound the same every time.
+	///
+	/// Take `0` is the song's own notes, and take `n` plays the beats of `takes[n - 1]` instead
+	/// (beats past the end of a take are silent). `order` decides which take each pass plays.
+	/// Takes are only references, so sections can share patterns without copying them.
+	Alternatives {
+		beats: u16,
+		takes: &'static [&'static [Option<&'static [NoteAndDuration]>]],
+		order: TakeOrder,
+	},
+}
+
+/// How a [`SongEventKind::Alternatives`] chooses a take on each pass.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TakeOrder {
+	/// Plays the takes in order, one per pass, starting over after the last one.
+	#[default]
+	Cycle,
+	/// Plays a random take each pass. See [`Player::set_variation_seed`].
+	Random,
+}
+
+/// How [`Player::jump_to_marker`] moves to a [`SongEventKind::Marker`].
+///
+/// Every transition waits for the next beat, so jumps stay in time with the song.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Transition {
+	/// Jumps straight to the marker on the next beat.
+	#[default]
+	Cut,
+	/// Keeps playing while fading out over `beats` beats, then jumps to the marker at full volume.
+	Fade { beats: u16 },
+	/// Plays `notes` for one beat instead of the song (a drum fill, for example), then jumps to the marker.
+	Fill { notes: &'static [NoteAndDuration] },
+}
+
+impl SongEventKind {
+	/// Creates a [`SongEventKind::TempoRamp`] from beats per minute, given how often [`Player::tick`] is called.
+	pub const fn tempo_ramp_bpm(
+		from_bpm: u16,
+		to_bpm: u16,
+		beats: u16,
+		tick_period_ms: u16,
+	) -> Self {
+		Self::TempoRamp {
+			from: bpm_to_ticks_per_beat(from_bpm, tick_period_ms),
+			to: bpm_to_ticks_per_beat(to_bpm, tick_period_ms),
+			beats,
+		}
+	}
+}
+
+/// Converts beats per minute to ticks per beat, given how often [`Player::tick`] is called.
+pub const fn bpm_to_ticks_per_beat(bpm: u16, tick_period_ms: u16) -> u16 {
+	if bpm == 0 || tick_period_ms == 0 {
+		return u16::MAX;
+	}
+	let ticks = saturate_u16(60_000 / (bpm as u32 * tick_period_ms as u32));
+	if ticks == 0 { 1 } else { ticks }
 }
 
 /// Represents a frequency and its duration.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct NoteAndDuration {
 	pub frequency: u16,
 	pub duration: u16,
+	/// The track (the instrument in `onlinesequencer.net`) the note belongs to.
+	pub track: u8,
+	/// The chance of this note playing each time its beat is reached, in percent, so looping
+	/// patterns can change a little on every pass. `100` (or more) always plays it.
+	/// The chance is rolled with the random numbers set up by [`Player::set_variation_seed`].
+	pub chance: u8,
+	/// How many evenly spaced hits the note is played as within its duration, like a drum machine's
+	/// ratchet. `1` (or `0`) plays it once. Each hit sounds for half of its share of the duration,
+	/// so every hit needs at least two ticks to be heard.
+	pub ratchet: u8,
+}
+
+impl NoteAndDuration {
+	/// Returns a note that always plays.
+	pub const fn new(frequency: u16, duration: u16, track: u8) -> Self {
+		Self {
+			frequency,
+			duration,
+			track,
+			chance: 100,
+			ratchet: 1,
+		}
+	}
+
+	/// Returns this note with a `percent` percent chance of playing. See [`NoteAndDuration::chance`].
+	pub const fn with_chance(self, percent: u8) -> Self {
+		Self {
+			chance: percent,
+			..self
+		}
+	}
+
+	/// Returns this note played as `hits` hits. See [`NoteAndDuration::ratchet`].
+	pub const fn with_ratchet(self, hits: u8) -> Self {
+		Self {
+			ratchet: hits,
+			..self
+		}
+	}
+}
+
+/// An inclusive range of frequencies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrequencyRange {
+	pub min: u16,
+	pub max: u16,
+}
+
+impl FrequencyRange {
+	/// Returns `true` if `frequency` is inside this range.
+	pub const fn contains(&self, frequency: u16) -> bool {
+		frequency >= self.min && frequency <= self.max
+	}
+
+	/// Returns the frequency in this range closest to `frequency`.
+	pub const fn clamp(&self, frequency: u16) -> u16 {
+		if frequency < self.min {
+			self.min
+		} else if frequency > self.max {
+			self.max
+		} else {
+			frequency
+		}
+	}
+
+	/// Returns `true` if all of `other` is inside this range.
+	pub const fn contains_range(&self, other: &FrequencyRange) -> bool {
+		other.min >= self.min && other.max <= self.max
+	}
+}
+
+/// Something that can produce the tones of a [`Song`].
+pub trait ToneOutput {
+	/// Returns the range of frequencies this output can produce.
+	fn playable_range(&self) -> FrequencyRange;
+
+	/// Starts playing `frequency` with the raw `duty` value.
+	fn set_tone(&mut self, frequency: u16, duty: u16) -> Result<(), ToneError>;
+
+	/// Returns the raw duty that is fully on. Defaults to `u16::MAX`.
+	///
+	/// Outputs with a lower duty resolution, like 8-bit PWMs, should return their own maximum so
+	/// [`set_tone_fraction`](ToneOutput::set_tone_fraction) can convert to it.
+	fn max_duty(&self) -> u16 {
+		u16::MAX
+	}
+
+	/// Starts playing `frequency` with `duty` as a fraction of fully on.
+	///
+	/// By default, this converts `duty` using [`max_duty`](ToneOutput::max_duty) and calls
+	/// [`set_tone`](ToneOutput::set_tone). Outputs whose duty range depends on the frequency should override it.
+	fn set_tone_fraction(&mut self, frequency: u16, duty: DutyFraction) -> Result<(), ToneError> {
+		self.set_tone(frequency, duty.to_duty(self.max_duty()))
+	}
+
+	/// Stops playing.
+	fn set_off(&mut self);
+
+	/// Tells the output the frequency of the clock driving it, in hertz.
+	/// Outputs that don't depend on it can ignore this.
+	fn set_clock_hz(&mut self, clock_hz: u32) {
+		let _ = clock_hz;
+	}
+}
+
+/// The most branches that can be followed when moving to a new beat.
+/// This stops branches that jump to each other from looping forever.
+const MAX_BRANCH_JUMPS: u8 = 8;
+
+/// How many fractional steps a tick is divided into when timing beats.
+/// This lets tempo ramps produce beats that aren't a whole number of ticks long.
+const TICK_FRACTION: u32 = 256;
+
+/// The system clock frequency assumed until told otherwise.
+const DEFAULT_SYS_CLOCK_HZ: u32 = 150_000_000;
+
+/// Returns `true` if `a` and `b` are at most roughly `cents` cents apart.
+///
+/// A cent is a 1200th of an octave, and `b` is within `cents` of `a` when `b / a` is below
+/// `2^(cents / 1200)`. For the small tolerances this is used for, that's close to
+/// `1 + cents * ln(2) / 1200`, which keeps this to integer math.
+const fn within_cents(a: u16, b: u16, cents: u16) -> bool {
+	let (low, high) = if a < b { (a, b) } else { (b, a) };
+	(high - low) as u64 * 1_000_000 <= low as u64 * cents as u64 * 578
+}
+
+/// Converts a `u32` to a `u16`, clamping to `u16::MAX`.
+const fn saturate_u16(value: u32) -> u16 {
+	if value > u16::MAX as u32 {
+		u16::MAX
+	} else {
+		value as u16
+	}
+}
+
+/// An error produced when an output cannot produce a tone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToneError {
+	/// The frequency is above the output's [`FrequencyRange`].
+	FrequencyTooHigh,
+	/// The frequency is below the output's [`FrequencyRange`].
+	FrequencyTooLow,
+	/// The output rejected the tone, for example because the duty is above its maximum.
+	Output,
+}
+
+impl ToneError {
+	/// Returns the stable numeric code of this error, for reporting it without formatting.
+	///
+	/// Codes never change between versions, and are unique across every error type in this crate:
+	/// each type has its own range (`0x10` for [`ToneError`], `0x20` for [`ProfileError`],
+	/// `0x30` for [`FskError`], `0x40` for [`SongParseError`], and `0x50` for [`raw::RawError`]),
+	/// so one byte identifies any error.
+	pub const fn as_code(self) -> u8 {
+		match self {
+			Self::FrequencyTooHigh => 0x10,
+			Self::FrequencyTooLow => 0x11,
+			Self::Output => 0x12,
+		}
+	}
+
+	/// Returns the error with the code `code`, or `None` if no error has that code. See [`Self::as_code`].
+	pub const fn from_code(code: u8) -> Option<Self> {
+		match code {
+			0x10 => Some(Self::FrequencyTooHigh),
+			0x11 => Some(Self::FrequencyTooLow),
+			0x12 => Some(Self::Output),
+			_ => None,
+		}
+	}
+}
+
+/// What a [`Player`] does when a song that isn't looping reaches its `end`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EndBehavior {
+	/// Stops every note at `end`.
+	#[default]
+	CutAtEnd,
+	/// Lets notes still playing at `end` ring for the rest of their written duration.
+	HoldLastNotes,
+	/// Lets notes still playing at `end` ring while fading out over this many ticks.
+	/// They stop early if their written duration runs out first.
+	FadeOut(u16),
 }
 
-/// The fractional clock divider used in PWM.
-/// Based on https://pico.implrust.com/buzzer/play-songs/code.html.
-const PWM_DIV_INT: u8 = 64;
+/// What a [`Player`] does with a note its outputs cannot produce.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnplayableNotePolicy {
+	/// The note is not played.
+	Skip,
+	/// The note is moved to the nearest playable frequency.
+	Clamp,
+	/// The note is moved up or down by octaves until it is playable.
+	/// Falls back to [`UnplayableNotePolicy::Clamp`] if no octave fits.
+	#[default]
+	OctaveFold,
+}
+
+impl UnplayableNotePolicy {
+	/// Returns the frequency that should be played instead of `frequency`,
+	/// or `None` if the note should be skipped.
+	pub const fn apply(self, frequency: u16, range: FrequencyRange) -> Option<u16> {
+		if range.contains(frequency) {
+			return Some(frequency);
+		}
+
+		match self {
+			Self::Skip => None,
+			Self::Clamp => Some(range.clamp(frequency)),
+			Self::OctaveFold => {
+				let mut folded = frequency as u32;
+				while folded != 0 && folded < range.min as u32 {
+					folded *= 2;
+				}
+				while folded > range.max as u32 {
+					folded /= 2;
+				}
+				Some(range.clamp(saturate_u16(folded)))
+			}
+		}
+	}
+}
+
+/// A tempo ramp in progress, started by [`SongEventKind::TempoRamp`].
+#[derive(Clone, Copy)]
+struct TempoRamp {
+	start_beat: u16,
+	from: u16,
+	to: u16,
+	beats: u16,
+}
+
+/// A note that hasn't started yet, returned by [`Player::lookahead_events`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UpcomingNote {
+	/// The beat the note starts on.
+	pub beat: u16,
+	/// How many beats from now the note starts. The next beat is `1`.
+	pub beats_ahead: u16,
+	/// Roughly how many ticks from now the note starts, assuming the tempo doesn't change.
+	pub ticks_ahead: u32,
+	pub note: NoteAndDuration,
+}
+
+/// How a [`Player`] ducks while the signal given to [`Player::duck_while`] is asserted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ducking {
+	/// Lowers the volume to `percent` percent, and keeps playing.
+	Attenuate { percent: u8 },
+	/// Silences the player and stops the song where it is, until the signal is released.
+	Pause,
+}
+
+/// Speeds a looping song up every time it loops, for practicing a piece until it's up to tempo.
+/// Set with [`Player::set_speed_trainer`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpeedTrainer {
+	/// The tempo of the first pass, in beats per minute.
+	pub min_bpm: u16,
+	/// The fastest tempo, in beats per minute. Once reached, the song keeps looping at this tempo.
+	pub max_bpm: u16,
+	/// How many beats per minute faster each pass is.
+	pub step_bpm: u16,
+	/// How often [`Player::tick`] is called, in milliseconds. See [`bpm_to_ticks_per_beat`].
+	pub tick_period_ms: u16,
+}
+
+/// The ducking set up by [`Player::duck_while`].
+#[derive(Clone, Copy)]
+struct Duck {
+	signal: fn() -> bool,
+	ducking: Ducking,
+	fade_ticks: u16,
+}
+
+/// A take being played in place of the song's notes, chosen by a [`SongEventKind::Alternatives`].
+#[derive(Clone, Copy)]
+struct ActiveTake {
+	start: u16,
+	beats: u16,
+	notes: &'static [Option<&'static [NoteAndDuration]>],
+}
+
+/// A fade out in progress after the end of a song, started by [`EndBehavior::FadeOut`].
+#[derive(Clone, Copy)]
+struct EndFade {
+	ticks_left: u16,
+	ticks: u16,
+}
+
+/// A set of tracks.
+#[derive(Clone, Copy, Default)]
+struct TrackSet([u64; 4]);
+
+impl TrackSet {
+	fn set(&mut self, track: u8, included: bool) {
+		let track = track as usize;
+		if included {
+			self.0[track / 64] |= 1 << (track % 64);
+		} else {
+			self.0[track / 64] &= !(1 << (track % 64));
+		}
+	}
 
-/// Generates the `top` value used in PWM.
-/// From https://pico.implrust.com/buzzer/play-songs/code.html.
-const fn get_top(freq: f64, div_int: u8) -> u16 {
-	assert!(div_int != 0, "Divider must not be 0");
+	fn contains(&self, track: u8) -> bool {
+		let track = track as usize;
+		self.0[track / 64] & (1 << (track % 64)) != 0
+	}
+
+	fn is_empty(&self) -> bool {
+		self.0 == [0; 4]
+	}
+}
+
+/// A fade in progress, started by [`Player::fade_in`] or [`Player::fade_out`].
+#[derive(Clone, Copy)]
+struct VolumeFade {
+	from_milli: u16,
+	to_milli: u16,
+	ticks_left: u16,
+	ticks: u16,
+}
+
+/// A crossfade in progress, started by [`Player::crossfade_to`]. The new song plays as the stinger.
+#[derive(Clone, Copy)]
+struct Crossfade<'a> {
+	song: &'a Song<'a>,
+	ticks_left: u16,
+	ticks: u16,
+}
+
+/// A jump to a marker in progress, started by [`Player::jump_to_marker`].
+#[derive(Clone, Copy)]
+struct PendingJump {
+	target: u16,
+	transition: Transition,
+	beats_played: u16,
+}
+
+/// What a [`Player`]'s PWM was last set to, so PWMs whose note hasn't changed aren't set again.
+#[derive(Clone, Copy, PartialEq)]
+enum ChannelOutput {
+	/// The PWM may have been changed by something else, so it needs setting either way.
+	Unknown,
+	Off,
+	Tone {
+		frequency: u16,
+		duty: u16,
+		fraction: bool,
+	},
+}
 
-	let result = 150_000_000. / (freq * div_int as f64);
+/// How a stinger started by [`Player::play_stinger`] gets its PWMs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StingerPolicy {
+	/// Only PWMs the song isn't using are used. Stinger notes without a free PWM are silent.
+	#[default]
+	SpareVoices,
+	/// PWMs are taken from the song as needed, starting from the last one.
+	StealVoices,
+}
+
+/// A short song played once on top of the current song.
+#[derive(Clone)]
+struct Stinger<'a, const MAX_SIMULTANEOUS_NOTES: usize> {
+	song: &'a Song<'a>,
+	policy: StingerPolicy,
+	waiting_for_beat: bool,
+	beat: i32,
+	beat_timer: u32,
+	playing_notes: arrayvec::ArrayVec<NoteAndDuration, MAX_SIMULTANEOUS_NOTES>,
+	/// How many beats each of `playing_notes` lasted when it started.
+	note_lengths: arrayvec::ArrayVec<u16, MAX_SIMULTANEOUS_NOTES>,
+}
 
-	assert!(result >= 1.0, "Frequency too high");
-	assert!(
-		result <= 65535.0,
-		"Frequency too low: TOP exceeds 65534 max"
-	);
+/// Shortens every note by one beat and removes the ones that finished, passing them to `on_expired`.
+fn expire_notes<const N: usize>(
+	notes: &mut arrayvec::ArrayVec<NoteAndDuration, N>,
+	mut on_expired: impl FnMut(usize, &NoteAndDuration),
+) {
+	let mut i = 0;
+	while i < notes.len() {
+		// Notes lasting no beats shouldn't be started, but expire straight away if they are.
+		notes[i].duration = notes[i].duration.saturating_sub(1);
+		if notes[i].duration == 0 {
+			on_expired(i, &notes.remove(i));
+		} else {
+			i += 1;
+		}
+	}
+}
 
-	result as u16 - 1
+/// Sends `event` to `events` if there is a queue, dropping it if the queue is full.
+/// Notes starting and stopping are also passed to `note_callback`, if there is one.
+fn send_event(
+	events: &Option<embassy_sync::channel::DynamicSender<'_, Event>>,
+	note_callback: Option<fn(Event)>,
+	event: Event,
+) {
+	if let Some(events) = events {
+		let _ = events.try_send(event);
+	}
+	if let Event::NoteOn { .. } | Event::NoteOff { .. } = event
+		&& let Some(callback) = note_callback
+	{
+		callback(event);
+	}
 }
 
 /// Plays a [`buzzer_music::Song`].
 ///
-/// ```rust
+/// ```rust,ignore
 /// let p = embassy_rp::init(Default::default());
 ///
 /// // Create Pwm instance.
@@ -64,95 +1042,1642 @@ const fn get_top(freq: f64, div_int: u8) -> u16 {
 ///
 /// // Update every 40ms.
 /// loop {
-/// 	player.tick();
-/// 	embassy_time::Timer::after_millis(40).await;
+///     player.tick();
+///     embassy_time::Timer::after_millis(40).await;
 /// }
 /// ```
 ///
-/// It can use one or more [`embassy_rp::pwm::Pwm`]s, but the count must be defined via `PWM_COUNT`.
+/// With the `time` feature, `player.play().await` runs this loop until the song ends, and
+/// `player.run(Duration::from_millis(40)).await` runs it forever on a `Ticker` that doesn't drift.
+///
+/// It can use one or more `embassy_rp::pwm::Pwm`s (with the `rp` feature), [`PwmOutput`]s wrapping any `embedded-hal` PWM,
+/// or any other [`ToneOutput`], but the count must be defined via `PWM_COUNT`.
 ///
 /// The `MAX_SIMULTANEOUS_NOTES` dictates the maximum number of notes that can play simultamously since
 /// the notes needs to be preemptively allocated on the stack via [`arrayvec::ArrayVec`].
-pub struct Player<'a, const PWM_COUNT: usize, const MAX_SIMULTANEOUS_NOTES: usize> {
-	song: &'a Song,
+/// Once it's reached, new notes steal the place of the oldest ones (see [`Player::set_track_sticky`]).
+pub struct Player<'a, O: ToneOutput, const PWM_COUNT: usize, const MAX_SIMULTANEOUS_NOTES: usize> {
+	song: &'a Song<'a>,
 	looping: bool,
 	ticks_per_beat: u16,
 	duty: u16,
-	pwms: [embassy_rp::pwm::Pwm<'a>; PWM_COUNT],
+	duty_is_fraction: bool,
+	volume_percent: u8,
+	pwms: [O; PWM_COUNT],
+	channel_gains: [u16; PWM_COUNT],
+	resonance_curve: ResonanceCurve,
+	channel_positions: [i8; PWM_COUNT],
+	pitch_scale_milli: u16,
+	transpose: i8,
+	pans: pan::PanTable,
+	strums: strum::StrumTable,
+	sticky_tracks: TrackSet,
+	muted_tracks: TrackSet,
+	solo_tracks: TrackSet,
+	sticky_min_beats: Option<u16>,
+	duty_limit: Option<DutyLimit>,
+	duty_limiters: [duty_limit::DutyLimiter; PWM_COUNT],
+	duty_limit_callback: Option<fn(usize, bool)>,
+	note_callback: Option<fn(Event)>,
+	/// What happened since [`Player::tick_events`] was last called.
+	tick_events: TickEvents,
+	channel_outputs: [ChannelOutput; PWM_COUNT],
+	/// Which PWMs notes can be played on. Disabled PWMs are left alone entirely.
+	enabled_outputs: [bool; PWM_COUNT],
+	category: SoundCategory,
+	policy: Option<&'a AudioPolicy>,
+	events: Option<embassy_sync::channel::DynamicSender<'a, Event>>,
+	beat_events: Option<embassy_sync::channel::DynamicSender<'a, Event>>,
+	muted: bool,
+	live_source: Option<&'a dyn LiveSource>,
+	unplayable_note_policy: UnplayableNotePolicy,
+	end_behavior: EndBehavior,
+	loop_overlap: u16,
+	frequency_snap_cents: u16,
+	max_slew_semitones: u8,
+	/// The frequency each PWM was playing as the tick started, or `0` if it was off,
+	/// so the slew limit moves each PWM at most once per tick.
+	slew_frequencies: [u16; PWM_COUNT],
+	slewing: bool,
+	loop_pass: u16,
+	speed_trainer: Option<SpeedTrainer>,
+	trainer_bpm: u16,
+	variation_random: XorShift32,
+	active_take: Option<ActiveTake>,
 
 	paused: bool,
-	timer: u16,
-	beat_timer: u16,
+	pause_on_beat: bool,
+	resume_on_beat: bool,
+	/// How far into a beat the song would be if it had kept playing while paused.
+	paused_timer: u32,
+	skipping_output: bool,
+	output_latency_ticks: i8,
+	delay_ticks: u16,
+	reference_tick_interval_ms: u16,
+	tick_interval_ms: u16,
+	beat_timer: u32,
+	beat_length: u32,
+	tempo_ramp: Option<TempoRamp>,
+	/// The multiplier and divider of the tempo, set by [`Player::set_tempo_ratio`].
+	tempo_ratio: (u8, u8),
+	pending_tempo_ratio: Option<(u8, u8)>,
+	beats_per_bar: u16,
+	/// How many beats have started since the tempo ratio last changed, to find bar lines.
+	ratio_beats: u32,
+	beat_stretch_percent: u16,
+	holding: bool,
+	branch_choice: u8,
+	branch_selector: Option<fn(u16) -> u8>,
+	pending_jump: Option<PendingJump>,
+	sections: &'a [u16],
+	fade_percent: u8,
+	end_fade: Option<EndFade>,
+	volume_fade: Option<VolumeFade>,
+	volume_fade_milli: u16,
+	duck: Option<Duck>,
+	duck_gain_milli: u16,
+	duck_paused: bool,
 	beat: i32,
-	current_combined_note_index: usize,
+	mixer: Mixer<MAX_SIMULTANEOUS_NOTES>,
 	playing_notes: arrayvec::ArrayVec<NoteAndDuration, MAX_SIMULTANEOUS_NOTES>,
+	/// The full duration of each of `playing_notes`, for spacing the hits of ratchets.
+	note_lengths: arrayvec::ArrayVec<u16, MAX_SIMULTANEOUS_NOTES>,
+	first_new_note: usize,
+	beat_ticks: u16,
+	retrigger_gap_ticks: u8,
+	/// The frequency each of the first `PWM_COUNT` playing notes had as the beat started, or `0`,
+	/// to find notes started again on the same PWM.
+	beat_start_frequencies: [u16; PWM_COUNT],
+	stats: PlaybackStats,
+	stinger: Option<Stinger<'a, MAX_SIMULTANEOUS_NOTES>>,
+	crossfade: Option<Crossfade<'a>>,
 }
 
-impl<'a, const PWM_COUNT: usize, const MAX_SIMULTANEOUS_NOTES: usize>
-	Player<'a, PWM_COUNT, MAX_SIMULTANEOUS_NOTES>
+impl<'a, O: ToneOutput, const PWM_COUNT: usize, const MAX_SIMULTANEOUS_NOTES: usize>
+	Player<'a, O, PWM_COUNT, MAX_SIMULTANEOUS_NOTES>
 {
 	/// The constructor.
 	///
 	/// `song` is a reference to the `buzzer_music::Song` to play.
 	/// `looping`, if true, will have the song start at the beginning once it ends.
+	/// Notes still sounding at the end carry into the next pass for the rest of their duration.
 	/// `ticks_per_beat` determines how many ticks must run before the next note is played.
+	/// The song's [`SongEvent`]s can change this during playback. A tempo of `0` is treated as `1`.
 	/// `duty` is the raw duty value assigned to the PWMs.
 	/// `pwms` is an array of PWMs of length `PWM_COUNT`.
+	///
+	/// Fails to compile if `PWM_COUNT` is `0`, or if `MAX_SIMULTANEOUS_NOTES` is smaller than
+	/// `PWM_COUNT` (some PWMs could never be used).
 	pub fn new(
-		song: &'a Song,
+		song: &'a Song<'a>,
 		looping: bool,
 		ticks_per_beat: u16,
 		duty: u16,
-		pwms: [embassy_rp::pwm::Pwm<'a>; PWM_COUNT],
+		pwms: [O; PWM_COUNT],
 	) -> Self {
+		const {
+			assert!(PWM_COUNT > 0, "A `Player` needs at least one PWM");
+			assert!(
+				MAX_SIMULTANEOUS_NOTES >= PWM_COUNT,
+				"`MAX_SIMULTANEOUS_NOTES` must be at least `PWM_COUNT`"
+			);
+		}
+
+		let ticks_per_beat = ticks_per_beat.max(1);
 		Self {
 			song,
 			looping,
 			ticks_per_beat,
 			duty,
+			duty_is_fraction: false,
+			volume_percent: 100,
 			pwms,
+			channel_gains: [100; PWM_COUNT],
+			resonance_curve: ResonanceCurve::new(),
+			channel_positions: [0; PWM_COUNT],
+			pitch_scale_milli: 1000,
+			transpose: 0,
+			pans: pan::PanTable::new(),
+			strums: strum::StrumTable::new(),
+			sticky_tracks: TrackSet::default(),
+			muted_tracks: TrackSet::default(),
+			solo_tracks: TrackSet::default(),
+			sticky_min_beats: None,
+			duty_limit: None,
+			duty_limiters: [duty_limit::DutyLimiter::new(); PWM_COUNT],
+			duty_limit_callback: None,
+			note_callback: None,
+			tick_events: TickEvents::NONE,
+			channel_outputs: [ChannelOutput::Unknown; PWM_COUNT],
+			enabled_outputs: [true; PWM_COUNT],
+			category: SoundCategory::default(),
+			policy: None,
+			events: None,
+			beat_events: None,
+			muted: false,
+			live_source: None,
+			unplayable_note_policy: UnplayableNotePolicy::default(),
+			end_behavior: EndBehavior::default(),
+			loop_overlap: 0,
+			frequency_snap_cents: 0,
+			max_slew_semitones: 0,
+			slew_frequencies: [0; PWM_COUNT],
+			slewing: false,
+			loop_pass: 0,
+			speed_trainer: None,
+			trainer_bpm: 0,
+			variation_random: XorShift32::new(0),
+			active_take: None,
 
 			paused: false,
-			timer: 0,
+			pause_on_beat: false,
+			resume_on_beat: false,
+			paused_timer: 0,
+			skipping_output: false,
+			output_latency_ticks: 0,
+			delay_ticks: 0,
+			reference_tick_interval_ms: 0,
+			tick_interval_ms: 0,
 			beat_timer: 0,
+			beat_length: ticks_per_beat as u32 * TICK_FRACTION,
+			tempo_ramp: None,
+			tempo_ratio: (1, 1),
+			pending_tempo_ratio: None,
+			beats_per_bar: 4,
+			ratio_beats: 0,
+			beat_stretch_percent: 100,
+			holding: false,
+			branch_choice: 0,
+			branch_selector: None,
+			pending_jump: None,
+			sections: &[],
+			fade_percent: 100,
+			end_fade: None,
+			volume_fade: None,
+			volume_fade_milli: 1000,
+			duck: None,
+			duck_gain_milli: 1000,
+			duck_paused: false,
 			beat: -1,
-			current_combined_note_index: 0,
+			mixer: Mixer::new(),
 			playing_notes: arrayvec::ArrayVec::new(),
+			note_lengths: arrayvec::ArrayVec::new(),
+			first_new_note: 0,
+			beat_ticks: 0,
+			retrigger_gap_ticks: 0,
+			beat_start_frequencies: [0; PWM_COUNT],
+			stats: PlaybackStats::default(),
+			stinger: None,
+			crossfade: None,
 		}
 	}
 
-	/// Pauses the song. It can be resumed using [`resume`].
+	/// Sets the raw duty given to the PWMs, like the `duty` given to [`Player::new`].
+	pub fn set_duty(&mut self, duty: u16) {
+		self.duty = duty;
+		self.duty_is_fraction = false;
+		if !self.paused {
+			self.update_channels();
+		}
+	}
+
+	/// Sets the duty as a fraction of fully on, which each PWM converts to its own duty range.
+	///
+	/// Use this instead of a raw duty when the PWMs have different resolutions (an 8-bit PWM next to a
+	/// 16-bit one, for example) so gains, fades, and ducking sound the same on all of them.
+	/// See [`ToneOutput::set_tone_fraction`].
+	pub fn set_duty_fraction(&mut self, duty: DutyFraction) {
+		self.duty = duty.0;
+		self.duty_is_fraction = true;
+		if !self.paused {
+			self.update_channels();
+		}
+	}
+
+	/// Sets the master volume as a percentage of the duty, from `0` (silent) to `100` (the full duty).
+	///
+	/// Unlike [`set_duty`], this scales whatever duty the player was given, so it can be tied to a
+	/// user setting or lowered at night without knowing the duty range of the PWMs.
+	/// Sounding notes are updated immediately. Values above `100` are treated as `100`.
+	pub fn set_volume(&mut self, percent: u8) {
+		let percent = percent.min(100);
+		if percent == self.volume_percent {
+			return;
+		}
+		self.volume_percent = percent;
+		if !self.paused {
+			self.update_channels();
+		}
+	}
+
+	/// Returns the master volume set by [`set_volume`].
+	pub fn volume(&self) -> u8 {
+		self.volume_percent
+	}
+
+	/// Returns the duty set by [`Player::new`], [`set_duty`], or [`set_duty_fraction`].
+	/// It is a [`DutyFraction`] if [`set_duty_fraction`] was called last.
+	pub fn duty(&self) -> u16 {
+		self.duty
+	}
+
+	/// Sets what happens to notes the PWMs cannot produce.
+	/// Defaults to [`UnplayableNotePolicy::OctaveFold`].
+	pub fn set_unplayable_note_policy(&mut self, policy: UnplayableNotePolicy) {
+		self.unplayable_note_policy = policy;
+	}
+
+	/// Sets what happens when the song reaches its `end` without looping.
+	/// Defaults to [`EndBehavior::CutAtEnd`].
+	pub fn set_end_behavior(&mut self, behavior: EndBehavior) {
+		self.end_behavior = behavior;
+	}
+
+	/// Returns the behavior set by [`set_end_behavior`].
+	pub fn end_behavior(&self) -> EndBehavior {
+		self.end_behavior
+	}
+
+	/// Overlaps the end of a looping song with the start of its next pass by `beats` beats.
+	///
+	/// Notes still sounding when a song loops always carry into the next pass, but a tail written
+	/// after the last note (an echo, or a chord ringing out) still delays the next pass. With an overlap,
+	/// the song's first `beats` beats start on top of its last `beats` beats, and the next pass continues
+	/// from there, so the seam can't be heard. Events on the overlapped beats, other than tempo changes,
+	/// only happen on the first pass.
+	///
+	/// The overlap is capped to half of the song. Defaults to `0`, which doesn't overlap.
+	pub fn set_loop_overlap(&mut self, beats: u16) {
+		self.loop_overlap = beats;
+	}
+
+	/// Keeps a PWM at the frequency it's playing when its next note is within `cents` cents of it.
+	///
+	/// Dense songs with tiny pitch differences between consecutive notes otherwise reconfigure the
+	/// PWMs constantly, which can click and costs CPU time. Snapping trades a little pitch accuracy
+	/// for fewer retunes. Since notes snap to what is playing rather than what was written, a slow slide
+	/// in small steps stays put until it has moved further than `cents` from where it started.
+	///
+	/// Defaults to `0`, which never snaps. A semitone is 100 cents.
+	pub fn set_frequency_snap_cents(&mut self, cents: u16) {
+		self.frequency_snap_cents = cents;
+	}
+
+	/// Limits how far a PWM's pitch can move in one tick to `semitones` semitones.
+	///
+	/// Cheap buzzers can click harshly on big jumps in pitch. With a limit, a PWM jumping further than
+	/// that slides there over the next few ticks instead, whatever the song says. Notes starting on a
+	/// silent PWM start at their own pitch, and the PWM cycling through notes that share it isn't limited,
+	/// so chords still sound like chords.
+	///
+	/// Defaults to `0`, which doesn't limit anything.
+	pub fn set_max_slew_semitones(&mut self, semitones: u8) {
+		self.max_slew_semitones = semitones;
+		self.record_slew_frequencies();
+		if self.slewing {
+			// Without a limit, PWMs still sliding go straight to their notes.
+			self.slewing = false;
+			self.update_channels();
+		}
+	}
+
+	/// Returns how many times the song has looped back to its start since it started playing.
+	pub fn loop_pass(&self) -> u16 {
+		self.loop_pass
+	}
+
+	/// Seeds the random choices of [`TakeOrder::Random`] and [`NoteAndDuration::chance`], so the same
+	/// seed plays the same takes and notes.
+	pub fn set_variation_seed(&mut self, seed: u32) {
+		self.variation_random = XorShift32::new(seed);
+	}
+
+	/// Returns how many beats a looping song overlaps by, after capping [`set_loop_overlap`] to half of the song.
+	fn effective_loop_overlap(&self) -> u16 {
+		if self.looping {
+			self.loop_overlap.min(self.song.end / 2)
+		} else {
+			0
+		}
+	}
+
+	/// Sets the gain of the PWM at index `channel` as a percentage of `duty`.
+	///
+	/// Buzzers of different sizes can be noticeably louder or quieter than each other at the same duty.
+	/// Calibrating each PWM's gain once per board keeps chords spread across them balanced.
+	/// Gains above `100` are allowed, but the duty is never raised above `u16::MAX`.
+	pub fn set_channel_gain(&mut self, channel: usize, gain_percent: u16) {
+		if let Some(gain) = self.channel_gains.get_mut(channel) {
+			*gain = gain_percent;
+		}
+	}
+
+	/// Sets the gain of every PWM at once. See [`set_channel_gain`].
+	pub fn set_channel_gains(&mut self, gains_percent: [u16; PWM_COUNT]) {
+		self.channel_gains = gains_percent;
+	}
+
+	/// Returns the gain of the PWM at index `channel` as a percentage, or `None` if there's no such PWM.
+	pub fn channel_gain(&self, channel: usize) -> Option<u16> {
+		self.channel_gains.get(channel).copied()
+	}
+
+	/// Sets a curve that adjusts the duty depending on the note's frequency.
+	///
+	/// Piezo buzzers are much louder near their resonant frequency. A curve that lowers
+	/// the gain around it keeps melodies from jumping out as they pass through.
+	pub fn set_resonance_curve(&mut self, curve: ResonanceCurve) {
+		self.resonance_curve = curve;
+	}
+
+	/// Sets where the PWM at index `channel` is, from `-100` (left) to `100` (right).
+	///
+	/// When buzzers are physically apart, panning tracks toward one side (see [`set_track_pan`])
+	/// quiets the buzzers on the other side. Defaults to `0` (the center), which is never quieted.
+	pub fn set_channel_position(&mut self, channel: usize, position: i8) {
+		if let Some(channel_position) = self.channel_positions.get_mut(channel) {
+			*channel_position = position.clamp(-100, 100);
+		}
+	}
+
+	/// Pans `track` to `pan`, from `-100` (left) to `100` (right). See [`pan_gain_percent`].
+	/// Returns `false` if [`MAX_PANNED_TRACKS`] other tracks are already panned.
+	pub fn set_track_pan(&mut self, track: u8, pan: i8) -> bool {
+		self.pans.sweep(track, pan, 0)
+	}
+
+	/// Gradually pans `track` from its current pan to `pan` over `beats` beats.
+	/// Returns `false` if [`MAX_PANNED_TRACKS`] other tracks are already panned.
+	pub fn sweep_track_pan(&mut self, track: u8, pan: i8, beats: u16) -> bool {
+		self.pans.sweep(track, pan, beats)
+	}
+
+	/// Returns the pan of `track`. Tracks that were never panned are in the center.
+	pub fn track_pan(&self, track: u8) -> i8 {
+		self.pans.pan(track)
+	}
+
+	/// Makes the notes of `track` sticky, or not.
+	///
+	/// When more than `MAX_SIMULTANEOUS_NOTES` notes play at once, new notes steal the place of the
+	/// oldest ones. Sticky notes are only stolen when every playing note is sticky, which keeps
+	/// sustained bass and pedal notes alive through busy passages.
+	pub fn set_track_sticky(&mut self, track: u8, sticky: bool) {
+		self.sticky_tracks.set(track, sticky);
+	}
+
+	/// Mutes the notes of `track`, or unmutes them, for example to drop the bass while an alert plays.
+	///
+	/// Muted notes aren't played at all, so they leave their PWMs to the other tracks. Notes of the
+	/// track that are sounding stop immediately, and unmuted notes start with the next one on the track.
+	pub fn set_track_muted(&mut self, track: u8, muted: bool) {
+		self.muted_tracks.set(track, muted);
+		self.stop_inaudible_notes();
+	}
+
+	/// Solos `track`, or stops soloing it.
+	///
+	/// While any track is soloed, only soloed tracks that aren't muted are played.
+	/// Like [`set_track_muted`], this takes effect immediately.
+	pub fn set_track_solo(&mut self, track: u8, solo: bool) {
+		self.solo_tracks.set(track, solo);
+		self.stop_inaudible_notes();
+	}
+
+	/// Returns `true` if the notes of `track` are played, following [`set_track_muted`] and [`set_track_solo`].
+	pub fn is_track_audible(&self, track: u8) -> bool {
+		!self.muted_tracks.contains(track)
+			&& (self.solo_tracks.is_empty() || self.solo_tracks.contains(track))
+	}
+
+	/// Stops the playing notes of tracks that were just muted or left out of a solo.
+	fn stop_inaudible_notes(&mut self) {
+		let mut index = 0;
+		while index < self.playing_notes.len() {
+			if self.is_track_audible(self.playing_notes[index].track) {
+				index += 1;
+				continue;
+			}
+			let note = self.playing_notes.remove(index);
+			self.note_lengths.remove(index);
+			if index < self.first_new_note {
+				self.first_new_note -= 1;
+			}
+			self.send_event(Event::note_off(&note));
+		}
+		if !self.paused {
+			self.update_channels();
+		}
+	}
+
+	/// Makes every note with at least `beats` beats left to play sticky, or `None` to only
+	/// use [`set_track_sticky`]. Defaults to `None`.
+	pub fn set_sticky_min_beats(&mut self, beats: Option<u16>) {
+		self.sticky_min_beats = beats;
+	}
+
+	/// Sets how many ticks a PWM goes quiet when a note starts on it with the same frequency it was
+	/// already playing, so repeated notes are heard one by one instead of merging into a long one.
+	/// A gap of `0` turns this off. Beats shorter than twice the gap are left alone.
+	///
+	/// Defaults to `0`, so repeated notes play as written unless a gap is set.
+	pub fn set_retrigger_gap(&mut self, ticks: u8) {
+		self.retrigger_gap_ticks = ticks;
+	}
+
+	/// Strums chords on `track`: instead of starting together, the notes of a chord start
+	/// `ticks_per_note` ticks apart, in `direction`. A `ticks_per_note` of `0` stops strumming.
+	///
+	/// A chord is every note of the track starting on the same beat. Notes that are still waiting
+	/// to start keep their PWMs quiet. Returns `false` if [`MAX_STRUMMED_TRACKS`] other tracks
+	/// are already strummed.
+	pub fn set_track_strum(
+		&mut self,
+		track: u8,
+		direction: StrumDirection,
+		ticks_per_note: u8,
+	) -> bool {
+		self.strums.set(track, direction, ticks_per_note)
+	}
+
+	/// Multiplies the pitch of everything this player plays by `scale / 1000`.
+	///
+	/// This is meant to be changed continuously, as often as every tick, for effects like Doppler
+	/// shifts or tying the pitch of a robot's sounds to its speed. The PWMs are updated immediately.
+	/// Scaled notes the PWMs can't produce follow the [`UnplayableNotePolicy`].
+	/// Defaults to `1000` (unchanged).
+	pub fn set_pitch_scale_milli(&mut self, scale: u16) {
+		if scale == self.pitch_scale_milli {
+			return;
+		}
+		self.pitch_scale_milli = scale;
+		if !self.paused {
+			self.update_channels();
+		}
+	}
+
+	/// Moves the pitch of everything this player plays by `semitones` semitones, up if positive and
+	/// down if negative, for example to lift a song an octave into the range a buzzer sounds good in.
+	///
+	/// This is applied before [`set_pitch_scale_milli`], and the PWMs are updated immediately.
+	/// Transposed notes the PWMs can't produce follow the [`UnplayableNotePolicy`].
+	/// Defaults to `0` (unchanged).
+	pub fn set_transpose(&mut self, semitones: i8) {
+		if semitones == self.transpose {
+			return;
+		}
+		self.transpose = semitones;
+		if !self.paused {
+			self.update_channels();
+		}
+	}
+
+	/// Ducks the player while `signal` returns `true`, for example while a voice prompt chip is talking.
+	///
+	/// `signal` is checked every [`tick`]. The volume fades between full and ducked over `fade_ticks` ticks
+	/// (immediately if `0`). With [`Ducking::Pause`], the song stops immediately and fades back in once
+	/// the signal is released. Replaces any earlier ducking.
+	pub fn duck_while(&mut self, signal: fn() -> bool, ducking: Ducking, fade_ticks: u16) {
+		self.duck = Some(Duck {
+			signal,
+			ducking,
+			fade_ticks,
+		});
+	}
+
+	/// Stops ducking set up by [`duck_while`], returning to full volume.
+	pub fn stop_ducking(&mut self) {
+		self.duck = None;
+		self.duck_paused = false;
+		self.set_duck_gain(1000);
+	}
+
+	/// Returns `true` if the player is currently ducked, or fading back from being ducked.
+	pub fn is_ducked(&self) -> bool {
+		self.duck_paused || self.duck_gain_milli < 1000
+	}
+
+	/// Follows the ducking signal for a tick.
+	/// Returns `true` if the song is paused by [`Ducking::Pause`].
+	fn update_ducking(&mut self) -> bool {
+		let Some(duck) = self.duck else {
+			return false;
+		};
+
+		let active = (duck.signal)();
+		let target = match duck.ducking {
+			Ducking::Pause if active => {
+				if !self.duck_paused {
+					self.duck_paused = true;
+					self.duck_gain_milli = 0;
+					for i in 0..PWM_COUNT {
+						self.set_channel_off(i);
+					}
+				}
+				return true;
+			}
+			Ducking::Attenuate { percent } if active => percent.min(100) as u16 * 10,
+			_ => 1000,
+		};
+		if self.duck_paused {
+			self.duck_paused = false;
+			self.update_channels();
+		}
+
+		let step = match duck.fade_ticks {
+			0 => 1000,
+			ticks => (1000 / ticks).max(1),
+		};
+		let gain = if self.duck_gain_milli < target {
+			(self.duck_gain_milli + step).min(target)
+		} else {
+			self.duck_gain_milli.saturating_sub(step).max(target)
+		};
+		self.set_duck_gain(gain);
+		false
+	}
+
+	/// Changes the ducking volume, updating the PWMs if it changed.
+	fn set_duck_gain(&mut self, gain_milli: u16) {
+		if gain_milli != self.duck_gain_milli {
+			self.duck_gain_milli = gain_milli;
+			if !self.paused {
+				self.update_channels();
+			}
+		}
+	}
+
+	/// Returns the pitch scale set by [`set_pitch_scale_milli`].
+	pub fn pitch_scale_milli(&self) -> u16 {
+		self.pitch_scale_milli
+	}
+
+	/// Returns the transposition set by [`set_transpose`].
+	pub fn transpose(&self) -> i8 {
+		self.transpose
+	}
+
+	/// Applies the channel gains, resonance curve, and clock frequency of a [`BoardProfile`].
+	pub fn apply_profile(&mut self, profile: &BoardProfile<PWM_COUNT>) {
+		self.channel_gains = profile.channel_gains;
+		self.resonance_curve = profile.resonance_curve.clone();
+		self.set_clock_hz(profile.clock_hz);
+	}
+
+	/// Tells the PWMs the frequency of the system clock driving them, in hertz, so notes stay in tune
+	/// when the clock isn't the default 150 MHz (an RP2040 at 125 MHz, or an overclocked one).
+	///
+	/// Notes already playing are retuned. Outputs that don't depend on the clock ignore this.
+	///
+	/// ```rust,ignore
+	/// let mut player = buzzer_music::Player::new(&SONG, true, 3, 100, [buzzer]);
+	/// player.set_clock_hz(embassy_rp::clocks::clk_sys_freq());
+	/// ```
+	pub fn set_clock_hz(&mut self, clock_hz: u32) {
+		for pwm in &mut self.pwms {
+			pwm.set_clock_hz(clock_hz);
+		}
+		// A new clock changes what the PWMs play, so they all need setting again.
+		self.channel_outputs = [ChannelOutput::Unknown; PWM_COUNT];
+		if !self.paused {
+			self.update_channels();
+		}
+	}
+
+	/// Sets the [`SoundCategory`] of this player's song. Defaults to [`SoundCategory::Music`].
+	pub fn set_category(&mut self, category: SoundCategory) {
+		self.category = category;
+	}
+
+	/// Returns the [`SoundCategory`] of this player's song.
+	pub fn category(&self) -> SoundCategory {
+		self.category
+	}
+
+	/// Sets the [`AudioPolicy`] this player follows, or `None` to ignore policies.
+	/// Changes to the policy take effect the next time a PWM is updated.
+	pub fn set_policy(&mut self, policy: Option<&'a AudioPolicy>) {
+		self.policy = policy;
+	}
+
+	/// Sets the [`EventQueue`] this player sends its [`Event`]s to, or `None` to stop sending them.
+	pub fn set_event_queue<M: embassy_sync::blocking_mutex::raw::RawMutex, const N: usize>(
+		&mut self,
+		queue: Option<&'a EventQueue<M, N>>,
+	) {
+		self.events = queue.map(EventQueue::sender);
+	}
+
+	/// Sets an [`EventQueue`] that only receives [`Event::BeatStarted`], or `None` to stop sending them.
+	///
+	/// This is a beat clock that follows the song's tempo, for driving haptic or visual metronomes.
+	/// Combine it with [`set_muted`] to keep the buzzer silent. See [`EventQueue::next_beat`].
+	pub fn set_beat_queue<M: embassy_sync::blocking_mutex::raw::RawMutex, const N: usize>(
+		&mut self,
+		queue: Option<&'a EventQueue<M, N>>,
+	) {
+		self.beat_events = queue.map(EventQueue::sender);
+	}
+
+	/// Keeps the PWMs off while the song, its events, and its beat clock keep running.
+	pub fn set_muted(&mut self, muted: bool) {
+		self.muted = muted;
+		if !self.paused {
+			self.update_channels();
+		}
+	}
+
+	/// Returns `true` if the player is muted by [`set_muted`].
+	pub fn is_muted(&self) -> bool {
+		self.muted
+	}
+
+	/// Sets a [`LiveSource`] (such as a [`LivePattern`]) whose notes are played on top of the song,
+	/// or `None` to remove it.
+	///
+	/// Live notes are only added while there is room for them, and follow the song's tempo.
+	pub fn set_live_source(&mut self, source: Option<&'a dyn LiveSource>) {
+		self.live_source = source;
+	}
+
+	/// Sends `event` to the [`EventQueue`], if there is one, and beats to the beat queue.
+	/// It is also recorded for [`tick_events`].
+	fn send_event(&mut self, event: Event) {
+		self.tick_events = self.tick_events.union(match event {
+			Event::BeatStarted { .. } => TickEvents::BEAT_ADVANCED,
+			Event::NoteOn { .. } => TickEvents::NOTES_STARTED,
+			Event::NoteOff { .. } => TickEvents::NONE,
+			Event::SongEnded => TickEvents::FINISHED,
+			Event::TempoChanged { .. } => TickEvents::NONE,
+		});
+		send_event(&self.events, self.note_callback, event);
+		if let Event::BeatStarted { .. } = event {
+			send_event(&self.beat_events, None, event);
+		}
+	}
+
+	/// Sets a budget limiting how long each PWM can be on, or `None` to remove it.
+	/// See [`DutyLimit`].
+	pub fn set_duty_limit(&mut self, limit: Option<DutyLimit>) {
+		self.duty_limit = limit;
+		if limit.is_none() {
+			self.duty_limiters = [duty_limit::DutyLimiter::new(); PWM_COUNT];
+		}
+	}
+
+	/// Sets a function called when a PWM's [`DutyLimit`] starts or stops silencing it.
+	/// It is given the index of the PWM and `true` if limiting engaged, `false` if it was released.
+	pub fn set_duty_limit_callback(&mut self, callback: Option<fn(usize, bool)>) {
+		self.duty_limit_callback = callback;
+	}
+
+	/// Sets a function called with an [`Event::NoteOn`] whenever a note starts and an
+	/// [`Event::NoteOff`] whenever one stops, or `None` to remove it.
+	///
+	/// It is called during [`tick`], as the notes change, so it can drive music-reactive lights
+	/// without an [`EventQueue`] or another task. Keep it short, since it delays the PWMs.
+	///
+	/// ```rust,ignore
+	/// player.set_note_callback(Some(|event| match event {
+	///     buzzer_music::Event::NoteOn { track, .. } => LEDS[track as usize].store(true, Ordering::Relaxed),
+	///     buzzer_music::Event::NoteOff { track, .. } => LEDS[track as usize].store(false, Ordering::Relaxed),
+	///     _ => {}
+	/// }));
+	/// ```
+	pub fn set_note_callback(&mut self, callback: Option<fn(Event)>) {
+		self.note_callback = callback;
+	}
+
+	/// Returns `true` if the PWM at index `channel` is being silenced by the [`DutyLimit`].
+	/// A channel out of range is never limited.
+	pub fn is_duty_limited(&self, channel: usize) -> bool {
+		self.duty_limiters
+			.get(channel)
+			.is_some_and(duty_limit::DutyLimiter::is_limited)
+	}
+
+	/// Sets how many ticks each note of an arpeggio is held for.
+	///
+	/// When more notes are playing than there are PWMs, the extra notes are cycled through the
+	/// last PWM. By default this happens every tick, which ties the arpeggio speed to how often
+	/// [`tick`] is called. Raising the divider lets [`tick`] run quickly (with a proportionally
+	/// larger `ticks_per_beat`) without the arpeggio becoming too fast. A divider of `0` is treated as `1`.
+	///
+	/// This is the same as calling [`Mixer::set_rate`] on [`mixer_mut`].
+	pub fn set_arpeggio_divider(&mut self, ticks: u16) {
+		self.mixer.set_rate(ticks);
+	}
+
+	/// Returns the [`Mixer`] that alternates notes sharing the last PWM.
+	pub fn mixer(&self) -> &Mixer<MAX_SIMULTANEOUS_NOTES> {
+		&self.mixer
+	}
+
+	/// Returns the [`Mixer`] that alternates notes sharing the last PWM,
+	/// so its rate and voice weights can be configured.
+	pub fn mixer_mut(&mut self) -> &mut Mixer<MAX_SIMULTANEOUS_NOTES> {
+		&mut self.mixer
+	}
+
+	/// Shifts when notes are played by `ticks`, to line the audio up with external visuals or video.
+	///
+	/// A positive value plays every note that many ticks earlier, making up for outputs that are
+	/// heard late. A negative value plays every note later. Changes take effect immediately and
+	/// are kept when the song is restarted or seeks.
+	pub fn set_output_latency_ticks(&mut self, ticks: i8) {
+		let change = ticks as i32 - self.output_latency_ticks as i32;
+		self.output_latency_ticks = ticks;
+		self.shift_schedule(change);
+	}
+
+	/// Returns the latency set by [`set_output_latency_ticks`].
+	pub fn output_latency_ticks(&self) -> i8 {
+		self.output_latency_ticks
+	}
+
+	/// Moves the song's schedule `ticks` earlier, or later if negative.
+	fn shift_schedule(&mut self, ticks: i32) {
+		if ticks < 0 {
+			self.delay_ticks = self.delay_ticks.saturating_add(ticks.unsigned_abs() as u16);
+			return;
+		}
+
+		// Cancel out any delay first, then move the current beat's timer forward.
+		let ticks = ticks as u32;
+		let delayed = (self.delay_ticks as u32).min(ticks);
+		self.delay_ticks -= delayed as u16;
+		self.beat_timer += (ticks - delayed) * TICK_FRACTION;
+	}
+
+	/// Tells the player how often [`tick`] is now called, in milliseconds, so the tempo stays the same
+	/// when the loop period changes (for example when switching power modes).
+	///
+	/// The first call only records the period that `ticks_per_beat`, the song's tempo events, and the
+	/// arpeggio divider were chosen for. Later calls rescale the current beat, the tempo, and the
+	/// arpeggio divider by how much the period changed. A period of `0` is ignored.
+	pub fn set_tick_interval_hint(&mut self, ms: u16) {
+		if ms == 0 {
+			return;
+		}
+		if self.reference_tick_interval_ms == 0 {
+			self.reference_tick_interval_ms = ms;
+			self.tick_interval_ms = ms;
+			return;
+		}
+
+		let old = self.tick_interval_ms as u64;
+		let rescale = |ticks: u32| (ticks as u64 * old / ms as u64).min(u32::MAX as u64) as u32;
+		self.tick_interval_ms = ms;
+		self.beat_length = rescale(self.beat_length).max(1);
+		self.beat_timer = rescale(self.beat_timer);
+		if let Some(stinger) = &mut self.stinger {
+			stinger.beat_timer = rescale(stinger.beat_timer);
+		}
+
+		let rate = rescale(self.mixer.rate() as u32 * TICK_FRACTION) / TICK_FRACTION;
+		self.mixer.set_rate(saturate_u16(rate));
+	}
+
+	/// Changes the tempo while the song plays, like a [`SongEventKind::SetTempo`] event does.
+	///
+	/// The current beat keeps its progress, so it's stretched or shortened rather than restarted, and
+	/// notes that are sounding carry on. Any tempo ramp in progress is stopped. This also becomes the
+	/// tempo the song restarts with, though the song's own tempo events still change it when reached.
+	/// A tempo of `0` is treated as `1`.
+	pub fn set_ticks_per_beat(&mut self, ticks_per_beat: u16) {
+		let ticks_per_beat = ticks_per_beat.max(1);
+		let old = self.beat_length.max(1) as u64;
+		let new = self.scaled_beat_length(ticks_per_beat);
+		let rescale = |ticks: u32| (ticks as u64 * new as u64 / old).min(u32::MAX as u64) as u32;
+		self.beat_timer = rescale(self.beat_timer);
+		if let Some(stinger) = &mut self.stinger {
+			stinger.beat_timer = rescale(stinger.beat_timer);
+		}
+		self.ticks_per_beat = ticks_per_beat;
+		self.beat_length = new;
+		self.tempo_ramp = None;
+	}
+
+	/// Changes the tempo to `bpm` beats per minute for a player ticking every `tick_period_ms` milliseconds.
+	/// See [`set_ticks_per_beat`] and [`bpm_to_ticks_per_beat`].
+	pub fn set_bpm(&mut self, bpm: u16, tick_period_ms: u16) {
+		self.set_ticks_per_beat(bpm_to_ticks_per_beat(bpm, tick_period_ms));
+	}
+
+	/// Starts speeding the song up each time it loops, or stops with `None`, leaving the tempo as it is.
+	///
+	/// The tempo changes to `min_bpm` straight away, then goes up by `step_bpm` at the start of every
+	/// pass until it reaches `max_bpm`. Each change is set like [`set_bpm`], so it's rounded to a whole
+	/// number of ticks per beat, overrides tempo events at the start of the song, and is sent as an
+	/// [`Event::TempoChanged`]. The player should loop for this to do anything past the first pass.
+	pub fn set_speed_trainer(&mut self, trainer: Option<SpeedTrainer>) {
+		self.speed_trainer = trainer;
+		if let Some(trainer) = trainer {
+			self.set_trainer_bpm(trainer.min_bpm.min(trainer.max_bpm));
+		}
+	}
+
+	/// Returns the tempo set by the speed trainer in beats per minute, or `None` without one.
+	/// See [`set_speed_trainer`].
+	pub fn speed_trainer_bpm(&self) -> Option<u16> {
+		self.speed_trainer.map(|_| self.trainer_bpm)
+	}
+
+	/// Moves the speed trainer on to the next pass, if there is one and it isn't at its fastest yet.
+	fn advance_speed_trainer(&mut self) {
+		let Some(trainer) = self.speed_trainer else {
+			return;
+		};
+		let bpm = self
+			.trainer_bpm
+			.saturating_add(trainer.step_bpm)
+			.min(trainer.max_bpm);
+		if bpm != self.trainer_bpm {
+			self.set_trainer_bpm(bpm);
+		}
+	}
+
+	/// Changes the tempo for the speed trainer and reports it.
+	fn set_trainer_bpm(&mut self, bpm: u16) {
+		let Some(trainer) = self.speed_trainer else {
+			return;
+		};
+		self.trainer_bpm = bpm;
+		self.set_bpm(bpm, trainer.tick_period_ms);
+		self.send_event(Event::TempoChanged { bpm });
+	}
+
+	/// Plays at `multiplier / divider` times the tempo of the song, so players ticked from the same
+	/// loop can share a master tempo while one plays a half-time bass line (`1, 2`) and another a
+	/// double-time arpeggio (`2, 1`). Tempo events and ramps are scaled by the same ratio.
+	///
+	/// Once the song is playing, the change waits for the next bar line, counted in beats of the master
+	/// tempo (see [`set_beats_per_bar`]), so players that change together stay in step. Bar lines are
+	/// counted from when the player started, was restarted, last seeked, or last changed its ratio.
+	/// Defaults to `1, 1`, and a `0` is treated as `1`.
+	pub fn set_tempo_ratio(&mut self, multiplier: u8, divider: u8) {
+		let ratio = (multiplier.max(1), divider.max(1));
+		if self.paused || self.beat < 0 {
+			self.pending_tempo_ratio = None;
+			self.apply_tempo_ratio(ratio);
+		} else {
+			self.pending_tempo_ratio = Some(ratio);
+		}
+	}
+
+	/// Returns the multiplier and divider set by [`set_tempo_ratio`], including one still waiting for a bar line.
+	pub fn tempo_ratio(&self) -> (u8, u8) {
+		self.pending_tempo_ratio.unwrap_or(self.tempo_ratio)
+	}
+
+	/// Sets how many beats of the master tempo are in a bar, for [`set_tempo_ratio`].
+	/// Defaults to `4`, and `0` is treated as `1`.
+	pub fn set_beats_per_bar(&mut self, beats: u16) {
+		self.beats_per_bar = beats.max(1);
+	}
+
+	/// Switches to a new tempo ratio, stretching or shortening the beat length to match.
+	fn apply_tempo_ratio(&mut self, ratio: (u8, u8)) {
+		let (multiplier, divider) = self.tempo_ratio;
+		let length = self.beat_length as u64 * multiplier as u64 * ratio.1 as u64
+			/ (divider as u64 * ratio.0 as u64);
+		self.beat_length = (length.min(u32::MAX as u64) as u32).max(1);
+		self.tempo_ratio = ratio;
+		self.ratio_beats = 0;
+	}
+
+	/// Converts a tempo in ticks per beat, as chosen for the first [`set_tick_interval_hint`],
+	/// into the length of a beat at the current tick period and tempo ratio.
+	fn scaled_beat_length(&self, ticks_per_beat: u16) -> u32 {
+		let mut length = ticks_per_beat as u64 * TICK_FRACTION as u64;
+		if self.tick_interval_ms != 0 {
+			length = length * self.reference_tick_interval_ms as u64 / self.tick_interval_ms as u64;
+		}
+		let (multiplier, divider) = self.tempo_ratio;
+		let length = length * divider as u64 / multiplier as u64;
+		(length.min(u32::MAX as u64) as u32).max(1)
+	}
+
+	/// Returns how many notes have been skipped or adjusted because the PWMs could not produce them.
+	pub fn adjusted_note_count(&self) -> u32 {
+		self.stats.out_of_range_notes
+	}
+
+	/// Returns the counters of everything that made playback differ from the song so far.
+	pub fn stats(&self) -> PlaybackStats {
+		self.stats
+	}
+
+	/// Sets every counter returned by [`stats`] back to `0`.
+	pub fn reset_stats(&mut self) {
+		self.stats = PlaybackStats::default();
+	}
+
+	/// Pauses the song, stopping any [`fade_in`] or [`fade_out`]. It can be resumed using [`resume`].
 	/// This doesn't do anything if already paused.
+	///
+	/// The PWMs are turned off, but the notes that were sounding are kept for [`resume`].
 	pub fn pause(&mut self) {
 		if !self.paused {
-			use embassy_rp::pwm::SetDutyCycle;
 			for i in 0..PWM_COUNT {
-				self.pwms[i].set_duty_cycle_fully_off().unwrap();
+				self.set_channel_off(i);
 			}
 			self.paused = true;
+			self.pause_on_beat = false;
+			self.paused_timer = self.beat_timer % self.current_beat_length();
+			self.volume_fade = None;
+			self.volume_fade_milli = 1000;
+		}
+	}
+
+	/// Pauses like [`pause`], but only once the current beat ends, so playback stops on the beat.
+	/// [`resume`] or [`resume_at_next_beat`] then start with the next beat.
+	/// This doesn't do anything if already paused.
+	pub fn pause_at_next_beat(&mut self) {
+		if !self.paused {
+			self.pause_on_beat = true;
+		}
+	}
+
+	/// Fades the volume out to silence over `ticks` ticks, then pauses, so stopping the music
+	/// isn't a hard cutoff. Pauses immediately if `ticks` is `0`.
+	///
+	/// Like after [`pause`], [`resume`] plays at full volume again. Replaces a [`fade_in`] in progress.
+	pub fn fade_out(&mut self, ticks: u16) {
+		if self.paused {
+			return;
+		}
+		if ticks == 0 {
+			self.pause();
+			return;
 		}
+		self.volume_fade = Some(VolumeFade {
+			from_milli: self.volume_fade_milli,
+			to_milli: 0,
+			ticks_left: ticks,
+			ticks,
+		});
+	}
+
+	/// Resumes after calling [`pause`], fading the volume in from silence over `ticks` ticks
+	/// (immediately if `0`).
+	///
+	/// If the song is already playing, this fades in from the current volume instead,
+	/// which turns a [`fade_out`] in progress back around.
+	pub fn fade_in(&mut self, ticks: u16) {
+		if self.paused {
+			self.volume_fade_milli = 0;
+			self.resume();
+		}
+		if ticks == 0 {
+			self.volume_fade = None;
+			self.volume_fade_milli = 1000;
+		} else {
+			self.volume_fade = Some(VolumeFade {
+				from_milli: self.volume_fade_milli,
+				to_milli: 1000,
+				ticks_left: ticks,
+				ticks,
+			});
+		}
+		self.update_channels();
+	}
+
+	/// Returns `true` while a [`fade_in`] or [`fade_out`] is in progress.
+	pub fn is_fading(&self) -> bool {
+		self.volume_fade.is_some()
+	}
+
+	/// Returns the range of frequencies every PWM of this player can produce.
+	pub fn playable_range(&self) -> FrequencyRange {
+		let mut range = self.pwms[0].playable_range();
+		for pwm in &self.pwms[1..] {
+			let other = pwm.playable_range();
+			range.min = range.min.max(other.min);
+			range.max = range.max.min(other.max);
+		}
+		range
+	}
+
+	/// Returns the PWMs, for example to read what a [`Player::shadow`]'s [`ShadowOutput`]s would be playing.
+	pub fn pwms(&self) -> &[O; PWM_COUNT] {
+		&self.pwms
+	}
+
+	/// Enables or disables the PWM at index `output`, without rebuilding the player.
+	///
+	/// A disabled PWM is turned off once and then left alone, so its buzzer can be powered down or the
+	/// PWM lent to another driver. Notes are spread over the enabled PWMs in order, sharing the last one
+	/// when there are more notes than PWMs, like with fewer PWMs. Enabling a PWM sets it up again, since
+	/// something else may have changed it. Every PWM starts enabled.
+	pub fn set_output_enabled(&mut self, output: usize, enabled: bool) {
+		let Some(&was_enabled) = self.enabled_outputs.get(output) else {
+			return;
+		};
+		if was_enabled == enabled {
+			return;
+		}
+
+		if enabled {
+			self.enabled_outputs[output] = true;
+			self.channel_outputs[output] = ChannelOutput::Unknown;
+		} else {
+			self.set_channel_off(output);
+			self.enabled_outputs[output] = false;
+		}
+		// The notes move to different PWMs, so the slew limit starts over rather than sliding between them.
+		self.slew_frequencies = [0; PWM_COUNT];
+		if !self.paused {
+			self.update_channels();
+		}
+	}
+
+	/// Returns `true` if the PWM at index `output` is enabled. See [`set_output_enabled`].
+	pub fn is_output_enabled(&self, output: usize) -> bool {
+		self.enabled_outputs.get(output).copied().unwrap_or(false)
 	}
 
 	/// Resumes after calling [`pause`].
 	/// This doesn't do anything if not paused.
+	///
+	/// Notes that were sounding when the song paused sound again straight away, with the duty they
+	/// would have now, rather than waiting for the next beat. The beat carries on from where it paused.
 	pub fn resume(&mut self) {
 		if self.paused {
 			self.paused = false;
+			self.resume_on_beat = false;
+			self.update_channels();
+		}
+	}
+
+	/// Resumes like [`resume`], but waits for the next beat, keeping time as if the song had
+	/// played on while paused, so playback restarts on the beat. What was left of the beat the song
+	/// paused in is skipped.
+	///
+	/// If the song isn't paused, this cancels a [`pause_at_next_beat`] that hasn't happened yet.
+	pub fn resume_at_next_beat(&mut self) {
+		if self.paused {
+			self.resume_on_beat = true;
+		} else {
+			self.pause_on_beat = false;
+		}
+	}
+
+	/// Keeps time while paused, resuming on the next beat if [`resume_at_next_beat`] was called.
+	fn tick_paused(&mut self) {
+		let beat_length = self.current_beat_length();
+		self.paused_timer += TICK_FRACTION;
+		if self.paused_timer < beat_length {
+			return;
+		}
+
+		self.paused_timer -= beat_length;
+		if self.resume_on_beat {
+			self.resume();
+			// The tick carries on as normal, which starts the next beat.
+			self.beat_timer = beat_length - TICK_FRACTION + self.paused_timer;
+		}
+	}
+
+	/// Switches to `song`, which starts from its beginning on the next beat.
+	/// Will play if paused.
+	///
+	/// If `keep_tail` is `true`, notes still sounding from the old song ring out for the rest of
+	/// their duration, so jukebox-style transitions sound continuous rather than clipped.
+	/// Otherwise they stop immediately.
+	pub fn swap_song(&mut self, song: &'a Song<'a>, keep_tail: bool) {
+		if self.crossfade.take().is_some() {
+			self.stop_stinger();
+		}
+		self.song = song;
+		self.reset_internally();
+		self.loop_pass = 0;
+		self.pending_jump = None;
+		self.fade_percent = 100;
+		if !keep_tail {
+			self.clear_notes();
+			self.update_channels();
+		}
+		self.resume();
+	}
+
+	/// Starts the song from the beginning.
+	/// Will play if paused.
+	pub fn restart(&mut self) {
+		self.reset_internally();
+		self.loop_pass = 0;
+		self.ratio_beats = 0;
+		self.beat_timer = 0;
+		self.delay_ticks = 0;
+		self.pending_jump = None;
+		self.fade_percent = 100;
+		self.shift_schedule(self.output_latency_ticks as i32);
+		self.pause();
+		self.resume();
+	}
+
+	/// Moves playback to `beat`, stopping the notes playing now. The song continues from `beat` on the
+	/// next beat, so playback can resume where it left off, for example after a power cycle.
+	/// Will play if paused.
+	///
+	/// The tempo is set to what it would be at `beat`, by applying the tempo events before it.
+	/// Other events before it are skipped, so no [`SongEventKind::Alternatives`] take carries over.
+	/// Notes that started before `beat` and would still be sounding aren't played.
+	///
+	/// Returns `false`, without seeking, if `beat` is past the end of the song.
+	///
+	/// ```rust,ignore
+	/// // Before powering down.
+	/// let beat = player.current_beat().unwrap_or(0);
+	/// save(beat);
+	///
+	/// // After powering up.
+	/// player.seek(load());
+	/// ```
+	pub fn seek(&mut self, beat: u16) -> bool {
+		if beat >= self.song.end {
+			return false;
+		}
+
+		self.reset_internally();
+		self.ratio_beats = 0;
+		self.beat_timer = 0;
+		self.delay_ticks = 0;
+		self.pending_jump = None;
+		self.fade_percent = 100;
+		// Catch the tempo up with the beats being skipped.
+		for skipped in 0..beat {
+			self.beat = skipped as i32;
+			self.apply_tempo_events();
+		}
+		self.beat = beat as i32 - 1;
+		self.shift_schedule(self.output_latency_ticks as i32);
+
+		self.clear_notes();
+		self.update_channels();
+		self.resume();
+		true
+	}
+
+	/// Returns the beat being played, or `None` if the song hasn't reached its first beat yet.
+	pub fn current_beat(&self) -> Option<u16> {
+		u16::try_from(self.beat).ok()
+	}
+
+	/// Returns how many beats the song lasts, its `end`.
+	pub fn total_beats(&self) -> u16 {
+		self.song.end
+	}
+
+	/// Returns how far through the song playback is, in thousandths, for progress bars.
+	///
+	/// This moves smoothly through each beat, and is `1000` once the song has finished.
+	/// It starts over from `0` each time a looping song does.
+	pub fn progress_milli(&self) -> u16 {
+		if self.is_finished() {
+			return 1000;
+		}
+		let Ok(beat) = u64::try_from(self.beat) else {
+			return 0;
+		};
+		let end = self.song.end.max(1) as u64;
+		let within = (self.beat_timer as u64 * 1000 / self.current_beat_length() as u64).min(1000);
+		((beat * 1000 + within) / end).min(1000) as u16
+	}
+
+	/// Returns `true` if the song is paused, including after it finished.
+	pub fn is_paused(&self) -> bool {
+		self.paused
+	}
+
+	/// Returns `true` if the song reached its end without looping and stopped.
+	/// [`restart`], [`seek`], and [`swap_song`] start it playing again.
+	pub fn is_finished(&self) -> bool {
+		self.paused && self.beat >= self.song.end as i32
+	}
+
+	/// Resets the song to the start.
+	fn reset_internally(&mut self) {
+		self.beat = -1;
+		self.active_take = None;
+		self.end_fade = None;
+		self.beat_length = self.scaled_beat_length(self.ticks_per_beat);
+		self.tempo_ramp = None;
+		self.beat_stretch_percent = 100;
+		self.holding = false;
+	}
+
+	/// Continues playback after a [`SongEventKind::Hold`].
+	/// This doesn't do anything if not holding.
+	pub fn release_hold(&mut self) {
+		self.holding = false;
+	}
+
+	/// Returns `true` if playback is waiting for [`release_hold`] to be called.
+	pub fn is_holding(&self) -> bool {
+		self.holding
+	}
+
+	/// Sets which target a [`SongEventKind::Branch`] jumps to.
+	/// This stays in effect for every branch until changed.
+	pub fn select_branch(&mut self, choice: u8) {
+		self.branch_choice = choice;
+	}
+
+	/// Sets a function that chooses which target a [`SongEventKind::Branch`] jumps to.
+	/// It is given the beat of the branch and returns the index of the target.
+	///
+	/// This takes priority over [`select_branch`]. Pass `None` to remove it.
+	pub fn set_branch_selector(&mut self, selector: Option<fn(u16) -> u8>) {
+		self.branch_selector = selector;
+	}
+
+	/// Jumps to the [`SongEventKind::Marker`] with `id`, using the marker's [`Transition`].
+	///
+	/// The jump starts on the next beat, even from the last beat of a song that doesn't loop.
+	/// Any jump already in progress is replaced. Returns `false` if the song has no such marker.
+	pub fn jump_to_marker(&mut self, id: u8) -> bool {
+		for event in self.song.events {
+			if let SongEventKind::Marker {
+				id: marker,
+				transition,
+			} = event.kind
+				&& marker == id
+				&& event.beat < self.song.end
+			{
+				self.pending_jump = Some(PendingJump {
+					target: event.beat,
+					transition,
+					beats_played: 0,
+				});
+				self.fade_percent = 100;
+				return true;
+			}
+		}
+		false
+	}
+
+	/// Sets the beats where sections start, usually found by [`Song::find_sections`], which
+	/// [`skip_to_next_section`] treats as markers. They must be in order.
+	pub fn set_sections(&mut self, sections: &'a [u16]) {
+		self.sections = sections;
+	}
+
+	/// Cuts to the start of the next section after the current beat, like [`jump_to_marker`].
+	///
+	/// Both the [`SongEventKind::Marker`]s of the song and the beats given to [`set_sections`]
+	/// start sections. Returns `false` if no section starts after the current beat.
+	pub fn skip_to_next_section(&mut self) -> bool {
+		let markers = self
+			.song
+			.events
+			.iter()
+			.filter_map(|event| match event.kind {
+				SongEventKind::Marker { .. } => Some(event.beat),
+				_ => None,
+			});
+		let next = markers
+			.chain(self.sections.iter().copied())
+			.filter(|&beat| beat as i32 > self.beat && beat < self.song.end)
+			.min();
+		let Some(target) = next else {
+			return false;
+		};
+
+		self.pending_jump = Some(PendingJump {
+			target,
+			transition: Transition::Cut,
+			beats_played: 0,
+		});
+		self.fade_percent = 100;
+		true
+	}
+
+	/// Returns the notes starting within the next `beats` beats, in order, without changing playback.
+	///
+	/// This lets a display scroll notes toward a hit-line before they sound. Looping songs wrap
+	/// around to the start. Branches, jumps, alternative takes, and live notes aren't predicted,
+	/// and notes the PWMs can't produce are adjusted by the [`UnplayableNotePolicy`] the same way
+	/// they will be played.
+	pub fn lookahead_events(&self, beats: u16) -> impl Iterator<Item = UpcomingNote> + '_ {
+		let range = self.playable_range();
+		let end = self.song.end as i32;
+		let ticks_left = self.current_beat_length().saturating_sub(self.beat_timer) / TICK_FRACTION;
+		let beat_ticks = self.beat_length / TICK_FRACTION;
+
+		(1..=beats)
+			.map_while(move |beats_ahead| {
+				let mut beat = self.beat + beats_ahead as i32;
+				if beat >= end {
+					if !self.looping || end == 0 {
+						return None;
+					}
+					beat %= end;
+				}
+				Some((beat as u16, beats_ahead))
+			})
+			.flat_map(move |(beat, beats_ahead)| {
+				let notes: &[NoteAndDuration] = match self.song.notes.get(beat as usize) {
+					Some(Some(notes)) => notes,
+					_ => &[],
+				};
+				let ticks_ahead = ticks_left + (beats_ahead as u32 - 1) * beat_ticks;
+				notes.iter().filter_map(move |note| {
+					let frequency = self.unplayable_note_policy.apply(note.frequency, range)?;
+					Some(UpcomingNote {
+						beat,
+						beats_ahead,
+						ticks_ahead,
+						note: NoteAndDuration { frequency, ..*note },
+					})
+				})
+			})
+	}
+
+	/// Returns a silent copy of this player, at the same point of the same song, playing on [`ShadowOutput`]s.
+	///
+	/// Ticking the shadow ahead of real time previews exactly what this player will do, including
+	/// branches, alternative takes, tempo changes, and voice stealing, without touching its PWMs or state.
+	/// Each [`ShadowOutput`] reports what its PWM would be playing. A new shadow can be taken
+	/// whenever the two should line up again, like after a jump or a live change.
+	///
+	/// The shadow doesn't send events or call callbacks until it's given its own with
+	/// [`set_event_queue`], [`set_duty_limit_callback`], and [`set_note_callback`], and it has no [`LiveSource`], since live
+	/// notes can't be known ahead of time. Its [`stats`] start from zero.
+	///
+	/// ```rust,ignore
+	/// let mut shadow = player.shadow();
+	/// shadow.tick_by(ticks_ahead, false);
+	/// let upcoming = shadow.pwms().map(|pwm| pwm.frequency());
+	/// ```
+	pub fn shadow(&self) -> Player<'a, ShadowOutput, PWM_COUNT, MAX_SIMULTANEOUS_NOTES> {
+		let mut pwms = core::array::from_fn(|i| ShadowOutput::mirroring(&self.pwms[i]));
+		for (pwm, output) in pwms.iter_mut().zip(self.channel_outputs) {
+			if let ChannelOutput::Tone {
+				frequency,
+				duty,
+				fraction,
+			} = output
+			{
+				let _ = if fraction {
+					pwm.set_tone_fraction(frequency, DutyFraction(duty))
+				} else {
+					pwm.set_tone(frequency, duty)
+				};
+			}
+		}
+
+		Player {
+			song: self.song,
+			looping: self.looping,
+			ticks_per_beat: self.ticks_per_beat,
+			duty: self.duty,
+			duty_is_fraction: self.duty_is_fraction,
+			volume_percent: self.volume_percent,
+			pwms,
+			channel_gains: self.channel_gains,
+			resonance_curve: self.resonance_curve.clone(),
+			channel_positions: self.channel_positions,
+			pitch_scale_milli: self.pitch_scale_milli,
+			transpose: self.transpose,
+			pans: self.pans.clone(),
+			strums: self.strums.clone(),
+			sticky_tracks: self.sticky_tracks,
+			muted_tracks: self.muted_tracks,
+			solo_tracks: self.solo_tracks,
+			sticky_min_beats: self.sticky_min_beats,
+			duty_limit: self.duty_limit,
+			duty_limiters: self.duty_limiters,
+			duty_limit_callback: None,
+			note_callback: None,
+			tick_events: TickEvents::NONE,
+			channel_outputs: self.channel_outputs,
+			enabled_outputs: self.enabled_outputs,
+			category: self.category,
+			policy: self.policy,
+			events: None,
+			beat_events: None,
+			muted: self.muted,
+			live_source: None,
+			unplayable_note_policy: self.unplayable_note_policy,
+			end_behavior: self.end_behavior,
+			loop_overlap: self.loop_overlap,
+			frequency_snap_cents: self.frequency_snap_cents,
+			max_slew_semitones: self.max_slew_semitones,
+			slew_frequencies: self.slew_frequencies,
+			slewing: self.slewing,
+			loop_pass: self.loop_pass,
+			speed_trainer: self.speed_trainer,
+			trainer_bpm: self.trainer_bpm,
+			variation_random: self.variation_random,
+			active_take: self.active_take,
+
+			paused: self.paused,
+			pause_on_beat: self.pause_on_beat,
+			resume_on_beat: self.resume_on_beat,
+			paused_timer: self.paused_timer,
+			skipping_output: self.skipping_output,
+			output_latency_ticks: self.output_latency_ticks,
+			delay_ticks: self.delay_ticks,
+			reference_tick_interval_ms: self.reference_tick_interval_ms,
+			tick_interval_ms: self.tick_interval_ms,
+			beat_timer: self.beat_timer,
+			beat_length: self.beat_length,
+			tempo_ramp: self.tempo_ramp,
+			tempo_ratio: self.tempo_ratio,
+			pending_tempo_ratio: self.pending_tempo_ratio,
+			beats_per_bar: self.beats_per_bar,
+			ratio_beats: self.ratio_beats,
+			beat_stretch_percent: self.beat_stretch_percent,
+			holding: self.holding,
+			branch_choice: self.branch_choice,
+			branch_selector: self.branch_selector,
+			pending_jump: self.pending_jump,
+			sections: self.sections,
+			fade_percent: self.fade_percent,
+			end_fade: self.end_fade,
+			volume_fade: self.volume_fade,
+			volume_fade_milli: self.volume_fade_milli,
+			duck: self.duck,
+			duck_gain_milli: self.duck_gain_milli,
+			duck_paused: self.duck_paused,
+			beat: self.beat,
+			mixer: self.mixer.clone(),
+			playing_notes: self.playing_notes.clone(),
+			note_lengths: self.note_lengths.clone(),
+			first_new_note: self.first_new_note,
+			beat_ticks: self.beat_ticks,
+			retrigger_gap_ticks: self.retrigger_gap_ticks,
+			beat_start_frequencies: self.beat_start_frequencies,
+			stats: PlaybackStats::default(),
+			stinger: self.stinger.clone(),
+			crossfade: self.crossfade,
+		}
+	}
+
+	/// Returns `true` if a jump started by [`jump_to_marker`] hasn't landed yet.
+	pub fn is_jumping(&self) -> bool {
+		self.pending_jump.is_some()
+	}
+
+	/// Advances the jump started by [`jump_to_marker`] by a beat.
+	/// Returns `true` if this beat plays a [`Transition::Fill`] instead of the song.
+	fn advance_jump(&mut self) -> bool {
+		let Some(jump) = &mut self.pending_jump else {
+			return false;
+		};
+
+		let played = jump.beats_played;
+		jump.beats_played += 1;
+		let landing = match jump.transition {
+			Transition::Cut => true,
+			Transition::Fade { beats } => {
+				if played < beats {
+					let remaining = (beats - played) as u32;
+					self.fade_percent = (remaining * 100 / (beats as u32 + 1)) as u8;
+					false
+				} else {
+					true
+				}
+			}
+			Transition::Fill { notes } => {
+				if played == 0 {
+					self.clear_notes();
+					for note in notes {
+						if let Some(note) = self.adjust_note(note) {
+							let note = NoteAndDuration {
+								duration: 1,
+								..note
+							};
+							self.start_note(note);
+						}
+					}
+					return true;
+				}
+				true
+			}
+		};
+
+		if landing && let Some(jump) = self.pending_jump.take() {
+			self.clear_notes();
+			self.fade_percent = 100;
+			self.beat = jump.target as i32;
+		}
+		false
+	}
+
+	/// Adds `note` to the playing notes, unless it lasts no beats, or its track is muted or left out
+	/// of a solo.
+	///
+	/// If `MAX_SIMULTANEOUS_NOTES` are already playing, the oldest note that isn't sticky is stolen
+	/// to make room. If they are all sticky, the oldest note is stolen.
+	fn start_note(&mut self, note: NoteAndDuration) {
+		if note.duration == 0 || !self.is_track_audible(note.track) {
+			return;
+		}
+		if self.playing_notes.is_full() {
+			let victim = self
+				.playing_notes
+				.iter()
+				.position(|playing| !self.is_sticky(playing))
+				.unwrap_or(0);
+			let stolen = self.playing_notes.remove(victim);
+			self.note_lengths.remove(victim);
+			self.stats.voice_steals = self.stats.voice_steals.saturating_add(1);
+			if victim < self.first_new_note {
+				self.first_new_note -= 1;
+			}
+			self.send_event(Event::note_off(&stolen));
+		}
+
+		self.playing_notes.push(note);
+		self.note_lengths.push(note.duration);
+		self.send_event(Event::note_on(&note));
+	}
+
+	/// Returns `true` if `note` should be kept when notes are stolen. See [`set_track_sticky`].
+	fn is_sticky(&self, note: &NoteAndDuration) -> bool {
+		self.sticky_tracks.contains(note.track)
+			|| self
+				.sticky_min_beats
+				.is_some_and(|beats| note.duration >= beats)
+	}
+
+	/// Stops every note of the song.
+	fn clear_notes(&mut self) {
+		for note in &self.playing_notes {
+			send_event(&self.events, self.note_callback, Event::note_off(note));
 		}
+		self.playing_notes.clear();
+		self.note_lengths.clear();
 	}
 
-	/// Starts the song from the beginning.
-	/// Will play if paused.
-	pub fn restart(&mut self) {
-		self.reset_internally();
-		self.pause();
-		self.resume();
+	/// Returns the beat the branch on the current beat jumps to, if any.
+	fn branch_target(&self) -> Option<u16> {
+		let beat = self.beat as u16;
+		for event in self.song.events {
+			if event.beat != beat {
+				continue;
+			}
+			if let SongEventKind::Branch { targets } = event.kind {
+				let choice = match self.branch_selector {
+					Some(selector) => selector(beat),
+					None => self.branch_choice,
+				};
+				return targets
+					.get(choice as usize)
+					.copied()
+					.filter(|target| *target < self.song.end);
+			}
+		}
+		None
 	}
 
-	/// Resets the song to the start.
-	fn reset_internally(&mut self) {
-		self.beat = -1;
-		self.timer = 0;
+	/// Returns how long the current beat lasts, including any fermata.
+	/// A beat is never shorter than a tick, since only one beat can start per tick.
+	fn current_beat_length(&self) -> u32 {
+		let length = self.beat_length as u64 * self.beat_stretch_percent as u64 / 100;
+		(length.min(u32::MAX as u64) as u32).max(TICK_FRACTION)
 	}
 
 	/// Updates the player.
@@ -163,103 +2688,944 @@ impl<'a, const PWM_COUNT: usize, const MAX_SIMULTANEOUS_NOTES: usize>
 	///
 	/// Returns `false` if paused, `true` if successful!
 	pub fn tick(&mut self) -> bool {
+		self.update_duty_limits();
+
 		if self.paused {
+			self.tick_paused();
+		}
+		if self.paused || self.update_ducking() || !self.tick_end_fade() || !self.tick_volume_fade()
+		{
 			return false;
 		}
 
+		self.tick_slew();
+
 		// Increment that timer!
-		self.timer += 1;
-		self.beat_timer += 1;
+		// While holding, the current beat is kept a tick from ending, so the next beat
+		// starts on the tick after the hold is released.
+		// While delayed by a negative latency, the song waits.
+		let beat_length = self.current_beat_length();
+		if self.delay_ticks > 0 {
+			self.delay_ticks -= 1;
+		} else {
+			self.beat_timer += TICK_FRACTION;
+		}
+		if self.holding {
+			self.beat_timer = self
+				.beat_timer
+				.min(beat_length.saturating_sub(TICK_FRACTION));
+		}
+
+		// Once we're hit enough ticks, increment the beat.
+		// Any leftover fraction of a tick is carried into the next beat.
+		if !self.holding && self.beat_timer >= beat_length {
+			if self.pause_on_beat {
+				// The next beat starts once the song resumes.
+				self.pause();
+				return false;
+			}
+			self.beat_timer -= beat_length;
+			if !self.play_beat() {
+				if self.crossfade.is_none() {
+					return false;
+				}
+				// The old song ended partway through a crossfade, so the new one takes over now.
+				self.finish_crossfade();
+				return true;
+			}
+			self.tick_stinger(true);
+		} else {
+			self.tick_stinger(false);
+			self.tick_within_beat();
+		}
+		self.tick_crossfade();
+
+		// If we're playing multiple notes at the same time, cycle them through the buzzer.
+		// Every mixer slot the note should be updated unless we're playing one note.
+		if self.mixer.tick() {
+			self.cycle_combined_notes();
+		}
+
+		true
+	}
+
+	/// Updates the player like [`tick`], and returns what happened during the tick, so the song
+	/// starting beats, looping, or finishing can be reacted to without checking its state every tick.
+	pub fn tick_events(&mut self) -> TickEvents {
+		self.tick_events = TickEvents::NONE;
+		self.tick();
+		self.tick_events
+	}
+
+	/// Plays the song until the player pauses, calling [`tick`] and then waiting with an
+	/// [`embassy_time::Timer`], so it replaces the usual loop:
+	///
+	/// ```rust,ignore
+	/// let mut player = buzzer_music::Player::new(&SONG, false, 3, 100, [buzzer]);
+	/// player.play().await;
+	/// ```
+	///
+	/// The wait is the period given to [`set_tick_interval_hint`], or 40ms if it was never called.
+	/// Songs that don't loop finish by pausing, so the future resolves then, while looping songs
+	/// play until it is dropped. It resolves right away if the player is already paused.
+	#[cfg(feature = "time")]
+	pub async fn play(&mut self) {
+		loop {
+			self.tick();
+			if self.paused {
+				return;
+			}
+			let ms = match self.tick_interval_ms {
+				0 => 40,
+				ms => ms,
+			};
+			embassy_time::Timer::after_millis(ms as u64).await;
+		}
+	}
+
+	/// Updates the player as if [`tick`] was called `ticks` times, to catch up after missing some.
+	///
+	/// Call this when the loop was held up (a flash erase, a burst of USB traffic, ...) so the
+	/// song stays on schedule instead of slowing down. If `skip_audio` is `true`, the notes of the
+	/// missed ticks aren't sent to the PWMs; only the notes playing at the end are.
+	///
+	/// Returns the result of the last tick. Calling this with `0` ticks does nothing.
+	pub fn tick_by(&mut self, ticks: u32, skip_audio: bool) -> bool {
+		if ticks == 0 {
+			return !self.paused;
+		}
+
+		self.stats.missed_ticks = self.stats.missed_ticks.saturating_add(ticks - 1);
+		self.skipping_output = skip_audio;
+		for _ in 1..ticks {
+			self.tick();
+		}
+		self.skipping_output = false;
+
+		let result = self.tick();
+		if skip_audio && !self.paused {
+			self.update_channels();
+		}
+		result
+	}
+
+	/// Starts the next notes of any strummed chords, and the next hits of any ratchets.
+	fn tick_within_beat(&mut self) {
+		self.beat_ticks = self.beat_ticks.saturating_add(1);
+		let strumming = !self.strums.is_empty()
+			&& (self.first_new_note..self.playing_notes.len())
+				.any(|index| self.strum_delay(index) == self.beat_ticks);
+		let ratcheting = (0..self.playing_notes.len()).any(|index| {
+			self.is_between_hits(index, self.beat_ticks)
+				!= self.is_between_hits(index, self.beat_ticks - 1)
+		});
+		let retriggering = self.beat_ticks == self.retrigger_gap_ticks as u16;
+		if strumming || ratcheting || retriggering {
+			self.update_channels();
+		}
+	}
+
+	/// Moves the PWMs still sliding towards their notes after a big jump one step closer.
+	/// See [`set_max_slew_semitones`].
+	fn tick_slew(&mut self) {
+		if self.max_slew_semitones == 0 {
+			return;
+		}
+
+		self.record_slew_frequencies();
+		if self.slewing {
+			self.slewing = false;
+			self.update_channels();
+		}
+	}
+
+	/// Records what each PWM is playing for [`limit_slew`].
+	fn record_slew_frequencies(&mut self) {
+		for (frequency, output) in self.slew_frequencies.iter_mut().zip(&self.channel_outputs) {
+			*frequency = match *output {
+				ChannelOutput::Tone { frequency, .. } => frequency,
+				_ => 0,
+			};
+		}
+	}
+
+	/// Returns `frequency`, moved no further than the slew limit from what the PWM at index
+	/// `pwm_index` was playing as the tick started. See [`set_max_slew_semitones`].
+	fn limit_slew(&mut self, pwm_index: usize, frequency: u16) -> u16 {
+		let from = self.slew_frequencies[pwm_index];
+		let channels = self.song_channel_count();
+		let shared = self.playing_notes.len() > channels
+			&& channels > 0
+			&& pwm_index == self.voice_output(channels - 1);
+		if self.max_slew_semitones == 0 || from == 0 || shared || !self.is_channel_on(pwm_index) {
+			return frequency;
+		}
+
+		let semitones = self.max_slew_semitones as u16;
+		let low = sonify::transpose_down(from, semitones);
+		let high = sonify::transpose_up(from, semitones);
+		let limited = frequency.clamp(low, high);
+		if limited != frequency {
+			self.slewing = true;
+		}
+		limited
+	}
+
+	/// Returns how many ticks after its beat started the playing note at `index` starts.
+	fn strum_delay(&self, index: usize) -> u16 {
+		match index.checked_sub(self.first_new_note) {
+			Some(index) => self
+				.strums
+				.delay(&self.playing_notes[self.first_new_note..], index),
+			None => 0,
+		}
+	}
+
+	/// Returns `true` if the playing note at `index` is part of a strum and hasn't started yet.
+	fn is_waiting_to_strum(&self, index: usize) -> bool {
+		!self.strums.is_empty() && self.strum_delay(index) > self.beat_ticks
+	}
+
+	/// Returns `true` if the playing note at `index` is silent between two hits of its ratchet,
+	/// `beat_ticks` ticks into the current beat.
+	fn is_between_hits(&self, index: usize, beat_ticks: u16) -> bool {
+		let note = &self.playing_notes[index];
+		if note.ratchet <= 1 {
+			return false;
+		}
+
+		let beat_length = (self.current_beat_length() / TICK_FRACTION) as u64;
+		let length = self.note_lengths[index] as u64;
+		let elapsed = (length - note.duration as u64) * beat_length + beat_ticks as u64;
+		let total = length * beat_length;
+		// Each hit sounds for the first half of its share of the note.
+		(elapsed * note.ratchet as u64 % total) * 2 >= total
+	}
+
+	/// Returns `true` if the playing note at `index` started this beat on a PWM that was already
+	/// playing its frequency, and is in the gap set by [`set_retrigger_gap`].
+	fn is_in_retrigger_gap(&self, index: usize) -> bool {
+		let gap = self.retrigger_gap_ticks as u32;
+		gap > 0
+			&& (self.beat_ticks as u32) < gap
+			&& gap * 2 * TICK_FRACTION <= self.current_beat_length()
+			&& index >= self.first_new_note
+			&& index < PWM_COUNT
+			&& self.beat_start_frequencies[index] == self.playing_notes[index].frequency
+	}
+
+	/// Returns `true` if the playing note at `index` should be silent for now.
+	fn is_resting(&self, index: usize) -> bool {
+		self.is_waiting_to_strum(index)
+			|| self.is_between_hits(index, self.beat_ticks)
+			|| self.is_in_retrigger_gap(index)
+	}
+
+	/// Moves the last PWM of the song to the next note that doesn't have a PWM of its own.
+	fn cycle_combined_notes(&mut self) {
+		let channels = self.song_channel_count();
+		if channels > 0 && self.playing_notes.len() > channels {
+			// The highest note sharing the PWM is treated as the melody.
+			let shared_notes = &self.playing_notes[channels - 1..];
+			let melody = shared_notes
+				.iter()
+				.enumerate()
+				.max_by_key(|(_, note)| note.frequency)
+				.map(|(voice, _)| voice);
+			let voice = self
+				.mixer
+				.next_voice_with_melody(shared_notes.len(), melody);
+
+			let index = voice + channels - 1;
+			let output = self.voice_output(channels - 1);
+			if self.is_resting(index) {
+				self.set_channel_off(output);
+			} else {
+				let (duty, _) = self.layer_duties();
+				self.set_frequency_and_duty(output, self.playing_notes[index], duty);
+			}
+		}
+	}
+
+	/// Plays `song` once on top of the current song.
+	///
+	/// The stinger takes PWMs starting from the last one and gives them back once it ends.
+	/// `policy` decides whether it only uses PWMs the song isn't using, or takes them from the song.
+	/// If `quantize` is `true`, the stinger waits for the song's next beat before starting.
+	///
+	/// Any stinger already playing is replaced.
+	pub fn play_stinger(&mut self, song: &'a Song<'a>, policy: StingerPolicy, quantize: bool) {
+		self.crossfade = None;
+		self.stinger = Some(Stinger {
+			song,
+			policy,
+			waiting_for_beat: quantize,
+			beat: -1,
+			// The first beat starts on the next tick.
+			beat_timer: self.current_beat_length().saturating_sub(TICK_FRACTION),
+			playing_notes: arrayvec::ArrayVec::new(),
+			note_lengths: arrayvec::ArrayVec::new(),
+		});
+	}
+
+	/// Stops the stinger started by [`play_stinger`], giving its PWMs back to the song.
+	pub fn stop_stinger(&mut self) {
+		if self.stinger.take().is_some() {
+			self.update_channels();
+		}
+	}
+
+	/// Returns `true` if a stinger started by [`play_stinger`] is playing.
+	pub fn is_stinger_playing(&self) -> bool {
+		self.stinger.is_some() && self.crossfade.is_none()
+	}
+
+	/// Switches to `song` over `ticks` ticks, fading the current song out while `song` fades in from
+	/// its beginning. Switches straight away, like [`swap_song`], if `ticks` is `0`.
+	///
+	/// Both songs play at once, so `song` starts on the PWMs the current song isn't using. Halfway
+	/// through, it starts taking them from the current song, as a [`StingerPolicy::StealVoices`]
+	/// stinger would. It follows the current song's tempo until the crossfade ends and it takes over.
+	/// A stinger that is playing is stopped.
+	pub fn crossfade_to(&mut self, song: &'a Song<'a>, ticks: u16) {
+		if ticks == 0 {
+			self.swap_song(song, false);
+			return;
+		}
+		self.play_stinger(song, StingerPolicy::SpareVoices, false);
+		self.crossfade = Some(Crossfade {
+			song,
+			ticks_left: ticks,
+			ticks,
+		});
+		self.resume();
+	}
+
+	/// Returns `true` while a crossfade started by [`crossfade_to`] is in progress.
+	pub fn is_crossfading(&self) -> bool {
+		self.crossfade.is_some()
+	}
+
+	/// Advances the crossfade by one tick, handing over to the new song once it's done.
+	fn tick_crossfade(&mut self) {
+		let Some(crossfade) = &mut self.crossfade else {
+			return;
+		};
+
+		crossfade.ticks_left -= 1;
+		if crossfade.ticks_left == 0 {
+			self.finish_crossfade();
+			return;
+		}
+		if crossfade.ticks_left <= crossfade.ticks / 2
+			&& let Some(stinger) = &mut self.stinger
+		{
+			stinger.policy = StingerPolicy::StealVoices;
+		}
+		self.update_channels();
+	}
+
+	/// Makes the song crossfading in the song being played, carrying on from where it is.
+	fn finish_crossfade(&mut self) {
+		let Some(crossfade) = self.crossfade.take() else {
+			return;
+		};
+		// A short song can finish before the crossfade does, so it's started again.
+		let Some(stinger) = self.stinger.take() else {
+			self.swap_song(crossfade.song, false);
+			return;
+		};
+
+		self.song = stinger.song;
+		self.reset_internally();
+		self.loop_pass = 0;
+		self.pending_jump = None;
+		self.fade_percent = 100;
+		// Catch the tempo up with the beats the new song already played.
+		for beat in 0..stinger.beat.max(0) {
+			self.beat = beat;
+			self.apply_events();
+		}
+		self.beat = stinger.beat;
+		self.beat_timer = stinger.beat_timer;
+		self.beat_ticks = (stinger.beat_timer / TICK_FRACTION) as u16;
+		self.holding = false;
+		self.beat_stretch_percent = 100;
+
+		self.clear_notes();
+		// The notes keep the lengths they started with, so their ratchets stay in step.
+		self.playing_notes = stinger.playing_notes;
+		self.note_lengths = stinger.note_lengths;
+		self.first_new_note = self.playing_notes.len();
+		self.update_channels();
+		self.resume();
+	}
+
+	/// Returns the duty of the song's notes and of the stinger's, which differ during a crossfade.
+	fn layer_duties(&self) -> (u16, u16) {
+		let Some(crossfade) = self.crossfade else {
+			return (self.duty, self.duty);
+		};
+
+		let duty = self.duty as u32;
+		let left = crossfade.ticks_left as u32;
+		let ticks = crossfade.ticks as u32;
+		(
+			(duty * left / ticks) as u16,
+			(duty * (ticks - left) / ticks) as u16,
+		)
+	}
+
+	/// Returns how many PWMs, counting back from the last one, the stinger is using.
+	fn stinger_channel_count(&self) -> usize {
+		let Some(stinger) = &self.stinger else {
+			return 0;
+		};
+
+		let voices = self.voice_count();
+		let available = match stinger.policy {
+			StingerPolicy::SpareVoices => voices.saturating_sub(self.playing_notes.len()),
+			StingerPolicy::StealVoices => voices,
+		};
+		stinger.playing_notes.len().min(available)
+	}
+
+	/// Returns how many voices, counting from the first one, the song is using.
+	fn song_channel_count(&self) -> usize {
+		self.voice_count() - self.stinger_channel_count()
+	}
+
+	/// Returns how many notes can sound at once on their own PWMs, which is how many PWMs are enabled.
+	fn voice_count(&self) -> usize {
+		self.enabled_outputs
+			.iter()
+			.filter(|enabled| **enabled)
+			.count()
+	}
+
+	/// Returns the index of the PWM that plays `voice`, the `voice`th enabled PWM.
+	fn voice_output(&self, voice: usize) -> usize {
+		self.enabled_outputs
+			.iter()
+			.enumerate()
+			.filter(|(_, enabled)| **enabled)
+			.nth(voice)
+			.map_or(0, |(output, _)| output)
+	}
+
+	/// Advances the stinger by one tick.
+	fn tick_stinger(&mut self, song_beat_started: bool) {
+		let beat_length = self.current_beat_length();
+		let Some(stinger) = &mut self.stinger else {
+			return;
+		};
+
+		if stinger.waiting_for_beat {
+			if !song_beat_started {
+				return;
+			}
+			stinger.waiting_for_beat = false;
+			stinger.beat_timer = beat_length;
+		} else {
+			stinger.beat_timer += TICK_FRACTION;
+		}
+
+		if stinger.beat_timer < beat_length {
+			return;
+		}
+		stinger.beat_timer = stinger.beat_timer.saturating_sub(beat_length);
+		stinger.beat += 1;
+
+		let note_lengths = &mut stinger.note_lengths;
+		expire_notes(&mut stinger.playing_notes, |index, _| {
+			note_lengths.remove(index);
+		});
+
+		// The stinger ends once it runs out of notes, so it doesn't wait for the rounded-up `end`.
+		let song = stinger.song;
+		let beat = stinger.beat as usize;
+		if beat >= song.notes.len() && stinger.playing_notes.is_empty() {
+			self.stop_stinger();
+			return;
+		}
+
+		if let Some(Some(notes)) = song.notes.get(beat) {
+			for note in *notes {
+				if let Some(note) = self.adjust_note(note)
+					&& let Some(stinger) = &mut self.stinger
+				{
+					stinger.playing_notes.push(note);
+					stinger.note_lengths.push(note.duration);
+				}
+			}
+		}
+
+		self.update_channels();
+	}
+
+	/// Moves to the next beat.
+	/// Returns `false` if the song ended.
+	fn play_beat(&mut self) -> bool {
+		self.beat += 1;
+		for (index, frequency) in self.beat_start_frequencies.iter_mut().enumerate() {
+			*frequency = self
+				.playing_notes
+				.get(index)
+				.map_or(0, |note| note.frequency);
+		}
+
+		// A new tempo ratio waits for a bar line of the master tempo.
+		let (multiplier, divider) = self.tempo_ratio;
+		if let Some(ratio) = self.pending_tempo_ratio
+			&& (self.ratio_beats as u64 * divider as u64)
+				.is_multiple_of(multiplier as u64 * self.beats_per_bar as u64)
+		{
+			self.pending_tempo_ratio = None;
+			self.apply_tempo_ratio(ratio);
+		}
+		self.ratio_beats = self.ratio_beats.wrapping_add(1);
 
 		// Let's check if we're at the end of the song.
 		// If so, go to the start of the song if `looping` is `true` (pause otherwise).
-		if self.timer != 0 && (self.timer % (self.ticks_per_beat * self.song.end) == 0) {
+		// A song that doesn't loop plays on past its end while a jump hasn't landed yet.
+		if self.beat >= self.song.end as i32 && (self.looping || self.pending_jump.is_none()) {
 			if !self.looping {
+				if self.ring_out() {
+					return true;
+				}
+				self.end_fade = None;
+				self.fade_percent = 100;
 				self.pause();
+				self.send_event(Event::SongEnded);
 				return false;
 			}
 			self.reset_internally();
+			self.beat = 0;
+			self.loop_pass = self.loop_pass.wrapping_add(1);
+			self.tick_events = self.tick_events.union(TickEvents::LOOPED);
+
+			// The beats overlapping the end already started, so continue after them.
+			// Their tempo changes still apply, so the next pass plays at the right speed.
+			let overlap = self.effective_loop_overlap();
+			for beat in 0..overlap {
+				self.beat = beat as i32;
+				self.apply_events();
+			}
+			self.beat = overlap as i32;
+			self.holding = false;
+			self.beat_stretch_percent = 100;
+			self.advance_speed_trainer();
 		}
 
-		// Once we're hit enough ticks, increment the beat.
-		if self.beat_timer == self.ticks_per_beat {
-			self.play_beat();
-			self.beat_timer = 0;
+		// Follow a jump started by `jump_to_marker`.
+		if self.advance_jump() {
+			self.send_event(Event::BeatStarted {
+				beat: self.beat as u16,
+			});
+			self.update_channels();
+			return true;
 		}
 
-		// If we're playing multiple notes at the same time, cycle them through the buzzer.
-		// Every tick the note should be updated unless we're playing one note.
-		if self.playing_notes.len() > PWM_COUNT {
-			if self.current_combined_note_index > (self.playing_notes.len() - PWM_COUNT) {
-				self.current_combined_note_index = 0;
+		// Follow any branches on this beat.
+		let mut jumps = 0;
+		while jumps < MAX_BRANCH_JUMPS
+			&& let Some(target) = self.branch_target()
+		{
+			self.beat = target as i32;
+			jumps += 1;
+		}
+
+		self.apply_events();
+		self.pans.advance_beat();
+		self.send_event(Event::BeatStarted {
+			beat: self.beat as u16,
+		});
+
+		// Remove expired notes from playing list
+		expire_notes(&mut self.playing_notes, |index, note| {
+			self.note_lengths.remove(index);
+			send_event(&self.events, self.note_callback, Event::note_off(note));
+		});
+		self.first_new_note = self.playing_notes.len();
+		self.beat_ticks = 0;
+
+		// Add new notes and their durations to the playing list
+		self.start_song_notes(self.beat as usize);
+
+		// Start the next pass's first beats on top of the end of a looping song.
+		let overlap = self.effective_loop_overlap();
+		let overlap_start = (self.song.end - overlap) as i32;
+		if overlap > 0 && self.beat >= overlap_start {
+			self.start_song_notes((self.beat - overlap_start) as usize);
+		}
+
+		// Add notes from the live source
+		if let Some(live_source) = self.live_source {
+			live_source.on_beat(&mut |note| {
+				if let Some(note) = self.adjust_note(&note) {
+					self.start_note(note);
+				}
+			});
+		}
+
+		// Only need to run these checks on beats
+		self.update_channels();
+
+		true
+	}
+
+	/// Plays a beat past the end of a song that isn't looping, following the [`EndBehavior`].
+	/// Returns `false` once the song should stop.
+	fn ring_out(&mut self) -> bool {
+		match self.end_behavior {
+			EndBehavior::CutAtEnd | EndBehavior::FadeOut(0) => return false,
+			EndBehavior::HoldLastNotes => {}
+			EndBehavior::FadeOut(ticks) => {
+				if self.end_fade.is_none() {
+					self.end_fade = Some(EndFade {
+						ticks_left: ticks,
+						ticks,
+					});
+				}
 			}
+		}
 
-			self.set_frequency_and_duty(
-				PWM_COUNT - 1,
-				self.playing_notes[self.current_combined_note_index + PWM_COUNT - 1].frequency,
-				self.duty,
-			);
+		expire_notes(&mut self.playing_notes, |index, note| {
+			self.note_lengths.remove(index);
+			send_event(&self.events, self.note_callback, Event::note_off(note));
+		});
+		self.first_new_note = self.playing_notes.len();
+		if self.playing_notes.is_empty() {
+			return false;
+		}
+		self.update_channels();
+		true
+	}
+
+	/// Advances the fade out started by [`EndBehavior::FadeOut`].
+	/// Returns `false` once it has finished and the song has stopped.
+	fn tick_end_fade(&mut self) -> bool {
+		let Some(fade) = &mut self.end_fade else {
+			return true;
+		};
 
-			self.current_combined_note_index += 1;
+		fade.ticks_left = fade.ticks_left.saturating_sub(1);
+		if fade.ticks_left == 0 {
+			self.end_fade = None;
+			self.fade_percent = 100;
+			self.pause();
+			self.send_event(Event::SongEnded);
+			return false;
 		}
+		self.fade_percent = (fade.ticks_left as u32 * 100 / fade.ticks as u32) as u8;
+		self.update_channels();
+		true
+	}
+
+	/// Advances the fade started by [`fade_in`] or [`fade_out`].
+	/// Returns `false` once a fade out has finished and the song has paused.
+	fn tick_volume_fade(&mut self) -> bool {
+		let Some(fade) = &mut self.volume_fade else {
+			return true;
+		};
 
+		fade.ticks_left = fade.ticks_left.saturating_sub(1);
+		let from = fade.from_milli as i32;
+		let to = fade.to_milli as i32;
+		self.volume_fade_milli =
+			(to + (from - to) * fade.ticks_left as i32 / fade.ticks as i32) as u16;
+		if fade.ticks_left == 0 {
+			self.volume_fade = None;
+			if to == 0 {
+				self.pause();
+				return false;
+			}
+		}
+		self.update_channels();
 		true
 	}
 
-	fn play_beat(&mut self) {
-		self.beat += 1;
+	/// Starts the song's notes on `beat`, if there are any, or the notes of the take playing instead.
+	fn start_song_notes(&mut self, beat: usize) {
+		let notes = match self.active_take {
+			Some(take)
+				if (take.start as usize..(take.start + take.beats) as usize).contains(&beat) =>
+			{
+				take.notes.get(beat - take.start as usize)
+			}
+			_ => self.song.notes.get(beat),
+		};
+		if let Some(Some(notes)) = notes {
+			for note in *notes {
+				if note.chance < 100 && self.variation_random.next_below(100) >= note.chance as u32
+				{
+					continue;
+				}
+				if let Some(note) = self.adjust_note(note) {
+					self.start_note(note);
+				}
+			}
+		}
+	}
 
-		// Remove expired notes from playing list
-		{
-			let mut i = 0;
-			while i < self.playing_notes.len() {
-				self.playing_notes[i].duration -= 1;
-				if self.playing_notes[i].duration <= 0 {
-					self.playing_notes.remove(i);
-				} else {
-					i += 1;
+	/// Applies the [`UnplayableNotePolicy`] to a note about to be played.
+	/// Returns `None` if the note should be skipped.
+	fn adjust_note(&mut self, note: &NoteAndDuration) -> Option<NoteAndDuration> {
+		let range = self.playable_range();
+		let frequency = self.unplayable_note_policy.apply(note.frequency, range);
+		if frequency != Some(note.frequency) {
+			self.stats.out_of_range_notes = self.stats.out_of_range_notes.saturating_add(1);
+		}
+		if frequency.is_none() {
+			self.stats.dropped_notes = self.stats.dropped_notes.saturating_add(1);
+		}
+
+		frequency.map(|frequency| NoteAndDuration { frequency, ..*note })
+	}
+
+	/// Sets every PWM to the note it should be playing.
+	/// The song's notes fill the PWMs from the first one, and the stinger's from the last one.
+	fn update_channels(&mut self) {
+		let song_channels = self.song_channel_count();
+		let (song_duty, stinger_duty) = self.layer_duties();
+
+		let voices = self.voice_count();
+
+		let mut i = 0;
+		while i < voices {
+			let note = if i < song_channels {
+				self.playing_notes.get(i).copied()
+			} else {
+				self.stinger
+					.as_ref()
+					.and_then(|stinger| stinger.playing_notes.get(voices - 1 - i))
+					.copied()
+			};
+
+			let output = self.voice_output(i);
+			match note {
+				Some(_) if i < song_channels && self.is_resting(i) => self.set_channel_off(output),
+				Some(note) if i < song_channels => {
+					self.set_frequency_and_duty(output, note, song_duty)
 				}
+				Some(note) => self.set_frequency_and_duty(output, note, stinger_duty),
+				None => self.set_channel_off(output),
 			}
+
+			i += 1;
 		}
+	}
 
-		// Add new notes and their durations to the playing list
-		if self.beat < self.song.notes.len() as i32 {
-			if let Some(notes) = &self.song.notes[self.beat as usize] {
-				for note in *notes {
-					self.playing_notes.push(*note);
+	/// Applies the song events on the current beat and updates the tempo.
+	fn apply_events(&mut self) {
+		let beat = self.beat as u16;
+		self.beat_stretch_percent = 100;
+
+		let song = self.song;
+		for event in song.events {
+			if event.beat != beat {
+				continue;
+			}
+
+			match event.kind {
+				SongEventKind::Fermata { percent } => {
+					self.beat_stretch_percent = percent;
+				}
+				SongEventKind::Hold => {
+					self.holding = true;
 				}
+				SongEventKind::Alternatives {
+					beats,
+					takes,
+					order,
+				} => {
+					let count = takes.len() as u32 + 1;
+					let take = match order {
+						TakeOrder::Cycle => self.loop_pass as u32 % count,
+						TakeOrder::Random => self.variation_random.next_below(count),
+					};
+					self.active_take = take.checked_sub(1).map(|take| ActiveTake {
+						start: beat,
+						beats,
+						notes: takes[take as usize],
+					});
+				}
+				SongEventKind::SetTempo { .. }
+				| SongEventKind::TempoRamp { .. }
+				| SongEventKind::Branch { .. }
+				| SongEventKind::Marker { .. } => {}
 			}
 		}
 
-		// Only need to run these checks on beats
-		{
-			let mut i = 0;
-			while i < PWM_COUNT {
-				use embassy_rp::pwm::SetDutyCycle;
+		self.apply_tempo_events();
+	}
 
-				if i >= self.playing_notes.len() {
-					self.pwms[i].set_duty_cycle_fully_off().unwrap();
-				} else {
-					self.set_frequency_and_duty(i, self.playing_notes[i].frequency, self.duty);
+	/// Applies the tempo events of the current beat, and moves any tempo ramp along.
+	fn apply_tempo_events(&mut self) {
+		let beat = self.beat as u16;
+		let song = self.song;
+		for event in song.events {
+			if event.beat != beat {
+				continue;
+			}
+
+			match event.kind {
+				SongEventKind::SetTempo { ticks_per_beat } => {
+					self.tempo_ramp = None;
+					self.beat_length = self.scaled_beat_length(ticks_per_beat);
+				}
+				SongEventKind::TempoRamp { from, to, beats } => {
+					self.tempo_ramp = Some(TempoRamp {
+						start_beat: beat,
+						from,
+						to,
+						beats,
+					});
 				}
+				_ => {}
+			}
+		}
+
+		// The speed trainer's tempo replaces the song's own at the start of every pass.
+		if beat == 0
+			&& let Some(trainer) = self.speed_trainer
+		{
+			self.tempo_ramp = None;
+			self.beat_length = self.scaled_beat_length(bpm_to_ticks_per_beat(
+				self.trainer_bpm,
+				trainer.tick_period_ms,
+			));
+		}
+
+		if let Some(ramp) = self.tempo_ramp {
+			let progress = beat.saturating_sub(ramp.start_beat);
+			if progress >= ramp.beats {
+				self.beat_length = self.scaled_beat_length(ramp.to);
+				self.tempo_ramp = None;
+			} else {
+				let from = self.scaled_beat_length(ramp.from) as i64;
+				let to = self.scaled_beat_length(ramp.to) as i64;
+				self.beat_length =
+					(from + (to - from) * progress as i64 / ramp.beats as i64) as u32;
+			}
+		}
+	}
+
+	/// Sets a PWM at index `pwm_index` to play `note` with `duty`.
+	fn set_frequency_and_duty(&mut self, pwm_index: usize, note: NoteAndDuration, duty: u16) {
+		if self.skipping_output
+			|| !self.enabled_outputs[pwm_index]
+			|| self.duty_limiters[pwm_index].is_limited()
+		{
+			return;
+		}
+		if self.muted {
+			self.set_channel_off(pwm_index);
+			return;
+		}
 
-				i += 1;
+		let mut frequency = note.frequency;
+		if self.transpose != 0 || self.pitch_scale_milli != 1000 {
+			let transposed = sonify::transpose(frequency, self.transpose);
+			let scaled = saturate_u16(transposed as u32 * self.pitch_scale_milli as u32 / 1000);
+			match self
+				.unplayable_note_policy
+				.apply(scaled, self.playable_range())
+			{
+				Some(scaled) => frequency = scaled,
+				None => {
+					self.set_channel_off(pwm_index);
+					return;
+				}
+			}
+		}
+		if self.frequency_snap_cents != 0
+			&& let ChannelOutput::Tone {
+				frequency: current, ..
+			} = self.channel_outputs[pwm_index]
+			&& within_cents(frequency, current, self.frequency_snap_cents)
+		{
+			frequency = current;
+		}
+		frequency = self.limit_slew(pwm_index, frequency);
+		let pan = self.pans.pan(note.track);
+		let gain = self.channel_gains[pwm_index] as u64
+			* self.resonance_curve.gain_percent(frequency) as u64
+			* (self.fade_percent as u64
+				* self.duck_gain_milli as u64
+				* self.volume_fade_milli as u64
+				* self.volume_percent as u64
+				/ 100_000_000)
+			* pan_gain_percent(self.channel_positions[pwm_index], pan) as u64;
+		let mut duty = saturate_u16((duty as u64 * gain / 100_000_000).min(u32::MAX as u64) as u32);
+		if duty == 0 {
+			self.set_channel_off(pwm_index);
+			return;
+		}
+		if let Some(policy) = self.policy {
+			duty = policy.apply(self.category, duty);
+			if duty == 0 {
+				self.set_channel_off(pwm_index);
+				return;
 			}
 		}
+
+		// Reconfiguring a PWM can click, so leave it alone if it's already playing this.
+		let output = ChannelOutput::Tone {
+			frequency,
+			duty,
+			fraction: self.duty_is_fraction,
+		};
+		if self.channel_outputs[pwm_index] == output {
+			return;
+		}
+
+		// Notes are checked against `playable_range` when added, so this should only fail
+		// if the PWM is given a frequency some other way. Keep it silent in that case.
+		let result = if self.duty_is_fraction {
+			self.pwms[pwm_index].set_tone_fraction(frequency, DutyFraction(duty))
+		} else {
+			self.pwms[pwm_index].set_tone(frequency, duty)
+		};
+		if result.is_err() {
+			self.stats.pwm_errors = self.stats.pwm_errors.saturating_add(1);
+			self.set_channel_off(pwm_index);
+		} else {
+			self.channel_outputs[pwm_index] = output;
+		}
 	}
 
-	/// Updates the `frequency` and `duty` of a PWM at index `pwm_index`.
-	fn set_frequency_and_duty(&mut self, pwm_index: usize, frequency: u16, duty: u16) {
-		use embassy_rp::pwm::SetDutyCycle;
+	/// Turns off the PWM at index `pwm_index`, unless it's already off or disabled.
+	fn set_channel_off(&mut self, pwm_index: usize) {
+		if self.enabled_outputs[pwm_index] && self.channel_outputs[pwm_index] != ChannelOutput::Off
+		{
+			self.pwms[pwm_index].set_off();
+			self.channel_outputs[pwm_index] = ChannelOutput::Off;
+		}
+	}
+
+	/// Returns `true` if the PWM at index `pwm_index` is playing a tone.
+	fn is_channel_on(&self, pwm_index: usize) -> bool {
+		matches!(self.channel_outputs[pwm_index], ChannelOutput::Tone { .. })
+	}
 
-		let pwm = &mut self.pwms[pwm_index];
-		pwm.set_duty_cycle_fully_off().unwrap(); // `set_config` doesn't work unless this off??
+	/// Updates each PWM's [`DutyLimit`] budget, silencing PWMs that ran out.
+	fn update_duty_limits(&mut self) {
+		let Some(limit) = self.duty_limit else {
+			return;
+		};
 
-		let mut pwm_config = embassy_rp::pwm::Config::default();
-		pwm_config.top = get_top(frequency as f64, PWM_DIV_INT);
-		pwm_config.divider = PWM_DIV_INT.into();
-		pwm.set_config(&pwm_config);
+		for i in 0..PWM_COUNT {
+			let Some(limited) = self.duty_limiters[i].update(&limit, self.is_channel_on(i)) else {
+				continue;
+			};
 
-		pwm.set_duty_cycle(duty).unwrap();
+			if limited {
+				self.set_channel_off(i);
+			}
+			if let Some(callback) = self.duty_limit_callback {
+				callback(i, limited);
+			}
+		}
 	}
 }
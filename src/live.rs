@@ -0,0 +1,228 @@
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::RawMutex;
+
+use crate::NoteAndDuration;
+
+/// The most rules a [`LivePattern`] can hold.
+pub const MAX_LIVE_RULES: usize = 16;
+
+/// When a [`LivePattern`] rule runs, counted in the pattern's beats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Schedule {
+	/// Runs once on `beat`, then is removed.
+	At(u32),
+	/// Runs on every beat that is `offset` beats after a multiple of `beats`.
+	Every { beats: u16, offset: u16 },
+}
+
+impl Schedule {
+	/// Returns `true` if this schedule runs on `beat`.
+	const fn runs_on(&self, beat: u32) -> bool {
+		match *self {
+			Schedule::At(at) => at == beat,
+			Schedule::Every { beats, offset } => {
+				beats != 0
+					&& beat >= offset as u32
+					&& (beat - offset as u32).is_multiple_of(beats as u32)
+			}
+		}
+	}
+}
+
+/// What a [`LivePattern`] rule does when it runs.
+#[derive(Clone, Copy, Debug)]
+pub enum LiveAction {
+	/// Plays a note.
+	Play(NoteAndDuration),
+	/// Calls a function with the pattern's beat.
+	Call(fn(u32)),
+}
+
+/// A scheduled [`LiveAction`].
+#[derive(Clone, Copy, Debug)]
+pub struct LiveRule {
+	pub schedule: Schedule,
+	pub action: LiveAction,
+}
+
+/// Something that adds notes to a [`crate::Player`] while it plays. See [`crate::Player::set_live_source`].
+pub trait LiveSource {
+	/// Called when the player starts a beat. Calls `play` for every note to start on it.
+	fn on_beat(&self, play: &mut dyn FnMut(NoteAndDuration));
+}
+
+/// A set of rules that can be changed while a [`crate::Player`] is playing, for live coding
+/// patterns on the device (for example, from commands sent over a serial connection).
+///
+/// The pattern counts its own beats from when it is first played, independent of the song.
+/// Rules are added with small composable calls:
+///
+/// ```rust,ignore
+/// static PATTERN: buzzer_music::LivePattern<CriticalSectionRawMutex> = buzzer_music::LivePattern::new();
+///
+/// player.set_live_source(Some(&PATTERN));
+///
+/// // From the serial task:
+/// let kick = buzzer_music::NoteAndDuration::new(65, 1, 0);
+/// PATTERN.every(4).play(kick);
+/// PATTERN.every(4).offset(2).play(hat);
+/// PATTERN.at(PATTERN.beat() + 8).call(|beat| defmt::info!("drop at {}", beat));
+/// ```
+pub struct LivePattern<M: RawMutex> {
+	state: Mutex<M, RefCell<LiveState>>,
+}
+
+struct LiveState {
+	beat: u32,
+	rules: arrayvec::ArrayVec<LiveRule, MAX_LIVE_RULES>,
+}
+
+impl<M: RawMutex> LivePattern<M> {
+	/// The constructor.
+	pub const fn new() -> Self {
+		Self {
+			state: Mutex::new(RefCell::new(LiveState {
+				beat: 0,
+				rules: arrayvec::ArrayVec::new_const(),
+			})),
+		}
+	}
+
+	/// Starts a rule that runs once on the pattern's `beat`.
+	pub fn at(&self, beat: u32) -> RuleBuilder<'_, M> {
+		RuleBuilder {
+			pattern: self,
+			schedule: Schedule::At(beat),
+		}
+	}
+
+	/// Starts a rule that runs once, `beats` beats after the next one.
+	pub fn after(&self, beats: u32) -> RuleBuilder<'_, M> {
+		self.at(self.beat().saturating_add(beats))
+	}
+
+	/// Starts a rule that runs every `beats` beats. A rule every `0` beats never runs.
+	pub fn every(&self, beats: u16) -> RuleBuilder<'_, M> {
+		RuleBuilder {
+			pattern: self,
+			schedule: Schedule::Every { beats, offset: 0 },
+		}
+	}
+
+	/// Adds `rule`. Returns `false` if the pattern already has [`MAX_LIVE_RULES`] rules.
+	pub fn add(&self, rule: LiveRule) -> bool {
+		self.state
+			.lock(|state| state.borrow_mut().rules.try_push(rule).is_ok())
+	}
+
+	/// Returns the rule at `index`, in the order they were added.
+	pub fn rule(&self, index: usize) -> Option<LiveRule> {
+		self.state
+			.lock(|state| state.borrow().rules.get(index).copied())
+	}
+
+	/// Removes and returns the rule at `index`. Later rules move down to fill the gap.
+	pub fn remove(&self, index: usize) -> Option<LiveRule> {
+		self.state.lock(|state| {
+			let mut state = state.borrow_mut();
+			(index < state.rules.len()).then(|| state.rules.remove(index))
+		})
+	}
+
+	/// Removes every rule.
+	pub fn clear(&self) {
+		self.state.lock(|state| state.borrow_mut().rules.clear());
+	}
+
+	/// Returns how many rules the pattern has.
+	pub fn len(&self) -> usize {
+		self.state.lock(|state| state.borrow().rules.len())
+	}
+
+	/// Returns `true` if the pattern has no rules.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Returns the beat the pattern plays next.
+	pub fn beat(&self) -> u32 {
+		self.state.lock(|state| state.borrow().beat)
+	}
+
+	/// Sets the beat the pattern plays next, for example to line it up with the song.
+	pub fn set_beat(&self, beat: u32) {
+		self.state.lock(|state| state.borrow_mut().beat = beat);
+	}
+}
+
+impl<M: RawMutex> Default for LivePattern<M> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<M: RawMutex> LiveSource for LivePattern<M> {
+	fn on_beat(&self, play: &mut dyn FnMut(NoteAndDuration)) {
+		// Take the actions out first, so callbacks can change the pattern.
+		let (beat, actions) = self.state.lock(|state| {
+			let mut state = state.borrow_mut();
+			let beat = state.beat;
+			state.beat = beat.wrapping_add(1);
+
+			let actions = state
+				.rules
+				.iter()
+				.filter(|rule| rule.schedule.runs_on(beat))
+				.map(|rule| rule.action)
+				.collect::<arrayvec::ArrayVec<LiveAction, MAX_LIVE_RULES>>();
+			state
+				.rules
+				.retain(|rule| !matches!(rule.schedule, Schedule::At(at) if at <= beat));
+			(beat, actions)
+		});
+
+		for action in actions {
+			match action {
+				LiveAction::Play(note) => play(note),
+				LiveAction::Call(callback) => callback(beat),
+			}
+		}
+	}
+}
+
+/// Builds a [`LiveRule`] for a [`LivePattern`]. Created by [`LivePattern::at`] and [`LivePattern::every`].
+pub struct RuleBuilder<'p, M: RawMutex> {
+	pattern: &'p LivePattern<M>,
+	schedule: Schedule,
+}
+
+impl<M: RawMutex> RuleBuilder<'_, M> {
+	/// Delays an [`LivePattern::every`] rule by `beats` beats within each period.
+	pub fn offset(mut self, beats: u16) -> Self {
+		if let Schedule::Every { offset, .. } = &mut self.schedule {
+			*offset = beats;
+		}
+		self
+	}
+
+	/// Plays `note` when the rule runs. Returns `false` if the pattern is full.
+	/// A note with a duration of `0` is never heard.
+	pub fn play(self, note: NoteAndDuration) -> bool {
+		self.then(LiveAction::Play(note))
+	}
+
+	/// Calls `callback` with the pattern's beat when the rule runs. Returns `false` if the pattern is full.
+	pub fn call(self, callback: fn(u32)) -> bool {
+		self.then(LiveAction::Call(callback))
+	}
+
+	/// Adds the rule with `action`. Returns `false` if the pattern is full.
+	pub fn then(self, action: LiveAction) -> bool {
+		self.pattern.add(LiveRule {
+			schedule: self.schedule,
+			action,
+		})
+	}
+}
@@ -0,0 +1,143 @@
+/// Fakes polyphony on a single output by quickly alternating between several voices.
+///
+/// A [`crate::Player`] uses this when more notes are playing than it has outputs: the extra notes
+/// take turns on its last output. Each turn is a "slot" lasting [`Mixer::rate`] ticks.
+///
+/// Voices are numbered in the order they share the output, and each gets as many slots in a
+/// row as its weight. A weight of `0` mutes the voice, unless every voice is muted.
+///
+/// A melody weight can also be set, which is given to the melody voice instead of its usual weight.
+/// For example, with two voices and a melody weight of `3`, the melody sounds for 3 of every 4 slots.
+#[derive(Clone)]
+pub struct Mixer<const MAX_VOICES: usize> {
+	rate: u16,
+	timer: u16,
+	weights: arrayvec::ArrayVec<u8, MAX_VOICES>,
+	melody_weight: Option<u8>,
+	current: usize,
+	next: usize,
+	slots_left: u8,
+}
+
+impl<const MAX_VOICES: usize> Mixer<MAX_VOICES> {
+	/// The constructor.
+	///
+	/// Voices alternate every tick and have a weight of `1`.
+	pub const fn new() -> Self {
+		Self {
+			rate: 1,
+			timer: 0,
+			weights: arrayvec::ArrayVec::new_const(),
+			melody_weight: None,
+			current: 0,
+			next: 0,
+			slots_left: 0,
+		}
+	}
+
+	/// Returns how many ticks each slot lasts.
+	pub fn rate(&self) -> u16 {
+		self.rate
+	}
+
+	/// Sets how many ticks each slot lasts. A rate of `0` is treated as `1`.
+	pub fn set_rate(&mut self, ticks: u16) {
+		self.rate = ticks.max(1);
+		self.timer = 0;
+	}
+
+	/// Returns the weight of `voice`.
+	pub fn weight(&self, voice: usize) -> u8 {
+		self.weights.get(voice).copied().unwrap_or(1)
+	}
+
+	/// Sets the weight of each voice, starting from voice `0`.
+	/// Voices without a weight in `weights` have a weight of `1`.
+	pub fn set_weights(&mut self, weights: &[u8]) {
+		self.weights.clear();
+		for weight in weights.iter().take(MAX_VOICES) {
+			self.weights.push(*weight);
+		}
+		self.reset();
+	}
+
+	/// Returns the weight given to the melody voice, if any.
+	pub fn melody_weight(&self) -> Option<u8> {
+		self.melody_weight
+	}
+
+	/// Sets the weight given to the melody voice instead of its usual weight.
+	/// A [`crate::Player`] treats the highest note sharing the output as the melody.
+	/// Pass `None` to weight the melody like any other voice.
+	pub fn set_melody_weight(&mut self, weight: Option<u8>) {
+		self.melody_weight = weight;
+		self.reset();
+	}
+
+	/// Starts the rotation again from voice `0`.
+	pub fn reset(&mut self) {
+		self.current = 0;
+		self.next = 0;
+		self.slots_left = 0;
+	}
+
+	/// Advances the mixer by one tick.
+	/// Returns `true` if a new slot starts, meaning [`Mixer::next_voice`] should be called.
+	pub fn tick(&mut self) -> bool {
+		self.timer += 1;
+		if self.timer >= self.rate {
+			self.timer = 0;
+			true
+		} else {
+			false
+		}
+	}
+
+	/// Returns which of `voice_count` voices should sound for the next slot.
+	pub fn next_voice(&mut self, voice_count: usize) -> usize {
+		self.next_voice_with_melody(voice_count, None)
+	}
+
+	/// Returns which of `voice_count` voices should sound for the next slot,
+	/// giving `melody` the melody weight if one is set.
+	pub fn next_voice_with_melody(&mut self, voice_count: usize, melody: Option<usize>) -> usize {
+		if voice_count == 0 {
+			return 0;
+		}
+
+		if self.slots_left == 0 || self.current >= voice_count {
+			self.slots_left = 0;
+
+			// Move on to the next voice with a weight, wrapping around.
+			for _ in 0..voice_count {
+				let voice = self.next % voice_count;
+				self.next = voice + 1;
+
+				let weight = match self.melody_weight {
+					Some(melody_weight) if melody == Some(voice) => melody_weight,
+					_ => self.weight(voice),
+				};
+				if weight > 0 {
+					self.current = voice;
+					self.slots_left = weight;
+					break;
+				}
+			}
+
+			// Every voice is muted, so just play the first one.
+			if self.slots_left == 0 {
+				self.current = 0;
+				self.slots_left = 1;
+			}
+		}
+
+		self.slots_left -= 1;
+		self.current
+	}
+}
+
+impl<const MAX_VOICES: usize> Default for Mixer<MAX_VOICES> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
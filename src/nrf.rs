@@ -0,0 +1,97 @@
+use core::convert::Infallible;
+
+use embassy_nrf::pwm::{DutyCycle, Prescaler, SimplePwm};
+use embedded_hal::pwm::{ErrorType, SetDutyCycle};
+
+use crate::hal::{set_pwm_tone, set_pwm_tone_fraction};
+use crate::{DutyFraction, FrequencyRange, PwmClock, SetFrequency, ToneError, ToneOutput};
+
+/// How nRF52 PWMs are clocked: the 16 MHz clock through a prescaler of 16,
+/// counting up to a 15-bit `COUNTERTOP` of at least `3`.
+const NRF_PWM_CLOCK: PwmClock = PwmClock {
+	clock_hz: 16_000_000,
+	divider: 16,
+	min_period: 3,
+	max_period: 32767,
+};
+
+/// A buzzer on one channel of an `embassy-nrf` `SimplePwm`, which a [`crate::Player`] can play on directly.
+///
+/// The counter top, and so the frequency, is shared by all 4 channels of a PWM, so each buzzer needs
+/// a PWM of its own. The counter top is also the maximum duty.
+///
+/// ```rust,ignore
+/// use embassy_nrf::pwm::{SimpleConfig, SimplePwm};
+///
+/// let pwm = SimplePwm::new_1ch(p.PWM0, p.P0_13, &SimpleConfig::default());
+/// let buzzer = buzzer_music::NrfBuzzer::new(pwm, 0);
+/// let mut player = buzzer_music::Player::new(&SONG, true, 3, 100, [buzzer]);
+/// ```
+pub struct NrfBuzzer<'d> {
+	pwm: SimplePwm<'d>,
+	channel: usize,
+}
+
+impl<'d> NrfBuzzer<'d> {
+	/// Plays on `channel` of `pwm`, from `0` to `3`. It starts silent.
+	pub fn new(mut pwm: SimplePwm<'d>, channel: usize) -> Self {
+		pwm.set_prescaler(Prescaler::Div16);
+		pwm.set_duty(channel, DutyCycle::inverted(0));
+		Self { pwm, channel }
+	}
+
+	/// Consumes this and returns the PWM.
+	pub fn into_inner(self) -> SimplePwm<'d> {
+		self.pwm
+	}
+}
+
+impl ErrorType for NrfBuzzer<'_> {
+	type Error = Infallible;
+}
+
+impl SetDutyCycle for NrfBuzzer<'_> {
+	fn max_duty_cycle(&self) -> u16 {
+		self.pwm.max_duty()
+	}
+
+	fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+		// With inverted polarity, the output is high while the counter is below the duty.
+		let duty = DutyCycle::inverted(duty.min(self.max_duty_cycle()));
+		self.pwm.set_duty(self.channel, duty);
+		Ok(())
+	}
+}
+
+impl SetFrequency for NrfBuzzer<'_> {
+	fn frequency_range(&self) -> FrequencyRange {
+		NRF_PWM_CLOCK.playable_range()
+	}
+
+	fn set_frequency(&mut self, frequency: u16) -> Result<(), ToneError> {
+		self.pwm.set_max_duty(NRF_PWM_CLOCK.period(frequency)?);
+		Ok(())
+	}
+}
+
+impl ToneOutput for NrfBuzzer<'_> {
+	fn playable_range(&self) -> FrequencyRange {
+		self.frequency_range()
+	}
+
+	fn set_tone(&mut self, frequency: u16, duty: u16) -> Result<(), ToneError> {
+		set_pwm_tone(self, frequency, duty)
+	}
+
+	fn max_duty(&self) -> u16 {
+		self.max_duty_cycle()
+	}
+
+	fn set_tone_fraction(&mut self, frequency: u16, duty: DutyFraction) -> Result<(), ToneError> {
+		set_pwm_tone_fraction(self, frequency, duty)
+	}
+
+	fn set_off(&mut self) {
+		self.pwm.set_duty(self.channel, DutyCycle::inverted(0));
+	}
+}
@@ -0,0 +1,93 @@
+/// The most tracks a [`crate::Player`] can pan.
+pub const MAX_PANNED_TRACKS: usize = 8;
+
+/// Returns the gain, in percent, of a PWM at `position` playing a track panned to `pan`.
+///
+/// Both range from `-100` (left) to `100` (right). A PWM plays at full volume until the pan
+/// moves past the center away from it, then fades out linearly, reaching silence at the far side.
+/// PWMs in the center always play at full volume.
+pub const fn pan_gain_percent(position: i8, pan: i8) -> u16 {
+	let distance = (position as i16 - pan as i16).unsigned_abs();
+	if distance >= 200 {
+		0
+	} else if distance <= 100 {
+		100
+	} else {
+		200 - distance
+	}
+}
+
+/// The pan of a track, and the sweep moving it, if any.
+#[derive(Clone, Copy)]
+struct TrackPan {
+	track: u8,
+	pan: i8,
+	target: i8,
+	beats_left: u16,
+}
+
+/// The pans of a [`crate::Player`]'s tracks.
+#[derive(Clone, Default)]
+pub(crate) struct PanTable {
+	tracks: arrayvec::ArrayVec<TrackPan, MAX_PANNED_TRACKS>,
+}
+
+impl PanTable {
+	pub(crate) const fn new() -> Self {
+		Self {
+			tracks: arrayvec::ArrayVec::new_const(),
+		}
+	}
+
+	/// Returns the pan of `track`. Tracks that were never panned are in the center.
+	pub(crate) fn pan(&self, track: u8) -> i8 {
+		self.tracks
+			.iter()
+			.find(|entry| entry.track == track)
+			.map_or(0, |entry| entry.pan)
+	}
+
+	/// Moves `track` from its current pan to `target` over `beats` beats, or immediately if `0`.
+	/// Returns `false` if [`MAX_PANNED_TRACKS`] other tracks are already panned.
+	pub(crate) fn sweep(&mut self, track: u8, target: i8, beats: u16) -> bool {
+		let target = target.clamp(-100, 100);
+		let index = match self.tracks.iter().position(|entry| entry.track == track) {
+			Some(index) => index,
+			None => {
+				let entry = TrackPan {
+					track,
+					pan: 0,
+					target: 0,
+					beats_left: 0,
+				};
+				if self.tracks.try_push(entry).is_err() {
+					return false;
+				}
+				self.tracks.len() - 1
+			}
+		};
+
+		let entry = &mut self.tracks[index];
+		entry.target = target;
+		entry.beats_left = beats;
+		if beats == 0 {
+			entry.pan = target;
+		}
+		true
+	}
+
+	/// Moves every sweep forward by a beat.
+	pub(crate) fn advance_beat(&mut self) {
+		for entry in &mut self.tracks {
+			if entry.beats_left == 0 {
+				continue;
+			}
+			let step = (entry.target as i16 - entry.pan as i16) / entry.beats_left as i16;
+			entry.pan = (entry.pan as i16 + step) as i8;
+			entry.beats_left -= 1;
+			if entry.beats_left == 0 {
+				entry.pan = entry.target;
+			}
+		}
+	}
+}
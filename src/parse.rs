@@ -0,0 +1,417 @@
+use arrayvec::ArrayVec;
+
+use crate::{NoteAndDuration, Song, SongMetadata};
+
+/// The frequencies of C4 to B4 in thousandths of a hertz, used to find the frequency of a note name.
+/// Scaling these by octaves gives the same frequencies [`crate::declare_song`] uses.
+const OCTAVE_MILLIHZ: [u32; 12] = [
+	261626, 277183, 293665, 311127, 329628, 349228, 369994, 391995, 415305, 440000, 466164, 493883,
+];
+
+/// An error produced when a song cannot be parsed by [`Song::parse`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SongParseError {
+	/// The entry at `index` (counting from `0`) doesn't have a valid time, note, or duration.
+	InvalidEntry { index: usize },
+	/// The entry at `index` uses a note name that isn't between `C0` and `B9`.
+	UnknownNote { index: usize },
+	/// The song has more notes than the notes buffer can hold.
+	TooManyNotes,
+	/// The song lasts more beats than the beats buffer can hold.
+	TooManyBeats,
+	/// The name or settings of an RTTTL ringtone aren't valid.
+	InvalidHeader,
+	/// The JSON of a beat grid isn't valid, or isn't shaped like a beat grid.
+	InvalidGrid,
+}
+
+impl SongParseError {
+	/// Returns the stable numeric code of this error, from `0x40`. See [`crate::ToneError::as_code`].
+	///
+	/// The entry `index` isn't part of the code, so [`Self::from_code`] gives an `index` of `0`.
+	pub const fn as_code(self) -> u8 {
+		match self {
+			Self::InvalidEntry { .. } => 0x40,
+			Self::UnknownNote { .. } => 0x41,
+			Self::TooManyNotes => 0x42,
+			Self::TooManyBeats => 0x43,
+			Self::InvalidHeader => 0x44,
+			Self::InvalidGrid => 0x45,
+		}
+	}
+
+	/// Returns the error with the code `code`, or `None` if it isn't one of these errors.
+	pub const fn from_code(code: u8) -> Option<Self> {
+		match code {
+			0x40 => Some(Self::InvalidEntry { index: 0 }),
+			0x41 => Some(Self::UnknownNote { index: 0 }),
+			0x42 => Some(Self::TooManyNotes),
+			0x43 => Some(Self::TooManyBeats),
+			0x44 => Some(Self::InvalidHeader),
+			0x45 => Some(Self::InvalidGrid),
+			_ => None,
+		}
+	}
+}
+
+/// Implements [`Song::parse`].
+pub(crate) fn parse<'a: 'b, 'b, const MAX_NOTES: usize, const MAX_BEATS: usize>(
+	text: &str,
+	notes: &'a mut ArrayVec<NoteAndDuration, MAX_NOTES>,
+	beats: &'b mut ArrayVec<Option<&'a [NoteAndDuration]>, MAX_BEATS>,
+) -> Result<Song<'b>, SongParseError> {
+	notes.clear();
+	beats.clear();
+
+	// Parse every note, keeping the beat it starts on alongside it.
+	let mut times = ArrayVec::<u16, MAX_NOTES>::new();
+	let mut end: usize = 0;
+	for (index, entry) in text
+		.split(';')
+		.map(str::trim)
+		.filter(|entry| !entry.is_empty())
+		.enumerate()
+	{
+		let (time, note) = parse_entry(entry, index)?;
+		end = end.max(time as usize + note.duration as usize);
+		if end > MAX_BEATS || end > (u16::MAX - 7) as usize {
+			return Err(SongParseError::TooManyBeats);
+		}
+		notes
+			.try_push(note)
+			.map_err(|_| SongParseError::TooManyNotes)?;
+		times
+			.try_push(time)
+			.map_err(|_| SongParseError::TooManyNotes)?;
+	}
+
+	// Sort the notes by beat, keeping their order within a beat.
+	// Songs are usually sorted already, which makes this fast.
+	for i in 1..notes.len() {
+		let mut j = i;
+		while j > 0 && times[j - 1] > times[j] {
+			times.swap(j - 1, j);
+			notes.swap(j - 1, j);
+			j -= 1;
+		}
+	}
+
+	let song = index_beats(notes, &times, end, beats);
+	Ok(Song {
+		end: song.end.div_ceil(8) * 8,
+		..song
+	})
+}
+
+/// Fills `beats` with the notes starting on each beat, and returns the song they make.
+///
+/// `times` is the beat each note starts on, in order. `end` must fit in `beats` and a `u16`.
+pub(crate) fn index_beats<'a: 'b, 'b, const MAX_NOTES: usize, const MAX_BEATS: usize>(
+	notes: &'a ArrayVec<NoteAndDuration, MAX_NOTES>,
+	times: &[u16],
+	end: usize,
+	beats: &'b mut ArrayVec<Option<&'a [NoteAndDuration]>, MAX_BEATS>,
+) -> Song<'b> {
+	let mut start = 0;
+	for beat in 0..end {
+		let count = times[start..]
+			.iter()
+			.take_while(|time| **time as usize == beat)
+			.count();
+		let slice = (count > 0).then(|| &notes[start..start + count]);
+		// `end` was checked against `MAX_BEATS` by the caller, so this always fits.
+		let _ = beats.try_push(slice);
+		start += count;
+	}
+
+	Song {
+		notes: beats.as_slice(),
+		end: end as u16,
+		events: &[],
+		metadata: SongMetadata::NONE,
+	}
+}
+
+/// The settings in the header of an RTTTL ringtone.
+struct RtttlHeader {
+	duration: u32,
+	octave: u32,
+	bpm: u32,
+}
+
+/// Implements [`Song::parse_rtttl`].
+pub(crate) fn parse_rtttl<'a: 'b, 'b, const MAX_NOTES: usize, const MAX_BEATS: usize>(
+	text: &str,
+	tick_ms: u16,
+	notes: &'a mut ArrayVec<NoteAndDuration, MAX_NOTES>,
+	beats: &'b mut ArrayVec<Option<&'a [NoteAndDuration]>, MAX_BEATS>,
+) -> Result<(Song<'b>, u16), SongParseError> {
+	notes.clear();
+	beats.clear();
+
+	let mut sections = text.splitn(3, ':');
+	let (Some(_name), Some(header), Some(body)) =
+		(sections.next(), sections.next(), sections.next())
+	else {
+		return Err(SongParseError::InvalidHeader);
+	};
+	let header = parse_rtttl_header(header)?;
+	let entries = || {
+		body.split(',')
+			.map(str::trim)
+			.filter(|entry| !entry.is_empty())
+			.enumerate()
+	};
+
+	// Each beat lasts as long as the longest duration every note is a whole number of.
+	let mut step = 0;
+	for (index, entry) in entries() {
+		let (_, duration) = parse_rtttl_note(entry, &header, index)?;
+		step = gcd(step, duration);
+	}
+	let step = step.max(1);
+
+	let mut times = ArrayVec::<u16, MAX_NOTES>::new();
+	let mut end: usize = 0;
+	for (index, entry) in entries() {
+		let (frequency, duration) = parse_rtttl_note(entry, &header, index)?;
+		let duration = duration / step;
+		if let Some(frequency) = frequency {
+			let note = NoteAndDuration::new(frequency, duration as u16, 0);
+			notes
+				.try_push(note)
+				.map_err(|_| SongParseError::TooManyNotes)?;
+			times
+				.try_push(end as u16)
+				.map_err(|_| SongParseError::TooManyNotes)?;
+		}
+		end += duration as usize;
+		if end > MAX_BEATS || end > u16::MAX as usize {
+			return Err(SongParseError::TooManyBeats);
+		}
+	}
+
+	// A quarter note lasts `60000 / bpm` milliseconds, and is 16 64th notes.
+	let divisor = (header.bpm as u64 * 16 * tick_ms.max(1) as u64).max(1);
+	let ticks_per_beat = (60000 * step as u64 + divisor / 2) / divisor;
+	let ticks_per_beat = ticks_per_beat.clamp(1, u16::MAX as u64) as u16;
+
+	Ok((index_beats(notes, &times, end, beats), ticks_per_beat))
+}
+
+/// Parses the `d=4,o=5,b=120` settings of an RTTTL ringtone. Unknown settings are ignored.
+fn parse_rtttl_header(header: &str) -> Result<RtttlHeader, SongParseError> {
+	let mut settings = RtttlHeader {
+		duration: 4,
+		octave: 6,
+		bpm: 63,
+	};
+	for setting in header
+		.split(',')
+		.map(str::trim)
+		.filter(|setting| !setting.is_empty())
+	{
+		let (key, value) = setting
+			.split_once('=')
+			.ok_or(SongParseError::InvalidHeader)?;
+		let value = value
+			.trim()
+			.parse::<u32>()
+			.map_err(|_| SongParseError::InvalidHeader)?;
+		match key.trim() {
+			"d" | "D" => settings.duration = value,
+			"o" | "O" => settings.octave = value,
+			"b" | "B" => settings.bpm = value,
+			_ => {}
+		}
+	}
+	if settings.bpm == 0 || rtttl_64ths(settings.duration, false).is_none() || settings.octave > 9 {
+		return Err(SongParseError::InvalidHeader);
+	}
+	Ok(settings)
+}
+
+/// Parses an RTTTL note like `8c#.6`, or a pause like `4p`.
+///
+/// Returns the frequency of the note (`None` for a pause) and its duration in 64th notes.
+fn parse_rtttl_note(
+	entry: &str,
+	header: &RtttlHeader,
+	index: usize,
+) -> Result<(Option<u16>, u32), SongParseError> {
+	let invalid = SongParseError::InvalidEntry { index };
+	let digits = entry.bytes().take_while(u8::is_ascii_digit).count();
+	let duration = match digits {
+		0 => header.duration,
+		_ => entry[..digits].parse().map_err(|_| invalid)?,
+	};
+
+	let mut rest = entry[digits..].bytes().peekable();
+	let class = match rest.next().map(|c| c.to_ascii_lowercase()) {
+		Some(b'c') => Some(0),
+		Some(b'd') => Some(2),
+		Some(b'e') => Some(4),
+		Some(b'f') => Some(5),
+		Some(b'g') => Some(7),
+		Some(b'a') => Some(9),
+		Some(b'b' | b'h') => Some(11),
+		Some(b'p') => None,
+		_ => return Err(invalid),
+	};
+	let sharp = rest.next_if_eq(&b'#').is_some();
+	let mut dotted = rest.next_if_eq(&b'.').is_some();
+	let octave = match rest.next_if(u8::is_ascii_digit) {
+		Some(octave) => (octave - b'0') as u32,
+		None => header.octave,
+	};
+	dotted |= rest.next_if_eq(&b'.').is_some();
+	if rest.next().is_some() {
+		return Err(invalid);
+	}
+
+	let duration = rtttl_64ths(duration, dotted).ok_or(invalid)?;
+	let Some(class) = class else {
+		return Ok((None, duration));
+	};
+
+	// B# is the C of the next octave.
+	let semitone = class + sharp as usize;
+	let octave = octave + (semitone / 12) as u32;
+	if octave > 9 {
+		return Err(SongParseError::UnknownNote { index });
+	}
+	Ok((Some(note_frequency(semitone % 12, octave)), duration))
+}
+
+/// Returns how many 64th notes an RTTTL duration lasts, or `None` if it isn't valid.
+fn rtttl_64ths(duration: u32, dotted: bool) -> Option<u32> {
+	if !matches!(duration, 1 | 2 | 4 | 8 | 16 | 32) {
+		return None;
+	}
+	let length = 64 / duration;
+	Some(if dotted { length * 3 / 2 } else { length })
+}
+
+/// Returns the greatest common divisor of `a` and `b`.
+fn gcd(a: u32, b: u32) -> u32 {
+	if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Parses one `time note duration instrument` entry. The instrument is optional.
+fn parse_entry(entry: &str, index: usize) -> Result<(u16, NoteAndDuration), SongParseError> {
+	let invalid = SongParseError::InvalidEntry { index };
+	let mut fields = entry.split_whitespace();
+	let time = fields.next().and_then(|time| parse_beats(time, false));
+	let name = fields.next().ok_or(invalid)?;
+	let duration = fields
+		.next()
+		.and_then(|duration| parse_beats(duration, true));
+	let track = match fields.next() {
+		Some(track) => parse_beats(track, false).ok_or(invalid)?,
+		None => 0,
+	};
+
+	let (Some(time), Some(duration)) = (time, duration) else {
+		return Err(invalid);
+	};
+	if duration == 0 || time > u16::MAX as u32 || duration > u16::MAX as u32 {
+		return Err(invalid);
+	}
+	let (name, chance, ratchet) = split_attributes(name).ok_or(invalid)?;
+	let frequency = parse_note_name(name).ok_or(SongParseError::UnknownNote { index })?;
+
+	let note = NoteAndDuration {
+		frequency,
+		duration: duration as u16,
+		track: track.min(u8::MAX as u32) as u8,
+		chance,
+		ratchet,
+	};
+	Ok((time as u16, note))
+}
+
+/// Splits the attributes off a note like `C5*3?50`, returning the note, its [`NoteAndDuration::chance`]
+/// after `?`, and its [`NoteAndDuration::ratchet`] after `*`, in either order.
+/// Notes without them always play, once. Returns `None` if an attribute isn't valid.
+pub(crate) fn split_attributes(note: &str) -> Option<(&str, u8, u8)> {
+	let (note, mut attributes) = note.split_at(note.find(['?', '*']).unwrap_or(note.len()));
+	let mut chance = 100;
+	let mut ratchet = 1;
+	while let Some(marker) = attributes.chars().next() {
+		let rest = &attributes[1..];
+		let (value, next) = rest.split_at(rest.find(['?', '*']).unwrap_or(rest.len()));
+		match (marker, value.parse().ok()?) {
+			('?', value) if value <= 100 => chance = value,
+			('*', value) if value > 0 => ratchet = value,
+			_ => return None,
+		}
+		attributes = next;
+	}
+	Some((note, chance, ratchet))
+}
+
+/// Parses a positive decimal number like `2` or `1.75`, rounding to the nearest whole number,
+/// or up if `round_up` is `true`.
+fn parse_beats(text: &str, round_up: bool) -> Option<u32> {
+	let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+	if whole.is_empty() && fraction.is_empty() {
+		return None;
+	}
+	if !whole
+		.bytes()
+		.chain(fraction.bytes())
+		.all(|c| c.is_ascii_digit())
+	{
+		return None;
+	}
+
+	let mut value: u32 = 0;
+	for digit in whole.bytes() {
+		value = value.checked_mul(10)?.checked_add((digit - b'0') as u32)?;
+	}
+	let round = if round_up {
+		fraction.bytes().any(|digit| digit != b'0')
+	} else {
+		fraction.bytes().next().is_some_and(|digit| digit >= b'5')
+	};
+	if round {
+		value = value.checked_add(1)?;
+	}
+	Some(value)
+}
+
+/// Returns the frequency of a note name like `C5` or `F#3`, the same as [`crate::declare_song`].
+pub(crate) fn parse_note_name(name: &str) -> Option<u16> {
+	let mut bytes = name.bytes();
+	let mut class = match bytes.next()? {
+		b'C' => 0,
+		b'D' => 2,
+		b'E' => 4,
+		b'F' => 5,
+		b'G' => 7,
+		b'A' => 9,
+		b'B' => 11,
+		_ => return None,
+	};
+	let mut octave = bytes.next()?;
+	if octave == b'#' {
+		// There is no E# or B#.
+		if class == 4 || class == 11 {
+			return None;
+		}
+		class += 1;
+		octave = bytes.next()?;
+	}
+	if !octave.is_ascii_digit() || bytes.next().is_some() {
+		return None;
+	}
+
+	Some(note_frequency(class, (octave - b'0') as u32))
+}
+
+/// Returns the frequency of the note `class` semitones above C in `octave`, from `0` to `9`.
+pub(crate) fn note_frequency(class: usize, octave: u32) -> u16 {
+	let numerator = OCTAVE_MILLIHZ[class] << octave;
+	let denominator = 1000 << 4;
+	((numerator + denominator / 2) / denominator) as u16
+}
@@ -0,0 +1,125 @@
+use crate::{Player, Song, TickEvents, ToneOutput};
+
+/// An ordered list of songs that a [`Player`] plays one after another.
+///
+/// The player should be made with [`Playlist::current`] and shouldn't loop, so each song ends and
+/// [`Playlist::tick`] can move on to the next one. [`Playlist::next`] and [`Playlist::previous`]
+/// skip between songs at any time.
+///
+/// ```rust
+/// use buzzer_music::{FrequencyRange, Player, Playlist, ShadowOutput, Song, declare_song};
+///
+/// const FIRST: Song = declare_song!("0 C5 1 0");
+/// const SECOND: Song = declare_song!("0 E5 1 0");
+///
+/// let mut playlist = Playlist::new([&FIRST, &SECOND]);
+/// let output = ShadowOutput::new(FrequencyRange { min: 20, max: 20_000 }, 1000);
+/// let mut player: Player<ShadowOutput, 1, 4> = Player::new(playlist.current(), false, 1, 500, [output]);
+///
+/// while playlist.index() == 0 {
+///     playlist.tick(&mut player);
+/// }
+/// assert!(core::ptr::eq(playlist.current(), &SECOND));
+/// ```
+pub struct Playlist<'a, const N: usize> {
+	songs: [&'a Song<'a>; N],
+	index: usize,
+	looping: bool,
+}
+
+impl<'a, const N: usize> Playlist<'a, N> {
+	/// The constructor. Starts at the first song and doesn't loop.
+	pub const fn new(songs: [&'a Song<'a>; N]) -> Self {
+		const {
+			assert!(N > 0, "A `Playlist` needs at least one song");
+		}
+
+		Self {
+			songs,
+			index: 0,
+			looping: false,
+		}
+	}
+
+	/// Sets whether the playlist goes back to the first song after the last one ends.
+	pub fn set_looping(&mut self, looping: bool) {
+		self.looping = looping;
+	}
+
+	/// Returns the song the playlist is on.
+	pub fn current(&self) -> &'a Song<'a> {
+		self.songs[self.index]
+	}
+
+	/// Returns the index of the song the playlist is on.
+	pub fn index(&self) -> usize {
+		self.index
+	}
+
+	/// Returns every song in the playlist, in order.
+	pub fn songs(&self) -> &[&'a Song<'a>; N] {
+		&self.songs
+	}
+
+	/// Ticks `player` like [`Player::tick_events`], moving on to the next song when the current one
+	/// ends. Returns what happened during the tick.
+	pub fn tick<O: ToneOutput, const PWM_COUNT: usize, const MAX_SIMULTANEOUS_NOTES: usize>(
+		&mut self,
+		player: &mut Player<'a, O, PWM_COUNT, MAX_SIMULTANEOUS_NOTES>,
+	) -> TickEvents {
+		let events = player.tick_events();
+		if events.contains(TickEvents::FINISHED) {
+			self.next(player);
+		}
+		events
+	}
+
+	/// Switches `player` to the next song, like [`Player::swap_song`].
+	///
+	/// Returns `false` and leaves `player` alone if this is the last song and the playlist doesn't loop.
+	pub fn next<O: ToneOutput, const PWM_COUNT: usize, const MAX_SIMULTANEOUS_NOTES: usize>(
+		&mut self,
+		player: &mut Player<'a, O, PWM_COUNT, MAX_SIMULTANEOUS_NOTES>,
+	) -> bool {
+		let index = if self.index + 1 < N {
+			self.index + 1
+		} else if self.looping {
+			0
+		} else {
+			return false;
+		};
+		self.select(index, player)
+	}
+
+	/// Switches `player` to the previous song, like [`Player::swap_song`].
+	///
+	/// Returns `false` and leaves `player` alone if this is the first song and the playlist doesn't loop.
+	pub fn previous<O: ToneOutput, const PWM_COUNT: usize, const MAX_SIMULTANEOUS_NOTES: usize>(
+		&mut self,
+		player: &mut Player<'a, O, PWM_COUNT, MAX_SIMULTANEOUS_NOTES>,
+	) -> bool {
+		let index = if self.index > 0 {
+			self.index - 1
+		} else if self.looping {
+			N - 1
+		} else {
+			return false;
+		};
+		self.select(index, player)
+	}
+
+	/// Switches `player` to the song at `index`, like [`Player::swap_song`].
+	/// Returns `false` and leaves `player` alone if there is no such song.
+	pub fn select<O: ToneOutput, const PWM_COUNT: usize, const MAX_SIMULTANEOUS_NOTES: usize>(
+		&mut self,
+		index: usize,
+		player: &mut Player<'a, O, PWM_COUNT, MAX_SIMULTANEOUS_NOTES>,
+	) -> bool {
+		let Some(&song) = self.songs.get(index) else {
+			return false;
+		};
+		self.index = index;
+		player.swap_song(song, false);
+		true
+	}
+}
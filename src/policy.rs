@@ -0,0 +1,150 @@
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// What kind of sound something plays, so an [`AudioPolicy`] can treat it accordingly.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SoundCategory {
+	/// Songs and background music.
+	#[default]
+	Music,
+	/// Feedback for user interaction, like keypress beeps.
+	Ui,
+	/// Alarms and warnings that must always be heard.
+	Alarm,
+	/// Status sounds like heartbeats and locator chirps.
+	Telemetry,
+}
+
+impl SoundCategory {
+	/// Every category.
+	pub const ALL: [SoundCategory; 4] = [Self::Music, Self::Ui, Self::Alarm, Self::Telemetry];
+
+	/// Returns the position of this category in [`SoundCategory::ALL`].
+	pub const fn index(self) -> usize {
+		match self {
+			Self::Music => 0,
+			Self::Ui => 1,
+			Self::Alarm => 2,
+			Self::Telemetry => 3,
+		}
+	}
+
+	/// Returns `true` if this category must not be silenced by quiet hours.
+	pub const fn is_critical(self) -> bool {
+		matches!(self, Self::Alarm)
+	}
+}
+
+/// Rules shared by every [`crate::Player`] and [`crate::Heartbeat`] given it, such as quiet hours.
+///
+/// Each [`SoundCategory`] has its own gain and can be muted, so settings like "mute keypress sounds"
+/// only need to change the policy rather than every place a sound is played.
+///
+/// While quiet, the volume of every sound is capped to a percentage of its usual duty,
+/// and non-critical sounds can be muted entirely. Quiet hours can be switched with
+/// [`AudioPolicy::set_quiet`] or read from a function, like one checking a real-time clock.
+///
+/// ```rust,ignore
+/// static POLICY: buzzer_music::AudioPolicy = buzzer_music::AudioPolicy::new().with_quiet_source(is_night);
+///
+/// player.set_policy(Some(&POLICY));
+/// ```
+pub struct AudioPolicy {
+	quiet: AtomicBool,
+	quiet_source: Option<fn() -> bool>,
+	quiet_volume_percent: AtomicU8,
+	quiet_mutes_non_critical: AtomicBool,
+	category_gains: [AtomicU8; 4],
+	category_mutes: [AtomicBool; 4],
+}
+
+impl AudioPolicy {
+	/// The constructor.
+	///
+	/// While quiet, volume is capped to 25% and non-critical sounds are muted.
+	pub const fn new() -> Self {
+		Self {
+			quiet: AtomicBool::new(false),
+			quiet_source: None,
+			quiet_volume_percent: AtomicU8::new(25),
+			quiet_mutes_non_critical: AtomicBool::new(true),
+			category_gains: [const { AtomicU8::new(100) }; 4],
+			category_mutes: [const { AtomicBool::new(false) }; 4],
+		}
+	}
+
+	/// Returns this policy with quiet hours also read from `source`.
+	pub const fn with_quiet_source(self, source: fn() -> bool) -> Self {
+		Self {
+			quiet_source: Some(source),
+			..self
+		}
+	}
+
+	/// Turns quiet hours on or off manually.
+	pub fn set_quiet(&self, quiet: bool) {
+		self.quiet.store(quiet, Ordering::Relaxed);
+	}
+
+	/// Returns `true` if quiet hours are on, either manually or from the quiet source.
+	pub fn is_quiet(&self) -> bool {
+		self.quiet.load(Ordering::Relaxed) || self.quiet_source.is_some_and(|source| source())
+	}
+
+	/// Sets the percentage of their usual duty sounds are capped to while quiet.
+	pub fn set_quiet_volume_percent(&self, percent: u8) {
+		self.quiet_volume_percent
+			.store(percent.min(100), Ordering::Relaxed);
+	}
+
+	/// Sets whether non-critical sounds are muted entirely while quiet.
+	pub fn set_quiet_mutes_non_critical(&self, mute: bool) {
+		self.quiet_mutes_non_critical.store(mute, Ordering::Relaxed);
+	}
+
+	/// Sets the percentage of their usual duty sounds of `category` play at.
+	pub fn set_category_gain(&self, category: SoundCategory, percent: u8) {
+		self.category_gains[category.index()].store(percent.min(100), Ordering::Relaxed);
+	}
+
+	/// Returns the percentage of their usual duty sounds of `category` play at, ignoring quiet hours and mutes.
+	pub fn category_gain(&self, category: SoundCategory) -> u8 {
+		self.category_gains[category.index()].load(Ordering::Relaxed)
+	}
+
+	/// Mutes or unmutes every sound of `category`, even critical ones.
+	pub fn set_category_muted(&self, category: SoundCategory, muted: bool) {
+		self.category_mutes[category.index()].store(muted, Ordering::Relaxed);
+	}
+
+	/// Returns `true` if sounds of `category` are muted with [`AudioPolicy::set_category_muted`].
+	pub fn is_category_muted(&self, category: SoundCategory) -> bool {
+		self.category_mutes[category.index()].load(Ordering::Relaxed)
+	}
+
+	/// Returns the percentage of its usual duty a sound of `category` should play at.
+	pub fn gain_percent(&self, category: SoundCategory) -> u8 {
+		if self.is_category_muted(category) {
+			return 0;
+		}
+
+		let gain = self.category_gain(category);
+		if !self.is_quiet() {
+			return gain;
+		}
+		if !category.is_critical() && self.quiet_mutes_non_critical.load(Ordering::Relaxed) {
+			return 0;
+		}
+		gain.min(self.quiet_volume_percent.load(Ordering::Relaxed))
+	}
+
+	/// Returns `duty` with the policy for `category` applied.
+	pub fn apply(&self, category: SoundCategory, duty: u16) -> u16 {
+		(duty as u32 * self.gain_percent(category) as u32 / 100) as u16
+	}
+}
+
+impl Default for AudioPolicy {
+	fn default() -> Self {
+		Self::new()
+	}
+}
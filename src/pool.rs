@@ -0,0 +1,281 @@
+use core::cell::{Cell, RefCell};
+
+use crate::{DutyFraction, FrequencyRange, ToneError, ToneOutput};
+
+/// Shares a set of outputs between several players, sound effects, and anything else that wants a buzzer.
+///
+/// Instead of owning outputs, each user leases [`PoolVoice`]s from the pool with a priority.
+/// A [`PoolVoice`] is a [`ToneOutput`], so it can be given to a [`crate::Player`] like a PWM.
+///
+/// When every output is leased, a request with a higher priority steals the output with the lowest priority.
+/// The voice it was stolen from goes quiet, and gets the output back once the thief releases it,
+/// playing whatever it was last told to play. Only the most recently stolen lease is remembered for each output.
+///
+/// ```rust,ignore
+/// let pool = buzzer_music::PlayerPool::new([buzzer_a, buzzer_b]);
+///
+/// // Music plays on both buzzers...
+/// let mut music = buzzer_music::Player::new(&SONG, true, 3, 100, pool.lease_all(0).unwrap());
+///
+/// // ...until a sound effect takes one of them.
+/// let mut sfx = buzzer_music::Player::new(&BEEP, false, 1, 100, [pool.lease(1).unwrap()]);
+/// ```
+pub struct PlayerPool<O: ToneOutput, const OUTPUT_COUNT: usize> {
+	channels: RefCell<[Channel<O>; OUTPUT_COUNT]>,
+	next_lease_id: Cell<u16>,
+}
+
+/// An output owned by a [`PlayerPool`] and who is leasing it.
+struct Channel<O: ToneOutput> {
+	output: O,
+	sounding: bool,
+	holder: Option<Lease>,
+	suspended: Option<Lease>,
+	/// What the holder and the suspended lease last played, or `None` if they turned the output off.
+	/// The suspended lease's tone is played once it gets the output back, since a [`crate::Player`]
+	/// doesn't play a note again while it thinks it's still sounding.
+	tone: Option<Tone>,
+	suspended_tone: Option<Tone>,
+}
+
+impl<O: ToneOutput> Channel<O> {
+	/// Plays `tone` on the output, or turns it off for `None`.
+	fn play(&mut self, tone: Option<Tone>) -> Result<(), ToneError> {
+		self.tone = tone;
+		let result = match tone {
+			Some(Tone::Raw(frequency, duty)) => self.output.set_tone(frequency, duty),
+			Some(Tone::Fraction(frequency, duty)) => self.output.set_tone_fraction(frequency, duty),
+			None => {
+				self.output.set_off();
+				Ok(())
+			}
+		};
+		self.sounding = tone.is_some() && result.is_ok();
+		result
+	}
+}
+
+/// A tone played on a [`PoolVoice`], with a raw duty or a [`DutyFraction`].
+#[derive(Clone, Copy)]
+enum Tone {
+	Raw(u16, u16),
+	Fraction(u16, DutyFraction),
+}
+
+/// Identifies a [`PoolVoice`] and its priority.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Lease {
+	id: u16,
+	priority: u8,
+}
+
+impl<O: ToneOutput, const OUTPUT_COUNT: usize> PlayerPool<O, OUTPUT_COUNT> {
+	/// The constructor.
+	///
+	/// `outputs` is an array of outputs the pool hands out.
+	pub fn new(outputs: [O; OUTPUT_COUNT]) -> Self {
+		Self {
+			channels: RefCell::new(outputs.map(|output| Channel {
+				output,
+				sounding: false,
+				holder: None,
+				suspended: None,
+				tone: None,
+				suspended_tone: None,
+			})),
+			next_lease_id: Cell::new(0),
+		}
+	}
+
+	/// Leases any output with `priority`.
+	///
+	/// Free outputs are used first. Otherwise the output with the lowest priority below `priority`
+	/// is stolen. Returns `None` if every output is held with an equal or higher priority.
+	pub fn lease(&self, priority: u8) -> Option<PoolVoice<'_, O, OUTPUT_COUNT>> {
+		let channel = {
+			let channels = self.channels.borrow();
+			let free = channels.iter().position(|channel| channel.holder.is_none());
+			free.or_else(|| {
+				channels
+					.iter()
+					.enumerate()
+					.filter_map(|(i, channel)| Some((i, channel.holder?.priority)))
+					.filter(|(_, held_priority)| *held_priority < priority)
+					.min_by_key(|(_, held_priority)| *held_priority)
+					.map(|(i, _)| i)
+			})?
+		};
+		self.lease_channel(channel, priority)
+	}
+
+	/// Leases `OUTPUT_COUNT` outputs with `priority`, in the pool's order.
+	/// Returns `None` (and leases nothing) unless every output can be leased.
+	pub fn lease_all(
+		&self,
+		priority: u8,
+	) -> Option<[PoolVoice<'_, O, OUTPUT_COUNT>; OUTPUT_COUNT]> {
+		let available = self
+			.channels
+			.borrow()
+			.iter()
+			.all(|channel| match channel.holder {
+				Some(holder) => holder.priority < priority,
+				None => true,
+			});
+		if !available {
+			return None;
+		}
+
+		(0..OUTPUT_COUNT)
+			.filter_map(|channel| self.lease_channel(channel, priority))
+			.collect::<arrayvec::ArrayVec<_, OUTPUT_COUNT>>()
+			.into_inner()
+			.ok()
+	}
+
+	/// Leases the output at index `channel` with `priority`.
+	///
+	/// If it is already leased with a lower priority, it is stolen.
+	/// Returns `None` if it is held with an equal or higher priority, or doesn't exist.
+	pub fn lease_channel(
+		&self,
+		channel: usize,
+		priority: u8,
+	) -> Option<PoolVoice<'_, O, OUTPUT_COUNT>> {
+		let mut channels = self.channels.borrow_mut();
+		let target = channels.get_mut(channel)?;
+
+		if let Some(holder) = target.holder {
+			if holder.priority >= priority {
+				return None;
+			}
+			target.suspended = Some(holder);
+			target.suspended_tone = target.tone;
+			let _ = target.play(None);
+		}
+
+		let id = self.next_lease_id.get();
+		self.next_lease_id.set(id.wrapping_add(1));
+		target.holder = Some(Lease { id, priority });
+
+		Some(PoolVoice {
+			pool: self,
+			channel,
+			id,
+		})
+	}
+
+	/// Returns `true` if the output at index `channel` isn't leased.
+	pub fn is_free(&self, channel: usize) -> bool {
+		self.channels
+			.borrow()
+			.get(channel)
+			.is_some_and(|channel| channel.holder.is_none())
+	}
+
+	/// Returns `true` if no output is playing a tone, even if some are leased.
+	pub fn is_silent(&self) -> bool {
+		self.channels
+			.borrow()
+			.iter()
+			.all(|channel| !channel.sounding)
+	}
+
+	/// Returns `true` if the lease `id` currently holds the output at `channel`.
+	fn is_held_by(&self, channel: usize, id: u16) -> bool {
+		self.channels.borrow()[channel]
+			.holder
+			.is_some_and(|holder| holder.id == id)
+	}
+
+	/// Plays `tone` on `channel` for the lease `id`, or turns it off for `None`.
+	/// If the output was stolen from `id`, `tone` is kept for when it gets the output back.
+	fn play(&self, channel: usize, id: u16, tone: Option<Tone>) -> Result<(), ToneError> {
+		let mut channels = self.channels.borrow_mut();
+		let channel = &mut channels[channel];
+		if channel.holder.is_some_and(|holder| holder.id == id) {
+			return channel.play(tone);
+		}
+		if channel
+			.suspended
+			.is_some_and(|suspended| suspended.id == id)
+		{
+			channel.suspended_tone = tone;
+		}
+		Ok(())
+	}
+
+	/// Ends the lease `id` on `channel`.
+	/// If it stole the output, the output goes back to the lease it was stolen from.
+	fn release(&self, channel: usize, id: u16) {
+		let mut channels = self.channels.borrow_mut();
+		let channel = &mut channels[channel];
+
+		if channel
+			.suspended
+			.is_some_and(|suspended| suspended.id == id)
+		{
+			channel.suspended = None;
+			channel.suspended_tone = None;
+		} else if channel.holder.is_some_and(|holder| holder.id == id) {
+			channel.holder = channel.suspended.take();
+			let tone = channel.suspended_tone.take();
+			let _ = channel.play(tone);
+		}
+	}
+}
+
+/// An output leased from a [`PlayerPool`].
+///
+/// While another voice has stolen its output, everything played on it is held back, and the last of it
+/// is played once the output is given back.
+/// The lease ends when this is dropped.
+pub struct PoolVoice<'p, O: ToneOutput, const OUTPUT_COUNT: usize> {
+	pool: &'p PlayerPool<O, OUTPUT_COUNT>,
+	channel: usize,
+	id: u16,
+}
+
+impl<O: ToneOutput, const OUTPUT_COUNT: usize> PoolVoice<'_, O, OUTPUT_COUNT> {
+	/// Returns the index of the pool output this voice leases.
+	pub fn channel(&self) -> usize {
+		self.channel
+	}
+
+	/// Returns `true` if this voice currently holds its output (it hasn't been stolen).
+	pub fn is_active(&self) -> bool {
+		self.pool.is_held_by(self.channel, self.id)
+	}
+}
+
+impl<O: ToneOutput, const OUTPUT_COUNT: usize> ToneOutput for PoolVoice<'_, O, OUTPUT_COUNT> {
+	fn playable_range(&self) -> FrequencyRange {
+		self.pool.channels.borrow()[self.channel]
+			.output
+			.playable_range()
+	}
+
+	fn set_tone(&mut self, frequency: u16, duty: u16) -> Result<(), ToneError> {
+		self.pool
+			.play(self.channel, self.id, Some(Tone::Raw(frequency, duty)))
+	}
+
+	fn max_duty(&self) -> u16 {
+		self.pool.channels.borrow()[self.channel].output.max_duty()
+	}
+
+	fn set_tone_fraction(&mut self, frequency: u16, duty: DutyFraction) -> Result<(), ToneError> {
+		self.pool
+			.play(self.channel, self.id, Some(Tone::Fraction(frequency, duty)))
+	}
+
+	fn set_off(&mut self) {
+		let _ = self.pool.play(self.channel, self.id, None);
+	}
+}
+
+impl<O: ToneOutput, const OUTPUT_COUNT: usize> Drop for PoolVoice<'_, O, OUTPUT_COUNT> {
+	fn drop(&mut self) {
+		self.pool.release(self.channel, self.id);
+	}
+}
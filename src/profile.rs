@@ -0,0 +1,320 @@
+use crate::saturate_u16;
+
+/// The most points a [`ResonanceCurve`] can have.
+pub const MAX_RESONANCE_POINTS: usize = 8;
+
+/// The first bytes of a serialized [`BoardProfile`], followed by the format version.
+const PROFILE_MAGIC: [u8; 3] = *b"BZP";
+
+/// The version of the serialized [`BoardProfile`] format.
+const PROFILE_VERSION: u8 = 1;
+
+/// The size of the buffer [`BoardProfile::load`] and [`BoardProfile::save`] use,
+/// enough for a profile with up to 42 channels.
+const PROFILE_BUFFER_SIZE: usize = 128;
+
+/// A gain at a frequency, used to build a [`ResonanceCurve`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResonancePoint {
+	pub frequency: u16,
+	pub gain_percent: u16,
+}
+
+/// A gain that depends on frequency, used to compensate for a buzzer's resonance.
+///
+/// The gain is linearly interpolated between points, and held flat before the first and after the last.
+/// An empty curve has a gain of `100` everywhere.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResonanceCurve {
+	points: arrayvec::ArrayVec<ResonancePoint, MAX_RESONANCE_POINTS>,
+}
+
+impl ResonanceCurve {
+	/// Creates an empty curve.
+	pub const fn new() -> Self {
+		Self {
+			points: arrayvec::ArrayVec::new_const(),
+		}
+	}
+
+	/// Creates a curve from `points`, which don't need to be sorted.
+	/// Returns an error if there are more than [`MAX_RESONANCE_POINTS`].
+	pub fn from_points(points: &[ResonancePoint]) -> Result<Self, ProfileError> {
+		let mut curve = Self::new();
+		for point in points {
+			curve.add_point(*point)?;
+		}
+		Ok(curve)
+	}
+
+	/// Adds a point, keeping the points sorted by frequency.
+	/// Returns an error if the curve is full.
+	pub fn add_point(&mut self, point: ResonancePoint) -> Result<(), ProfileError> {
+		let index = self
+			.points
+			.iter()
+			.position(|other| other.frequency > point.frequency)
+			.unwrap_or(self.points.len());
+		self.points
+			.try_insert(index, point)
+			.map_err(|_| ProfileError::TooManyPoints)
+	}
+
+	/// Returns the points, sorted by frequency.
+	pub fn points(&self) -> &[ResonancePoint] {
+		&self.points
+	}
+
+	/// Returns the gain at `frequency` as a percentage.
+	pub fn gain_percent(&self, frequency: u16) -> u16 {
+		let (Some(first), Some(last)) = (self.points.first(), self.points.last()) else {
+			return 100;
+		};
+
+		if frequency <= first.frequency {
+			return first.gain_percent;
+		}
+		if frequency >= last.frequency {
+			return last.gain_percent;
+		}
+
+		for pair in self.points.windows(2) {
+			let (a, b) = (pair[0], pair[1]);
+			if frequency <= b.frequency {
+				let span = (b.frequency - a.frequency) as i32;
+				if span == 0 {
+					return b.gain_percent;
+				}
+				let offset = (frequency - a.frequency) as i32;
+				let gain = a.gain_percent as i32
+					+ (b.gain_percent as i32 - a.gain_percent as i32) * offset / span;
+				return saturate_u16(gain.max(0) as u32);
+			}
+		}
+
+		last.gain_percent
+	}
+}
+
+/// The audio calibration of a board, so tuning done once (for example during manufacturing)
+/// can be stored in flash and applied at boot with [`crate::Player::apply_profile`].
+///
+/// `CHANNELS` should match the `PWM_COUNT` of the [`crate::Player`] it is applied to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoardProfile<const CHANNELS: usize> {
+	/// The gain of each PWM as a percentage.
+	pub channel_gains: [u16; CHANNELS],
+	/// The frequency-dependent gain applied to every PWM.
+	pub resonance_curve: ResonanceCurve,
+	/// The frequency of the clock driving the PWMs, in hertz.
+	pub clock_hz: u32,
+}
+
+impl<const CHANNELS: usize> BoardProfile<CHANNELS> {
+	/// The most bytes [`BoardProfile::to_bytes`] can write.
+	pub const MAX_SIZE: usize = 4 + 4 + 1 + CHANNELS * 2 + 1 + MAX_RESONANCE_POINTS * 4 + 1;
+
+	/// Creates a profile that doesn't change anything, for a 150 MHz clock.
+	pub const fn new() -> Self {
+		Self {
+			channel_gains: [100; CHANNELS],
+			resonance_curve: ResonanceCurve::new(),
+			clock_hz: crate::DEFAULT_SYS_CLOCK_HZ,
+		}
+	}
+
+	/// Writes the profile into `buffer` and returns how many bytes were written.
+	///
+	/// The format is: `BZP`, a version byte, the clock (`u32`), the channel count (`u8`),
+	/// each gain (`u16`), the point count (`u8`), each point (`u16` frequency, `u16` gain),
+	/// then a checksum byte. Numbers are little-endian.
+	pub fn to_bytes(&self, buffer: &mut [u8]) -> Result<usize, ProfileError> {
+		let mut writer = ByteWriter { buffer, len: 0 };
+		writer.write(&PROFILE_MAGIC)?;
+		writer.write(&[PROFILE_VERSION])?;
+		writer.write(&self.clock_hz.to_le_bytes())?;
+		writer.write(&[CHANNELS as u8])?;
+		for gain in self.channel_gains {
+			writer.write(&gain.to_le_bytes())?;
+		}
+		writer.write(&[self.resonance_curve.points().len() as u8])?;
+		for point in self.resonance_curve.points() {
+			writer.write(&point.frequency.to_le_bytes())?;
+			writer.write(&point.gain_percent.to_le_bytes())?;
+		}
+
+		let checksum = checksum(&writer.buffer[..writer.len]);
+		writer.write(&[checksum])?;
+		Ok(writer.len)
+	}
+
+	/// Reads a profile written by [`BoardProfile::to_bytes`].
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProfileError> {
+		let mut reader = ByteReader { bytes, position: 0 };
+		if reader.read::<3>()? != PROFILE_MAGIC {
+			return Err(ProfileError::Invalid);
+		}
+		if reader.read::<1>()?[0] != PROFILE_VERSION {
+			return Err(ProfileError::UnsupportedVersion);
+		}
+
+		let clock_hz = u32::from_le_bytes(reader.read()?);
+		if reader.read::<1>()?[0] as usize != CHANNELS {
+			return Err(ProfileError::ChannelCountMismatch);
+		}
+
+		let mut channel_gains = [100; CHANNELS];
+		for gain in &mut channel_gains {
+			*gain = u16::from_le_bytes(reader.read()?);
+		}
+
+		let mut resonance_curve = ResonanceCurve::new();
+		for _ in 0..reader.read::<1>()?[0] {
+			resonance_curve.add_point(ResonancePoint {
+				frequency: u16::from_le_bytes(reader.read()?),
+				gain_percent: u16::from_le_bytes(reader.read()?),
+			})?;
+		}
+
+		let expected = checksum(&bytes[..reader.position]);
+		if reader.read::<1>()?[0] != expected {
+			return Err(ProfileError::Invalid);
+		}
+
+		Ok(Self {
+			channel_gains,
+			resonance_curve,
+			clock_hz,
+		})
+	}
+
+	/// Loads a profile from `storage`.
+	pub fn load<S: ProfileStorage>(storage: &mut S) -> Result<Self, ProfileError> {
+		let mut buffer = [0; PROFILE_BUFFER_SIZE];
+		let buffer = buffer
+			.get_mut(..Self::MAX_SIZE)
+			.ok_or(ProfileError::TooLarge)?;
+		let len = storage.load(buffer).map_err(|_| ProfileError::Storage)?;
+		Self::from_bytes(&buffer[..len.min(buffer.len())])
+	}
+
+	/// Saves this profile to `storage`.
+	pub fn save<S: ProfileStorage>(&self, storage: &mut S) -> Result<(), ProfileError> {
+		let mut buffer = [0; PROFILE_BUFFER_SIZE];
+		let buffer = buffer
+			.get_mut(..Self::MAX_SIZE)
+			.ok_or(ProfileError::TooLarge)?;
+		let len = self.to_bytes(buffer)?;
+		storage
+			.store(&buffer[..len])
+			.map_err(|_| ProfileError::Storage)
+	}
+}
+
+impl<const CHANNELS: usize> Default for BoardProfile<CHANNELS> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Somewhere a [`BoardProfile`] can be persisted, like a flash page or EEPROM.
+pub trait ProfileStorage {
+	type Error;
+
+	/// Reads the stored bytes into `buffer` and returns how many were read.
+	fn load(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error>;
+
+	/// Replaces the stored bytes with `bytes`.
+	fn store(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// An error produced when building, reading, or writing a [`BoardProfile`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfileError {
+	/// A [`ResonanceCurve`] can't hold more than [`MAX_RESONANCE_POINTS`].
+	TooManyPoints,
+	/// The buffer is too small to hold the profile.
+	BufferTooSmall,
+	/// The profile has too many channels to fit in the internal buffer.
+	TooLarge,
+	/// The bytes aren't a profile, or are corrupted.
+	Invalid,
+	/// The bytes were written by an unsupported version of the format.
+	UnsupportedVersion,
+	/// The profile was written for a different number of channels.
+	ChannelCountMismatch,
+	/// The [`ProfileStorage`] returned an error.
+	Storage,
+}
+
+impl ProfileError {
+	/// Returns the stable numeric code of this error, from `0x20`. See [`crate::ToneError::as_code`].
+	pub const fn as_code(self) -> u8 {
+		match self {
+			Self::TooManyPoints => 0x20,
+			Self::BufferTooSmall => 0x21,
+			Self::TooLarge => 0x22,
+			Self::Invalid => 0x23,
+			Self::UnsupportedVersion => 0x24,
+			Self::ChannelCountMismatch => 0x25,
+			Self::Storage => 0x26,
+		}
+	}
+
+	/// Returns the error with the code `code`, or `None` if it isn't one of these errors.
+	pub const fn from_code(code: u8) -> Option<Self> {
+		match code {
+			0x20 => Some(Self::TooManyPoints),
+			0x21 => Some(Self::BufferTooSmall),
+			0x22 => Some(Self::TooLarge),
+			0x23 => Some(Self::Invalid),
+			0x24 => Some(Self::UnsupportedVersion),
+			0x25 => Some(Self::ChannelCountMismatch),
+			0x26 => Some(Self::Storage),
+			_ => None,
+		}
+	}
+}
+
+/// A simple checksum to catch erased or corrupted storage.
+fn checksum(bytes: &[u8]) -> u8 {
+	bytes
+		.iter()
+		.fold(0u8, |sum, byte| sum.rotate_left(1) ^ byte)
+}
+
+/// Writes bytes into a buffer, failing if it runs out of space.
+struct ByteWriter<'b> {
+	buffer: &'b mut [u8],
+	len: usize,
+}
+
+impl ByteWriter<'_> {
+	fn write(&mut self, bytes: &[u8]) -> Result<(), ProfileError> {
+		let end = self.len + bytes.len();
+		self.buffer
+			.get_mut(self.len..end)
+			.ok_or(ProfileError::BufferTooSmall)?
+			.copy_from_slice(bytes);
+		self.len = end;
+		Ok(())
+	}
+}
+
+/// Reads bytes from a slice, failing if it runs out.
+struct ByteReader<'b> {
+	bytes: &'b [u8],
+	position: usize,
+}
+
+impl ByteReader<'_> {
+	fn read<const N: usize>(&mut self) -> Result<[u8; N], ProfileError> {
+		let end = self.position + N;
+		let bytes = self
+			.bytes
+			.get(self.position..end)
+			.ok_or(ProfileError::Invalid)?;
+		self.position = end;
+		bytes.try_into().map_err(|_| ProfileError::Invalid)
+	}
+}
@@ -0,0 +1,78 @@
+/// A source of random numbers for effects that need them, like [`crate::Geiger`].
+///
+/// Any `FnMut() -> u32` is a source, so a hardware RNG can be used directly. For behavior that is
+/// reproducible between runs, use an [`XorShift32`] with a fixed seed.
+pub trait RandomSource {
+	/// Returns the next random number.
+	fn next_u32(&mut self) -> u32;
+
+	/// Returns a random number from `0` up to, but not including, `bound`.
+	/// Returns `0` if `bound` is `0`.
+	fn next_below(&mut self, bound: u32) -> u32 {
+		if bound == 0 {
+			return 0;
+		}
+		// Multiplying instead of using `%` avoids favoring small numbers as much.
+		((self.next_u32() as u64 * bound as u64) >> 32) as u32
+	}
+}
+
+impl<F: FnMut() -> u32> RandomSource for F {
+	fn next_u32(&mut self) -> u32 {
+		self()
+	}
+}
+
+/// A small, fast pseudorandom number generator.
+///
+/// The same seed always produces the same numbers, on any target.
+/// It's not suitable for anything that needs to be unpredictable.
+///
+/// ```rust
+/// use buzzer_music::{RandomSource, XorShift32};
+///
+/// let mut a = XorShift32::new(1234);
+/// let mut b = XorShift32::new(1234);
+/// assert_eq!(a.next_u32(), b.next_u32());
+/// assert!(a.next_below(10) < 10);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct XorShift32 {
+	state: u32,
+}
+
+impl XorShift32 {
+	/// The seed used in place of `0`, which would only ever produce `0`.
+	const ZERO_SEED: u32 = 0x9E37_79B9;
+
+	/// The constructor.
+	///
+	/// `seed` picks the sequence of numbers produced.
+	pub const fn new(seed: u32) -> Self {
+		Self {
+			state: if seed == 0 { Self::ZERO_SEED } else { seed },
+		}
+	}
+
+	/// Returns the next random number. The same as [`RandomSource::next_u32`], but usable in `const`.
+	pub const fn next(&mut self) -> u32 {
+		let mut x = self.state;
+		x ^= x << 13;
+		x ^= x >> 17;
+		x ^= x << 5;
+		self.state = x;
+		x
+	}
+}
+
+impl Default for XorShift32 {
+	fn default() -> Self {
+		Self::new(0)
+	}
+}
+
+impl RandomSource for XorShift32 {
+	fn next_u32(&mut self) -> u32 {
+		self.next()
+	}
+}
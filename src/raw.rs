@@ -0,0 +1,421 @@
+//! A versioned byte format for [`Song`]s, for songs made by build tools, stored in flash, or
+//! written by older versions of this crate.
+//!
+//! A [`Song`] is made of references, so its layout in memory can change between versions of this
+//! crate. This format doesn't: [`decode`] reads every version up to [`VERSION`], and new versions
+//! only ever add to it.
+//!
+//! Version `3` is laid out as follows, with numbers in little-endian:
+//! - `BZS` and the version byte.
+//! - The song's `end` (`u16`).
+//! - The note count (`u16`), then each note in order of the beat it starts on: the beat (`u16`),
+//!   `frequency` (`u16`), `duration` (`u16`), `track` (`u8`), `chance` (`u8`), and `ratchet` (`u8`).
+//!   Version `1` doesn't have the `chance`, so its notes always play, and versions `1` and `2` don't
+//!   have the `ratchet`, so their notes play once. A `duration` of `0` isn't valid.
+//! - The event count (`u16`), then each event: its beat (`u16`), its kind (`u8`), the length of the
+//!   rest of the event (`u8`), and the rest of the event. Kinds are `0` for
+//!   [`SongEventKind::SetTempo`], `1` for [`SongEventKind::TempoRamp`], `2` for [`SongEventKind::Fermata`],
+//!   `3` for [`SongEventKind::Hold`], and `4` for [`SongEventKind::Marker`] (its `id`, then `0` for
+//!   [`Transition::Cut`] or `1` and the beats of a [`Transition::Fade`]). Their fields follow in order.
+//!   Events of unknown kinds, and any bytes after the fields of known ones, are skipped, so songs
+//!   from newer tools still load.
+//! - A checksum byte over everything before it.
+//!
+//! Events that refer to other data ([`SongEventKind::Branch`], [`SongEventKind::Alternatives`], and
+//! [`Transition::Fill`]) can't be stored, and neither can a song's [`SongMetadata`](crate::SongMetadata), so decoded songs
+//! have none.
+//!
+//! ```rust
+//! use arrayvec::ArrayVec;
+//! use buzzer_music::{declare_song, raw};
+//!
+//! const SONG: buzzer_music::Song = declare_song!("0 C5 1 0;2 E5 2 0;2 G4 1 1");
+//!
+//! let mut bytes = [0; 64];
+//! let len = raw::encode(&SONG, &mut bytes).unwrap();
+//! assert_eq!(len, raw::encoded_len(&SONG));
+//!
+//! let mut notes = ArrayVec::<buzzer_music::NoteAndDuration, 8>::new();
+//! let mut beats = ArrayVec::<_, 8>::new();
+//! let mut events = ArrayVec::<_, 4>::new();
+//! let song = raw::decode(&bytes[..len], &mut notes, &mut beats, &mut events).unwrap();
+//! assert_eq!(song.end, SONG.end);
+//! assert_eq!(song.notes[2].unwrap().len(), 2);
+//! ```
+
+use arrayvec::ArrayVec;
+
+use crate::parse::index_beats;
+use crate::{NoteAndDuration, Song, SongEvent, SongEventKind, Transition};
+
+/// The newest version of the format, which [`encode`] writes.
+pub const VERSION: u8 = 3;
+
+/// The bytes every song starts with.
+const MAGIC: [u8; 3] = *b"BZS";
+
+/// The bytes before the notes: the magic, the version, `end`, and the note count.
+const HEADER_SIZE: usize = 3 + 1 + 2 + 2;
+
+/// The bytes each note takes.
+const NOTE_SIZE: usize = 9;
+
+/// An error produced when a song can't be written or read in the raw format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RawError {
+	/// The buffer is too small to hold the song.
+	BufferTooSmall,
+	/// The bytes aren't a song, or are corrupted.
+	Invalid,
+	/// The bytes were written by a newer version of the format.
+	UnsupportedVersion,
+	/// The song has an event that refers to other data, which can't be stored.
+	UnsupportedEvent,
+	/// The song has more notes than the notes buffer can hold.
+	TooManyNotes,
+	/// The song lasts more beats than the beats buffer can hold.
+	TooManyBeats,
+	/// The song has more events than the events buffer can hold.
+	TooManyEvents,
+}
+
+impl RawError {
+	/// Returns the stable numeric code of this error, from `0x50`. See [`crate::ToneError::as_code`].
+	pub const fn as_code(self) -> u8 {
+		match self {
+			Self::BufferTooSmall => 0x50,
+			Self::Invalid => 0x51,
+			Self::UnsupportedVersion => 0x52,
+			Self::UnsupportedEvent => 0x53,
+			Self::TooManyNotes => 0x54,
+			Self::TooManyBeats => 0x55,
+			Self::TooManyEvents => 0x56,
+		}
+	}
+
+	/// Returns the error with the code `code`, or `None` if it isn't one of these errors.
+	pub const fn from_code(code: u8) -> Option<Self> {
+		match code {
+			0x50 => Some(Self::BufferTooSmall),
+			0x51 => Some(Self::Invalid),
+			0x52 => Some(Self::UnsupportedVersion),
+			0x53 => Some(Self::UnsupportedEvent),
+			0x54 => Some(Self::TooManyNotes),
+			0x55 => Some(Self::TooManyBeats),
+			0x56 => Some(Self::TooManyEvents),
+			_ => None,
+		}
+	}
+}
+
+/// Returns the version of the format `bytes` were written with, or `None` if they aren't a song.
+pub fn version(bytes: &[u8]) -> Option<u8> {
+	match bytes {
+		[a, b, c, version, ..] if [*a, *b, *c] == MAGIC => Some(*version),
+		_ => None,
+	}
+}
+
+/// Returns how many bytes [`encode`] writes for `song`.
+pub fn encoded_len(song: &Song) -> usize {
+	let notes = song
+		.notes
+		.iter()
+		.flatten()
+		.map(|notes| notes.len())
+		.sum::<usize>();
+	let events = song
+		.events
+		.iter()
+		.map(|event| 4 + event_payload(&event.kind).map_or(0, |(_, len)| len))
+		.sum::<usize>();
+	HEADER_SIZE + notes * NOTE_SIZE + 2 + events + 1
+}
+
+/// Writes `song` into `buffer` in the newest version of the format, and returns how many bytes were written.
+pub fn encode(song: &Song, buffer: &mut [u8]) -> Result<usize, RawError> {
+	let mut writer = Writer { buffer, len: 0 };
+	writer.write(&MAGIC)?;
+	writer.write(&[VERSION])?;
+	writer.write(&song.end.to_le_bytes())?;
+
+	let count = song
+		.notes
+		.iter()
+		.flatten()
+		.map(|notes| notes.len())
+		.sum::<usize>();
+	writer.write(
+		&u16::try_from(count)
+			.map_err(|_| RawError::TooManyNotes)?
+			.to_le_bytes(),
+	)?;
+	for (beat, notes) in song.notes.iter().enumerate() {
+		for note in notes.iter().flat_map(|notes| notes.iter()) {
+			writer.write(&(beat as u16).to_le_bytes())?;
+			writer.write(&note.frequency.to_le_bytes())?;
+			writer.write(&note.duration.to_le_bytes())?;
+			writer.write(&[note.track, note.chance, note.ratchet])?;
+		}
+	}
+
+	let count = u16::try_from(song.events.len()).map_err(|_| RawError::TooManyEvents)?;
+	writer.write(&count.to_le_bytes())?;
+	for event in song.events {
+		let (kind, len) = event_payload(&event.kind).ok_or(RawError::UnsupportedEvent)?;
+		writer.write(&event.beat.to_le_bytes())?;
+		writer.write(&[kind, len as u8])?;
+		match event.kind {
+			SongEventKind::SetTempo { ticks_per_beat } => {
+				writer.write(&ticks_per_beat.to_le_bytes())?
+			}
+			SongEventKind::TempoRamp { from, to, beats } => {
+				writer.write(&from.to_le_bytes())?;
+				writer.write(&to.to_le_bytes())?;
+				writer.write(&beats.to_le_bytes())?;
+			}
+			SongEventKind::Fermata { percent } => writer.write(&percent.to_le_bytes())?,
+			SongEventKind::Marker { id, transition } => {
+				writer.write(&[id])?;
+				match transition {
+					Transition::Fade { beats } => {
+						writer.write(&[1])?;
+						writer.write(&beats.to_le_bytes())?;
+					}
+					_ => writer.write(&[0])?,
+				}
+			}
+			_ => {}
+		}
+	}
+
+	let checksum = checksum(&writer.buffer[..writer.len]);
+	writer.write(&[checksum])?;
+	Ok(writer.len)
+}
+
+/// Reads a song written in any version of the format up to [`VERSION`].
+///
+/// The song's notes are stored in `notes`, the list of notes starting on each beat in `beats`,
+/// and its events in `events`, like [`Song::parse`]. All three are cleared first.
+pub fn decode<
+	'a: 'b,
+	'b,
+	const MAX_NOTES: usize,
+	const MAX_BEATS: usize,
+	const MAX_EVENTS: usize,
+>(
+	bytes: &[u8],
+	notes: &'a mut ArrayVec<NoteAndDuration, MAX_NOTES>,
+	beats: &'b mut ArrayVec<Option<&'a [NoteAndDuration]>, MAX_BEATS>,
+	events: &'b mut ArrayVec<SongEvent, MAX_EVENTS>,
+) -> Result<Song<'b>, RawError> {
+	notes.clear();
+	beats.clear();
+	events.clear();
+
+	let Some((checksum_byte, body)) = bytes.split_last() else {
+		return Err(RawError::Invalid);
+	};
+	let version = match version(body) {
+		None => return Err(RawError::Invalid),
+		Some(version) if version == 0 || version > VERSION => {
+			return Err(RawError::UnsupportedVersion);
+		}
+		Some(version) => version,
+	};
+	if checksum(body) != *checksum_byte {
+		return Err(RawError::Invalid);
+	}
+
+	let mut reader = Reader {
+		bytes: body,
+		position: MAGIC.len() + 1,
+	};
+	let end = reader.u16()? as usize;
+	if end > MAX_BEATS {
+		return Err(RawError::TooManyBeats);
+	}
+
+	let mut times = ArrayVec::<u16, MAX_NOTES>::new();
+	for _ in 0..reader.u16()? {
+		let beat = reader.u16()?;
+		// Notes are stored in order of the beat they start on, within the song.
+		if beat as usize >= end || times.last().is_some_and(|last| beat < *last) {
+			return Err(RawError::Invalid);
+		}
+		let note = NoteAndDuration {
+			frequency: reader.u16()?,
+			duration: reader.u16()?,
+			track: reader.u8()?,
+			chance: if version >= 2 { reader.u8()? } else { 100 },
+			ratchet: if version >= 3 { reader.u8()? } else { 1 },
+		};
+		// A note lasting no beats would never end.
+		if note.duration == 0 {
+			return Err(RawError::Invalid);
+		}
+		notes.try_push(note).map_err(|_| RawError::TooManyNotes)?;
+		times.try_push(beat).map_err(|_| RawError::TooManyNotes)?;
+	}
+
+	for _ in 0..reader.u16()? {
+		let beat = reader.u16()?;
+		let kind = reader.u8()?;
+		let len = reader.u8()? as usize;
+		let mut payload = Reader {
+			bytes: reader.take(len)?,
+			position: 0,
+		};
+		let kind = match kind {
+			0 => SongEventKind::SetTempo {
+				ticks_per_beat: payload.u16()?,
+			},
+			1 => SongEventKind::TempoRamp {
+				from: payload.u16()?,
+				to: payload.u16()?,
+				beats: payload.u16()?,
+			},
+			2 => SongEventKind::Fermata {
+				percent: payload.u16()?,
+			},
+			3 => SongEventKind::Hold,
+			4 => SongEventKind::Marker {
+				id: payload.u8()?,
+				transition: match payload.u8()? {
+					0 => Transition::Cut,
+					1 => Transition::Fade {
+						beats: payload.u16()?,
+					},
+					_ => return Err(RawError::Invalid),
+				},
+			},
+			// Written by a newer tool, so skip it.
+			_ => continue,
+		};
+		events
+			.try_push(SongEvent { beat, kind })
+			.map_err(|_| RawError::TooManyEvents)?;
+	}
+	if reader.position != body.len() {
+		return Err(RawError::Invalid);
+	}
+
+	let mut song = index_beats(notes, &times, end, beats);
+	song.events = events.as_slice();
+	Ok(song)
+}
+
+/// Returns the kind byte and payload length of `kind`, or `None` if it can't be stored.
+fn event_payload(kind: &SongEventKind) -> Option<(u8, usize)> {
+	match kind {
+		SongEventKind::SetTempo { .. } => Some((0, 2)),
+		SongEventKind::TempoRamp { .. } => Some((1, 6)),
+		SongEventKind::Fermata { .. } => Some((2, 2)),
+		SongEventKind::Hold => Some((3, 0)),
+		SongEventKind::Marker {
+			transition: Transition::Cut,
+			..
+		} => Some((4, 2)),
+		SongEventKind::Marker {
+			transition: Transition::Fade { .. },
+			..
+		} => Some((4, 4)),
+		SongEventKind::Marker {
+			transition: Transition::Fill { .. },
+			..
+		}
+		| SongEventKind::Branch { .. }
+		| SongEventKind::Alternatives { .. } => None,
+	}
+}
+
+/// A simple checksum to catch erased or corrupted storage.
+fn checksum(bytes: &[u8]) -> u8 {
+	bytes
+		.iter()
+		.fold(0u8, |sum, byte| sum.rotate_left(1) ^ byte)
+}
+
+/// Writes bytes into a buffer, failing if it runs out of space.
+struct Writer<'b> {
+	buffer: &'b mut [u8],
+	len: usize,
+}
+
+impl Writer<'_> {
+	fn write(&mut self, bytes: &[u8]) -> Result<(), RawError> {
+		let end = self.len + bytes.len();
+		self.buffer
+			.get_mut(self.len..end)
+			.ok_or(RawError::BufferTooSmall)?
+			.copy_from_slice(bytes);
+		self.len = end;
+		Ok(())
+	}
+}
+
+/// Reads little-endian values from a slice, failing if it runs out.
+struct Reader<'b> {
+	bytes: &'b [u8],
+	position: usize,
+}
+
+impl<'b> Reader<'b> {
+	fn take(&mut self, len: usize) -> Result<&'b [u8], RawError> {
+		let bytes = self
+			.bytes
+			.get(self.position..self.position + len)
+			.ok_or(RawError::Invalid)?;
+		self.position += len;
+		Ok(bytes)
+	}
+
+	fn u8(&mut self) -> Result<u8, RawError> {
+		Ok(self.take(1)?[0])
+	}
+
+	fn u16(&mut self) -> Result<u16, RawError> {
+		let bytes = self.take(2)?;
+		Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::SongMetadata;
+
+	/// Encodes `song` and decodes it again.
+	fn round_trip(song: &Song) -> Result<(u16, usize), RawError> {
+		let mut bytes = [0; 64];
+		let len = encode(song, &mut bytes)?;
+		let mut notes = ArrayVec::<NoteAndDuration, 8>::new();
+		let mut beats = ArrayVec::<_, 8>::new();
+		let mut events = ArrayVec::<_, 4>::new();
+		let song = decode(&bytes[..len], &mut notes, &mut beats, &mut events)?;
+		Ok((song.end, song.notes.iter().flatten().count()))
+	}
+
+	#[test]
+	fn decodes_what_it_encodes() {
+		let song = Song {
+			notes: &[Some(&[NoteAndDuration::new(523, 2, 0)]), None],
+			end: 2,
+			events: &[],
+			metadata: SongMetadata::NONE,
+		};
+		assert_eq!(round_trip(&song), Ok((2, 1)));
+	}
+
+	#[test]
+	fn rejects_notes_without_a_duration() {
+		let song = Song {
+			notes: &[Some(&[NoteAndDuration::new(523, 0, 0)])],
+			end: 1,
+			events: &[],
+			metadata: SongMetadata::NONE,
+		};
+		assert_eq!(round_trip(&song), Err(RawError::Invalid));
+	}
+}
@@ -0,0 +1,75 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use embassy_rp::pwm::{Pwm, SetDutyCycle};
+
+use crate::hal::{set_pwm_tone, set_pwm_tone_fraction};
+use crate::{
+	DEFAULT_SYS_CLOCK_HZ, DutyFraction, FrequencyRange, PwmClock, SetFrequency, ToneError,
+	ToneOutput,
+};
+
+/// The system clock frequency used to calculate the PWM `top`.
+/// The system clock is shared by every PWM slice, so this is shared too.
+static RP_CLOCK_HZ: AtomicU32 = AtomicU32::new(DEFAULT_SYS_CLOCK_HZ);
+
+/// The fractional clock divider used in PWM.
+/// Based on https://pico.implrust.com/buzzer/play-songs/code.html.
+const PWM_DIV_INT: u8 = 64;
+const _: () = assert!(PWM_DIV_INT != 0, "Divider must not be 0");
+
+impl SetFrequency for Pwm<'_> {
+	fn frequency_range(&self) -> FrequencyRange {
+		pwm_clock().playable_range()
+	}
+
+	fn set_frequency(&mut self, frequency: u16) -> Result<(), ToneError> {
+		// `set_config` doesn't work unless this off??
+		self.set_duty_cycle_fully_off()
+			.map_err(|_| ToneError::Output)?;
+
+		let mut pwm_config = embassy_rp::pwm::Config::default();
+		// The slice counts from `0` to `top`, so a period of `top + 1` ticks.
+		pwm_config.top = pwm_clock().period(frequency)? - 1;
+		pwm_config.divider = PWM_DIV_INT.into();
+		self.set_config(&pwm_config);
+		Ok(())
+	}
+
+	fn set_clock_hz(&mut self, clock_hz: u32) {
+		RP_CLOCK_HZ.store(clock_hz, Ordering::Relaxed);
+	}
+}
+
+/// RP PWMs can be given to a [`crate::Player`] directly, without wrapping them in a [`crate::PwmOutput`].
+impl ToneOutput for Pwm<'_> {
+	fn playable_range(&self) -> FrequencyRange {
+		self.frequency_range()
+	}
+
+	fn set_tone(&mut self, frequency: u16, duty: u16) -> Result<(), ToneError> {
+		set_pwm_tone(self, frequency, duty)
+	}
+
+	fn set_tone_fraction(&mut self, frequency: u16, duty: DutyFraction) -> Result<(), ToneError> {
+		set_pwm_tone_fraction(self, frequency, duty)
+	}
+
+	fn set_off(&mut self) {
+		let _ = self.set_duty_cycle_fully_off();
+	}
+
+	fn set_clock_hz(&mut self, clock_hz: u32) {
+		SetFrequency::set_clock_hz(self, clock_hz);
+	}
+}
+
+/// Returns how RP PWM slices are clocked, from the system clock and [`PWM_DIV_INT`].
+/// Based on https://pico.implrust.com/buzzer/play-songs/code.html.
+fn pwm_clock() -> PwmClock {
+	PwmClock {
+		clock_hz: RP_CLOCK_HZ.load(Ordering::Relaxed),
+		divider: PWM_DIV_INT as u32,
+		min_period: 1,
+		max_period: 65535,
+	}
+}
@@ -0,0 +1,104 @@
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Ticker};
+
+use crate::{Player, ToneOutput};
+
+/// Pauses and resumes a [`Player`] from other tasks while [`Player::run_with_control`] drives it.
+///
+/// Only the latest request counts, so pausing and resuming again before the next tick does nothing.
+///
+/// ```rust,ignore
+/// static CONTROL: buzzer_music::RunControl<CriticalSectionRawMutex> = buzzer_music::RunControl::new();
+///
+/// #[embassy_executor::task]
+/// async fn music(mut player: Player<'static, Pwm<'static>, 1, 4>) {
+///     player.run_with_control(Duration::from_millis(40), &CONTROL).await
+/// }
+///
+/// // From a button task:
+/// CONTROL.pause();
+/// ```
+pub struct RunControl<M: RawMutex> {
+	/// `true` to pause and `false` to resume.
+	request: Signal<M, bool>,
+}
+
+impl<M: RawMutex> RunControl<M> {
+	/// The constructor.
+	pub const fn new() -> Self {
+		Self {
+			request: Signal::new(),
+		}
+	}
+
+	/// Pauses the player before its next tick, like [`Player::pause`].
+	pub fn pause(&self) {
+		self.request.signal(true);
+	}
+
+	/// Resumes the player, like [`Player::resume`].
+	pub fn resume(&self) {
+		self.request.signal(false);
+	}
+}
+
+impl<M: RawMutex> Default for RunControl<M> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<'a, O: ToneOutput, const PWM_COUNT: usize, const MAX_SIMULTANEOUS_NOTES: usize>
+	Player<'a, O, PWM_COUNT, MAX_SIMULTANEOUS_NOTES>
+{
+	/// Calls [`Player::tick`] every `tick_period` forever, on an [`embassy_time::Ticker`].
+	///
+	/// Unlike waiting a fixed time after each tick, the ticker keeps the period steady however long
+	/// the ticks take, so the tempo doesn't drift. This also tells the player the period with
+	/// [`Player::set_tick_interval_hint`].
+	pub async fn run(&mut self, tick_period: Duration) -> ! {
+		self.set_tick_interval_hint(period_ms(tick_period));
+		let mut ticker = Ticker::every(tick_period);
+		loop {
+			self.tick();
+			ticker.next().await;
+		}
+	}
+
+	/// Like [`Player::run`], but pauses and resumes when asked to by `control`.
+	///
+	/// While paused, the player isn't ticked at all, and the ticker restarts once it resumes.
+	pub async fn run_with_control<M: RawMutex>(
+		&mut self,
+		tick_period: Duration,
+		control: &RunControl<M>,
+	) -> ! {
+		self.set_tick_interval_hint(period_ms(tick_period));
+		let mut ticker = Ticker::every(tick_period);
+		loop {
+			if let Some(pause) = control.request.try_take() {
+				if pause {
+					self.pause();
+				} else {
+					self.resume();
+				}
+			}
+
+			if self.is_paused() {
+				// Wait for a request to resume, ignoring repeated pauses.
+				while control.request.wait().await {}
+				self.resume();
+				ticker.reset();
+			}
+
+			self.tick();
+			ticker.next().await;
+		}
+	}
+}
+
+/// Returns `period` in whole milliseconds, for [`Player::set_tick_interval_hint`].
+fn period_ms(period: Duration) -> u16 {
+	period.as_millis().min(u16::MAX as u64) as u16
+}
@@ -0,0 +1,117 @@
+use arrayvec::ArrayVec;
+
+use crate::parse::index_beats;
+use crate::{NoteAndDuration, Song, SongParseError};
+
+/// A song built note by note at runtime, for songs generated on the device or received over a radio.
+///
+/// It holds up to `NOTES` notes in a song up to `BEATS` beats long. Notes can be added in any order.
+/// [`RuntimeSong::song`] turns it into a [`Song`] any [`crate::Player`] can play, using a buffer for
+/// the notes starting on each beat, like [`Song::parse`] does.
+///
+/// ```rust
+/// use arrayvec::ArrayVec;
+/// use buzzer_music::{FrequencyRange, Player, RuntimeSong, ShadowOutput};
+///
+/// let mut builder = RuntimeSong::<16, 8>::new();
+/// for (beat, frequency) in [523, 659, 784].into_iter().enumerate() {
+///     builder.add_note(beat as u16 * 2, frequency, 2).unwrap();
+/// }
+///
+/// let mut beats = ArrayVec::new();
+/// let song = builder.song(&mut beats);
+/// assert_eq!(song.end, 6);
+/// assert_eq!(song.notes[2].unwrap()[0].frequency, 659);
+///
+/// let output = ShadowOutput::new(FrequencyRange { min: 20, max: 20_000 }, 1000);
+/// let mut player: Player<ShadowOutput, 1, 4> = Player::new(&song, false, 2, 500, [output]);
+/// player.tick();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RuntimeSong<const BEATS: usize, const NOTES: usize> {
+	/// The notes, sorted by the beat they start on.
+	notes: ArrayVec<NoteAndDuration, NOTES>,
+	/// The beat each note starts on, in the same order.
+	times: ArrayVec<u16, NOTES>,
+	end: u16,
+}
+
+impl<const BEATS: usize, const NOTES: usize> RuntimeSong<BEATS, NOTES> {
+	/// Creates an empty song.
+	pub const fn new() -> Self {
+		Self {
+			notes: ArrayVec::new_const(),
+			times: ArrayVec::new_const(),
+			end: 0,
+		}
+	}
+
+	/// Adds a note on track `0` playing `frequency` for `duration` beats from `beat`.
+	/// See [`RuntimeSong::add`].
+	pub fn add_note(
+		&mut self,
+		beat: u16,
+		frequency: u16,
+		duration: u16,
+	) -> Result<(), SongParseError> {
+		self.add(beat, NoteAndDuration::new(frequency, duration, 0))
+	}
+
+	/// Adds `note` starting on `beat`, after any notes already starting on that beat.
+	/// A duration of `0` is treated as `1`.
+	///
+	/// Returns an error, and leaves the song alone, if it already has `NOTES` notes
+	/// or the note would end after `BEATS` beats.
+	pub fn add(&mut self, beat: u16, note: NoteAndDuration) -> Result<(), SongParseError> {
+		let note = NoteAndDuration {
+			duration: note.duration.max(1),
+			..note
+		};
+		let end = beat as usize + note.duration as usize;
+		if end > BEATS || end > u16::MAX as usize {
+			return Err(SongParseError::TooManyBeats);
+		}
+		if self.notes.is_full() {
+			return Err(SongParseError::TooManyNotes);
+		}
+
+		let index = self.times.partition_point(|time| *time <= beat);
+		self.notes.insert(index, note);
+		self.times.insert(index, beat);
+		self.end = self.end.max(end as u16);
+		Ok(())
+	}
+
+	/// Removes every note.
+	pub fn clear(&mut self) {
+		self.notes.clear();
+		self.times.clear();
+		self.end = 0;
+	}
+
+	/// Returns how many notes the song has.
+	pub fn len(&self) -> usize {
+		self.notes.len()
+	}
+
+	/// Returns `true` if the song has no notes.
+	pub fn is_empty(&self) -> bool {
+		self.notes.is_empty()
+	}
+
+	/// Returns the beat the last note ends on.
+	pub fn end(&self) -> u16 {
+		self.end
+	}
+
+	/// Returns the song, with the notes starting on each beat stored in `beats`, which is cleared first.
+	///
+	/// The song ends when its last note does, and has no events. Add some with [`Song::with_events`].
+	pub fn song<'s: 'b, 'b>(
+		&'s self,
+		beats: &'b mut ArrayVec<Option<&'s [NoteAndDuration]>, BEATS>,
+	) -> Song<'b> {
+		beats.clear();
+		index_beats(&self.notes, &self.times, self.end as usize, beats)
+	}
+}
@@ -0,0 +1,58 @@
+use crate::{FrequencyRange, ToneError, ToneOutput};
+
+/// A silent [`ToneOutput`] that remembers what it was told to play, used by [`crate::Player::shadow`]
+/// and by the helpers in `testing`.
+///
+/// It reports the same [`FrequencyRange`] and maximum duty as the output it stands in for, so a
+/// shadow player adjusts and mixes notes exactly like the real one does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShadowOutput {
+	range: FrequencyRange,
+	max_duty: u16,
+	tone: Option<(u16, u16)>,
+}
+
+impl ShadowOutput {
+	/// Returns a silent output that can play `range` and has a maximum duty of `max_duty`.
+	pub fn new(range: FrequencyRange, max_duty: u16) -> Self {
+		Self {
+			range,
+			max_duty,
+			tone: None,
+		}
+	}
+
+	/// Returns a silent output with the same range and maximum duty as `output`.
+	pub fn mirroring(output: &impl ToneOutput) -> Self {
+		Self::new(output.playable_range(), output.max_duty())
+	}
+
+	/// Returns the frequency and raw duty this output would be playing, or `None` if it would be off.
+	pub fn tone(&self) -> Option<(u16, u16)> {
+		self.tone
+	}
+
+	/// Returns the frequency this output would be playing, or `None` if it would be off.
+	pub fn frequency(&self) -> Option<u16> {
+		self.tone.map(|(frequency, _)| frequency)
+	}
+}
+
+impl ToneOutput for ShadowOutput {
+	fn playable_range(&self) -> FrequencyRange {
+		self.range
+	}
+
+	fn set_tone(&mut self, frequency: u16, duty: u16) -> Result<(), ToneError> {
+		self.tone = Some((frequency, duty));
+		Ok(())
+	}
+
+	fn max_duty(&self) -> u16 {
+		self.max_duty
+	}
+
+	fn set_off(&mut self) {
+		self.tone = None;
+	}
+}
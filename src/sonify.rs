@@ -0,0 +1,313 @@
+use crate::{AudioPolicy, FrequencyRange, SoundCategory, ToneOutput};
+
+/// The frequency ratio of a semitone, in millionths.
+const SEMITONE_MILLIONTHS: u64 = 1_059_463;
+
+/// Returns `frequency` moved up by `semitones` semitones, saturating at `u16::MAX`.
+pub(crate) const fn transpose_up(frequency: u16, semitones: u16) -> u16 {
+	// Octaves are exact, and the remaining semitones are done with 16 fractional bits.
+	let mut result = (frequency as u64) << 16;
+	let mut octaves = semitones / 12;
+	while octaves > 0 && result <= (u16::MAX as u64) << 16 {
+		result *= 2;
+		octaves -= 1;
+	}
+	let mut i = 0;
+	while i < semitones % 12 && result <= (u16::MAX as u64) << 16 {
+		result = result * SEMITONE_MILLIONTHS / 1_000_000;
+		i += 1;
+	}
+
+	let result = (result + (1 << 15)) >> 16;
+	if result > u16::MAX as u64 {
+		u16::MAX
+	} else {
+		result as u16
+	}
+}
+
+/// Returns `frequency` moved down by `semitones` semitones, rounding to the nearest hertz.
+pub(crate) const fn transpose_down(frequency: u16, semitones: u16) -> u16 {
+	let mut result = (frequency as u64) << 16;
+	let mut octaves = semitones / 12;
+	while octaves > 0 && result > 0 {
+		result /= 2;
+		octaves -= 1;
+	}
+	let mut i = 0;
+	while i < semitones % 12 && result > 0 {
+		result = result * 1_000_000 / SEMITONE_MILLIONTHS;
+		i += 1;
+	}
+
+	((result + (1 << 15)) >> 16) as u16
+}
+
+/// Returns `frequency` moved by `semitones` semitones, up if positive and down if negative.
+pub(crate) const fn transpose(frequency: u16, semitones: i8) -> u16 {
+	if semitones < 0 {
+		transpose_down(frequency, semitones.unsigned_abs() as u16)
+	} else {
+		transpose_up(frequency, semitones as u16)
+	}
+}
+
+/// The pitches a [`Sonifier`] is allowed to play.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Scale {
+	/// Any frequency, so small changes in the input are heard as small changes in pitch.
+	#[default]
+	Continuous,
+	/// Every semitone.
+	Chromatic,
+	/// The major scale, starting from the lowest frequency.
+	Major,
+	/// The major pentatonic scale, starting from the lowest frequency.
+	MajorPentatonic,
+	/// The minor pentatonic scale, starting from the lowest frequency.
+	MinorPentatonic,
+}
+
+impl Scale {
+	/// Returns `true` if the note `semitones` semitones above the root is in this scale.
+	const fn contains(&self, semitones: u16) -> bool {
+		let degree = semitones % 12;
+		match self {
+			Scale::Continuous | Scale::Chromatic => true,
+			Scale::Major => matches!(degree, 0 | 2 | 4 | 5 | 7 | 9 | 11),
+			Scale::MajorPentatonic => matches!(degree, 0 | 2 | 4 | 7 | 9),
+			Scale::MinorPentatonic => matches!(degree, 0 | 3 | 5 | 7 | 10),
+		}
+	}
+}
+
+/// Turns a live stream of numbers into sound, for "hear your sensor" debugging and assistive devices.
+///
+/// Each value pushed is mapped from the input range onto a pitch, a volume, and optionally a beep rate,
+/// with exponential smoothing so noisy inputs don't jitter. Higher values are higher, louder, and faster.
+///
+/// ```rust,ignore
+/// // Distance sensor reading 0 to 2000mm, beeping faster as things get closer.
+/// let mut sonifier = buzzer_music::Sonifier::new(buzzer, 0, 2000);
+/// sonifier.set_scale(buzzer_music::Scale::MajorPentatonic);
+/// sonifier.set_interval_range_ticks(Some((25, 2)));
+/// loop {
+///     sonifier.push(2000 - sensor.read_mm());
+///     sonifier.tick();
+///     embassy_time::Timer::after_millis(20).await;
+/// }
+/// ```
+pub struct Sonifier<'p, O: ToneOutput> {
+	output: O,
+	input_min: i32,
+	input_max: i32,
+	smoothing_percent: u8,
+	frequency_range: FrequencyRange,
+	scale: Scale,
+	duty_range: (u16, u16),
+	interval_range_ticks: Option<(u16, u16)>,
+	beep_ticks: u16,
+	category: SoundCategory,
+	policy: Option<&'p AudioPolicy>,
+
+	smoothed: Option<i64>,
+	timer: u16,
+	beep_ticks_left: u16,
+	playing: Option<(u16, u16)>,
+}
+
+impl<'p, O: ToneOutput> Sonifier<'p, O> {
+	/// The constructor.
+	///
+	/// `output` is what the sound is played on. It can be a [`crate::PoolVoice`] to share a buzzer.
+	/// `input_min` and `input_max` are the values mapped to the lowest and highest sounds.
+	/// Values outside them are clamped.
+	pub fn new(output: O, input_min: i32, input_max: i32) -> Self {
+		Self {
+			output,
+			input_min,
+			input_max,
+			smoothing_percent: 100,
+			frequency_range: FrequencyRange {
+				min: 262,
+				max: 1047,
+			},
+			scale: Scale::default(),
+			duty_range: (100, 100),
+			interval_range_ticks: None,
+			beep_ticks: 1,
+			category: SoundCategory::Telemetry,
+			policy: None,
+
+			smoothed: None,
+			timer: 0,
+			beep_ticks_left: 0,
+			playing: None,
+		}
+	}
+
+	/// Sets how much of each new value is mixed into the smoothed value, from `1` to `100`.
+	/// Lower values are smoother but slower to follow the input. Defaults to `100` (no smoothing).
+	pub fn set_smoothing_percent(&mut self, percent: u8) {
+		self.smoothing_percent = percent.clamp(1, 100);
+	}
+
+	/// Sets the frequencies the input is mapped onto. Defaults to C4 to C6.
+	pub fn set_frequency_range(&mut self, range: FrequencyRange) {
+		self.frequency_range = range;
+	}
+
+	/// Sets the pitches that can be played. Defaults to [`Scale::Continuous`].
+	pub fn set_scale(&mut self, scale: Scale) {
+		self.scale = scale;
+	}
+
+	/// Sets the duties (volumes) the lowest and highest values are played at. Defaults to `100` for both.
+	pub fn set_duty_range(&mut self, min: u16, max: u16) {
+		self.duty_range = (min, max);
+	}
+
+	/// Beeps instead of playing a continuous tone, with `Some((slowest, fastest))` ticks between the starts
+	/// of beeps for the lowest and highest values. `None` plays a continuous tone. Defaults to `None`.
+	pub fn set_interval_range_ticks(&mut self, range: Option<(u16, u16)>) {
+		self.interval_range_ticks = range;
+	}
+
+	/// Sets how many ticks each beep lasts. Defaults to `1`.
+	pub fn set_beep_ticks(&mut self, ticks: u16) {
+		self.beep_ticks = ticks.max(1);
+	}
+
+	/// Sets the [`SoundCategory`] of the sound. Defaults to [`SoundCategory::Telemetry`].
+	pub fn set_category(&mut self, category: SoundCategory) {
+		self.category = category;
+	}
+
+	/// Sets the [`AudioPolicy`] the sound follows, or `None` to ignore policies.
+	pub fn set_policy(&mut self, policy: Option<&'p AudioPolicy>) {
+		self.policy = policy;
+	}
+
+	/// Adds a new input value.
+	pub fn push(&mut self, value: i32) {
+		let value = (value.clamp(
+			self.input_min.min(self.input_max),
+			self.input_max.max(self.input_min),
+		) as i64) << 8;
+		self.smoothed = Some(match self.smoothed {
+			Some(smoothed) => smoothed + (value - smoothed) * self.smoothing_percent as i64 / 100,
+			None => value,
+		});
+	}
+
+	/// Forgets the input and goes silent until a new value is pushed.
+	pub fn clear(&mut self) {
+		self.smoothed = None;
+		self.stop();
+	}
+
+	/// Returns where the smoothed input is in the input range, from `0` to `1000`.
+	/// Returns `None` if no value has been pushed.
+	pub fn position_milli(&self) -> Option<u16> {
+		let smoothed = self.smoothed?;
+		let span = (self.input_max as i64 - self.input_min as i64) << 8;
+		if span == 0 {
+			return Some(1000);
+		}
+		let offset = smoothed - ((self.input_min as i64) << 8);
+		Some((offset * 1000 / span).clamp(0, 1000) as u16)
+	}
+
+	/// Returns the frequency the current input is played at.
+	pub fn frequency(&self) -> Option<u16> {
+		let position = self.position_milli()? as u32;
+		let FrequencyRange { min, max } = self.frequency_range;
+		if self.scale == Scale::Continuous || max <= min {
+			return Some(lerp(min, max, position));
+		}
+
+		// Count the notes of the scale in the range, then pick one by position.
+		let mut notes = 0;
+		let mut semitones = 0;
+		while transpose_up(min, semitones) <= max && semitones < u16::MAX {
+			if self.scale.contains(semitones) {
+				notes += 1;
+			}
+			semitones += 1;
+		}
+
+		let mut target = (position * notes.max(1) as u32 / 1001) as u16;
+		let mut semitones = 0;
+		loop {
+			if self.scale.contains(semitones) {
+				if target == 0 {
+					return Some(transpose_up(min, semitones));
+				}
+				target -= 1;
+			}
+			semitones += 1;
+		}
+	}
+
+	/// Updates the sound. This should be called every loop, like [`crate::Player::tick`].
+	pub fn tick(&mut self) {
+		let (Some(position), Some(frequency)) = (self.position_milli(), self.frequency()) else {
+			self.stop();
+			return;
+		};
+
+		let duty = lerp(self.duty_range.0, self.duty_range.1, position as u32);
+		let duty = match self.policy {
+			Some(policy) => policy.apply(self.category, duty),
+			None => duty,
+		};
+
+		let sounding = match self.interval_range_ticks {
+			None => true,
+			Some((slowest, fastest)) => {
+				let interval = lerp(slowest, fastest, position as u32).max(1);
+				self.timer = self.timer.saturating_add(1);
+				if self.timer >= interval {
+					self.timer = 0;
+					self.beep_ticks_left = self.beep_ticks;
+				}
+				if self.beep_ticks_left > 0 {
+					self.beep_ticks_left -= 1;
+					true
+				} else {
+					false
+				}
+			}
+		};
+
+		if !sounding || duty == 0 {
+			self.stop();
+		} else if self.playing != Some((frequency, duty)) {
+			self.playing = self
+				.output
+				.set_tone(frequency, duty)
+				.is_ok()
+				.then_some((frequency, duty));
+		}
+	}
+
+	/// Consumes this sonifier and returns its output.
+	pub fn into_output(mut self) -> O {
+		self.stop();
+		self.output
+	}
+
+	/// Turns the output off.
+	fn stop(&mut self) {
+		if self.playing.take().is_some() {
+			self.output.set_off();
+		}
+	}
+}
+
+/// Returns the value `position_milli / 1000` of the way from `from` to `to`.
+const fn lerp(from: u16, to: u16, position_milli: u32) -> u16 {
+	let from = from as i64;
+	let to = to as i64;
+	(from + (to - from) * position_milli as i64 / 1000) as u16
+}
@@ -0,0 +1,29 @@
+/// Counters of everything that made a [`crate::Player`] sound different from its song.
+///
+/// Returned by [`crate::Player::stats`]. Field units can report these in telemetry so
+/// degraded audio doesn't go unnoticed. Every counter saturates instead of wrapping.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PlaybackStats {
+	/// Notes cut short because too many notes were playing at once.
+	pub voice_steals: u32,
+	/// Notes that were never played, because the PWMs could not produce them.
+	pub dropped_notes: u32,
+	/// Notes the PWMs could not produce, whether they were moved or dropped.
+	/// See [`crate::UnplayableNotePolicy`].
+	pub out_of_range_notes: u32,
+	/// Ticks that were caught up on with [`crate::Player::tick_by`] instead of happening on time.
+	pub missed_ticks: u32,
+	/// Times a PWM returned an error when given a tone.
+	pub pwm_errors: u32,
+}
+
+impl PlaybackStats {
+	/// Returns `true` if playback hasn't degraded at all.
+	pub const fn is_clean(&self) -> bool {
+		self.voice_steals == 0
+			&& self.dropped_notes == 0
+			&& self.out_of_range_notes == 0
+			&& self.missed_ticks == 0
+			&& self.pwm_errors == 0
+	}
+}
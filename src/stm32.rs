@@ -0,0 +1,113 @@
+use core::convert::Infallible;
+
+use embassy_stm32::time::Hertz;
+use embassy_stm32::timer::simple_pwm::SimplePwm;
+use embassy_stm32::timer::{Channel, GeneralInstance4Channel};
+use embedded_hal::pwm::{ErrorType, SetDutyCycle};
+
+use crate::hal::{set_pwm_tone, set_pwm_tone_fraction};
+use crate::{DutyFraction, FrequencyRange, SetFrequency, ToneError, ToneOutput, saturate_u16};
+
+/// The fewest timer clock cycles one cycle of a tone lasts, so the duty has some resolution left.
+const MIN_PERIOD: u32 = 2;
+
+/// A buzzer on one channel of an `embassy-stm32` timer, which a [`crate::Player`] can play on directly.
+///
+/// On STM32 chips, the frequency belongs to the timer while the duty belongs to one of its channels,
+/// so each buzzer needs a timer of its own. Unlike an RP2040 slice, the timer works out its own
+/// prescaler and auto-reload value for each frequency from the clock the RCC gives it, and the
+/// maximum duty follows the auto-reload value.
+///
+/// ```rust,ignore
+/// use embassy_stm32::gpio::OutputType;
+/// use embassy_stm32::time::Hertz;
+/// use embassy_stm32::timer::Channel;
+/// use embassy_stm32::timer::low_level::CountingMode;
+/// use embassy_stm32::timer::simple_pwm::{PwmPin, SimplePwm};
+///
+/// let pin = PwmPin::new(p.PA6, OutputType::PushPull);
+/// let pwm = SimplePwm::new(p.TIM3, Some(pin), None, None, None, Hertz(440), CountingMode::EdgeAlignedUp);
+/// let buzzer = buzzer_music::Stm32Buzzer::new(pwm, Channel::Ch1);
+/// let mut player = buzzer_music::Player::new(&SONG, true, 3, 100, [buzzer]);
+/// ```
+pub struct Stm32Buzzer<'d, T: GeneralInstance4Channel> {
+	pwm: SimplePwm<'d, T>,
+	channel: Channel,
+}
+
+impl<'d, T: GeneralInstance4Channel> Stm32Buzzer<'d, T> {
+	/// Plays on `channel` of `pwm`, which is enabled and starts silent.
+	pub fn new(mut pwm: SimplePwm<'d, T>, channel: Channel) -> Self {
+		let mut output = pwm.channel(channel);
+		output.set_duty_cycle_fully_off();
+		output.enable();
+		Self { pwm, channel }
+	}
+
+	/// Consumes this and returns the timer's PWM driver.
+	pub fn into_inner(self) -> SimplePwm<'d, T> {
+		self.pwm
+	}
+}
+
+impl<T: GeneralInstance4Channel> ErrorType for Stm32Buzzer<'_, T> {
+	type Error = Infallible;
+}
+
+impl<T: GeneralInstance4Channel> SetDutyCycle for Stm32Buzzer<'_, T> {
+	fn max_duty_cycle(&self) -> u16 {
+		self.pwm.max_duty_cycle()
+	}
+
+	fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+		self.pwm.channel(self.channel).set_duty_cycle(duty);
+		Ok(())
+	}
+}
+
+impl<T: GeneralInstance4Channel> SetFrequency for Stm32Buzzer<'_, T> {
+	fn frequency_range(&self) -> FrequencyRange {
+		// The 16-bit prescaler and auto-reload value together divide the clock by up to 2^32,
+		// which is below 1 hertz for any timer clock.
+		let clock_hz = embassy_stm32::rcc::frequency::<T>().0;
+		FrequencyRange {
+			min: 1,
+			max: saturate_u16(clock_hz / MIN_PERIOD),
+		}
+	}
+
+	fn set_frequency(&mut self, frequency: u16) -> Result<(), ToneError> {
+		let range = self.frequency_range();
+		if frequency < range.min {
+			return Err(ToneError::FrequencyTooLow);
+		}
+		if frequency > range.max {
+			return Err(ToneError::FrequencyTooHigh);
+		}
+
+		self.pwm.set_frequency(Hertz(frequency as u32));
+		Ok(())
+	}
+}
+
+impl<T: GeneralInstance4Channel> ToneOutput for Stm32Buzzer<'_, T> {
+	fn playable_range(&self) -> FrequencyRange {
+		self.frequency_range()
+	}
+
+	fn set_tone(&mut self, frequency: u16, duty: u16) -> Result<(), ToneError> {
+		set_pwm_tone(self, frequency, duty)
+	}
+
+	fn max_duty(&self) -> u16 {
+		self.max_duty_cycle()
+	}
+
+	fn set_tone_fraction(&mut self, frequency: u16, duty: DutyFraction) -> Result<(), ToneError> {
+		set_pwm_tone_fraction(self, frequency, duty)
+	}
+
+	fn set_off(&mut self) {
+		self.pwm.channel(self.channel).set_duty_cycle_fully_off();
+	}
+}
@@ -0,0 +1,88 @@
+use crate::NoteAndDuration;
+
+/// The most tracks a [`crate::Player`] can strum.
+pub const MAX_STRUMMED_TRACKS: usize = 8;
+
+/// The order a chord's notes start in when strummed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StrumDirection {
+	/// From the lowest note to the highest, like a downstroke on a guitar.
+	#[default]
+	Up,
+	/// From the highest note to the lowest.
+	Down,
+}
+
+/// How a track is strummed.
+#[derive(Clone, Copy)]
+struct TrackStrum {
+	track: u8,
+	direction: StrumDirection,
+	ticks_per_note: u8,
+}
+
+/// The strums of a [`crate::Player`]'s tracks.
+#[derive(Clone, Default)]
+pub(crate) struct StrumTable {
+	tracks: arrayvec::ArrayVec<TrackStrum, MAX_STRUMMED_TRACKS>,
+}
+
+impl StrumTable {
+	pub(crate) const fn new() -> Self {
+		Self {
+			tracks: arrayvec::ArrayVec::new_const(),
+		}
+	}
+
+	/// Strums `track` in `direction`, starting each note `ticks_per_note` ticks after the last.
+	/// A `ticks_per_note` of `0` stops strumming the track.
+	/// Returns `false` if [`MAX_STRUMMED_TRACKS`] other tracks are already strummed.
+	pub(crate) fn set(&mut self, track: u8, direction: StrumDirection, ticks_per_note: u8) -> bool {
+		let index = self.tracks.iter().position(|entry| entry.track == track);
+		if ticks_per_note == 0 {
+			if let Some(index) = index {
+				self.tracks.swap_remove(index);
+			}
+			return true;
+		}
+
+		let entry = TrackStrum {
+			track,
+			direction,
+			ticks_per_note,
+		};
+		match index {
+			Some(index) => self.tracks[index] = entry,
+			None => return self.tracks.try_push(entry).is_ok(),
+		}
+		true
+	}
+
+	/// Returns how many ticks after the beat starts `chord[index]` should start.
+	///
+	/// `chord` is every note that started on the beat. Only the notes on the same track
+	/// are counted as part of its chord.
+	pub(crate) fn delay(&self, chord: &[NoteAndDuration], index: usize) -> u16 {
+		let note = chord[index];
+		let Some(strum) = self.tracks.iter().find(|entry| entry.track == note.track) else {
+			return 0;
+		};
+
+		// Count the notes on the track that start before this one. Equal notes go in order.
+		let earlier = chord
+			.iter()
+			.enumerate()
+			.filter(|(i, other)| *i != index && other.track == note.track)
+			.filter(|(i, other)| match strum.direction {
+				StrumDirection::Up => (other.frequency, *i) < (note.frequency, index),
+				StrumDirection::Down => (other.frequency, index) > (note.frequency, *i),
+			})
+			.count();
+		earlier as u16 * strum.ticks_per_note as u16
+	}
+
+	/// Returns `true` if no tracks are strummed.
+	pub(crate) fn is_empty(&self) -> bool {
+		self.tracks.is_empty()
+	}
+}
@@ -0,0 +1,284 @@
+use crate::Song;
+use crate::analysis::BeatTimeline;
+
+/// How close to a note's onset a press must be, in ticks, for each [`Grade`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HitWindows {
+	pub perfect_ticks: u16,
+	pub great_ticks: u16,
+	pub good_ticks: u16,
+}
+
+impl Default for HitWindows {
+	fn default() -> Self {
+		Self {
+			perfect_ticks: 1,
+			great_ticks: 2,
+			good_ticks: 4,
+		}
+	}
+}
+
+/// How accurately a note was played.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Grade {
+	Perfect,
+	Great,
+	Good,
+	/// The note wasn't played within [`HitWindows::good_ticks`].
+	Miss,
+}
+
+/// The result of a press that matched a note.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Judgment {
+	/// The beat of the note.
+	pub beat: u16,
+	/// How many ticks after the onset the press was. Negative if it was early.
+	pub offset_ticks: i32,
+	pub grade: Grade,
+}
+
+/// Counts of each [`Grade`] for a song or a phrase.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Score {
+	pub perfect: u16,
+	pub great: u16,
+	pub good: u16,
+	pub missed: u16,
+	/// Presses that didn't match any note.
+	pub extra: u16,
+}
+
+impl Score {
+	/// Returns how many notes have been judged.
+	pub const fn judged(&self) -> u16 {
+		self.perfect + self.great + self.good + self.missed
+	}
+
+	/// Returns an accuracy from `0` to `100`. Perfect notes count fully, great notes 70%,
+	/// good notes 40%, and misses and extra presses nothing.
+	/// Returns `100` if nothing has been judged.
+	pub const fn accuracy_percent(&self) -> u8 {
+		let total = self.judged() as u32 + self.extra as u32;
+		if total == 0 {
+			return 100;
+		}
+		let points = self.perfect as u32 * 100 + self.great as u32 * 70 + self.good as u32 * 40;
+		(points / total) as u8
+	}
+
+	/// Adds `grade` to the counts.
+	fn record(&mut self, grade: Grade) {
+		match grade {
+			Grade::Perfect => self.perfect += 1,
+			Grade::Great => self.great += 1,
+			Grade::Good => self.good += 1,
+			Grade::Miss => self.missed += 1,
+		}
+	}
+}
+
+/// Scores button presses against the note onsets of a [`Song`], for rhythm games and music teaching.
+///
+/// Press times are in ticks since beat `0` of the song started, and are matched to the earliest
+/// onset not yet judged. Beats with several notes are a single onset. Call [`update`] regularly
+/// so notes that were never pressed are counted as misses.
+///
+/// The song is split into phrases of `phrase_beats` beats (8 by default), each scored separately.
+///
+/// ```rust,ignore
+/// let mut play_along = buzzer_music::PlayAlong::new(&SONG, 3);
+/// play_along.set_track(Some(1));
+///
+/// loop {
+///     if button.is_pressed() {
+///         if let Some(judgment) = play_along.press(ticks) {
+///             show(judgment.grade);
+///         }
+///     }
+///     play_along.update(ticks);
+///     ticks += 1;
+/// }
+/// ```
+pub struct PlayAlong<'s> {
+	song: &'s Song<'s>,
+	track: Option<u8>,
+	windows: HitWindows,
+	phrase_beats: u16,
+	phrase_callback: Option<fn(u16, Score)>,
+
+	timeline: BeatTimeline,
+	has_onset: bool,
+	phrase: u16,
+	phrase_score: Score,
+	score: Score,
+}
+
+impl<'s> PlayAlong<'s> {
+	/// The constructor.
+	///
+	/// `ticks_per_beat` is the tempo the song is played at, the same as given to [`crate::Player::new`].
+	pub fn new(song: &'s Song<'s>, ticks_per_beat: u16) -> Self {
+		let mut play_along = Self {
+			song,
+			track: None,
+			windows: HitWindows::default(),
+			phrase_beats: 8,
+			phrase_callback: None,
+
+			timeline: BeatTimeline::new(ticks_per_beat),
+			has_onset: true,
+			phrase: 0,
+			phrase_score: Score::default(),
+			score: Score::default(),
+		};
+		play_along.find_onset();
+		play_along
+	}
+
+	/// Only scores notes on `track`, or every note if `None`.
+	/// This should be set before any presses.
+	pub fn set_track(&mut self, track: Option<u8>) {
+		self.track = track;
+		self.find_onset();
+	}
+
+	/// Sets how close presses must be to score each [`Grade`].
+	pub fn set_hit_windows(&mut self, windows: HitWindows) {
+		self.windows = windows;
+	}
+
+	/// Sets how many beats each phrase lasts. A length of `0` is treated as `1`.
+	pub fn set_phrase_beats(&mut self, beats: u16) {
+		self.phrase_beats = beats.max(1);
+	}
+
+	/// Sets a function called with the index and [`Score`] of each phrase once all its notes are judged.
+	pub fn set_phrase_callback(&mut self, callback: Option<fn(u16, Score)>) {
+		self.phrase_callback = callback;
+	}
+
+	/// Scores a press at `tick`.
+	/// Returns the [`Judgment`] of the note it matched, or `None` if it didn't match one.
+	pub fn press(&mut self, tick: u32) -> Option<Judgment> {
+		self.update(tick);
+
+		let offset = tick as i64 - self.onset_tick()? as i64;
+		let distance = offset.unsigned_abs();
+		let grade = if distance <= self.windows.perfect_ticks as u64 {
+			Grade::Perfect
+		} else if distance <= self.windows.great_ticks as u64 {
+			Grade::Great
+		} else if distance <= self.windows.good_ticks as u64 {
+			Grade::Good
+		} else {
+			self.score.extra += 1;
+			self.phrase_score.extra += 1;
+			return None;
+		};
+
+		let judgment = Judgment {
+			beat: self.timeline.beat(),
+			offset_ticks: offset as i32,
+			grade,
+		};
+		self.judge(grade);
+		Some(judgment)
+	}
+
+	/// Counts every note whose window ended before `tick` as missed.
+	/// Returns how many notes were missed.
+	pub fn update(&mut self, tick: u32) -> u16 {
+		let mut missed = 0;
+		while let Some(onset) = self.onset_tick()
+			&& onset + (self.windows.good_ticks as u64) < tick as u64
+		{
+			self.judge(Grade::Miss);
+			missed += 1;
+		}
+		missed
+	}
+
+	/// Returns the score of the whole song so far.
+	pub fn score(&self) -> Score {
+		self.score
+	}
+
+	/// Returns the score of the current phrase so far.
+	pub fn phrase_score(&self) -> Score {
+		self.phrase_score
+	}
+
+	/// Returns the index of the current phrase.
+	pub fn phrase(&self) -> u16 {
+		self.phrase
+	}
+
+	/// Returns `true` once every note has been judged.
+	pub fn is_finished(&self) -> bool {
+		!self.has_onset
+	}
+
+	/// Returns the tick of the next note to judge, if there is one.
+	fn onset_tick(&self) -> Option<u64> {
+		self.has_onset.then(|| self.timeline.start_tick())
+	}
+
+	/// Records `grade` for the current onset and moves to the next one.
+	fn judge(&mut self, grade: Grade) {
+		self.score.record(grade);
+		self.phrase_score.record(grade);
+		if self.timeline.advance(self.song) {
+			self.find_onset();
+		} else {
+			self.has_onset = false;
+			self.finish_phrase();
+		}
+	}
+
+	/// Moves forward from the current beat to the next one with a note to play.
+	fn find_onset(&mut self) {
+		if !self.has_onset {
+			return;
+		}
+
+		while !self.is_onset(self.timeline.beat()) {
+			if !self.timeline.advance(self.song) {
+				self.has_onset = false;
+				self.finish_phrase();
+				return;
+			}
+		}
+
+		let phrase = self.timeline.beat() / self.phrase_beats;
+		if phrase != self.phrase {
+			self.finish_phrase();
+			self.phrase = phrase;
+		}
+	}
+
+	/// Returns `true` if a note that should be played starts on `beat`.
+	fn is_onset(&self, beat: u16) -> bool {
+		if beat >= self.song.end {
+			return false;
+		}
+		let Some(Some(notes)) = self.song.notes.get(beat as usize) else {
+			return false;
+		};
+		match self.track {
+			Some(track) => notes.iter().any(|note| note.track == track),
+			None => !notes.is_empty(),
+		}
+	}
+
+	/// Reports the current phrase's score and starts a new one.
+	fn finish_phrase(&mut self) {
+		if self.phrase_score != Score::default()
+			&& let Some(callback) = self.phrase_callback
+		{
+			callback(self.phrase, self.phrase_score);
+		}
+		self.phrase_score = Score::default();
+	}
+}
@@ -0,0 +1,81 @@
+use crate::{DutyFraction, FrequencyRange, ToneError, ToneOutput};
+
+/// A [`ToneOutput`] that plays everything on a `primary` output and mirrors it to a `secondary` one.
+///
+/// This lets a [`crate::Player`] drive a buzzer and, for example, a [`crate::LedOutput`] or
+/// [`crate::HapticOutput`] at the same time without a custom wrapper.
+///
+/// The primary output is in charge: its [`FrequencyRange`] is used and its errors are reported.
+/// Notes the secondary output can't produce are ignored on it.
+///
+/// ```rust,ignore
+/// let output = buzzer_music::TeeOutput::new(buzzer_pwm, buzzer_music::LedOutput::new([led_pwm], 100));
+/// let mut player = buzzer_music::Player::new(&SONG, true, 3, 100, [output]);
+/// ```
+pub struct TeeOutput<A: ToneOutput, B: ToneOutput> {
+	primary: A,
+	secondary: B,
+}
+
+impl<A: ToneOutput, B: ToneOutput> TeeOutput<A, B> {
+	/// The constructor.
+	pub fn new(primary: A, secondary: B) -> Self {
+		Self { primary, secondary }
+	}
+
+	/// Returns the primary output.
+	pub fn primary(&self) -> &A {
+		&self.primary
+	}
+
+	/// Returns the secondary output.
+	pub fn secondary(&self) -> &B {
+		&self.secondary
+	}
+
+	/// Returns the secondary output mutably, for example to reconfigure it during playback.
+	pub fn secondary_mut(&mut self) -> &mut B {
+		&mut self.secondary
+	}
+
+	/// Consumes this output and returns the primary and secondary outputs.
+	pub fn into_inner(self) -> (A, B) {
+		(self.primary, self.secondary)
+	}
+}
+
+impl<A: ToneOutput, B: ToneOutput> ToneOutput for TeeOutput<A, B> {
+	fn playable_range(&self) -> FrequencyRange {
+		self.primary.playable_range()
+	}
+
+	fn set_tone(&mut self, frequency: u16, duty: u16) -> Result<(), ToneError> {
+		let result = self.primary.set_tone(frequency, duty);
+		if result.is_ok() && self.secondary.set_tone(frequency, duty).is_err() {
+			self.secondary.set_off();
+		}
+		result
+	}
+
+	fn max_duty(&self) -> u16 {
+		self.primary.max_duty()
+	}
+
+	fn set_tone_fraction(&mut self, frequency: u16, duty: DutyFraction) -> Result<(), ToneError> {
+		let result = self.primary.set_tone_fraction(frequency, duty);
+		if result.is_ok() && self.secondary.set_tone_fraction(frequency, duty).is_err() {
+			self.secondary.set_off();
+		}
+		result
+	}
+
+	fn set_off(&mut self) {
+		self.primary.set_off();
+		self.secondary.set_off();
+	}
+
+	fn set_clock_hz(&mut self, clock_hz: u32) {
+		self.primary.set_clock_hz(clock_hz);
+		self.secondary.set_clock_hz(clock_hz);
+	}
+}
@@ -0,0 +1,191 @@
+//! Helpers for testing songs and player configurations on the host, with the `std` feature.
+//!
+//! [`expect`] plays a song on silent [`ShadowOutput`]s and records which notes are sounding as
+//! each beat starts, so tests can check an arrangement beat by beat:
+//!
+//! ```rust
+//! use buzzer_music::declare_song;
+//! use buzzer_music::testing::expect;
+//!
+//! const SONG: buzzer_music::Song = declare_song!("0 C5 4 0;0 E5 2 1;4 G5 1 0");
+//! expect(&SONG)
+//!     .at_beat(0).plays(&["C5", "E5"])
+//!     .at_beat(2).plays(&["C5"])
+//!     .at_beat(4).plays(&["G5"])
+//!     .at_beat(5).is_silent();
+//! ```
+//!
+//! [`expect_with`] configures the player first, to test transposition, muted tracks, and the like.
+
+// Failed checks panic like `assert!` does, which is what tests expect, even with `strict-lints`.
+#![cfg_attr(feature = "strict-lints", allow(clippy::panic))]
+
+use std::format;
+use std::string::String;
+use std::vec::Vec;
+
+use crate::parse::parse_note_name;
+use crate::{FrequencyRange, Player, ShadowOutput, Song};
+
+/// How many notes the player used by [`expect`] plays at once, each on its own output.
+pub const CHANNELS: usize = 8;
+
+/// The player [`expect_with`] configures.
+pub type TestPlayer<'a> = Player<'a, ShadowOutput, CHANNELS, CHANNELS>;
+
+/// The most ticks a song is played for, so songs that never end still finish rendering.
+const MAX_TICKS: u32 = 1_000_000;
+
+/// The notes sounding on each beat of a song, recorded by [`expect`] or [`expect_with`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expectation {
+	/// The frequencies sounding as each beat starts, in order, or `None` for beats never played.
+	beats: Vec<Option<Vec<u16>>>,
+}
+
+/// Plays `song` once, without looping, and records which notes sound on each beat.
+///
+/// The player ticks twice per beat, with every note on its own output that can play any
+/// frequency from 20 to 20000 hertz.
+pub fn expect<'a>(song: &'a Song<'a>) -> Expectation {
+	expect_with(song, |_| {})
+}
+
+/// Like [`expect`], but calls `configure` on the player before it starts playing.
+///
+/// ```rust
+/// use buzzer_music::declare_song;
+/// use buzzer_music::testing::expect_with;
+///
+/// const SONG: buzzer_music::Song = declare_song!("0 C5 1 0;0 E5 1 1");
+/// expect_with(&SONG, |player| player.set_track_muted(1, true))
+///     .at_beat(0)
+///     .plays(&["C5"]);
+/// ```
+pub fn expect_with<'a>(
+	song: &'a Song<'a>,
+	configure: impl FnOnce(&mut TestPlayer<'a>),
+) -> Expectation {
+	let output = ShadowOutput::new(
+		FrequencyRange {
+			min: 20,
+			max: 20000,
+		},
+		1000,
+	);
+	let mut player: TestPlayer<'a> = Player::new(song, false, 2, 500, [output; CHANNELS]);
+	configure(&mut player);
+
+	let mut expectation = Expectation { beats: Vec::new() };
+	let mut last_beat = None;
+	let mut ticks = 0;
+	while player.tick() && ticks < MAX_TICKS {
+		let beat = player.current_beat();
+		if beat != last_beat
+			&& let Some(beat) = beat
+		{
+			expectation.record(beat, &player);
+		}
+		last_beat = beat;
+		ticks += 1;
+	}
+	expectation
+}
+
+impl Expectation {
+	/// Returns a check of the notes sounding as `beat` starts.
+	pub fn at_beat(&self, beat: u16) -> BeatExpectation<'_> {
+		BeatExpectation {
+			expectation: self,
+			beat,
+		}
+	}
+
+	/// Returns the frequencies sounding as `beat` starts, lowest first,
+	/// or `None` if it was never played.
+	pub fn sounding(&self, beat: u16) -> Option<&[u16]> {
+		self.beats.get(beat as usize)?.as_deref()
+	}
+
+	/// Records the frequencies `player` is sounding on `beat`.
+	fn record(&mut self, beat: u16, player: &TestPlayer) {
+		let mut sounding: Vec<u16> = player
+			.pwms()
+			.iter()
+			.filter_map(|output| match output.tone() {
+				Some((frequency, duty)) if duty > 0 => Some(frequency),
+				_ => None,
+			})
+			.collect();
+		sounding.sort_unstable();
+
+		let beat = beat as usize;
+		if self.beats.len() <= beat {
+			self.beats.resize(beat + 1, None);
+		}
+		self.beats[beat] = Some(sounding);
+	}
+}
+
+/// A check of one beat of an [`Expectation`], made with [`Expectation::at_beat`].
+#[derive(Clone, Copy, Debug)]
+pub struct BeatExpectation<'e> {
+	expectation: &'e Expectation,
+	beat: u16,
+}
+
+impl<'e> BeatExpectation<'e> {
+	/// Panics unless exactly `notes` are sounding as the beat starts, in any order.
+	///
+	/// Notes are written like in a song, like `"C5"` or `"F#3"`, or as a frequency in hertz.
+	/// Returns the [`Expectation`] so more beats can be checked.
+	#[track_caller]
+	pub fn plays(&self, notes: &[&str]) -> &'e Expectation {
+		let mut expected: Vec<u16> = notes.iter().map(|note| frequency_of(note)).collect();
+		expected.sort_unstable();
+
+		match self.expectation.sounding(self.beat) {
+			Some(sounding) if sounding == expected => {}
+			Some(sounding) => panic!(
+				"expected beat {} to play {} ({}), but it played {}",
+				self.beat,
+				notes.join(", "),
+				hertz_list(&expected),
+				hertz_list(sounding),
+			),
+			None => panic!(
+				"expected beat {} to play {}, but it was never played",
+				self.beat,
+				notes.join(", ")
+			),
+		}
+		self.expectation
+	}
+
+	/// Panics unless nothing is sounding as the beat starts.
+	#[track_caller]
+	pub fn is_silent(&self) -> &'e Expectation {
+		self.plays(&[])
+	}
+}
+
+/// Returns the frequency of a note name or a number of hertz, panicking if it is neither.
+#[track_caller]
+fn frequency_of(note: &str) -> u16 {
+	match parse_note_name(note).or_else(|| note.parse().ok()) {
+		Some(frequency) => frequency,
+		None => panic!("`{note}` is not a note name or a frequency"),
+	}
+}
+
+/// Formats `frequencies` for a failed check, like `523 Hz, 659 Hz` or `nothing`.
+fn hertz_list(frequencies: &[u16]) -> String {
+	if frequencies.is_empty() {
+		return String::from("nothing");
+	}
+	let parts: Vec<String> = frequencies
+		.iter()
+		.map(|frequency| format!("{frequency} Hz"))
+		.collect();
+	parts.join(", ")
+}
@@ -0,0 +1,1057 @@
+//! Tests of the [`Player`] state machine, checking what it writes to [`ShadowOutput`]s tick by tick.
+
+// Failed checks panic, like in any test, even with `strict-lints`.
+#![cfg_attr(feature = "strict-lints", allow(clippy::panic, clippy::unwrap_used))]
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+use crate::{
+	EndBehavior, FrequencyRange, LivePattern, NoteAndDuration, Player, PlayerPool, Playlist,
+	ShadowOutput, Song, SongEvent, SongEventKind, SongMetadata, SpeedTrainer, StingerPolicy,
+	TakeOrder, ToneError, ToneOutput, Transition,
+};
+
+/// An output that can play any audible frequency, with a maximum duty of `1000`.
+fn output() -> ShadowOutput {
+	ShadowOutput::new(
+		FrequencyRange {
+			min: 20,
+			max: 20_000,
+		},
+		1000,
+	)
+}
+
+/// A [`ShadowOutput`] that also counts how many times it was set up, to check what the player leaves alone.
+#[derive(Clone, Copy)]
+struct CountingOutput {
+	shadow: ShadowOutput,
+	writes: u32,
+}
+
+impl ToneOutput for CountingOutput {
+	fn playable_range(&self) -> FrequencyRange {
+		self.shadow.playable_range()
+	}
+
+	fn set_tone(&mut self, frequency: u16, duty: u16) -> Result<(), ToneError> {
+		self.writes += 1;
+		self.shadow.set_tone(frequency, duty)
+	}
+
+	fn max_duty(&self) -> u16 {
+		self.shadow.max_duty()
+	}
+
+	fn set_off(&mut self) {
+		self.writes += 1;
+		self.shadow.set_off();
+	}
+}
+
+/// A [`ShadowOutput`] kept outside of whatever plays on it, like a [`PlayerPool`], so tests can still see it.
+struct SharedOutput<'c>(&'c Cell<ShadowOutput>);
+
+impl ToneOutput for SharedOutput<'_> {
+	fn playable_range(&self) -> FrequencyRange {
+		self.0.get().playable_range()
+	}
+
+	fn set_tone(&mut self, frequency: u16, duty: u16) -> Result<(), ToneError> {
+		let mut output = self.0.get();
+		let result = output.set_tone(frequency, duty);
+		self.0.set(output);
+		result
+	}
+
+	fn max_duty(&self) -> u16 {
+		self.0.get().max_duty()
+	}
+
+	fn set_off(&mut self) {
+		let mut output = self.0.get();
+		output.set_off();
+		self.0.set(output);
+	}
+}
+
+/// Returns a song with `notes`, ending after its last beat.
+const fn song<'a>(notes: &'a [Option<&'a [NoteAndDuration]>]) -> Song<'a> {
+	Song {
+		notes,
+		end: notes.len() as u16,
+		events: &[],
+		metadata: SongMetadata::NONE,
+	}
+}
+
+/// Returns what each output of `player` is playing, as `(frequency, duty)`.
+fn tones<const PWM_COUNT: usize, const MAX_SIMULTANEOUS_NOTES: usize>(
+	player: &Player<ShadowOutput, PWM_COUNT, MAX_SIMULTANEOUS_NOTES>,
+) -> [Option<(u16, u16)>; PWM_COUNT] {
+	core::array::from_fn(|i| player.pwms()[i].tone())
+}
+
+#[test]
+fn zero_length_song_notes_are_skipped() {
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(523, 0, 0)]),
+		Some(&[NoteAndDuration::new(659, 1, 0)]),
+	]);
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, false, 1, 500, [output()]);
+
+	player.tick();
+	assert_eq!(tones(&player), [None]);
+	player.tick();
+	assert_eq!(tones(&player), [Some((659, 500))]);
+}
+
+#[test]
+fn zero_length_live_notes_are_skipped() {
+	const SONG: Song = song(&[None, None, None]);
+	let pattern = LivePattern::<NoopRawMutex>::new();
+	assert!(pattern.at(0).play(NoteAndDuration::new(523, 0, 0)));
+	assert!(pattern.at(1).play(NoteAndDuration::new(659, 1, 0)));
+
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, false, 1, 500, [output()]);
+	player.set_live_source(Some(&pattern));
+	player.tick();
+	assert_eq!(tones(&player), [None]);
+	player.tick();
+	assert_eq!(tones(&player), [Some((659, 500))]);
+	player.tick();
+	assert_eq!(tones(&player), [None]);
+}
+
+#[test]
+fn crossfade_moves_the_duty_from_one_song_to_the_other() {
+	const MENU: Song = song(&[Some(&[NoteAndDuration::new(440, 8, 0)]), None, None, None]);
+	// One hit of the ratchet per beat, sounding for the first of its two ticks.
+	const GAME: Song = song(&[
+		Some(&[NoteAndDuration::new(523, 4, 0).with_ratchet(4)]),
+		None,
+		None,
+		None,
+	]);
+	let mut player: Player<ShadowOutput, 2, 4> =
+		Player::new(&MENU, false, 2, 500, [output(), output()]);
+	player.tick();
+	player.tick();
+	assert_eq!(tones(&player), [Some((440, 500)), None]);
+
+	// The new song starts on the spare PWM, and the duty moves over a quarter per tick.
+	player.crossfade_to(&GAME, 4);
+	player.tick();
+	assert_eq!(tones(&player), [Some((440, 375)), Some((523, 125))]);
+	player.tick();
+	assert_eq!(tones(&player), [Some((440, 250)), Some((523, 250))]);
+	player.tick();
+	assert_eq!(tones(&player), [Some((440, 125)), Some((523, 375))]);
+	assert!(player.is_crossfading());
+
+	// Once it takes over, its note keeps ratcheting in step with the beats it started on.
+	let expected = [None, Some((523, 500)), None, Some((523, 500)), None];
+	for tone in expected {
+		player.tick();
+		assert!(!player.is_crossfading());
+		assert_eq!(tones(&player), [tone, None]);
+	}
+}
+
+#[test]
+fn only_changed_channels_are_written() {
+	const SONG: Song = song(&[
+		Some(&[
+			NoteAndDuration::new(440, 4, 0),
+			NoteAndDuration::new(523, 1, 1),
+		]),
+		Some(&[NoteAndDuration::new(587, 1, 1)]),
+		Some(&[NoteAndDuration::new(587, 1, 1)]),
+		None,
+	]);
+	let output = CountingOutput {
+		shadow: output(),
+		writes: 0,
+	};
+	let mut player: Player<CountingOutput, 2, 4> = Player::new(&SONG, false, 1, 500, [output; 2]);
+
+	// The held note is only written when it starts and stops, and the repeated note isn't written again.
+	let expected = [
+		([Some((440, 500)), Some((523, 500))], [1, 1]),
+		([Some((440, 500)), Some((587, 500))], [1, 2]),
+		([Some((440, 500)), Some((587, 500))], [1, 2]),
+		([Some((440, 500)), None], [1, 3]),
+		([None, None], [2, 3]),
+		([None, None], [2, 3]),
+	];
+	for (tones, writes) in expected {
+		player.tick();
+		assert_eq!(player.pwms().map(|output| output.shadow.tone()), tones);
+		assert_eq!(player.pwms().map(|output| output.writes), writes);
+	}
+}
+
+#[test]
+fn big_jumps_slide_at_the_slew_limit() {
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(220, 1, 0)]),
+		Some(&[NoteAndDuration::new(880, 1, 0)]),
+		None,
+		Some(&[NoteAndDuration::new(880, 1, 0)]),
+	]);
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, false, 4, 500, [output()]);
+	player.set_max_slew_semitones(12);
+
+	// Two octaves up takes two ticks at an octave per tick, but a note on a silent PWM starts at its own pitch.
+	let expected = [
+		None,
+		None,
+		None,
+		Some(220),
+		Some(220),
+		Some(220),
+		Some(220),
+		Some(440),
+		Some(880),
+		Some(880),
+		Some(880),
+		None,
+		None,
+		None,
+		None,
+		Some(880),
+	];
+	for frequency in expected {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+}
+
+#[test]
+fn disabled_outputs_are_skipped_and_set_up_again() {
+	const CHORD: Song = song(&[
+		Some(&[
+			NoteAndDuration::new(262, 2, 0),
+			NoteAndDuration::new(330, 2, 1),
+			NoteAndDuration::new(392, 2, 2),
+		]),
+		None,
+		None,
+	]);
+	let mut player: Player<ShadowOutput, 3, 4> =
+		Player::new(&CHORD, false, 2, 500, [output(), output(), output()]);
+	player.tick();
+	player.tick();
+	assert_eq!(
+		tones(&player),
+		[Some((262, 500)), Some((330, 500)), Some((392, 500))]
+	);
+
+	// The chord moves off the disabled PWM, and the last PWM cycles through the notes left over.
+	player.set_output_enabled(1, false);
+	assert_eq!(tones(&player), [Some((262, 500)), None, Some((330, 500))]);
+	for last in [330, 392, 330] {
+		player.tick();
+		assert_eq!(tones(&player), [Some((262, 500)), None, Some((last, 500))]);
+	}
+
+	player.set_output_enabled(1, true);
+	assert_eq!(
+		tones(&player),
+		[Some((262, 500)), Some((330, 500)), Some((392, 500))]
+	);
+}
+
+#[test]
+fn speed_trainer_overrides_the_songs_starting_tempo() {
+	// 100 ticks per beat is 60 beats per minute at 10ms per tick, like a `declare_rtttl!` song at `b=60`.
+	const EVENTS: &[SongEvent] = &[SongEvent {
+		beat: 0,
+		kind: SongEventKind::SetTempo {
+			ticks_per_beat: 100,
+		},
+	}];
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(440, 1, 0)]),
+		Some(&[NoteAndDuration::new(523, 1, 0)]),
+	])
+	.with_events(EVENTS);
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, true, 100, 500, [output()]);
+	player.set_speed_trainer(Some(SpeedTrainer {
+		min_bpm: 240,
+		max_bpm: 300,
+		step_bpm: 60,
+		tick_period_ms: 10,
+	}));
+
+	// Returns how many ticks pass until the PWM plays `frequency`.
+	let mut ticks_until = |frequency| {
+		let mut ticks = 0;
+		loop {
+			player.tick();
+			ticks += 1;
+			if player.pwms()[0].frequency() == Some(frequency) {
+				return ticks;
+			}
+		}
+	};
+	ticks_until(440);
+	// The first pass plays at 240 beats per minute, 25 ticks a beat, and the second at 300, 20 ticks a beat.
+	assert_eq!(ticks_until(523), 25);
+	assert_eq!(ticks_until(440), 25);
+	assert_eq!(ticks_until(523), 20);
+	assert_eq!(ticks_until(440), 20);
+	assert_eq!(player.speed_trainer_bpm(), Some(300));
+}
+
+#[test]
+fn stolen_pool_voices_sound_again_when_released() {
+	const MUSIC: Song = song(&[Some(&[NoteAndDuration::new(440, 8, 0)]), None, None, None]);
+	const BEEP: Song = song(&[Some(&[NoteAndDuration::new(1047, 1, 0)])]);
+	let shared = Cell::new(output());
+	let pool = PlayerPool::new([SharedOutput(&shared)]);
+	let mut music = Player::<_, 1, 4>::new(&MUSIC, false, 1, 500, pool.lease_all(0).unwrap());
+	music.tick();
+	assert_eq!(shared.get().tone(), Some((440, 500)));
+
+	// The beep outranks the music, so it takes the buzzer.
+	let mut beep = Player::<_, 1, 4>::new(&BEEP, false, 1, 500, [pool.lease(1).unwrap()]);
+	beep.tick();
+	music.tick();
+	assert_eq!(shared.get().tone(), Some((1047, 500)));
+
+	// The music's note is still held, so it comes back as soon as the beep lets go.
+	drop(beep);
+	assert_eq!(shared.get().tone(), Some((440, 500)));
+	music.tick();
+	assert_eq!(shared.get().tone(), Some((440, 500)));
+}
+
+#[test]
+fn seeking_only_catches_up_the_tempo() {
+	const TAKE: &[Option<&[NoteAndDuration]>] = &[
+		Some(&[NoteAndDuration::new(880, 1, 0)]),
+		Some(&[NoteAndDuration::new(988, 1, 0)]),
+		Some(&[NoteAndDuration::new(1047, 1, 0)]),
+		Some(&[NoteAndDuration::new(1175, 1, 0)]),
+	];
+	const EVENTS: &[SongEvent] = &[
+		SongEvent {
+			beat: 0,
+			kind: SongEventKind::Alternatives {
+				beats: 4,
+				takes: &[TAKE],
+				order: TakeOrder::Cycle,
+			},
+		},
+		SongEvent {
+			beat: 1,
+			kind: SongEventKind::SetTempo { ticks_per_beat: 2 },
+		},
+	];
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(440, 1, 0)]),
+		Some(&[NoteAndDuration::new(494, 1, 0)]),
+		Some(&[NoteAndDuration::new(523, 1, 0)]),
+		Some(&[NoteAndDuration::new(587, 1, 0)]),
+	])
+	.with_events(EVENTS);
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, true, 1, 500, [output()]);
+	player.set_output_latency_ticks(-1);
+
+	// The second pass plays the take, from the first beat.
+	let expected = [
+		None,
+		Some(440),
+		Some(494),
+		Some(494),
+		Some(523),
+		Some(523),
+		Some(587),
+		Some(587),
+		Some(880),
+	];
+	for frequency in expected {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+
+	// Seeking into the take plays the song's own notes, at the tempo and latency they'd have there.
+	assert!(player.seek(2));
+	for frequency in [None, None, Some(523), Some(523), Some(587)] {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+}
+
+#[test]
+fn repeated_notes_only_retrigger_with_a_gap() {
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(440, 1, 0)]),
+		Some(&[NoteAndDuration::new(440, 1, 0)]),
+	]);
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, false, 2, 500, [output()]);
+	for frequency in [None, Some(440), Some(440), Some(440), Some(440)] {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+
+	// With a gap, the second note starts with a tick of silence.
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, false, 2, 500, [output()]);
+	player.set_retrigger_gap(1);
+	for frequency in [None, Some(440), Some(440), None, Some(440)] {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+}
+
+#[test]
+fn tempo_ramps_lengthen_each_beat_in_turn() {
+	const EVENTS: &[SongEvent] = &[SongEvent {
+		beat: 0,
+		kind: SongEventKind::TempoRamp {
+			from: 2,
+			to: 4,
+			beats: 2,
+		},
+	}];
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(440, 1, 0)]),
+		Some(&[NoteAndDuration::new(494, 1, 0)]),
+		Some(&[NoteAndDuration::new(523, 1, 0)]),
+		Some(&[NoteAndDuration::new(587, 1, 0)]),
+	])
+	.with_events(EVENTS);
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, false, 2, 500, [output()]);
+
+	// The beats last 2, 3, then 4 ticks, and the tempo stays at the end of the ramp.
+	let expected = [
+		None,
+		Some(440),
+		Some(440),
+		Some(494),
+		Some(494),
+		Some(494),
+		Some(523),
+		Some(523),
+		Some(523),
+		Some(523),
+		Some(587),
+		Some(587),
+		Some(587),
+		Some(587),
+		None,
+	];
+	for frequency in expected {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+}
+
+#[test]
+fn fermatas_stretch_a_beat_and_holds_wait_for_release() {
+	const EVENTS: &[SongEvent] = &[
+		SongEvent {
+			beat: 0,
+			kind: SongEventKind::Fermata { percent: 200 },
+		},
+		SongEvent {
+			beat: 1,
+			kind: SongEventKind::Hold,
+		},
+	];
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(440, 1, 0)]),
+		Some(&[NoteAndDuration::new(494, 1, 0)]),
+		Some(&[NoteAndDuration::new(523, 1, 0)]),
+	])
+	.with_events(EVENTS);
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, false, 2, 500, [output()]);
+
+	// The first beat lasts twice as long, and the second lasts until it's released.
+	let expected = [
+		None,
+		Some(440),
+		Some(440),
+		Some(440),
+		Some(440),
+		Some(494),
+		Some(494),
+		Some(494),
+		Some(494),
+	];
+	for frequency in expected {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+	assert!(player.is_holding());
+
+	player.release_hold();
+	assert!(!player.is_holding());
+	for frequency in [Some(523), Some(523), None] {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+}
+
+#[test]
+fn branches_jump_to_the_chosen_target() {
+	const EVENTS: &[SongEvent] = &[SongEvent {
+		beat: 1,
+		kind: SongEventKind::Branch { targets: &[0, 3] },
+	}];
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(440, 1, 0)]),
+		Some(&[NoteAndDuration::new(494, 1, 0)]),
+		Some(&[NoteAndDuration::new(523, 1, 0)]),
+		Some(&[NoteAndDuration::new(587, 1, 0)]),
+	])
+	.with_events(EVENTS);
+
+	// The branch beat itself isn't played, the target is.
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, false, 1, 500, [output()]);
+	player.select_branch(1);
+	for frequency in [Some(440), Some(587), None] {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+
+	// A selector takes priority, and a target out of bounds plays on from the branch.
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, false, 1, 500, [output()]);
+	player.select_branch(1);
+	player.set_branch_selector(Some(|_| 2));
+	for frequency in [Some(440), Some(494), Some(523), Some(587), None] {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+}
+
+#[test]
+fn stingers_play_over_the_song_and_give_their_pwms_back() {
+	const SONG: Song = song(&[Some(&[NoteAndDuration::new(440, 4, 0)]), None, None, None]);
+	const STINGER: Song = song(&[
+		Some(&[NoteAndDuration::new(1047, 1, 0)]),
+		Some(&[NoteAndDuration::new(1175, 1, 0)]),
+	]);
+
+	// The stinger waits for the next beat, then plays on the PWM the song isn't using.
+	let mut player: Player<ShadowOutput, 2, 4> =
+		Player::new(&SONG, false, 2, 500, [output(), output()]);
+	for _ in 0..3 {
+		player.tick();
+	}
+	player.play_stinger(&STINGER, StingerPolicy::SpareVoices, true);
+	let expected = [None, Some(1047), Some(1047), Some(1175), Some(1175), None];
+	for frequency in expected {
+		assert_eq!(player.pwms()[0].frequency(), Some(440));
+		assert_eq!(player.pwms()[1].frequency(), frequency);
+		player.tick();
+	}
+
+	// Stealing takes the song's PWM, which plays the song's note again once the stinger ends.
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, false, 2, 500, [output()]);
+	player.tick();
+	player.tick();
+	player.play_stinger(&STINGER, StingerPolicy::StealVoices, false);
+	for frequency in [Some(1047), Some(1047), Some(1175), Some(1175), Some(440)] {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+}
+
+#[test]
+fn output_latency_moves_every_note() {
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(440, 1, 0)]),
+		Some(&[NoteAndDuration::new(494, 1, 0)]),
+	]);
+
+	// Without latency, the first beat starts on the fourth tick.
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, false, 4, 500, [output()]);
+	player.set_output_latency_ticks(1);
+	let expected = [
+		None,
+		None,
+		Some(440),
+		Some(440),
+		Some(440),
+		Some(440),
+		Some(494),
+	];
+	for frequency in expected {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+
+	// Going from a tick early to a tick late delays the next beat by two ticks, and seeking keeps it late.
+	player.set_output_latency_ticks(-1);
+	for frequency in [Some(494), Some(494), Some(494), Some(494), Some(494), None] {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+	assert!(player.seek(0));
+	for frequency in [None, None, None, None, Some(440)] {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+}
+
+#[test]
+fn catching_up_can_skip_the_missed_notes() {
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(440, 1, 0)]),
+		Some(&[NoteAndDuration::new(494, 1, 0)]),
+		Some(&[NoteAndDuration::new(523, 1, 0)]),
+		Some(&[NoteAndDuration::new(587, 1, 0)]),
+	]);
+	let output = CountingOutput {
+		shadow: output(),
+		writes: 0,
+	};
+
+	// Both end up on the same beat, but only one of them sends the missed notes to the PWM.
+	let mut player: Player<CountingOutput, 1, 4> = Player::new(&SONG, false, 1, 500, [output]);
+	assert!(player.tick_by(3, false));
+	assert_eq!(player.pwms()[0].shadow.frequency(), Some(523));
+	assert_eq!(player.pwms()[0].writes, 3);
+
+	let mut player: Player<CountingOutput, 1, 4> = Player::new(&SONG, false, 1, 500, [output]);
+	assert!(player.tick_by(3, true));
+	assert_eq!(player.pwms()[0].shadow.frequency(), Some(523));
+	assert_eq!(player.pwms()[0].writes, 1);
+	for frequency in [Some(587), None] {
+		player.tick();
+		assert_eq!(player.pwms()[0].shadow.frequency(), frequency);
+	}
+}
+
+#[test]
+fn tick_interval_hints_keep_the_tempo() {
+	const EVENTS: &[SongEvent] = &[SongEvent {
+		beat: 2,
+		kind: SongEventKind::SetTempo { ticks_per_beat: 8 },
+	}];
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(440, 1, 0)]),
+		Some(&[NoteAndDuration::new(494, 1, 0)]),
+		Some(&[NoteAndDuration::new(523, 1, 0)]),
+	])
+	.with_events(EVENTS);
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, false, 4, 500, [output()]);
+	player.set_tick_interval_hint(10);
+	for frequency in [None, None, None, Some(440)] {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+
+	// Ticking half as often halves the ticks in a beat, including for the song's own tempo changes.
+	player.set_tick_interval_hint(20);
+	let expected = [
+		Some(440),
+		Some(494),
+		Some(494),
+		Some(523),
+		Some(523),
+		Some(523),
+		Some(523),
+		None,
+	];
+	for frequency in expected {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+}
+
+#[test]
+fn marker_transitions_play_before_landing() {
+	const EVENTS: &[SongEvent] = &[
+		SongEvent {
+			beat: 0,
+			kind: SongEventKind::Marker {
+				id: 0,
+				transition: Transition::Fade { beats: 2 },
+			},
+		},
+		SongEvent {
+			beat: 3,
+			kind: SongEventKind::Marker {
+				id: 1,
+				transition: Transition::Cut,
+			},
+		},
+		SongEvent {
+			beat: 3,
+			kind: SongEventKind::Marker {
+				id: 2,
+				transition: Transition::Fill {
+					notes: &[NoteAndDuration::new(1047, 1, 0)],
+				},
+			},
+		},
+	];
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(440, 1, 0)]),
+		Some(&[NoteAndDuration::new(494, 1, 0)]),
+		Some(&[NoteAndDuration::new(523, 1, 0)]),
+		Some(&[NoteAndDuration::new(587, 1, 0)]),
+	])
+	.with_events(EVENTS);
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, false, 1, 500, [output()]);
+	player.tick();
+	assert_eq!(tones(&player), [Some((440, 500))]);
+
+	// A cut lands on the next beat.
+	assert!(player.jump_to_marker(1));
+	player.tick();
+	assert_eq!(tones(&player), [Some((587, 500))]);
+
+	// A fill plays for a beat first.
+	assert!(player.jump_to_marker(2));
+	for tone in [Some((1047, 500)), Some((587, 500)), None] {
+		player.tick();
+		assert_eq!(tones(&player), [tone]);
+	}
+
+	// A fade keeps playing the song more and more quietly, then lands at full volume.
+	assert!(player.seek(0));
+	player.tick();
+	assert!(player.jump_to_marker(0));
+	assert!(!player.jump_to_marker(3));
+	for tone in [Some((494, 330)), Some((523, 165)), Some((440, 500))] {
+		player.tick();
+		assert_eq!(tones(&player), [tone]);
+		assert_eq!(player.is_jumping(), tone.unwrap().0 != 440);
+	}
+}
+
+#[test]
+fn sticky_notes_are_stolen_last() {
+	const SONG: Song = song(&[
+		Some(&[
+			NoteAndDuration::new(110, 3, 1),
+			NoteAndDuration::new(440, 1, 0),
+		]),
+		Some(&[
+			NoteAndDuration::new(494, 1, 0),
+			NoteAndDuration::new(523, 1, 0),
+		]),
+		None,
+	]);
+
+	// Without sticky notes, the held bass note is the oldest, so it's the first to go.
+	let mut player: Player<ShadowOutput, 2, 2> =
+		Player::new(&SONG, false, 1, 500, [output(), output()]);
+	let expected = [[Some(110), Some(440)], [Some(494), Some(523)], [None, None]];
+	for frequencies in expected {
+		player.tick();
+		assert_eq!(player.pwms().map(|output| output.frequency()), frequencies);
+	}
+
+	// A sticky bass note keeps sounding, whether its track is sticky or it's long enough, and
+	// the melody's newest note takes the place of the other.
+	for by_length in [false, true] {
+		let mut player: Player<ShadowOutput, 2, 2> =
+			Player::new(&SONG, false, 1, 500, [output(), output()]);
+		if by_length {
+			player.set_sticky_min_beats(Some(2));
+		} else {
+			player.set_track_sticky(1, true);
+		}
+		let expected = [
+			[Some(110), Some(440)],
+			[Some(110), Some(523)],
+			[Some(110), None],
+		];
+		for frequencies in expected {
+			player.tick();
+			assert_eq!(player.pwms().map(|output| output.frequency()), frequencies);
+		}
+	}
+}
+
+#[test]
+fn end_behavior_decides_how_the_last_notes_end() {
+	// The last note is written to last past the end of the song.
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(440, 1, 0)]),
+		Some(&[NoteAndDuration::new(523, 4, 0)]),
+	]);
+	let ring = Some((523, 500));
+	let cases = [
+		(EndBehavior::CutAtEnd, [ring, None, None, None, None]),
+		(EndBehavior::HoldLastNotes, [ring, ring, ring, ring, None]),
+		(
+			EndBehavior::FadeOut(3),
+			[ring, ring, Some((523, 330)), Some((523, 165)), None],
+		),
+	];
+	for (behavior, expected) in cases {
+		let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, false, 1, 500, [output()]);
+		player.set_end_behavior(behavior);
+		player.tick();
+		for tone in expected {
+			player.tick();
+			assert_eq!(tones(&player), [tone], "{behavior:?}");
+		}
+		player.tick();
+		assert_eq!(tones(&player), [None], "{behavior:?}");
+		assert!(player.is_finished(), "{behavior:?}");
+	}
+}
+
+#[test]
+fn loop_overlap_starts_the_next_pass_early() {
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(440, 1, 0)]),
+		Some(&[NoteAndDuration::new(494, 1, 0)]),
+		None,
+		Some(&[NoteAndDuration::new(220, 2, 1)]),
+	]);
+	let mut player: Player<ShadowOutput, 2, 4> =
+		Player::new(&SONG, true, 1, 500, [output(), output()]);
+	player.set_loop_overlap(1);
+
+	// The first beat plays on top of the last, and the held note carries into the next pass.
+	let expected = [
+		[Some(440), None],
+		[Some(494), None],
+		[None, None],
+		[Some(220), Some(440)],
+		[Some(220), Some(494)],
+		[None, None],
+		[Some(220), Some(440)],
+	];
+	for frequencies in expected {
+		player.tick();
+		assert_eq!(player.pwms().map(|output| output.frequency()), frequencies);
+	}
+	assert_eq!(player.loop_pass(), 1);
+}
+
+#[test]
+fn alternatives_change_the_notes_each_pass() {
+	const EVENTS: &[SongEvent] = &[SongEvent {
+		beat: 0,
+		kind: SongEventKind::Alternatives {
+			beats: 2,
+			takes: &[&[Some(&[NoteAndDuration::new(880, 1, 0)])]],
+			order: TakeOrder::Cycle,
+		},
+	}];
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(440, 1, 0)]),
+		Some(&[NoteAndDuration::new(494, 1, 0)]),
+	])
+	.with_events(EVENTS);
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, true, 1, 500, [output()]);
+
+	// The take is shorter than the section, so the rest of the section is silent.
+	let expected = [Some(440), Some(494), Some(880), None, Some(440), Some(494)];
+	for frequency in expected {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+
+	// Random takes follow the seed, so the same seed plays the same takes.
+	const RANDOM_EVENTS: &[SongEvent] = &[SongEvent {
+		beat: 0,
+		kind: SongEventKind::Alternatives {
+			beats: 2,
+			takes: &[&[Some(&[NoteAndDuration::new(880, 1, 0)])]],
+			order: TakeOrder::Random,
+		},
+	}];
+	const RANDOM_SONG: Song = SONG.with_events(RANDOM_EVENTS);
+	let mut players: [Player<ShadowOutput, 1, 4>; 2] =
+		core::array::from_fn(|_| Player::new(&RANDOM_SONG, true, 1, 500, [output()]));
+	let mut takes = [0; 2];
+	for player in &mut players {
+		player.set_variation_seed(7);
+	}
+	for _ in 0..20 {
+		for player in &mut players {
+			player.tick();
+		}
+		let frequency = players[0].pwms()[0].frequency();
+		assert_eq!(players[1].pwms()[0].frequency(), frequency);
+		match frequency {
+			Some(440) => takes[0] += 1,
+			Some(880) => takes[1] += 1,
+			_ => {}
+		}
+	}
+	assert!(takes[0] > 0 && takes[1] > 0);
+}
+
+#[test]
+fn tempo_ratio_changes_wait_for_a_bar_line() {
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(440, 1, 0)]),
+		Some(&[NoteAndDuration::new(494, 1, 0)]),
+		Some(&[NoteAndDuration::new(523, 1, 0)]),
+		Some(&[NoteAndDuration::new(587, 1, 0)]),
+	]);
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, false, 2, 500, [output()]);
+	player.set_beats_per_bar(2);
+	player.tick();
+	player.tick();
+	assert_eq!(player.pwms()[0].frequency(), Some(440));
+
+	// Half time starts with the next bar, two beats in, where each beat becomes twice as long.
+	player.set_tempo_ratio(1, 2);
+	assert_eq!(player.tempo_ratio(), (1, 2));
+	let expected = [
+		Some(440),
+		Some(494),
+		Some(494),
+		Some(523),
+		Some(523),
+		Some(523),
+		Some(523),
+		Some(587),
+	];
+	for frequency in expected {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+}
+
+#[test]
+fn muted_and_unsoloed_tracks_stop_and_free_their_pwms() {
+	const SONG: Song = song(&[
+		Some(&[
+			NoteAndDuration::new(440, 1, 0),
+			NoteAndDuration::new(110, 2, 1),
+		]),
+		Some(&[NoteAndDuration::new(494, 1, 0)]),
+		Some(&[
+			NoteAndDuration::new(523, 1, 0),
+			NoteAndDuration::new(131, 1, 1),
+		]),
+		Some(&[
+			NoteAndDuration::new(587, 1, 0),
+			NoteAndDuration::new(147, 1, 1),
+		]),
+	]);
+	let mut player: Player<ShadowOutput, 2, 4> =
+		Player::new(&SONG, false, 1, 500, [output(), output()]);
+	player.tick();
+	assert_eq!(tones(&player), [Some((440, 500)), Some((110, 500))]);
+
+	// The bass stops straight away, and stays stopped after being unmuted until its next note.
+	player.set_track_muted(1, true);
+	assert!(!player.is_track_audible(1));
+	assert_eq!(tones(&player), [Some((440, 500)), None]);
+	player.set_track_muted(1, false);
+	player.tick();
+	assert_eq!(tones(&player), [Some((494, 500)), None]);
+
+	// Soloing the bass leaves it the first PWM.
+	player.set_track_solo(1, true);
+	assert!(!player.is_track_audible(0));
+	assert_eq!(tones(&player), [None, None]);
+	player.tick();
+	assert_eq!(tones(&player), [Some((131, 500)), None]);
+	player.set_track_solo(1, false);
+	player.tick();
+	assert_eq!(tones(&player), [Some((587, 500)), Some((147, 500))]);
+}
+
+#[test]
+fn resuming_sounds_held_notes_straight_away() {
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(440, 2, 0)]),
+		None,
+		Some(&[NoteAndDuration::new(494, 1, 0)]),
+	]);
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, false, 2, 500, [output()]);
+	for _ in 0..3 {
+		player.tick();
+	}
+	assert_eq!(tones(&player), [Some((440, 500))]);
+
+	// Pausing silences the note, and time stands still until the song resumes.
+	player.pause();
+	for _ in 0..3 {
+		assert!(!player.tick());
+		assert_eq!(tones(&player), [None]);
+	}
+	player.resume();
+	assert_eq!(tones(&player), [Some((440, 500))]);
+	for frequency in [Some(440), Some(440), Some(494)] {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+}
+
+#[test]
+fn quantized_pauses_stop_and_start_on_the_beat() {
+	const SONG: Song = song(&[
+		Some(&[NoteAndDuration::new(440, 1, 0)]),
+		Some(&[NoteAndDuration::new(494, 1, 0)]),
+		Some(&[NoteAndDuration::new(523, 1, 0)]),
+	]);
+	let mut player: Player<ShadowOutput, 1, 4> = Player::new(&SONG, false, 2, 500, [output()]);
+	player.tick();
+	player.tick();
+
+	// The first beat plays out, then the song stops where the second would have started.
+	player.pause_at_next_beat();
+	player.tick();
+	assert_eq!(player.pwms()[0].frequency(), Some(440));
+	assert!(!player.is_paused());
+	player.tick();
+	assert_eq!(player.pwms()[0].frequency(), None);
+	assert!(player.is_paused());
+
+	// Resuming waits for the next beat.
+	player.resume_at_next_beat();
+	assert!(player.is_paused());
+	for frequency in [None, Some(494), Some(494), Some(523)] {
+		player.tick();
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+	}
+}
+
+#[test]
+fn playlists_move_on_when_a_song_ends() {
+	const FIRST: Song = song(&[Some(&[NoteAndDuration::new(440, 1, 0)])]);
+	const SECOND: Song = song(&[
+		Some(&[NoteAndDuration::new(494, 1, 0)]),
+		Some(&[NoteAndDuration::new(523, 1, 0)]),
+	]);
+	let mut playlist = Playlist::new([&FIRST, &SECOND]);
+	let mut player: Player<ShadowOutput, 1, 4> =
+		Player::new(playlist.current(), false, 1, 500, [output()]);
+
+	// The second song starts on the tick after the first one ends, and the last one stays finished.
+	let expected = [
+		(Some(440), 0),
+		(None, 1),
+		(Some(494), 1),
+		(Some(523), 1),
+		(None, 1),
+		(None, 1),
+	];
+	for (frequency, index) in expected {
+		playlist.tick(&mut player);
+		assert_eq!(player.pwms()[0].frequency(), frequency);
+		assert_eq!(playlist.index(), index);
+	}
+	assert!(player.is_finished());
+	assert!(!playlist.next(&mut player));
+
+	// A looping playlist wraps around both ways.
+	playlist.set_looping(true);
+	assert!(playlist.next(&mut player));
+	assert_eq!(playlist.index(), 0);
+	playlist.tick(&mut player);
+	assert_eq!(player.pwms()[0].frequency(), Some(440));
+	assert!(playlist.previous(&mut player));
+	assert_eq!(playlist.index(), 1);
+	assert_eq!(player.pwms()[0].frequency(), None);
+	playlist.tick(&mut player);
+	assert_eq!(player.pwms()[0].frequency(), Some(494));
+}
@@ -0,0 +1,221 @@
+use crate::ToneOutput;
+use crate::led::OCTAVE_CENTIHZ;
+
+/// How a measured frequency compares to the nearest note, produced by [`TuningReading::new`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TuningReading {
+	/// The pitch class of the nearest note, from `0` for C to `11` for B.
+	pub pitch_class: u8,
+	/// The octave of the nearest note, where A4 is 440 Hz.
+	pub octave: i8,
+	/// The frequency of the nearest note, in hertz.
+	pub reference_frequency: u16,
+	/// How far the measured frequency is from the nearest note, in cents. Positive if sharp.
+	pub cents: i16,
+}
+
+impl TuningReading {
+	/// Finds the note nearest to `frequency_centihz`, a frequency in hundredths of a hertz.
+	/// Returns `None` if the frequency is `0`.
+	pub const fn new(frequency_centihz: u32) -> Option<Self> {
+		if frequency_centihz == 0 {
+			return None;
+		}
+
+		// Find the octave, then compare against that octave's notes without losing precision.
+		let mut octave: i8 = 4;
+		let mut frequency = frequency_centihz as u64;
+		let mut c = OCTAVE_CENTIHZ[0] as u64;
+		while frequency < c {
+			frequency *= 2;
+			octave -= 1;
+		}
+		while frequency >= c * 2 {
+			c *= 2;
+			octave += 1;
+		}
+		let shift = (octave - 4) as i32;
+
+		let mut best = 0;
+		let mut best_cents = i64::MAX;
+		let mut i = 0;
+		while i <= 12 {
+			let reference = if i == 12 {
+				OCTAVE_CENTIHZ[0] as u64 * 2
+			} else {
+				OCTAVE_CENTIHZ[i] as u64
+			};
+			let reference = if shift > 0 {
+				reference << shift
+			} else {
+				reference
+			};
+			let cents = cents_between(frequency, reference);
+			if cents.abs() < best_cents.abs() {
+				best = i;
+				best_cents = cents;
+			}
+			i += 1;
+		}
+
+		let (pitch_class, octave) = if best == 12 {
+			(0, octave + 1)
+		} else {
+			(best as u8, octave)
+		};
+		let reference = OCTAVE_CENTIHZ[pitch_class as usize] as u64;
+		let reference = if octave >= 4 {
+			reference << (octave - 4)
+		} else {
+			reference >> (4 - octave)
+		};
+		let reference_frequency = (reference + 50) / 100;
+
+		Some(Self {
+			pitch_class,
+			octave,
+			reference_frequency: if reference_frequency > u16::MAX as u64 {
+				u16::MAX
+			} else {
+				reference_frequency as u16
+			},
+			cents: best_cents as i16,
+		})
+	}
+
+	/// Returns `true` if the measured frequency is within `tolerance_cents` of the nearest note.
+	pub const fn is_in_tune(&self, tolerance_cents: u16) -> bool {
+		self.cents.unsigned_abs() <= tolerance_cents
+	}
+}
+
+/// Approximates how many cents `frequency` is above `reference`.
+/// Accurate to within a cent for anything under a semitone apart.
+const fn cents_between(frequency: u64, reference: u64) -> i64 {
+	// 1200 / ln(2) * ln(f / r) ≈ 3462 * (f - r) / (f + r)
+	let difference = frequency as i64 - reference as i64;
+	3462 * difference / (frequency + reference) as i64
+}
+
+/// Helps tune an instrument by playing the nearest note to a measured frequency,
+/// beeping to show whether the instrument is flat or sharp.
+///
+/// When in tune the note plays steadily. When flat it pulses slowly, and when sharp it pulses quickly.
+/// The frequency is measured by the application (for example, from a microphone and comparator).
+///
+/// ```rust,ignore
+/// let mut tuner = buzzer_music::Tuner::new(buzzer);
+/// loop {
+///     tuner.set_frequency_centihz(measure_frequency_centihz());
+///     tuner.tick();
+///     embassy_time::Timer::after_millis(40).await;
+/// }
+/// ```
+pub struct Tuner<O: ToneOutput> {
+	output: O,
+	duty: u16,
+	tolerance_cents: u16,
+	flat_pulse_ticks: u16,
+	sharp_pulse_ticks: u16,
+
+	reading: Option<TuningReading>,
+	timer: u16,
+	playing: Option<u16>,
+}
+
+impl<O: ToneOutput> Tuner<O> {
+	/// The constructor.
+	///
+	/// `output` is what the reference notes are played on.
+	pub fn new(output: O) -> Self {
+		Self {
+			output,
+			duty: 100,
+			tolerance_cents: 5,
+			flat_pulse_ticks: 6,
+			sharp_pulse_ticks: 2,
+
+			reading: None,
+			timer: 0,
+			playing: None,
+		}
+	}
+
+	/// Sets the duty the reference notes are played with. Defaults to `100`.
+	pub fn set_duty(&mut self, duty: u16) {
+		self.duty = duty;
+	}
+
+	/// Sets how many cents from the note counts as in tune. Defaults to `5`.
+	pub fn set_tolerance_cents(&mut self, cents: u16) {
+		self.tolerance_cents = cents;
+	}
+
+	/// Sets how many ticks the note is on, then off, for each pulse when flat and when sharp.
+	/// Defaults to `6` and `2`.
+	pub fn set_pulse_ticks(&mut self, flat: u16, sharp: u16) {
+		self.flat_pulse_ticks = flat.max(1);
+		self.sharp_pulse_ticks = sharp.max(1);
+	}
+
+	/// Sets the measured frequency, in hundredths of a hertz, or `None` (or `0`) for no signal.
+	pub fn set_frequency_centihz(&mut self, frequency_centihz: Option<u32>) {
+		self.reading = match frequency_centihz {
+			Some(frequency) => TuningReading::new(frequency),
+			None => None,
+		};
+	}
+
+	/// Returns how the measured frequency compares to the nearest note.
+	pub fn reading(&self) -> Option<TuningReading> {
+		self.reading
+	}
+
+	/// Updates the beeps. This should be called every loop, like [`crate::Player::tick`].
+	pub fn tick(&mut self) {
+		let Some(reading) = self.reading else {
+			self.stop();
+			return;
+		};
+
+		let pulse_ticks = if reading.is_in_tune(self.tolerance_cents) {
+			None
+		} else if reading.cents < 0 {
+			Some(self.flat_pulse_ticks)
+		} else {
+			Some(self.sharp_pulse_ticks)
+		};
+
+		let on = match pulse_ticks {
+			Some(pulse_ticks) => {
+				self.timer = (self.timer + 1) % (pulse_ticks * 2);
+				self.timer < pulse_ticks
+			}
+			None => true,
+		};
+
+		let frequency = reading.reference_frequency;
+		if !on {
+			self.stop();
+		} else if self.playing != Some(frequency) {
+			self.playing = self
+				.output
+				.set_tone(frequency, self.duty)
+				.is_ok()
+				.then_some(frequency);
+		}
+	}
+
+	/// Consumes the tuner and returns its output.
+	pub fn into_output(mut self) -> O {
+		self.stop();
+		self.output
+	}
+
+	/// Turns the output off.
+	fn stop(&mut self) {
+		if self.playing.take().is_some() {
+			self.output.set_off();
+		}
+	}
+}